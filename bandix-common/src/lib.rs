@@ -21,6 +21,43 @@ pub struct PacketHeader {
 #[cfg(feature = "user")]
 unsafe impl aya::Pod for PacketHeader {}
 
+// ============================================================================
+// DNS 拦截规则（eBPF 与用户态共享）
+// ============================================================================
+
+/// 拦截动作：直接丢弃查询
+pub const DNS_BLOCK_ACTION_DROP: u8 = 1;
+/// 拦截动作：就地改写为 NXDOMAIN 响应并回送给设备（仅 IPv4/UDP，其余情况退化为丢弃）
+pub const DNS_BLOCK_ACTION_NXDOMAIN: u8 = 2;
+
+/// 域名哈希使用 FNV-1a 64 位
+pub const DNS_HASH_OFFSET: u64 = 0xcbf29ce484222325;
+pub const DNS_HASH_PRIME: u64 = 0x100000001b3;
+
+/// 向哈希中追加一个字节（按 ASCII 小写处理）
+#[inline(always)]
+pub fn dns_hash_byte(hash: u64, byte: u8) -> u64 {
+    (hash ^ byte.to_ascii_lowercase() as u64).wrapping_mul(DNS_HASH_PRIME)
+}
+
+/// 计算域名（点分格式，不含结尾的点）的哈希，与 eBPF 中按 QNAME 计算的结果一致
+pub fn dns_name_hash(name: &str) -> u64 {
+    let mut hash = DNS_HASH_OFFSET;
+    for b in name.trim_end_matches('.').bytes() {
+        hash = dns_hash_byte(hash, b);
+    }
+    hash
+}
+
+/// 拦截规则映射的 key：[mac (6 字节), 域名哈希 (8 字节，小端)]
+/// mac 全为 0 表示规则适用于所有设备
+pub fn dns_block_key(mac: &[u8; 6], hash: u64) -> [u8; 14] {
+    let mut key = [0u8; 14];
+    key[..6].copy_from_slice(mac);
+    key[6..].copy_from_slice(&hash.to_le_bytes());
+    key
+}
+
 #[derive(Debug, Clone, Copy)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct DeviceTrafficStats {
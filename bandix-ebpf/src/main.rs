@@ -8,7 +8,9 @@ use aya_ebpf::bindings::{TC_ACT_PIPE, TC_ACT_SHOT};
 use aya_ebpf::macros::classifier;
 use aya_ebpf::programs::TcContext;
 
-use crate::utils::{is_dns_enabled, is_traffic_enabled};
+use crate::utils::{is_arp_enabled, is_dhcp_enabled, is_dns_enabled, is_traffic_enabled};
+use modules::arp::{handle_arp_egress, handle_arp_ingress};
+use modules::dhcp::{handle_dhcp_egress, handle_dhcp_ingress};
 use modules::dns::{handle_dns_egress, handle_dns_ingress};
 use modules::traffic::{handle_traffic_egress, handle_traffic_ingress};
 
@@ -23,6 +25,14 @@ fn process_module_result(result: Result<i32, ()>) -> Option<i32> {
 
 #[classifier]
 pub fn shared_ingress(ctx: TcContext) -> i32 {
+    // DHCP 与 ARP/NDP 监听只复制报文，放在最前面以免被限速丢弃的报文漏掉
+    if is_dhcp_enabled() {
+        let _ = handle_dhcp_ingress(&ctx);
+    }
+    if is_arp_enabled() {
+        let _ = handle_arp_ingress(&ctx);
+    }
+
     if is_traffic_enabled() {
         if let Some(result) = process_module_result(handle_traffic_ingress(&ctx)) {
             return result;
@@ -40,6 +50,14 @@ pub fn shared_ingress(ctx: TcContext) -> i32 {
 
 #[classifier]
 pub fn shared_egress(ctx: TcContext) -> i32 {
+    // DHCP 与 ARP/NDP 监听只复制报文，放在最前面以免被限速丢弃的报文漏掉
+    if is_dhcp_enabled() {
+        let _ = handle_dhcp_egress(&ctx);
+    }
+    if is_arp_enabled() {
+        let _ = handle_arp_egress(&ctx);
+    }
+
     if is_traffic_enabled() {
         if let Some(result) = process_module_result(handle_traffic_egress(&ctx)) {
             return result;
@@ -0,0 +1,9 @@
+// ARP/NDP 监听 module maps
+
+use aya_ebpf::macros::map;
+use aya_ebpf::maps::RingBuf;
+
+// RingBuf for sending ARP and ICMPv6 NS/NA packets to userspace
+// Size: 64KB, neighbor discovery traffic is low volume
+#[map]
+pub static ARP_DATA: RingBuf = RingBuf::with_byte_size(1024 * 64, 0);
@@ -0,0 +1,122 @@
+// ARP/NDP 监听 module
+// 将 ARP 报文与 ICMPv6 邻居请求/通告（NS/NA）转发到用户态，用于检测网关仿冒、IP 冲突与 MAC 频繁变化
+// 只复制数据包，不修改也不拦截
+
+pub mod maps;
+
+use aya_ebpf::{bindings::TC_ACT_PIPE, helpers::bpf_ktime_get_ns, programs::TcContext};
+use bandix_common::PacketHeader;
+use core::cmp;
+
+use maps::ARP_DATA;
+
+const ETH_TYPE_ARP: u16 = 0x0806;
+const ETH_TYPE_IPV6: u16 = 0x86DD;
+const ETH_HEADER_LEN: usize = 14;
+const ETH_TYPE_OFFSET: usize = 12;
+const ARP_LEN: usize = 28;
+const IPV6_HEADER_LEN: usize = 40;
+const IPV6_NEXT_HEADER_OFFSET: usize = 6;
+const PROTO_ICMPV6: u8 = 58;
+// NS/NA 固定部分：类型、代码、校验和、标志/保留 8 字节 + 目标地址 16 字节
+const NDP_MIN_LEN: usize = 24;
+const ICMPV6_NEIGHBOR_SOLICIT: u8 = 135;
+const ICMPV6_NEIGHBOR_ADVERT: u8 = 136;
+
+// 以太网头 + IPv6 头 + NS/NA 与链路层地址选项不超过 128 字节
+const MAX_PAYLOAD: usize = 128;
+
+#[repr(C)]
+pub struct Record {
+    pub header: PacketHeader,
+    pub data: [u8; MAX_PAYLOAD],
+}
+
+#[inline(always)]
+pub fn handle_arp_ingress(ctx: &TcContext) -> Result<i32, ()> {
+    try_handle_arp(ctx, 0)
+}
+
+#[inline(always)]
+pub fn handle_arp_egress(ctx: &TcContext) -> Result<i32, ()> {
+    try_handle_arp(ctx, 1)
+}
+
+#[inline(always)]
+fn try_handle_arp(ctx: &TcContext, direction: u32) -> Result<i32, ()> {
+    let len = ctx.len() as usize;
+    if !is_neighbor_packet(ctx, len) {
+        return Ok(TC_ACT_PIPE);
+    }
+
+    let copy_len = cmp::min(len, MAX_PAYLOAD);
+
+    let mut entry = match ARP_DATA.reserve::<Record>(0) {
+        Some(entry) => entry,
+        None => return Ok(TC_ACT_PIPE),
+    };
+    let entry_ptr = entry.as_mut_ptr() as *mut u8;
+
+    let header = PacketHeader {
+        timestamp: unsafe { bpf_ktime_get_ns() },
+        packet_len: len as u32,
+        captured_len: copy_len as u32,
+        ifindex: 0,
+        direction, // 0=Ingress, 1=Egress
+    };
+    unsafe {
+        core::ptr::write_unaligned(entry_ptr as *mut PacketHeader, header);
+    }
+
+    let data_offset = core::mem::size_of::<PacketHeader>();
+    for i in 0..copy_len {
+        match ctx.load(i) {
+            Ok(byte) => unsafe {
+                *entry_ptr.add(data_offset + i) = byte;
+            },
+            Err(_) => {
+                unsafe {
+                    let header_ptr = entry_ptr as *mut PacketHeader;
+                    (*header_ptr).captured_len = i as u32;
+                }
+                break;
+            }
+        }
+    }
+
+    entry.submit(0);
+    Ok(TC_ACT_PIPE)
+}
+
+/// 检查是否为 ARP 报文，或不带扩展头的 ICMPv6 邻居请求/通告
+#[inline(always)]
+fn is_neighbor_packet(ctx: &TcContext, len: usize) -> bool {
+    if len < ETH_HEADER_LEN + ARP_LEN {
+        return false;
+    }
+
+    let eth_type = match (ctx.load::<u8>(ETH_TYPE_OFFSET), ctx.load::<u8>(ETH_TYPE_OFFSET + 1)) {
+        (Ok(b1), Ok(b2)) => u16::from_be_bytes([b1, b2]),
+        _ => return false,
+    };
+    if eth_type == ETH_TYPE_ARP {
+        return true;
+    }
+    if eth_type != ETH_TYPE_IPV6 || len < ETH_HEADER_LEN + IPV6_HEADER_LEN + NDP_MIN_LEN {
+        return false;
+    }
+
+    let next_header: u8 = match ctx.load(ETH_HEADER_LEN + IPV6_NEXT_HEADER_OFFSET) {
+        Ok(b) => b,
+        Err(_) => return false,
+    };
+    if next_header != PROTO_ICMPV6 {
+        return false;
+    }
+
+    let icmp_type: u8 = match ctx.load(ETH_HEADER_LEN + IPV6_HEADER_LEN) {
+        Ok(b) => b,
+        Err(_) => return false,
+    };
+    icmp_type == ICMPV6_NEIGHBOR_SOLICIT || icmp_type == ICMPV6_NEIGHBOR_ADVERT
+}
@@ -0,0 +1,9 @@
+// DHCP 监听 module maps
+
+use aya_ebpf::macros::map;
+use aya_ebpf::maps::RingBuf;
+
+// RingBuf for sending DHCP packets (UDP 67/68) to userspace
+// Size: 64KB, DHCP traffic is low volume
+#[map]
+pub static DHCP_DATA: RingBuf = RingBuf::with_byte_size(1024 * 64, 0);
@@ -0,0 +1,139 @@
+// DHCP 监听 module
+// 将 DHCPv4 报文（UDP 67/68）转发到用户态，用于学习设备的 IP、主机名与厂商类别
+// 只复制数据包，不修改也不拦截
+
+pub mod maps;
+
+use aya_ebpf::{bindings::TC_ACT_PIPE, helpers::bpf_ktime_get_ns, programs::TcContext};
+use bandix_common::PacketHeader;
+use core::cmp;
+
+use maps::DHCP_DATA;
+
+const DHCP_SERVER_PORT: u16 = 67;
+const DHCP_CLIENT_PORT: u16 = 68;
+const PROTO_UDP: u8 = 17;
+const ETH_TYPE_IPV4: u16 = 0x0800;
+const ETH_HEADER_LEN: usize = 14;
+const ETH_TYPE_OFFSET: usize = 12;
+const IPV4_HEADER_MIN_LEN: usize = 20;
+const IPV4_HEADER_MAX_LEN: usize = 60;
+const IPV4_IHL_OFFSET: usize = 0;
+const IPV4_PROTOCOL_OFFSET: usize = 9;
+const UDP_HEADER_LEN: usize = 8;
+// BOOTP 固定部分 236 字节 + magic cookie 4 字节
+const DHCP_MIN_LEN: usize = 240;
+
+// DHCP 报文通常不超过 576 字节，带较多选项时也在以太网 MTU 之内
+const MAX_PAYLOAD: usize = 1500;
+
+#[repr(C)]
+pub struct Record {
+    pub header: PacketHeader,
+    pub data: [u8; MAX_PAYLOAD],
+}
+
+#[inline(always)]
+pub fn handle_dhcp_ingress(ctx: &TcContext) -> Result<i32, ()> {
+    try_handle_dhcp(ctx, 0)
+}
+
+#[inline(always)]
+pub fn handle_dhcp_egress(ctx: &TcContext) -> Result<i32, ()> {
+    try_handle_dhcp(ctx, 1)
+}
+
+#[inline(always)]
+fn try_handle_dhcp(ctx: &TcContext, direction: u32) -> Result<i32, ()> {
+    let len = ctx.len() as usize;
+    if !is_dhcp_packet(ctx, len) {
+        return Ok(TC_ACT_PIPE);
+    }
+
+    let copy_len = cmp::min(len, MAX_PAYLOAD);
+
+    let mut entry = match DHCP_DATA.reserve::<Record>(0) {
+        Some(entry) => entry,
+        None => return Ok(TC_ACT_PIPE),
+    };
+    let entry_ptr = entry.as_mut_ptr() as *mut u8;
+
+    let header = PacketHeader {
+        timestamp: unsafe { bpf_ktime_get_ns() },
+        packet_len: len as u32,
+        captured_len: copy_len as u32,
+        ifindex: 0,
+        direction, // 0=Ingress, 1=Egress
+    };
+    unsafe {
+        core::ptr::write_unaligned(entry_ptr as *mut PacketHeader, header);
+    }
+
+    let data_offset = core::mem::size_of::<PacketHeader>();
+    for i in 0..copy_len {
+        match ctx.load(i) {
+            Ok(byte) => unsafe {
+                *entry_ptr.add(data_offset + i) = byte;
+            },
+            Err(_) => {
+                unsafe {
+                    let header_ptr = entry_ptr as *mut PacketHeader;
+                    (*header_ptr).captured_len = i as u32;
+                }
+                break;
+            }
+        }
+    }
+
+    entry.submit(0);
+    Ok(TC_ACT_PIPE)
+}
+
+/// 检查是否为 IPv4 UDP 67/68 之间的 DHCP 报文
+#[inline(always)]
+fn is_dhcp_packet(ctx: &TcContext, len: usize) -> bool {
+    if len < ETH_HEADER_LEN + IPV4_HEADER_MIN_LEN + UDP_HEADER_LEN + DHCP_MIN_LEN {
+        return false;
+    }
+
+    let eth_type = match (ctx.load::<u8>(ETH_TYPE_OFFSET), ctx.load::<u8>(ETH_TYPE_OFFSET + 1)) {
+        (Ok(b1), Ok(b2)) => u16::from_be_bytes([b1, b2]),
+        _ => return false,
+    };
+    if eth_type != ETH_TYPE_IPV4 {
+        return false;
+    }
+
+    let ip_header_start = ETH_HEADER_LEN;
+    let ihl_byte: u8 = match ctx.load(ip_header_start + IPV4_IHL_OFFSET) {
+        Ok(b) => b,
+        Err(_) => return false,
+    };
+    let ip_header_len = (ihl_byte & 0x0F) as usize * 4;
+    if ip_header_len < IPV4_HEADER_MIN_LEN || ip_header_len > IPV4_HEADER_MAX_LEN {
+        return false;
+    }
+
+    let protocol: u8 = match ctx.load(ip_header_start + IPV4_PROTOCOL_OFFSET) {
+        Ok(b) => b,
+        Err(_) => return false,
+    };
+    if protocol != PROTO_UDP {
+        return false;
+    }
+
+    let udp_header_start = ip_header_start + ip_header_len;
+    if len < udp_header_start + UDP_HEADER_LEN + DHCP_MIN_LEN {
+        return false;
+    }
+    let src_port = match (ctx.load::<u8>(udp_header_start), ctx.load::<u8>(udp_header_start + 1)) {
+        (Ok(b1), Ok(b2)) => u16::from_be_bytes([b1, b2]),
+        _ => return false,
+    };
+    let dst_port = match (ctx.load::<u8>(udp_header_start + 2), ctx.load::<u8>(udp_header_start + 3)) {
+        (Ok(b1), Ok(b2)) => u16::from_be_bytes([b1, b2]),
+        _ => return false,
+    };
+
+    (src_port == DHCP_CLIENT_PORT && dst_port == DHCP_SERVER_PORT) || (src_port == DHCP_SERVER_PORT && dst_port == DHCP_CLIENT_PORT)
+}
@@ -2,9 +2,18 @@
 // 这module contains DNS-related eBPF maps
 
 use aya_ebpf::macros::map;
-use aya_ebpf::maps::RingBuf;
+use aya_ebpf::maps::{Array, HashMap, RingBuf};
 
 // RingBuf for sending DNS packet data to userspace
 // Size: 256KB
 #[map]
 pub static DNS_DATA: RingBuf = RingBuf::with_byte_size(1024 * 256, 0);
+
+// DNS blocking rules, key: [mac (6 bytes, all zero = all devices), domain hash (8 bytes, little endian)]
+// value: block action (1=drop, 2=NXDOMAIN)
+#[map]
+pub static DNS_BLOCK_RULES: HashMap<[u8; 14], u8> = HashMap::with_max_entries(131072, 0);
+
+// DNS blocking config: index 0 = number of active rules (0 skips the lookup entirely)
+#[map]
+pub static DNS_BLOCK_CONFIG: Array<u32> = Array::with_max_entries(1, 0);
@@ -3,14 +3,22 @@
 
 pub mod maps;
 
-use aya_ebpf::{bindings::TC_ACT_PIPE, helpers::bpf_ktime_get_ns, programs::TcContext};
-use bandix_common::PacketHeader;
+use aya_ebpf::{
+    bindings::{TC_ACT_PIPE, TC_ACT_SHOT},
+    helpers::{bpf_ktime_get_ns, bpf_l3_csum_replace, bpf_redirect, bpf_skb_change_tail, bpf_skb_store_bytes},
+    programs::TcContext,
+};
+use bandix_common::{dns_block_key, dns_hash_byte, PacketHeader, DNS_BLOCK_ACTION_NXDOMAIN, DNS_HASH_OFFSET};
 use core::cmp;
 
-use maps::DNS_DATA;
+use maps::{DNS_BLOCK_CONFIG, DNS_BLOCK_RULES, DNS_DATA};
 
 // Protocol constants
 const DNS_PORT: u16 = 53;
+// 局域网名称发现协议：mDNS、LLMNR、NetBIOS 名称服务
+const MDNS_PORT: u16 = 5353;
+const LLMNR_PORT: u16 = 5355;
+const NETBIOS_NS_PORT: u16 = 137;
 const PROTO_UDP: u8 = 17;
 const PROTO_TCP: u8 = 6;
 const ETH_TYPE_IPV4: u16 = 0x0800;
@@ -48,6 +56,31 @@ const IPV6_NEXT_HEADER_OFFSET: usize = 6;
 // Define fixed maximum record structure for reserve<T>
 const MAX_PAYLOAD: usize = 1500;
 
+// DNS 拦截：QNAME 最多扫描的字节数（RFC 1035 上限 255），以及计算哈希的后缀数
+// （保留最后 MAX_SUFFIXES 个后缀，例如 a.b.example.com -> ..., example.com, com）
+const MAX_QNAME_LEN: usize = 255;
+const MAX_SUFFIXES: usize = 8;
+
+// 将被拦截的查询改写为响应时用到的以太网 / IPv4 / UDP 字段偏移
+const ETH_ADDR_LEN: usize = 6;
+const IPV4_TOTAL_LEN_OFFSET: usize = 2;
+const IPV4_CHECKSUM_OFFSET: usize = 10;
+const IPV4_SRC_OFFSET: usize = 12;
+const UDP_LEN_OFFSET: usize = 4;
+const UDP_CHECKSUM_OFFSET: usize = 6;
+// QTYPE + QCLASS
+const DNS_QUESTION_TAIL_LEN: usize = 4;
+
+/// DNS 负载在数据包中的位置
+#[derive(Clone, Copy)]
+struct DnsPayload {
+    dns_offset: usize, // DNS 数据起始偏移（TCP 时指向 2 字节长度前缀）
+    is_tcp: bool,
+    is_ipv6: bool,
+    has_header: bool, // 负载以 DNS 头开始；多段 TCP 消息的后续分段为 false
+    local_name: bool, // mDNS / LLMNR / NetBIOS 名称通告，仅用于用户态学习设备名称，不参与拦截
+}
+
 #[repr(C)]
 pub struct Record {
     pub header: PacketHeader,
@@ -78,10 +111,13 @@ fn try_handle_dns(ctx: &TcContext, direction: u32) -> Result<i32, ()> {
     }
 
     // 过滤DNS packets in kernel space, only process DNS traffic
-    if !is_dns_packet(&ctx, len as usize) {
-        // Not a DNS packet, pass through without processing
-        return Ok(TC_ACT_PIPE);
-    }
+    let payload = match find_dns_payload(&ctx, len as usize) {
+        Some(payload) => payload,
+        None => {
+            // Not a DNS packet, pass through without processing
+            return Ok(TC_ACT_PIPE);
+        }
+    };
 
     // 计算data length to copy (not exceeding MAX_PAYLOAD)
     let copy_len = cmp::min(len as usize, MAX_PAYLOAD);
@@ -90,8 +126,8 @@ fn try_handle_dns(ctx: &TcContext, direction: u32) -> Result<i32, ()> {
     let mut entry = match DNS_DATA.reserve::<Record>(0) {
         Some(entry) => entry,
         None => {
-            // Ringbuf is full, skip this packet
-            return Ok(TC_ACT_PIPE);
+            // Ringbuf 已满：不记录，但仍然执行拦截
+            return Ok(dns_block_verdict(ctx, &payload, len as usize, direction));
         }
     };
 
@@ -131,7 +167,248 @@ fn try_handle_dns(ctx: &TcContext, direction: u32) -> Result<i32, ()> {
     // Submit data to userspace
     entry.submit(0);
 
-    Ok(TC_ACT_PIPE)
+    // 先记录再判断是否拦截，被拦截的查询在用户态仍可见
+    Ok(dns_block_verdict(ctx, &payload, len as usize, direction))
+}
+
+/// 判断是否需要拦截该 DNS 数据包
+/// 仅处理入口方向（来自局域网设备）的查询，返回 TC 动作
+#[inline(always)]
+fn dns_block_verdict(ctx: &TcContext, payload: &DnsPayload, len: usize, direction: u32) -> i32 {
+    // 名称通告只复制到用户态，不拦截也不改写（组播查询改写后会伪造来自组播地址的应答）
+    if direction != 0 || !payload.has_header || payload.local_name {
+        return TC_ACT_PIPE;
+    }
+
+    // 未加载规则时跳过查找
+    let rule_count = match DNS_BLOCK_CONFIG.get(0) {
+        Some(count) => unsafe { core::ptr::read_volatile(count as *const u32) },
+        None => 0,
+    };
+    if rule_count == 0 {
+        return TC_ACT_PIPE;
+    }
+
+    let dns_start = if payload.is_tcp { payload.dns_offset + 2 } else { payload.dns_offset };
+
+    // 只拦截查询（QR 位为 0）
+    let flags_hi: u8 = match ctx.load(dns_start + 2) {
+        Ok(b) => b,
+        Err(_) => return TC_ACT_PIPE,
+    };
+    if flags_hi & 0x80 != 0 {
+        return TC_ACT_PIPE;
+    }
+
+    // 源 MAC 即发起查询的设备
+    let mut mac = [0u8; 6];
+    for i in 0..ETH_ADDR_LEN {
+        mac[i] = match ctx.load(ETH_ADDR_LEN + i) {
+            Ok(b) => b,
+            Err(_) => return TC_ACT_PIPE,
+        };
+    }
+
+    let qname_offset = dns_start + DNS_HEADER_MIN_LEN;
+    let (hashes, label_count, qname_len) = match qname_suffix_hashes(ctx, qname_offset, len) {
+        Qname::Parsed(hashes, label_count, qname_len) => (hashes, label_count, qname_len),
+        Qname::Invalid => return TC_ACT_PIPE,
+        // 无法计算后缀哈希，按拦截处理，避免用超长名称绕过规则
+        Qname::TooLong => return TC_ACT_SHOT,
+    };
+    let action = lookup_block_action(&mac, &hashes, label_count);
+    if action == 0 {
+        return TC_ACT_PIPE;
+    }
+
+    if action == DNS_BLOCK_ACTION_NXDOMAIN && !payload.is_tcp && !payload.is_ipv6 {
+        let question_end = qname_offset + qname_len + DNS_QUESTION_TAIL_LEN;
+        if let Ok(ret) = rewrite_nxdomain_ipv4(ctx, payload.dns_offset, question_end, len) {
+            return ret;
+        }
+    }
+
+    TC_ACT_SHOT
+}
+
+/// QNAME 解析结果
+enum Qname {
+    /// (各后缀哈希, 标签数, QNAME 字节数（含结尾的 0）)
+    Parsed([u64; MAX_SUFFIXES], usize, usize),
+    /// 名称无效、使用压缩指针或超出数据包
+    Invalid,
+    /// 超过 MAX_QNAME_LEN 字节仍未结束
+    TooLong,
+}
+
+/// 计算 QNAME 各后缀的哈希（与用户态 dns_name_hash 结果一致）
+#[inline(always)]
+fn qname_suffix_hashes(ctx: &TcContext, qname_offset: usize, len: usize) -> Qname {
+    let mut hashes = [0u64; MAX_SUFFIXES];
+    let mut label_count = 0usize;
+    let mut label_remaining = 0u8;
+
+    for i in 0..MAX_QNAME_LEN {
+        let offset = qname_offset + i;
+        if offset >= len {
+            return Qname::Invalid;
+        }
+        let b: u8 = match ctx.load(offset) {
+            Ok(b) => b,
+            Err(_) => return Qname::Invalid,
+        };
+
+        if label_remaining == 0 {
+            if b == 0 {
+                // 名称结束
+                return Qname::Parsed(hashes, label_count, i + 1);
+            }
+            if b & 0xC0 != 0 {
+                // 问题部分不应出现压缩指针
+                return Qname::Invalid;
+            }
+            // 新标签：先给已有的后缀追加 '.'，再开始一个新后缀
+            for s in 0..MAX_SUFFIXES {
+                if s < label_count {
+                    hashes[s] = dns_hash_byte(hashes[s], b'.');
+                }
+            }
+            hashes[label_count % MAX_SUFFIXES] = DNS_HASH_OFFSET;
+            label_count += 1;
+            label_remaining = b;
+        } else {
+            for s in 0..MAX_SUFFIXES {
+                if s < label_count {
+                    hashes[s] = dns_hash_byte(hashes[s], b);
+                }
+            }
+            label_remaining -= 1;
+        }
+    }
+
+    Qname::TooLong
+}
+
+/// 查找拦截动作：先匹配设备规则，再匹配全局规则（mac 全 0）
+#[inline(always)]
+fn lookup_block_action(mac: &[u8; 6], hashes: &[u64; MAX_SUFFIXES], label_count: usize) -> u8 {
+    let valid = cmp::min(label_count, MAX_SUFFIXES);
+    let any_device = [0u8; 6];
+
+    for s in 0..MAX_SUFFIXES {
+        if s >= valid {
+            break;
+        }
+        if let Some(action) = unsafe { DNS_BLOCK_RULES.get(&dns_block_key(mac, hashes[s])) } {
+            return *action;
+        }
+        if let Some(action) = unsafe { DNS_BLOCK_RULES.get(&dns_block_key(&any_device, hashes[s])) } {
+            return *action;
+        }
+    }
+
+    0
+}
+
+/// 将 IPv4/UDP 查询就地改写为 NXDOMAIN 响应，并从同一接口回送给设备
+/// - 截掉问题部分之后的数据（例如 EDNS OPT 记录），同步修改 IPv4 / UDP 长度
+/// - 交换 MAC、IP、端口（IPv4 头校验和不受交换影响，UDP 校验和置 0 表示不校验）
+/// - 设置 QR/RA 标志和 RCODE=3，只保留一个问题，清空应答/授权/附加计数
+#[inline(always)]
+fn rewrite_nxdomain_ipv4(ctx: &TcContext, dns_offset: usize, question_end: usize, len: usize) -> Result<i32, ()> {
+    let skb = ctx.skb.skb;
+    let udp_offset = dns_offset - UDP_HEADER_LEN;
+    let ip_offset = ETH_HEADER_LEN;
+
+    if question_end > len {
+        return Err(());
+    }
+    if question_end < len {
+        let ret = unsafe { bpf_skb_change_tail(skb, question_end as u32, 0) };
+        if ret != 0 {
+            return Err(());
+        }
+
+        // IPv4 总长度：用增量方式更新头校验和
+        let old_total = [
+            ctx.load::<u8>(ip_offset + IPV4_TOTAL_LEN_OFFSET).map_err(|_| ())?,
+            ctx.load::<u8>(ip_offset + IPV4_TOTAL_LEN_OFFSET + 1).map_err(|_| ())?,
+        ];
+        let new_total = ((question_end - ip_offset) as u16).to_be_bytes();
+        let ret = unsafe {
+            bpf_l3_csum_replace(
+                skb,
+                (ip_offset + IPV4_CHECKSUM_OFFSET) as u32,
+                u16::from_ne_bytes(old_total) as u64,
+                u16::from_ne_bytes(new_total) as u64,
+                2,
+            )
+        };
+        if ret != 0 {
+            return Err(());
+        }
+        store_bytes(skb, ip_offset + IPV4_TOTAL_LEN_OFFSET, &new_total)?;
+
+        let new_udp_len = ((question_end - udp_offset) as u16).to_be_bytes();
+        store_bytes(skb, udp_offset + UDP_LEN_OFFSET, &new_udp_len)?;
+    }
+
+    // 以太网：交换目的与源 MAC
+    let mut eth = [0u8; 12];
+    for i in 0..12 {
+        eth[i] = ctx.load(i).map_err(|_| ())?;
+    }
+    let mut swapped_eth = [0u8; 12];
+    swapped_eth[..ETH_ADDR_LEN].copy_from_slice(&eth[ETH_ADDR_LEN..]);
+    swapped_eth[ETH_ADDR_LEN..].copy_from_slice(&eth[..ETH_ADDR_LEN]);
+    store_bytes(skb, 0, &swapped_eth)?;
+
+    // IPv4：交换源与目的地址
+    let mut addrs = [0u8; 8];
+    for i in 0..8 {
+        addrs[i] = ctx.load(ip_offset + IPV4_SRC_OFFSET + i).map_err(|_| ())?;
+    }
+    let mut swapped_addrs = [0u8; 8];
+    swapped_addrs[..4].copy_from_slice(&addrs[4..]);
+    swapped_addrs[4..].copy_from_slice(&addrs[..4]);
+    store_bytes(skb, ip_offset + IPV4_SRC_OFFSET, &swapped_addrs)?;
+
+    // UDP：交换端口并清空校验和
+    let mut ports = [0u8; 4];
+    for i in 0..4 {
+        ports[i] = ctx.load(udp_offset + i).map_err(|_| ())?;
+    }
+    let swapped_ports = [ports[2], ports[3], ports[0], ports[1]];
+    store_bytes(skb, udp_offset, &swapped_ports)?;
+    store_bytes(skb, udp_offset + UDP_CHECKSUM_OFFSET, &[0u8; 2])?;
+
+    // DNS 头：QR=1，保留 opcode 与 RD，RA=1，RCODE=3（NXDOMAIN）
+    let flags_hi: u8 = ctx.load(dns_offset + 2).map_err(|_| ())?;
+    let flags = [0x80 | (flags_hi & 0x79), 0x80 | 0x03];
+    store_bytes(skb, dns_offset + 2, &flags)?;
+    // QDCOUNT = 1，ANCOUNT / NSCOUNT / ARCOUNT = 0
+    store_bytes(skb, dns_offset + 4, &[0u8, 1, 0, 0, 0, 0, 0, 0])?;
+
+    let ifindex = unsafe { (*skb).ifindex };
+    Ok(unsafe { bpf_redirect(ifindex, 0) } as i32)
+}
+
+#[inline(always)]
+fn store_bytes<const N: usize>(skb: *mut aya_ebpf::bindings::__sk_buff, offset: usize, bytes: &[u8; N]) -> Result<(), ()> {
+    let ret = unsafe {
+        bpf_skb_store_bytes(
+            skb as *mut _,
+            offset as u32,
+            bytes.as_ptr() as *const core::ffi::c_void,
+            N as u32,
+            0,
+        )
+    };
+    if ret == 0 {
+        Ok(())
+    } else {
+        Err(())
+    }
 }
 
 /// 验证DNS header format
@@ -208,60 +485,61 @@ fn validate_dns_header(ctx: &TcContext, dns_offset: usize, len: usize, is_tcp: b
 /// 检查是否packet is DNS packet in kernel space
 /// Check Ethernet type, IP protocol and UDP port
 /// Supports IPv4 and IPv6
-fn is_dns_packet(ctx: &TcContext, len: usize) -> bool {
+/// 返回 DNS 负载所在位置，不是 DNS 数据包时返回 None
+fn find_dns_payload(ctx: &TcContext, len: usize) -> Option<DnsPayload> {
     // At least need Ethernet header
     if len < ETH_HEADER_LEN {
-        return false;
+        return None;
     }
 
     // Read Ethernet type
     let eth_type_bytes = match (ctx.load(ETH_TYPE_OFFSET), ctx.load(ETH_TYPE_OFFSET + 1)) {
         (Ok(b1), Ok(b2)) => [b1, b2],
-        _ => return false,
+        _ => return None,
     };
     let eth_type = u16::from_be_bytes(eth_type_bytes);
 
     // 处理IPv4 or IPv6
     match eth_type {
-        ETH_TYPE_IPV4 => is_dns_ipv4(ctx, len),
-        ETH_TYPE_IPV6 => is_dns_ipv6(ctx, len),
-        _ => false,
+        ETH_TYPE_IPV4 => find_dns_ipv4(ctx, len),
+        ETH_TYPE_IPV6 => find_dns_ipv6(ctx, len),
+        _ => None,
     }
 }
 
 /// Check IPv4 DNS packet (optimized: port check first)
-fn is_dns_ipv4(ctx: &TcContext, len: usize) -> bool {
+fn find_dns_ipv4(ctx: &TcContext, len: usize) -> Option<DnsPayload> {
     // IPv4 header start position (after Ethernet header)
     let ip_header_start = ETH_HEADER_LEN;
 
     // At least need IPv4 header minimum length
     if len < ip_header_start + IPV4_HEADER_MIN_LEN {
-        return false;
+        return None;
     }
 
     // Read IPv4 header length (IHL, Internet Header Length)
     // IHL is in the lower 4 bits of the first byte, unit is 4 bytes
     let ihl_byte: u8 = match ctx.load(ip_header_start + IPV4_IHL_OFFSET) {
         Ok(b) => b,
-        Err(_) => return false,
+        Err(_) => return None,
     };
     let ihl = (ihl_byte & 0x0F) as usize;
     let ip_header_len = ihl * 4;
 
     // Validate IPv4 header length
     if ip_header_len < IPV4_HEADER_MIN_LEN || ip_header_len > IPV4_HEADER_MAX_LEN {
-        return false;
+        return None;
     }
 
     // Check if packet length is sufficient
     if len < ip_header_start + ip_header_len {
-        return false;
+        return None;
     }
 
     // Check protocol type, DNS uses UDP or TCP
     let protocol: u8 = match ctx.load(ip_header_start + IPV4_PROTOCOL_OFFSET) {
         Ok(b) => b,
-        Err(_) => return false,
+        Err(_) => return None,
     };
 
     // Support both UDP and TCP
@@ -272,30 +550,23 @@ fn is_dns_ipv4(ctx: &TcContext, len: usize) -> bool {
 
             // Check UDP header length
             if len < udp_header_start + UDP_HEADER_LEN {
-                return false;
+                return None;
             }
 
             // Parse UDP ports - check port first for early return
             let src_port_bytes = match (ctx.load(udp_header_start), ctx.load(udp_header_start + 1)) {
                 (Ok(b1), Ok(b2)) => [b1, b2],
-                _ => return false,
+                _ => return None,
             };
             let src_port = u16::from_be_bytes(src_port_bytes);
 
             let dst_port_bytes = match (ctx.load(udp_header_start + 2), ctx.load(udp_header_start + 3)) {
                 (Ok(b1), Ok(b2)) => [b1, b2],
-                _ => return false,
+                _ => return None,
             };
             let dst_port = u16::from_be_bytes(dst_port_bytes);
 
-            // 检查是否either port is DNS port
-            if src_port != DNS_PORT && dst_port != DNS_PORT {
-                return false;
-            }
-
-            // Port matches, now validate DNS header format
-            let dns_offset = udp_header_start + UDP_HEADER_LEN;
-            validate_dns_header(ctx, dns_offset, len, false)
+            find_udp_payload(ctx, udp_header_start + UDP_HEADER_LEN, len, src_port, dst_port, false)
         }
         PROTO_TCP => {
             // TCP DNS
@@ -303,46 +574,111 @@ fn is_dns_ipv4(ctx: &TcContext, len: usize) -> bool {
 
             // Check TCP header minimum length
             if len < tcp_header_start + TCP_HEADER_MIN_LEN {
-                return false;
+                return None;
             }
 
             // Parse TCP ports - check port first for early return
             let src_port_bytes = match (ctx.load(tcp_header_start), ctx.load(tcp_header_start + 1)) {
                 (Ok(b1), Ok(b2)) => [b1, b2],
-                _ => return false,
+                _ => return None,
             };
             let src_port = u16::from_be_bytes(src_port_bytes);
 
             let dst_port_bytes = match (ctx.load(tcp_header_start + 2), ctx.load(tcp_header_start + 3)) {
                 (Ok(b1), Ok(b2)) => [b1, b2],
-                _ => return false,
+                _ => return None,
             };
             let dst_port = u16::from_be_bytes(dst_port_bytes);
 
             // 检查是否either port is DNS port
             if src_port != DNS_PORT && dst_port != DNS_PORT {
-                return false;
+                return None;
             }
 
             // Port matches, now validate DNS header format
             // TCP header data offset is in bits 12-15 of byte 12, unit is 4 bytes
             let data_offset_byte: u8 = match ctx.load(tcp_header_start + 12) {
                 Ok(b) => b,
-                Err(_) => return false,
+                Err(_) => return None,
             };
             let tcp_header_len = ((data_offset_byte >> 4) as usize) * 4;
             if tcp_header_len < TCP_HEADER_MIN_LEN {
-                return false;
+                return None;
             }
 
             let dns_offset = tcp_header_start + tcp_header_len;
-            validate_dns_header(ctx, dns_offset, len, true)
+            if validate_dns_header(ctx, dns_offset, len, true) {
+                Some(DnsPayload {
+                    dns_offset,
+                    is_tcp: true,
+                    is_ipv6: false,
+                    has_header: true,
+                    local_name: false,
+                })
+            } else {
+                // 多段 TCP DNS 消息的后续分段：交给用户态重组
+                // 用 IP 总长度判断是否有负载，避免把带填充的纯 ACK 也送上去
+                let total_len_bytes = match (ctx.load(ip_header_start + 2), ctx.load(ip_header_start + 3)) {
+                    (Ok(b1), Ok(b2)) => [b1, b2],
+                    _ => return None,
+                };
+                let ip_end = ip_header_start + u16::from_be_bytes(total_len_bytes) as usize;
+                if ip_end > dns_offset {
+                    Some(DnsPayload {
+                        dns_offset,
+                        is_tcp: true,
+                        is_ipv6: false,
+                        has_header: false,
+                        local_name: false,
+                    })
+                } else {
+                    None
+                }
+            }
         }
         _ => {
             // Not UDP or TCP, skip
-            false
+            None
+        }
+    }
+}
+
+/// 按端口识别 UDP 负载：DNS（53）需要通过头部校验；名称发现协议只要求完整的头部，
+/// 因为 NetBIOS 的刷新 / 注册操作码超出了 DNS 头部校验允许的范围
+#[inline(always)]
+fn find_udp_payload(ctx: &TcContext, dns_offset: usize, len: usize, src_port: u16, dst_port: u16, is_ipv6: bool) -> Option<DnsPayload> {
+    if src_port == DNS_PORT || dst_port == DNS_PORT {
+        if !validate_dns_header(ctx, dns_offset, len, false) {
+            return None;
+        }
+        return Some(DnsPayload {
+            dns_offset,
+            is_tcp: false,
+            is_ipv6,
+            has_header: true,
+            local_name: false,
+        });
+    }
+
+    if is_local_name_port(src_port) || is_local_name_port(dst_port) {
+        if len < dns_offset + DNS_HEADER_MIN_LEN {
+            return None;
         }
+        return Some(DnsPayload {
+            dns_offset,
+            is_tcp: false,
+            is_ipv6,
+            has_header: true,
+            local_name: true,
+        });
     }
+
+    None
+}
+
+#[inline(always)]
+fn is_local_name_port(port: u16) -> bool {
+    port == MDNS_PORT || port == LLMNR_PORT || port == NETBIOS_NS_PORT
 }
 
 /// 检查是否protocol is a known IPv6 extension header
@@ -362,19 +698,19 @@ fn is_ipv6_extension_header(next_header: u8) -> bool {
 
 /// Check IPv6 DNS packet (optimized: port check first)
 /// Supports processing IPv6 extension headers (unroll loop to avoid verifier issues)
-fn is_dns_ipv6(ctx: &TcContext, len: usize) -> bool {
+fn find_dns_ipv6(ctx: &TcContext, len: usize) -> Option<DnsPayload> {
     // IPv6 header start position (after Ethernet header)
     let ipv6_header_start = ETH_HEADER_LEN;
 
     // At least need IPv6 header
     if len < ipv6_header_start + IPV6_HEADER_LEN {
-        return false;
+        return None;
     }
 
     // IPv6 Next Header field is at offset 6 (relative to IPv6 header)
     let mut next_header: u8 = match ctx.load(ipv6_header_start + IPV6_NEXT_HEADER_OFFSET) {
         Ok(b) => b,
-        Err(_) => return false,
+        Err(_) => return None,
     };
 
     // Current header position (starting after IPv6 header)
@@ -388,78 +724,78 @@ fn is_dns_ipv6(ctx: &TcContext, len: usize) -> bool {
     if next_header != PROTO_UDP && next_header != PROTO_TCP {
         // 如果it's not an extension header, it's not a valid DNS packet
         if !is_ipv6_extension_header(next_header) {
-            return false;
+            return None;
         }
         if len < offset + IPV6_EXT_HEADER_MIN_LEN {
-            return false;
+            return None;
         }
         let ext_len: u8 = match ctx.load(offset + 1) {
             Ok(b) => b,
-            Err(_) => return false,
+            Err(_) => return None,
         };
         let ext_total = IPV6_EXT_HEADER_MIN_LEN + (ext_len as usize * 8);
         if len < offset + ext_total {
-            return false;
+            return None;
         }
         next_header = match ctx.load(offset) {
             Ok(b) => b,
-            Err(_) => return false,
+            Err(_) => return None,
         };
         offset += ext_total;
         if offset >= len {
-            return false;
+            return None;
         }
     }
 
     // Second extension header (if any)
     if next_header != PROTO_UDP && next_header != PROTO_TCP {
         if !is_ipv6_extension_header(next_header) {
-            return false;
+            return None;
         }
         if len < offset + IPV6_EXT_HEADER_MIN_LEN {
-            return false;
+            return None;
         }
         let ext_len: u8 = match ctx.load(offset + 1) {
             Ok(b) => b,
-            Err(_) => return false,
+            Err(_) => return None,
         };
         let ext_total = IPV6_EXT_HEADER_MIN_LEN + (ext_len as usize * 8);
         if len < offset + ext_total {
-            return false;
+            return None;
         }
         next_header = match ctx.load(offset) {
             Ok(b) => b,
-            Err(_) => return false,
+            Err(_) => return None,
         };
         offset += ext_total;
         if offset >= len {
-            return false;
+            return None;
         }
     }
 
     // Third extension header (if any)
     if next_header != PROTO_UDP && next_header != PROTO_TCP {
         if !is_ipv6_extension_header(next_header) {
-            return false;
+            return None;
         }
         if len < offset + IPV6_EXT_HEADER_MIN_LEN {
-            return false;
+            return None;
         }
         let ext_len: u8 = match ctx.load(offset + 1) {
             Ok(b) => b,
-            Err(_) => return false,
+            Err(_) => return None,
         };
         let ext_total = IPV6_EXT_HEADER_MIN_LEN + (ext_len as usize * 8);
         if len < offset + ext_total {
-            return false;
+            return None;
         }
         next_header = match ctx.load(offset) {
             Ok(b) => b,
-            Err(_) => return false,
+            Err(_) => return None,
         };
         offset += ext_total;
         if offset >= len {
-            return false;
+            return None;
         }
     }
 
@@ -469,73 +805,92 @@ fn is_dns_ipv6(ctx: &TcContext, len: usize) -> bool {
             // UDP DNS
             // Check UDP header length
             if len < offset + UDP_HEADER_LEN {
-                return false;
+                return None;
             }
 
             // Parse UDP ports - check port first for early return
             let src_port_bytes = match (ctx.load(offset), ctx.load(offset + 1)) {
                 (Ok(b1), Ok(b2)) => [b1, b2],
-                _ => return false,
+                _ => return None,
             };
             let src_port = u16::from_be_bytes(src_port_bytes);
 
             let dst_port_bytes = match (ctx.load(offset + 2), ctx.load(offset + 3)) {
                 (Ok(b1), Ok(b2)) => [b1, b2],
-                _ => return false,
+                _ => return None,
             };
             let dst_port = u16::from_be_bytes(dst_port_bytes);
 
-            // 检查是否either port is DNS port
-            if src_port != DNS_PORT && dst_port != DNS_PORT {
-                return false;
-            }
-
-            // Port matches, now validate DNS header format
-            let dns_offset = offset + UDP_HEADER_LEN;
-            validate_dns_header(ctx, dns_offset, len, false)
+            find_udp_payload(ctx, offset + UDP_HEADER_LEN, len, src_port, dst_port, true)
         }
         PROTO_TCP => {
             // TCP DNS
             // Check TCP header minimum length
             if len < offset + TCP_HEADER_MIN_LEN {
-                return false;
+                return None;
             }
 
             // Parse TCP ports - check port first for early return
             let src_port_bytes = match (ctx.load(offset), ctx.load(offset + 1)) {
                 (Ok(b1), Ok(b2)) => [b1, b2],
-                _ => return false,
+                _ => return None,
             };
             let src_port = u16::from_be_bytes(src_port_bytes);
 
             let dst_port_bytes = match (ctx.load(offset + 2), ctx.load(offset + 3)) {
                 (Ok(b1), Ok(b2)) => [b1, b2],
-                _ => return false,
+                _ => return None,
             };
             let dst_port = u16::from_be_bytes(dst_port_bytes);
 
             // 检查是否either port is DNS port
             if src_port != DNS_PORT && dst_port != DNS_PORT {
-                return false;
+                return None;
             }
 
             // Port matches, now validate DNS header format
             // TCP header data offset is in bits 12-15 of byte 12, unit is 4 bytes
             let data_offset_byte: u8 = match ctx.load(offset + 12) {
                 Ok(b) => b,
-                Err(_) => return false,
+                Err(_) => return None,
             };
             let tcp_header_len = ((data_offset_byte >> 4) as usize) * 4;
             if tcp_header_len < TCP_HEADER_MIN_LEN {
-                return false;
+                return None;
             }
 
             let dns_offset = offset + tcp_header_len;
-            validate_dns_header(ctx, dns_offset, len, true)
+            if validate_dns_header(ctx, dns_offset, len, true) {
+                Some(DnsPayload {
+                    dns_offset,
+                    is_tcp: true,
+                    is_ipv6: true,
+                    has_header: true,
+                    local_name: false,
+                })
+            } else {
+                // 后续分段，处理方式同 find_dns_ipv4
+                let payload_len_bytes = match (ctx.load(ipv6_header_start + 4), ctx.load(ipv6_header_start + 5)) {
+                    (Ok(b1), Ok(b2)) => [b1, b2],
+                    _ => return None,
+                };
+                let ip_end = ipv6_header_start + IPV6_HEADER_LEN + u16::from_be_bytes(payload_len_bytes) as usize;
+                if ip_end > dns_offset {
+                    Some(DnsPayload {
+                        dns_offset,
+                        is_tcp: true,
+                        is_ipv6: true,
+                        has_header: false,
+                        local_name: false,
+                    })
+                } else {
+                    None
+                }
+            }
         }
         _ => {
             // Not UDP or TCP
-            false
+            None
         }
     }
 }
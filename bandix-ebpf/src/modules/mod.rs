@@ -1,2 +1,4 @@
+pub mod arp;
+pub mod dhcp;
 pub mod dns;
 pub mod traffic;
@@ -1,5 +1,5 @@
 use aya_ebpf::macros::map;
-use aya_ebpf::maps::HashMap;
+use aya_ebpf::maps::{HashMap, LruHashMap};
 
 // ============================================================================
 // Traffic Monitoring Maps
@@ -20,3 +20,12 @@ pub static MAC_RATE_LIMITS: HashMap<[u8; 6], [u64; 2]> = HashMap::with_max_entri
 // rate bucket status: [download token number, upload token number, last update time(ns)]
 #[map]
 pub static RATE_BUCKETS: HashMap<[u8; 6], [u64; 3]> = HashMap::with_max_entries(1024, 0);
+
+// ============================================================================
+// Remote Endpoint Maps
+// ============================================================================
+
+// wan traffic of a mac address per remote ip, key: [mac (6 bytes), remote ip (16 bytes, ipv4 is ipv4-mapped ::ffff:a.b.c.d)]
+// value: [wan send bytes, wan receive bytes]
+#[map]
+pub static REMOTE_TRAFFIC: LruHashMap<[u8; 22], [u64; 2]> = LruHashMap::with_max_entries(16384, 0);
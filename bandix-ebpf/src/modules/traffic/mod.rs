@@ -11,7 +11,7 @@ use network_types::eth::EthHdr;
 use network_types::ip::{Ipv4Hdr, Ipv6Hdr};
 
 use crate::utils::{get_current_time, is_subnet_ip, is_subnet_ipv6, min, ptr_at, subnet::IPV4_SUBNET_INFO};
-use maps::{MAC_RATE_LIMITS, MAC_TRAFFIC, RATE_BUCKETS};
+use maps::{MAC_RATE_LIMITS, MAC_TRAFFIC, RATE_BUCKETS, REMOTE_TRAFFIC};
 
 // ============================================================================
 // Public Entry Points
@@ -233,6 +233,13 @@ fn monitor_traffic(src_mac: &[u8; 6], dst_mac: &[u8; 6], data_len: u64, src_ip:
         let is_local_traffic = src_is_local;
         update_traffic_stats(&dst_mac, data_len, true, is_local_traffic);
     }
+
+    // record wan traffic per remote ip (used for domain attribution in userspace)
+    if src_is_local && !dst_is_local {
+        update_remote_traffic(&src_mac, &ipv4_mapped(dst_ip), data_len, false);
+    } else if dst_is_local && !src_is_local {
+        update_remote_traffic(&dst_mac, &ipv4_mapped(src_ip), data_len, true);
+    }
 }
 
 #[inline]
@@ -252,6 +259,46 @@ fn monitor_traffic_v6(src_mac: &[u8; 6], dst_mac: &[u8; 6], data_len: u64, src_i
         let is_local_traffic = src_is_local;
         update_traffic_stats(&dst_mac, data_len, true, is_local_traffic);
     }
+
+    // record wan traffic per remote ip (used for domain attribution in userspace)
+    if src_is_local && !dst_is_local {
+        update_remote_traffic(&src_mac, dst_ip, data_len, false);
+    } else if dst_is_local && !src_is_local {
+        update_remote_traffic(&dst_mac, src_ip, data_len, true);
+    }
+}
+
+#[inline(always)]
+fn ipv4_mapped(ip: &[u8; 4]) -> [u8; 16] {
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, ip[0], ip[1], ip[2], ip[3]]
+}
+
+#[inline]
+fn update_remote_traffic(mac: &[u8; 6], remote_ip: &[u8; 16], data_len: u64, is_rx: bool) {
+    let mut key = [0u8; 22];
+    key[0..6].copy_from_slice(mac);
+    key[6..22].copy_from_slice(remote_ip);
+
+    match REMOTE_TRAFFIC.get_ptr_mut(&key) {
+        Some(t) => unsafe {
+            if is_rx {
+                // wan receive bytes
+                (*t)[1] = (*t)[1] + data_len;
+            } else {
+                // wan send bytes
+                (*t)[0] = (*t)[0] + data_len;
+            }
+        },
+        None => {
+            let mut stats = [0u64; 2];
+            if is_rx {
+                stats[1] = data_len;
+            } else {
+                stats[0] = data_len;
+            }
+            let _ = REMOTE_TRAFFIC.insert(&key, &stats, 0);
+        }
+    }
 }
 
 // ============================================================================
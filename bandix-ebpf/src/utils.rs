@@ -23,11 +23,13 @@ pub mod config {
     use aya_ebpf::macros::map;
     use aya_ebpf::maps::Array;
 
-    // Module enable flags: [traffic_enabled, dns_enabled]
+    // Module enable flags: [traffic_enabled, dns_enabled, dhcp_enabled, arp_enabled]
     // Index 0: traffic module (0=disabled, 1=enabled)
     // Index 1: DNS module (0=disabled, 1=enabled)
+    // Index 2: DHCP snooping (0=disabled, 1=enabled)
+    // Index 3: ARP/NDP spoof detection (0=disabled, 1=enabled)
     #[map]
-    pub static MODULE_ENABLE_FLAGS: Array<u8> = Array::with_max_entries(2, 0);
+    pub static MODULE_ENABLE_FLAGS: Array<u8> = Array::with_max_entries(4, 0);
 }
 
 use config::MODULE_ENABLE_FLAGS;
@@ -209,6 +211,8 @@ pub fn get_current_time() -> u64 {
 pub mod module_index {
     pub const TRAFFIC: u32 = 0;
     pub const DNS: u32 = 1;
+    pub const DHCP: u32 = 2;
+    pub const ARP: u32 = 3;
 }
 
 /// 检查是否a module is enabled by index
@@ -232,3 +236,15 @@ pub fn is_traffic_enabled() -> bool {
 pub fn is_dns_enabled() -> bool {
     is_module_enabled(module_index::DNS)
 }
+
+/// 检查是否DHCP snooping is enabled
+#[inline(always)]
+pub fn is_dhcp_enabled() -> bool {
+    is_module_enabled(module_index::DHCP)
+}
+
+/// 检查是否ARP/NDP spoof detection is enabled
+#[inline(always)]
+pub fn is_arp_enabled() -> bool {
+    is_module_enabled(module_index::ARP)
+}
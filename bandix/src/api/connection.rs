@@ -47,6 +47,8 @@ impl ConnectionApiHandler {
                     mac_address: format_mac(mac),
                     ip_address: format_ip(&device_stats.ip_address),
                     hostname,
+                    vendor: crate::oui::lookup_vendor(mac),
+                    randomized_mac: crate::oui::is_randomized_mac(mac),
                     tcp_connections: device_stats.tcp_connections,
                     udp_connections: device_stats.udp_connections,
                     established_tcp: device_stats.established_tcp,
@@ -82,6 +84,8 @@ pub struct DeviceConnectionInfo {
     pub mac_address: String,
     pub ip_address: String,
     pub hostname: String,
+    pub vendor: Option<String>,
+    pub randomized_mac: bool,
     pub tcp_connections: u32,
     pub udp_connections: u32,
     pub established_tcp: u32,
@@ -1,8 +1,17 @@
 use super::{ApiResponse, HttpRequest, HttpResponse};
+use super::traffic::TimeSlotApi;
 use crate::command::Options;
-use crate::monitor::DnsQueryRecord;
+use crate::monitor::alert::{DnsAlertDetector, DnsAlertKind};
+use crate::monitor::block::DnsBlockEngine;
+use crate::monitor::category::CategoryEngine;
+use crate::monitor::localname::LocalNameRegistry;
+use crate::monitor::resolver::{BypassKind, ResolverCompliance};
+use crate::storage::dns::{DnsBlockAction, DnsBlockPolicy, DnsMetricsManager, DnsRuntimeConfig};
+use crate::storage::traffic::TimeSlot;
+use crate::monitor::{DnsIpIndexEntry, DnsQueryRecord};
 use chrono::{Local, TimeZone};
 use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 use std::sync::{Arc, Mutex};
 use std::time::{SystemTime, UNIX_EPOCH};
 
@@ -25,6 +34,10 @@ pub struct DnsQueryInfo {
     pub response_records: Vec<String>, // 所有响应记录（A, AAAA, CNAME, HTTPS 等）
     pub device_mac: String,            // 设备 MAC 地址
     pub device_name: String,           // 设备主机名
+    pub vendor: Option<String>,        // 设备厂商（由 MAC 的 OUI 解析）
+    pub randomized_mac: bool,          // 设备是否使用随机化 MAC
+    pub categories: Vec<String>,       // 域名所属分类
+    pub blocked: bool,                 // 是否被拦截策略拦截
 }
 
 /// DNS 查询响应结构
@@ -61,6 +74,15 @@ pub struct ResponseCodeStats {
     pub percentage: f64,
 }
 
+/// 单个设备的分类统计
+#[derive(Serialize, Deserialize)]
+pub struct DeviceCategoryStats {
+    pub device_mac: String,
+    pub device_name: String,
+    pub total: usize,             // 该设备命中分类的查询总数
+    pub categories: Vec<TopItem>, // 按分类计数（降序）
+}
+
 /// DNS 统计信息，用于 API 响应
 #[derive(Serialize, Deserialize)]
 pub struct DnsStatsInfo {
@@ -89,6 +111,15 @@ pub struct DnsStatsInfo {
     pub top_devices: Vec<TopItem>,     // 最活跃的设备（前10）
     pub top_dns_servers: Vec<TopItem>, // 最常用的 DNS 服务器（前10）
 
+    // 分类统计
+    pub categories: Vec<TopItem>,                    // 各分类的查询数（全部分类）
+    pub device_categories: Vec<DeviceCategoryStats>, // 各设备的分类分布
+
+    // 拦截统计
+    pub blocked_queries: usize,            // 被拦截的查询数
+    pub top_blocked_domains: Vec<TopItem>, // 被拦截最多的域名（前10）
+    pub blocked_devices: Vec<TopItem>,     // 各设备被拦截的查询数
+
     // 设备统计
     pub unique_devices: usize, // 唯一设备数
 
@@ -104,13 +135,214 @@ pub struct DnsStatsResponse {
     pub stats: DnsStatsInfo,
 }
 
+/// 反向查询条目：某个设备在某个时间解析出该 IP 的域名
+#[derive(Serialize, Deserialize)]
+pub struct DnsLookupRecord {
+    pub domain: String,
+    pub device_mac: String,
+    pub device_name: String,
+    pub timestamp: u64,              // Unix 时间戳，毫秒
+    pub timestamp_formatted: String, // 格式化的时间字符串
+}
+
+/// 反向查询响应结构
+#[derive(Serialize, Deserialize)]
+pub struct DnsLookupResponse {
+    pub ip: String,
+    pub domains: Vec<String>, // 解析到该 IP 的去重域名（最近的在前）
+    pub devices: Vec<String>, // 解析过该 IP 的去重设备 MAC（最近的在前）
+    pub total: usize,         // 匹配的记录总数
+    pub records: Vec<DnsLookupRecord>,
+}
+
+/// 已加载的分类列表
+#[derive(Serialize, Deserialize)]
+pub struct DnsCategoryListInfo {
+    pub category: String,
+    pub file: String,
+    pub domain_count: usize,
+}
+
+/// 分类列表响应结构
+#[derive(Serialize, Deserialize)]
+pub struct DnsCategoriesResponse {
+    pub directory: String,
+    pub lists: Vec<DnsCategoryListInfo>,
+}
+
+/// DNS 拦截策略，用于 API 请求/响应
+#[derive(Serialize, Deserialize)]
+pub struct DnsBlockPolicyApi {
+    #[serde(default)]
+    pub id: Option<String>, // 为空时自动生成
+    #[serde(default)]
+    pub mac: Option<String>, // 为空或 "all" 表示所有设备
+    #[serde(default)]
+    pub action: Option<String>, // "drop" 或 "nxdomain"（默认）
+    #[serde(default)]
+    pub time_slot: Option<TimeSlotApi>, // 为空表示全天候
+    #[serde(default)]
+    pub domains: Vec<String>,
+    #[serde(default)]
+    pub categories: Vec<String>,
+}
+
+/// DNS 拦截策略响应结构
+#[derive(Serialize, Deserialize)]
+pub struct DnsBlockPoliciesResponse {
+    pub active_rules: usize, // 当前同步到 eBPF 的规则数
+    pub policies: Vec<DnsBlockPolicyApi>,
+}
+
+/// 删除拦截策略请求结构
+#[derive(Serialize, Deserialize)]
+pub struct DeleteDnsBlockPolicyRequest {
+    pub id: String,
+}
+
+/// 单条解析器绕过记录
+#[derive(Serialize, Deserialize)]
+pub struct ResolverBypassInfo {
+    pub kind: String,   // external_dns / dot / doh
+    pub server: String, // 远端服务器 IP
+    pub detail: String, // DoH 时为匹配到的服务域名
+    pub count: u64,
+    pub first_seen: u64, // Unix 时间戳，毫秒
+    pub last_seen: u64,
+}
+
+/// 设备的解析器合规报告
+#[derive(Serialize, Deserialize)]
+pub struct DeviceResolverCompliance {
+    pub device_mac: String,
+    pub device_name: String,
+    pub compliant: bool,
+    pub compliant_queries: u64,
+    pub external_dns_queries: u64,
+    pub dot_connections: u64,
+    pub doh_connections: u64,
+    pub violations: Vec<ResolverBypassInfo>,
+}
+
+/// 解析器合规响应结构
+#[derive(Serialize, Deserialize)]
+pub struct ResolverComplianceResponse {
+    pub approved_resolvers: Vec<String>,
+    pub doh_servers: usize, // 已加载的 DoH 服务器条目数
+    pub total_devices: usize,
+    pub non_compliant_devices: usize,
+    pub devices: Vec<DeviceResolverCompliance>,
+}
+
+/// 可疑 DNS 告警
+#[derive(Serialize, Deserialize)]
+pub struct DnsAlertInfo {
+    pub id: u64,
+    pub timestamp: u64, // Unix 时间戳，毫秒
+    pub timestamp_formatted: String,
+    pub kind: String, // high_entropy / long_name / txt_burst / nxdomain_burst
+    pub device_mac: String,
+    pub device_ip: String,
+    pub device_name: String,
+    pub domain: String,
+    pub detail: String,
+    pub value: f64,
+    pub threshold: f64,
+}
+
+/// 当前生效的告警阈值
+#[derive(Serialize, Deserialize)]
+pub struct DnsAlertThresholdsInfo {
+    pub entropy: f64,
+    pub qname_length: usize,
+    pub txt_per_minute: usize,
+    pub nxdomain_per_minute: usize,
+}
+
+/// 告警列表响应结构
+#[derive(Serialize, Deserialize)]
+pub struct DnsAlertsResponse {
+    pub thresholds: DnsAlertThresholdsInfo,
+    pub total: usize,
+    pub alerts: Vec<DnsAlertInfo>,
+}
+
+/// 单个小时的 DNS 指标，用于 API 响应
+#[derive(Serialize, Deserialize)]
+pub struct DnsMetricsEntry {
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub queries: u64,
+    pub responses: u64,
+    pub nxdomain: u64,
+    pub unique_domains: u64,    // 该小时内查询过的不同域名数
+    pub median_latency_ms: u64, // 响应延迟中位数，无匹配响应时为 0
+    pub latency_samples: u64,
+}
+
+/// 设备 DNS 指标时间序列响应结构
+#[derive(Serialize, Deserialize)]
+pub struct DnsMetricsResponse {
+    pub mac: String,
+    pub hostname: String,
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub aggregation: String, // 固定为 "hourly"
+    pub total_queries: u64,
+    pub total_nxdomain: u64,
+    pub metrics: Vec<DnsMetricsEntry>,
+}
+
+/// 通过名称发现协议学到的单个名称
+#[derive(Serialize, Deserialize)]
+pub struct DnsLocalNameInfo {
+    pub name: String,
+    pub source: String, // mdns / llmnr / netbios
+    pub last_seen_ms: u64,
+}
+
+/// 单个设备的名称发现结果
+#[derive(Serialize, Deserialize)]
+pub struct DnsLocalDeviceInfo {
+    pub mac: String,
+    pub hostname: String,                // 当前生效的主机名（可能来自手动绑定或 ubus）
+    pub discovered_name: Option<String>, // 按来源优先级选出的发现名称
+    pub names: Vec<DnsLocalNameInfo>,
+    pub services: Vec<String>, // 通告的服务类型，例如 _airplay._tcp
+    pub last_seen_ms: u64,
+}
+
+/// 名称发现列表响应结构
+#[derive(Serialize, Deserialize)]
+pub struct DnsLocalNamesResponse {
+    pub total: usize,
+    pub devices: Vec<DnsLocalDeviceInfo>,
+}
+
 /// DNS 配置，用于 API 响应
 #[derive(Serialize, Deserialize)]
 pub struct DnsConfigInfo {
     pub enabled: bool,
     pub monitored_interfaces: Vec<String>,
-    pub log_level: String,
-    pub retention_days: u64,
+    pub max_records: usize,
+    pub current_records: usize,
+    pub store_responses: bool,
+    pub ignored_domains: Vec<String>,
+    pub ignored_devices: Vec<String>,
+    pub retention_days: u64, // 小时级 DNS 指标的保留天数
+}
+
+/// DNS 配置更新请求，未提供的字段保持不变
+#[derive(Serialize, Deserialize)]
+pub struct DnsConfigUpdateRequest {
+    #[serde(default)]
+    pub max_records: Option<usize>,
+    #[serde(default)]
+    pub store_responses: Option<bool>,
+    #[serde(default)]
+    pub ignored_domains: Option<Vec<String>>,
+    #[serde(default)]
+    pub ignored_devices: Option<Vec<String>>,
 }
 
 /// DNS 配置响应结构
@@ -122,10 +354,18 @@ pub struct DnsConfigResponse {
 /// DNS 监控 API handler
 #[derive(Clone)]
 pub struct DnsApiHandler {
-    #[allow(dead_code)]
     options: Options,
     dns_queries: Arc<Mutex<Vec<DnsQueryRecord>>>,
+    dns_ip_index: Arc<Mutex<std::collections::HashMap<String, Vec<DnsIpIndexEntry>>>>,
+    unstored_responses: Arc<Mutex<VecDeque<DnsQueryRecord>>>, // 未保存但应答 IP 仍在索引中的响应
     hostname_bindings: Arc<Mutex<std::collections::HashMap<[u8; 6], String>>>,
+    category_engine: Arc<CategoryEngine>,
+    block_engine: Arc<DnsBlockEngine>,
+    resolver_compliance: Arc<ResolverCompliance>,
+    alert_detector: Arc<DnsAlertDetector>,
+    dns_metrics: Arc<DnsMetricsManager>,
+    dns_config: Arc<Mutex<DnsRuntimeConfig>>,
+    local_names: Arc<LocalNameRegistry>,
     boot_time_offset_ns: u64, // 用于将单调时间转换为 Unix 时间戳的偏移量
 }
 
@@ -133,7 +373,16 @@ impl DnsApiHandler {
     pub fn new(
         options: Options,
         dns_queries: Arc<Mutex<Vec<DnsQueryRecord>>>,
+        dns_ip_index: Arc<Mutex<std::collections::HashMap<String, Vec<DnsIpIndexEntry>>>>,
+        unstored_responses: Arc<Mutex<VecDeque<DnsQueryRecord>>>,
         hostname_bindings: Arc<Mutex<std::collections::HashMap<[u8; 6], String>>>,
+        category_engine: Arc<CategoryEngine>,
+        block_engine: Arc<DnsBlockEngine>,
+        resolver_compliance: Arc<ResolverCompliance>,
+        alert_detector: Arc<DnsAlertDetector>,
+        dns_metrics: Arc<DnsMetricsManager>,
+        dns_config: Arc<Mutex<DnsRuntimeConfig>>,
+        local_names: Arc<LocalNameRegistry>,
     ) -> Self {
         // 计算启动时间偏移量：Unix 时间 - 单调时间
         // 如果有可用的第一个 DNS 记录的时间戳，则使用它作为参考，
@@ -143,7 +392,16 @@ impl DnsApiHandler {
         Self {
             options,
             dns_queries,
+            dns_ip_index,
+            unstored_responses,
             hostname_bindings,
+            category_engine,
+            block_engine,
+            resolver_compliance,
+            alert_detector,
+            dns_metrics,
+            dns_config,
+            local_names,
             boot_time_offset_ns,
         }
     }
@@ -205,7 +463,18 @@ impl DnsApiHandler {
 
 impl DnsApiHandler {
     pub fn supported_routes(&self) -> Vec<&'static str> {
-        vec!["/api/dns/queries", "/api/dns/stats", "/api/dns/config"]
+        vec![
+            "/api/dns/queries",
+            "/api/dns/stats",
+            "/api/dns/config",
+            "/api/dns/lookup",
+            "/api/dns/categories",
+            "/api/dns/block/policies",
+            "/api/dns/compliance",
+            "/api/dns/alerts",
+            "/api/dns/metrics",
+            "/api/dns/names",
+        ]
     }
 
     pub async fn handle_request(&self, request: &HttpRequest) -> Result<HttpResponse, anyhow::Error> {
@@ -224,6 +493,61 @@ impl DnsApiHandler {
                     Ok(HttpResponse::error(405, "Method not allowed".to_string()))
                 }
             }
+            "/api/dns/categories" => {
+                if request.method == "GET" {
+                    self.handle_categories().await
+                } else {
+                    Ok(HttpResponse::error(405, "Method not allowed".to_string()))
+                }
+            }
+            "/api/dns/categories/reload" => {
+                if request.method == "POST" {
+                    self.handle_reload_categories().await
+                } else {
+                    Ok(HttpResponse::error(405, "Method not allowed".to_string()))
+                }
+            }
+            "/api/dns/block/policies" => match request.method.as_str() {
+                "GET" => self.handle_block_policies().await,
+                "POST" => self.handle_set_block_policy(request).await,
+                "DELETE" => self.handle_delete_block_policy(request).await,
+                _ => Ok(HttpResponse::error(405, "Method not allowed".to_string())),
+            },
+            "/api/dns/lookup" => {
+                if request.method == "GET" {
+                    self.handle_lookup(request).await
+                } else {
+                    Ok(HttpResponse::error(405, "Method not allowed".to_string()))
+                }
+            }
+            "/api/dns/compliance" => {
+                if request.method == "GET" {
+                    self.handle_compliance(request).await
+                } else {
+                    Ok(HttpResponse::error(405, "Method not allowed".to_string()))
+                }
+            }
+            "/api/dns/alerts" => {
+                if request.method == "GET" {
+                    self.handle_alerts(request).await
+                } else {
+                    Ok(HttpResponse::error(405, "Method not allowed".to_string()))
+                }
+            }
+            "/api/dns/metrics" => {
+                if request.method == "GET" {
+                    self.handle_metrics(request).await
+                } else {
+                    Ok(HttpResponse::error(405, "Method not allowed".to_string()))
+                }
+            }
+            "/api/dns/names" => {
+                if request.method == "GET" {
+                    self.handle_local_names(request).await
+                } else {
+                    Ok(HttpResponse::error(405, "Method not allowed".to_string()))
+                }
+            }
             "/api/dns/config" => match request.method.as_str() {
                 "GET" => self.handle_get_config().await,
                 "POST" => self.handle_set_config(request).await,
@@ -371,6 +695,8 @@ impl DnsApiHandler {
                 response_time_ms,
                 device_mac: mac,
                 device_name,
+                categories: Vec::new(),
+                blocked: false,
             });
         }
     }
@@ -383,6 +709,8 @@ impl DnsApiHandler {
     /// - is_query: 按查询类型过滤（true=查询，false=响应）
     /// - query_type: 按 DNS 记录类型过滤（例如：A, AAAA, CNAME, MX, TXT, NS, SOA, PTR）
     /// - dns_server: 按 DNS 服务器 IP 地址过滤（查询时为 destination_ip，响应时为 source_ip）
+    /// - category: 按域名分类过滤（精确匹配，大小写不敏感；"none" 表示未分类）
+    /// - blocked: 按是否被拦截过滤（true/false）
     /// - page: 页码（默认：1）
     /// - page_size: 每页记录数（默认：20，最大：1000）
     /// - limit: （已弃用，使用 page_size）返回的最大记录数
@@ -395,6 +723,8 @@ impl DnsApiHandler {
         let is_query_filter = request.query_params.get("is_query").and_then(|s| s.parse::<bool>().ok());
         let query_type_filter = request.query_params.get("query_type").map(|s| s.to_uppercase());
         let dns_server_filter = request.query_params.get("dns_server");
+        let category_filter = request.query_params.get("category").map(|s| s.to_lowercase());
+        let blocked_filter = request.query_params.get("blocked").and_then(|s| s.parse::<bool>().ok());
 
         // Pagination parameters
         let page = request
@@ -472,6 +802,24 @@ impl DnsApiHandler {
                     }
                 }
 
+                // 按域名分类过滤
+                if let Some(ref category) = category_filter {
+                    if category == "none" {
+                        if !q.categories.is_empty() {
+                            return false;
+                        }
+                    } else if !q.categories.iter().any(|c| c == category) {
+                        return false;
+                    }
+                }
+
+                // 按拦截状态过滤
+                if let Some(blocked) = blocked_filter {
+                    if q.blocked != blocked {
+                        return false;
+                    }
+                }
+
                 true
             })
             .map(|(idx, _)| idx)
@@ -552,6 +900,8 @@ impl DnsApiHandler {
                 // 格式化timestamp
                 let timestamp_formatted = Self::format_timestamp(unix_timestamp_ms);
 
+                let device_mac_bytes = Self::parse_mac_address(&q.device_mac).ok();
+
                 // 根据 MAC 地址从绑定中获取最新的主机名
                 let device_name = if !q.device_mac.is_empty() {
                     // 解析MAC address string to [u8; 6]
@@ -585,6 +935,10 @@ impl DnsApiHandler {
                     response_records: q.response_records.clone(),
                     device_mac: q.device_mac.clone(),
                     device_name,
+                    vendor: device_mac_bytes.as_ref().and_then(crate::oui::lookup_vendor),
+                    randomized_mac: device_mac_bytes.as_ref().map(crate::oui::is_randomized_mac).unwrap_or(false),
+                    categories: q.categories.clone(),
+                    blocked: q.blocked,
                 }
             })
             .collect();
@@ -641,6 +995,11 @@ impl DnsApiHandler {
                 top_query_types: vec![],
                 top_devices: vec![],
                 top_dns_servers: vec![],
+                categories: vec![],
+                device_categories: vec![],
+                blocked_queries: 0,
+                top_blocked_domains: vec![],
+                blocked_devices: vec![],
                 unique_devices: 0,
                 time_range_start: 0,
                 time_range_end: 0,
@@ -813,6 +1172,95 @@ impl DnsApiHandler {
         });
         top_dns_servers.truncate(10);
 
+        // 分类统计（仅统计查询）
+        let mut category_map: HashMap<String, usize> = HashMap::new();
+        let mut device_category_map: HashMap<String, (String, HashMap<String, usize>)> = HashMap::new();
+        for query in queries.iter().filter(|q| q.is_query && !q.categories.is_empty()) {
+            for category in query.categories.iter() {
+                *category_map.entry(category.clone()).or_insert(0) += 1;
+            }
+
+            if query.device_mac.is_empty() {
+                continue;
+            }
+            let device_name = Self::parse_mac_address(&query.device_mac)
+                .ok()
+                .and_then(|mac| hostname_bindings.get(&mac).cloned())
+                .filter(|name| !name.is_empty())
+                .unwrap_or_else(|| query.device_name.clone());
+            let entry = device_category_map
+                .entry(query.device_mac.clone())
+                .or_insert_with(|| (device_name, HashMap::new()));
+            for category in query.categories.iter() {
+                *entry.1.entry(category.clone()).or_insert(0) += 1;
+            }
+        }
+        let mut categories: Vec<TopItem> = category_map.into_iter().map(|(name, count)| TopItem { name, count }).collect();
+        categories.sort_by(|a, b| match b.count.cmp(&a.count) {
+            std::cmp::Ordering::Equal => a.name.cmp(&b.name),
+            other => other,
+        });
+
+        let mut device_categories: Vec<DeviceCategoryStats> = device_category_map
+            .into_iter()
+            .map(|(device_mac, (device_name, counts))| {
+                let total = counts.values().sum();
+                let mut items: Vec<TopItem> = counts.into_iter().map(|(name, count)| TopItem { name, count }).collect();
+                items.sort_by(|a, b| match b.count.cmp(&a.count) {
+                    std::cmp::Ordering::Equal => a.name.cmp(&b.name),
+                    other => other,
+                });
+                DeviceCategoryStats {
+                    device_mac,
+                    device_name,
+                    total,
+                    categories: items,
+                }
+            })
+            .collect();
+        device_categories.sort_by(|a, b| match b.total.cmp(&a.total) {
+            std::cmp::Ordering::Equal => a.device_mac.cmp(&b.device_mac),
+            other => other,
+        });
+
+        // 拦截统计
+        let mut blocked_domain_map: HashMap<String, usize> = HashMap::new();
+        let mut blocked_device_map: HashMap<String, usize> = HashMap::new();
+        let mut blocked_queries = 0;
+        for query in queries.iter().filter(|q| q.is_query && q.blocked) {
+            blocked_queries += 1;
+            *blocked_domain_map.entry(query.domain.clone()).or_insert(0) += 1;
+            let device_key = Self::parse_mac_address(&query.device_mac)
+                .ok()
+                .and_then(|mac| hostname_bindings.get(&mac).cloned())
+                .filter(|name| !name.is_empty())
+                .unwrap_or_else(|| {
+                    if !query.device_name.is_empty() {
+                        query.device_name.clone()
+                    } else {
+                        query.device_mac.clone()
+                    }
+                });
+            *blocked_device_map.entry(device_key).or_insert(0) += 1;
+        }
+        let mut top_blocked_domains: Vec<TopItem> = blocked_domain_map
+            .into_iter()
+            .map(|(name, count)| TopItem { name, count })
+            .collect();
+        top_blocked_domains.sort_by(|a, b| match b.count.cmp(&a.count) {
+            std::cmp::Ordering::Equal => a.name.cmp(&b.name),
+            other => other,
+        });
+        top_blocked_domains.truncate(10);
+        let mut blocked_devices: Vec<TopItem> = blocked_device_map
+            .into_iter()
+            .map(|(name, count)| TopItem { name, count })
+            .collect();
+        blocked_devices.sort_by(|a, b| match b.count.cmp(&a.count) {
+            std::cmp::Ordering::Equal => a.name.cmp(&b.name),
+            other => other,
+        });
+
         // 唯一设备
         let unique_devices: std::collections::HashSet<String> = queries
             .iter()
@@ -848,6 +1296,11 @@ impl DnsApiHandler {
             top_query_types,
             top_devices,
             top_dns_servers,
+            categories,
+            device_categories,
+            blocked_queries,
+            top_blocked_domains,
+            blocked_devices,
             unique_devices: unique_devices_count,
             time_range_start: self.convert_to_unix_timestamp(time_range_start),
             time_range_end: self.convert_to_unix_timestamp(time_range_end),
@@ -860,16 +1313,523 @@ impl DnsApiHandler {
         Ok(HttpResponse::ok(body))
     }
 
+    /// 处理/api/dns/categories endpoint，列出已加载的分类列表
+    async fn handle_categories(&self) -> Result<HttpResponse, anyhow::Error> {
+        let lists = self
+            .category_engine
+            .lists()
+            .into_iter()
+            .map(|l| DnsCategoryListInfo {
+                category: l.category,
+                file: l.file,
+                domain_count: l.domain_count,
+            })
+            .collect();
+
+        let response = DnsCategoriesResponse {
+            directory: self.category_engine.dir().to_string_lossy().to_string(),
+            lists,
+        };
+
+        let api_response = ApiResponse::success(response);
+        let body = serde_json::to_string(&api_response)?;
+        Ok(HttpResponse::ok(body))
+    }
+
+    /// 处理/api/dns/categories/reload endpoint，立即重新加载分类列表
+    /// 仅影响之后记录的查询，已有记录的分类不会改变
+    async fn handle_reload_categories(&self) -> Result<HttpResponse, anyhow::Error> {
+        if let Err(e) = self.category_engine.reload() {
+            return Ok(HttpResponse::error(500, format!("Failed to reload category lists: {}", e)));
+        }
+        // 按分类拦截的规则需要重新编译
+        self.block_engine.mark_dirty();
+        self.handle_categories().await
+    }
+
+    /// 处理/api/dns/block/policies endpoint (GET)
+    async fn handle_block_policies(&self) -> Result<HttpResponse, anyhow::Error> {
+        let policies = self
+            .block_engine
+            .policies()
+            .iter()
+            .map(|p| DnsBlockPolicyApi {
+                id: Some(p.id.clone()),
+                mac: Some(p.mac.map(|m| crate::utils::format_utils::format_mac(&m)).unwrap_or_else(|| "all".to_string())),
+                action: Some(p.action.as_str().to_string()),
+                time_slot: Some(TimeSlotApi::from(&p.time_slot)),
+                domains: p.domains.clone(),
+                categories: p.categories.clone(),
+            })
+            .collect();
+
+        let response = DnsBlockPoliciesResponse {
+            active_rules: self.block_engine.active_rule_count(),
+            policies,
+        };
+        let api_response = ApiResponse::success(response);
+        let body = serde_json::to_string(&api_response)?;
+        Ok(HttpResponse::ok(body))
+    }
+
+    /// 处理/api/dns/block/policies endpoint (POST)，按 id 新增或替换策略
+    async fn handle_set_block_policy(&self, request: &HttpRequest) -> Result<HttpResponse, anyhow::Error> {
+        let body = request.body.as_ref().ok_or_else(|| anyhow::anyhow!("Missing request body"))?;
+        let req: DnsBlockPolicyApi = serde_json::from_str(body)?;
+
+        let id = match req.id.as_deref().map(|s| s.trim()).filter(|s| !s.is_empty()) {
+            Some(id) => id.to_string(),
+            None => format!("policy-{}", chrono::Utc::now().timestamp_millis()),
+        };
+        if !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return Ok(HttpResponse::error(400, "Policy id may only contain letters, digits, '-' and '_'".to_string()));
+        }
+
+        let mac = match req.mac.as_deref().map(|s| s.trim()) {
+            None | Some("") | Some("all") => None,
+            Some(mac_str) => match crate::utils::network_utils::parse_mac_address(mac_str) {
+                Ok(mac) => Some(mac),
+                Err(_) => return Ok(HttpResponse::error(400, format!("Invalid MAC address: {}", mac_str))),
+            },
+        };
+
+        let action = match req.action.as_deref() {
+            None => DnsBlockAction::NxDomain,
+            Some(action) => match DnsBlockAction::parse(action) {
+                Ok(action) => action,
+                Err(e) => return Ok(HttpResponse::error(400, e.to_string())),
+            },
+        };
+
+        let time_slot = match req.time_slot.as_ref() {
+            None => TimeSlot::all_time(),
+            Some(slot) => match TimeSlot::try_from(slot) {
+                Ok(slot) => slot,
+                Err(e) => return Ok(HttpResponse::error(400, format!("Invalid time slot: {}", e))),
+            },
+        };
+
+        let normalize = |items: &Vec<String>| -> Vec<String> {
+            let mut out: Vec<String> = items
+                .iter()
+                .map(|s| s.trim().trim_end_matches('.').to_lowercase())
+                .filter(|s| !s.is_empty())
+                .collect();
+            out.sort();
+            out.dedup();
+            out
+        };
+        let domains = normalize(&req.domains);
+        let categories = normalize(&req.categories);
+        if domains.is_empty() && categories.is_empty() {
+            return Ok(HttpResponse::error(400, "At least one domain or category is required".to_string()));
+        }
+        if domains.iter().chain(categories.iter()).any(|s| s.contains(',') || s.contains(char::is_whitespace)) {
+            return Ok(HttpResponse::error(400, "Domains and categories must not contain commas or spaces".to_string()));
+        }
+
+        let policy = DnsBlockPolicy {
+            id: id.clone(),
+            mac,
+            time_slot,
+            action,
+            domains,
+            categories,
+        };
+
+        self.block_engine.upsert_policy(policy);
+        crate::storage::dns::save_block_policies(self.options.data_dir(), &self.block_engine.policies())?;
+
+        log::info!("DNS block policy {} saved", id);
+
+        self.handle_block_policies().await
+    }
+
+    /// 处理/api/dns/block/policies endpoint (DELETE)
+    async fn handle_delete_block_policy(&self, request: &HttpRequest) -> Result<HttpResponse, anyhow::Error> {
+        let body = request.body.as_ref().ok_or_else(|| anyhow::anyhow!("Missing request body"))?;
+        let req: DeleteDnsBlockPolicyRequest = serde_json::from_str(body)?;
+
+        if !self.block_engine.remove_policy(&req.id) {
+            return Ok(HttpResponse::error(404, format!("DNS block policy not found: {}", req.id)));
+        }
+        crate::storage::dns::save_block_policies(self.options.data_dir(), &self.block_engine.policies())?;
+
+        log::info!("DNS block policy {} deleted", req.id);
+
+        let api_response = ApiResponse::success(());
+        let body = serde_json::to_string(&api_response)?;
+        Ok(HttpResponse::ok(body))
+    }
+
+    /// 处理/api/dns/lookup endpoint
+    ///
+    /// 查询参数：
+    /// - ip: 要反查的 IP 地址（必填，IPv4 或 IPv6）
+    /// - limit: 返回的最大记录数（默认：100，最大：1000）
+    async fn handle_lookup(&self, request: &HttpRequest) -> Result<HttpResponse, anyhow::Error> {
+        let ip_param = match request.query_params.get("ip") {
+            Some(ip) if !ip.trim().is_empty() => ip.trim(),
+            _ => return Ok(HttpResponse::error(400, "Missing required parameter: ip".to_string())),
+        };
+
+        // 规范化 IP 地址格式，与 response_ips 中的格式保持一致
+        let ip = match ip_param.parse::<std::net::IpAddr>() {
+            Ok(addr) => addr.to_string(),
+            Err(_) => return Ok(HttpResponse::error(400, format!("Invalid IP address: {}", ip_param))),
+        };
+
+        let limit = request
+            .query_params
+            .get("limit")
+            .and_then(|s| s.parse::<usize>().ok())
+            .unwrap_or(100)
+            .min(1000);
+
+        let mut entries: Vec<DnsIpIndexEntry> = if let Ok(index) = self.dns_ip_index.lock() {
+            index.get(&ip).cloned().unwrap_or_default()
+        } else {
+            return Ok(HttpResponse::error(500, "Failed to lock DNS IP index".to_string()));
+        };
+
+        // 最新的在前
+        entries.sort_by_key(|e| std::cmp::Reverse(e.timestamp));
+
+        let mut domains: Vec<String> = Vec::new();
+        let mut devices: Vec<String> = Vec::new();
+        for entry in entries.iter() {
+            if !domains.contains(&entry.domain) {
+                domains.push(entry.domain.clone());
+            }
+            if !entry.device_mac.is_empty() && !devices.contains(&entry.device_mac) {
+                devices.push(entry.device_mac.clone());
+            }
+        }
+
+        let hostname_bindings = if let Ok(bindings) = self.hostname_bindings.lock() {
+            bindings.clone()
+        } else {
+            std::collections::HashMap::new()
+        };
+
+        let total = entries.len();
+        let records: Vec<DnsLookupRecord> = entries
+            .into_iter()
+            .take(limit)
+            .map(|e| {
+                let unix_timestamp_ms = self.convert_to_unix_timestamp(e.timestamp);
+                let device_name = Self::parse_mac_address(&e.device_mac)
+                    .ok()
+                    .and_then(|mac| hostname_bindings.get(&mac).cloned())
+                    .unwrap_or(e.device_name);
+                DnsLookupRecord {
+                    domain: e.domain,
+                    device_mac: e.device_mac,
+                    device_name,
+                    timestamp: unix_timestamp_ms,
+                    timestamp_formatted: Self::format_timestamp(unix_timestamp_ms),
+                }
+            })
+            .collect();
+
+        let response = DnsLookupResponse {
+            ip,
+            domains,
+            devices,
+            total,
+            records,
+        };
+
+        let api_response = ApiResponse::success(response);
+        let body = serde_json::to_string(&api_response)?;
+        Ok(HttpResponse::ok(body))
+    }
+
+    /// 处理/api/dns/compliance endpoint，返回各设备的解析器合规报告
+    /// 支持 mac= 参数只查询单个设备
+    async fn handle_compliance(&self, request: &HttpRequest) -> Result<HttpResponse, anyhow::Error> {
+        let mac_filter = match request.query_params.get("mac") {
+            Some(mac) if !mac.trim().is_empty() => match Self::parse_mac_address(mac.trim()) {
+                Ok(mac) => Some(mac),
+                Err(e) => return Ok(HttpResponse::error(400, format!("Invalid MAC address: {}", e))),
+            },
+            _ => None,
+        };
+
+        let bindings = self.hostname_bindings.lock().map(|b| b.clone()).unwrap_or_default();
+
+        let mut devices: Vec<DeviceResolverCompliance> = self
+            .resolver_compliance
+            .report()
+            .into_iter()
+            .filter(|(mac, _)| mac_filter.map(|m| m == *mac).unwrap_or(true))
+            .map(|(mac, device)| {
+                let count_of = |kind: BypassKind| -> u64 {
+                    device.violations.iter().filter(|v| v.kind == kind).map(|v| v.count).sum()
+                };
+                let external_dns_queries = count_of(BypassKind::ExternalDns);
+                let dot_connections = count_of(BypassKind::DoT);
+                let doh_connections = count_of(BypassKind::DoH);
+
+                let mut violations: Vec<ResolverBypassInfo> = device
+                    .violations
+                    .iter()
+                    .map(|v| ResolverBypassInfo {
+                        kind: v.kind.as_str().to_string(),
+                        server: v.server.clone(),
+                        detail: v.detail.clone(),
+                        count: v.count,
+                        first_seen: v.first_seen_ms,
+                        last_seen: v.last_seen_ms,
+                    })
+                    .collect();
+                violations.sort_by_key(|v| std::cmp::Reverse(v.last_seen));
+
+                DeviceResolverCompliance {
+                    device_mac: crate::utils::format_utils::format_mac(&mac),
+                    device_name: bindings.get(&mac).cloned().unwrap_or_default(),
+                    compliant: violations.is_empty(),
+                    compliant_queries: device.compliant_queries,
+                    external_dns_queries,
+                    dot_connections,
+                    doh_connections,
+                    violations,
+                }
+            })
+            .collect();
+
+        // 不合规的设备在前，其余按 MAC 排序
+        devices.sort_by(|a, b| a.compliant.cmp(&b.compliant).then_with(|| a.device_mac.cmp(&b.device_mac)));
+
+        let response = ResolverComplianceResponse {
+            approved_resolvers: self
+                .resolver_compliance
+                .approved_resolvers()
+                .iter()
+                .map(|ip| ip.to_string())
+                .collect(),
+            doh_servers: self.resolver_compliance.doh_server_count(),
+            total_devices: devices.len(),
+            non_compliant_devices: devices.iter().filter(|d| !d.compliant).count(),
+            devices,
+        };
+
+        let api_response = ApiResponse::success(response);
+        let body = serde_json::to_string(&api_response)?;
+        Ok(HttpResponse::ok(body))
+    }
+
+    /// 处理/api/dns/alerts endpoint，返回可疑 DNS 告警（最新的在前）
+    /// 支持 mac=、kind=、since_ms= 过滤与 limit（默认 100，最大 1000）
+    async fn handle_alerts(&self, request: &HttpRequest) -> Result<HttpResponse, anyhow::Error> {
+        let mac_filter = request
+            .query_params
+            .get("mac")
+            .map(|m| m.trim().to_lowercase())
+            .filter(|m| !m.is_empty());
+        let kind_filter = match request.query_params.get("kind") {
+            Some(kind) if !kind.trim().is_empty() => match DnsAlertKind::parse(kind) {
+                Some(kind) => Some(kind),
+                None => return Ok(HttpResponse::error(400, format!("Invalid alert kind: {}", kind))),
+            },
+            _ => None,
+        };
+        let since_ms = request
+            .query_params
+            .get("since_ms")
+            .and_then(|s| s.parse::<u64>().ok())
+            .unwrap_or(0);
+        let limit = request
+            .query_params
+            .get("limit")
+            .and_then(|s| s.parse::<usize>().ok())
+            .unwrap_or(100)
+            .min(1000);
+
+        let matched: Vec<_> = self
+            .alert_detector
+            .alerts()
+            .into_iter()
+            .filter(|a| mac_filter.as_ref().map(|m| a.device_mac.to_lowercase() == *m).unwrap_or(true))
+            .filter(|a| kind_filter.map(|k| a.kind == k).unwrap_or(true))
+            .filter(|a| a.ts_ms >= since_ms)
+            .collect();
+        let total = matched.len();
+
+        let alerts = matched
+            .into_iter()
+            .take(limit)
+            .map(|a| DnsAlertInfo {
+                id: a.id,
+                timestamp: a.ts_ms,
+                timestamp_formatted: Self::format_timestamp(a.ts_ms),
+                kind: a.kind.as_str().to_string(),
+                device_mac: a.device_mac,
+                device_ip: a.device_ip,
+                device_name: a.device_name,
+                domain: a.domain,
+                detail: a.detail,
+                value: a.value,
+                threshold: a.threshold,
+            })
+            .collect();
+
+        let thresholds = self.alert_detector.thresholds();
+        let response = DnsAlertsResponse {
+            thresholds: DnsAlertThresholdsInfo {
+                entropy: thresholds.entropy,
+                qname_length: thresholds.qname_length,
+                txt_per_minute: thresholds.txt_per_minute,
+                nxdomain_per_minute: thresholds.nxdomain_per_minute,
+            },
+            total,
+            alerts,
+        };
+
+        let api_response = ApiResponse::success(response);
+        let body = serde_json::to_string(&api_response)?;
+        Ok(HttpResponse::ok(body))
+    }
+
+    /// 处理/api/dns/metrics endpoint，返回设备的小时级 DNS 指标
+    /// 查询参数：
+    ///   - mac: 设备 MAC 地址（必填）
+    ///   - start_ms: 开始时间戳，毫秒（可选，默认为 365 天前）
+    ///   - end_ms: 结束时间戳，毫秒（可选，默认为现在）
+    async fn handle_metrics(&self, request: &HttpRequest) -> Result<HttpResponse, anyhow::Error> {
+        let mac = match request.query_params.get("mac") {
+            Some(mac_str) => match Self::parse_mac_address(mac_str) {
+                Ok(mac) => mac,
+                Err(e) => return Ok(HttpResponse::error(400, format!("Invalid MAC address: {}", e))),
+            },
+            None => return Ok(HttpResponse::error(400, "Missing required parameter: mac".to_string())),
+        };
+
+        let now_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+        let start_ms = request
+            .query_params
+            .get("start_ms")
+            .and_then(|s| s.parse::<u64>().ok())
+            .unwrap_or_else(|| now_ms.saturating_sub(365 * 24 * 3600 * 1000));
+        let end_ms = request
+            .query_params
+            .get("end_ms")
+            .and_then(|s| s.parse::<u64>().ok())
+            .unwrap_or(now_ms);
+
+        if start_ms >= end_ms {
+            return Ok(HttpResponse::error(
+                400,
+                "Invalid time range: start_ms must be less than end_ms".to_string(),
+            ));
+        }
+
+        let metrics: Vec<DnsMetricsEntry> = self
+            .dns_metrics
+            .query(&mac, start_ms, end_ms)
+            .into_iter()
+            .map(|r| DnsMetricsEntry {
+                start_ms: r.start_ts_ms,
+                end_ms: r.end_ts_ms,
+                queries: r.queries,
+                responses: r.responses,
+                nxdomain: r.nxdomain,
+                unique_domains: r.unique_domains,
+                median_latency_ms: r.median_latency_ms,
+                latency_samples: r.latency_samples,
+            })
+            .collect();
+
+        let hostname = self
+            .hostname_bindings
+            .lock()
+            .ok()
+            .and_then(|b| b.get(&mac).cloned())
+            .unwrap_or_default();
+
+        let response = DnsMetricsResponse {
+            mac: crate::utils::format_utils::format_mac(&mac),
+            hostname,
+            start_ms,
+            end_ms,
+            aggregation: "hourly".to_string(),
+            total_queries: metrics.iter().map(|m| m.queries).sum(),
+            total_nxdomain: metrics.iter().map(|m| m.nxdomain).sum(),
+            metrics,
+        };
+
+        let api_response = ApiResponse::success(response);
+        let body = serde_json::to_string(&api_response)?;
+        Ok(HttpResponse::ok(body))
+    }
+
+    /// 处理/api/dns/names endpoint
+    /// 列出通过 mDNS / LLMNR / NetBIOS 学到的设备名称与服务类型，可用 mac 参数过滤
+    async fn handle_local_names(&self, request: &HttpRequest) -> Result<HttpResponse, anyhow::Error> {
+        let mac_filter = match request.query_params.get("mac") {
+            Some(mac_str) => match Self::parse_mac_address(mac_str) {
+                Ok(mac) => Some(mac),
+                Err(e) => return Ok(HttpResponse::error(400, format!("Invalid MAC address: {}", e))),
+            },
+            None => None,
+        };
+
+        let bindings = self.hostname_bindings.lock().map(|b| b.clone()).unwrap_or_default();
+        let mut devices: Vec<DnsLocalDeviceInfo> = self
+            .local_names
+            .entries()
+            .into_iter()
+            .filter(|(mac, _)| mac_filter.map(|f| f == *mac).unwrap_or(true))
+            .map(|(mac, entry)| DnsLocalDeviceInfo {
+                mac: crate::utils::format_utils::format_mac(&mac),
+                hostname: bindings.get(&mac).cloned().unwrap_or_default(),
+                discovered_name: entry.best_name().map(|n| n.name.clone()),
+                names: entry
+                    .names
+                    .iter()
+                    .map(|n| DnsLocalNameInfo {
+                        name: n.name.clone(),
+                        source: n.source.as_str().to_string(),
+                        last_seen_ms: n.last_seen_ms,
+                    })
+                    .collect(),
+                services: entry.services.iter().cloned().collect(),
+                last_seen_ms: entry.last_seen_ms,
+            })
+            .collect();
+        devices.sort_by_key(|d| std::cmp::Reverse(d.last_seen_ms));
+
+        let response = DnsLocalNamesResponse {
+            total: devices.len(),
+            devices,
+        };
+
+        let api_response = ApiResponse::success(response);
+        let body = serde_json::to_string(&api_response)?;
+        Ok(HttpResponse::ok(body))
+    }
+
     /// 处理/api/dns/config GET endpoint
     async fn handle_get_config(&self) -> Result<HttpResponse, anyhow::Error> {
-        // TODO: 实现 DNS 配置检索
-        // 这会返回当前的 DNS 监控配置
+        let current = self.dns_config.lock().unwrap().clone();
+        let current_records = self.dns_queries.lock().map(|q| q.len()).unwrap_or(0);
+
+        let mut ignored_devices: Vec<String> = current
+            .ignored_devices
+            .iter()
+            .map(crate::utils::format_utils::format_mac)
+            .collect();
+        ignored_devices.sort();
 
         let config = DnsConfigInfo {
-            enabled: false,
-            monitored_interfaces: vec![],
-            log_level: "info".to_string(),
-            retention_days: 7,
+            enabled: self.options.enable_dns(),
+            monitored_interfaces: vec![self.options.iface().to_string()],
+            max_records: current.max_records,
+            current_records,
+            store_responses: current.store_responses,
+            ignored_domains: current.ignored_domains,
+            ignored_devices,
+            retention_days: self.dns_metrics.retention_days(),
         };
 
         let response = DnsConfigResponse { config };
@@ -879,14 +1839,278 @@ impl DnsApiHandler {
     }
 
     /// 处理/api/dns/config POST endpoint
-    async fn handle_set_config(&self, _request: &HttpRequest) -> Result<HttpResponse, anyhow::Error> {
-        // TODO: 实现 DNS 配置更新
-        // 这会允许更新 DNS 监控设置
-
-        // 现在，只返回未实现的响应
-        Ok(HttpResponse::error(
-            501,
-            "DNS configuration update not yet implemented".to_string(),
-        ))
+    /// 修改立即作用于 DNS 模块（包括已保存的记录），并持久化到 dns_config.txt
+    async fn handle_set_config(&self, request: &HttpRequest) -> Result<HttpResponse, anyhow::Error> {
+        let body = request.body.as_ref().ok_or_else(|| anyhow::anyhow!("Missing request body"))?;
+        let req: DnsConfigUpdateRequest = serde_json::from_str(body)?;
+
+        let mut updated = self.dns_config.lock().unwrap().clone();
+
+        if let Some(max_records) = req.max_records {
+            if max_records == 0 {
+                return Ok(HttpResponse::error(400, "max_records must be greater than 0".to_string()));
+            }
+            updated.max_records = max_records;
+        }
+        if let Some(store_responses) = req.store_responses {
+            updated.store_responses = store_responses;
+        }
+        if let Some(domains) = req.ignored_domains.as_ref() {
+            let mut out: Vec<String> = domains
+                .iter()
+                .map(|s| s.trim().trim_end_matches('.').to_lowercase())
+                .filter(|s| !s.is_empty())
+                .collect();
+            if out.iter().any(|s| s.contains(',') || s.contains(char::is_whitespace)) {
+                return Ok(HttpResponse::error(400, "Domains must not contain commas or spaces".to_string()));
+            }
+            out.sort();
+            out.dedup();
+            updated.ignored_domains = out;
+        }
+        if let Some(devices) = req.ignored_devices.as_ref() {
+            let mut out = std::collections::HashSet::new();
+            for mac_str in devices.iter().map(|s| s.trim()).filter(|s| !s.is_empty()) {
+                match Self::parse_mac_address(mac_str) {
+                    Ok(mac) => {
+                        out.insert(mac);
+                    }
+                    Err(_) => return Ok(HttpResponse::error(400, format!("Invalid MAC address: {}", mac_str))),
+                }
+            }
+            updated.ignored_devices = out;
+        }
+
+        crate::storage::dns::save_runtime_config(self.options.data_dir(), &updated)?;
+        *self.dns_config.lock().unwrap() = updated.clone();
+
+        // 将新配置应用到已保存的记录：移除被忽略的记录、按需移除响应、截断到 max_records。
+        // 不再保存的响应与数据包路径一样移入 unstored_responses，/api/dns/lookup 仍可反查
+        let is_ignored = |q: &DnsQueryRecord| {
+            let mac = Self::parse_mac_address(&q.device_mac).ok();
+            updated.is_ignored(mac.as_ref(), &q.domain)
+        };
+        if let Ok(mut queries) = self.dns_queries.lock() {
+            let (kept, removed): (Vec<DnsQueryRecord>, Vec<DnsQueryRecord>) = queries
+                .drain(..)
+                .partition(|q| !is_ignored(q) && (q.is_query || updated.store_responses));
+            *queries = kept;
+
+            if let Ok(mut index) = self.dns_ip_index.lock() {
+                crate::monitor::dns::trim_records(&mut queries, updated.max_records, &mut index);
+
+                if let Ok(mut unstored) = self.unstored_responses.lock() {
+                    let (ignored, kept): (Vec<DnsQueryRecord>, Vec<DnsQueryRecord>) = unstored.drain(..).partition(|q| is_ignored(q));
+                    *unstored = kept.into();
+                    for record in ignored.iter() {
+                        crate::monitor::dns::unindex_answer_ips(&mut index, record);
+                    }
+                    for record in removed {
+                        if is_ignored(&record) || record.response_ips.is_empty() {
+                            crate::monitor::dns::unindex_answer_ips(&mut index, &record);
+                        } else {
+                            unstored.push_back(record);
+                        }
+                    }
+                    // 按时间排序后淘汰最早的
+                    unstored.make_contiguous().sort_by_key(|q| q.timestamp);
+                    while unstored.len() > updated.max_records {
+                        if let Some(evicted) = unstored.pop_front() {
+                            crate::monitor::dns::unindex_answer_ips(&mut index, &evicted);
+                        }
+                    }
+                }
+            }
+        }
+
+        log::info!("DNS configuration updated");
+
+        self.handle_get_config().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use std::collections::{HashMap, HashSet};
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("bandix-dns-api-{}-{}", name, std::process::id()))
+    }
+
+    /// 以给定的已保存记录创建处理器，应答 IP 索引与记录同步
+    fn handler(data_dir: &std::path::Path, queries: Vec<DnsQueryRecord>) -> DnsApiHandler {
+        let options = Options::parse_from(["bandix", "--iface", "br-lan", "--data-dir", data_dir.to_str().unwrap()]);
+        let data_dir = options.data_dir().to_string();
+        let thresholds = crate::monitor::alert::DnsAlertThresholds::from_options(&options);
+        let mut index = HashMap::new();
+        for record in queries.iter() {
+            crate::monitor::dns::index_answer_ips(&mut index, record);
+        }
+        DnsApiHandler::new(
+            options.clone(),
+            Arc::new(Mutex::new(queries)),
+            Arc::new(Mutex::new(index)),
+            Arc::new(Mutex::new(VecDeque::new())),
+            Arc::new(Mutex::new(HashMap::new())),
+            Arc::new(CategoryEngine::new(&data_dir)),
+            Arc::new(DnsBlockEngine::new()),
+            Arc::new(ResolverCompliance::new(&data_dir, HashSet::new())),
+            Arc::new(DnsAlertDetector::new(thresholds)),
+            Arc::new(DnsMetricsManager::new(&data_dir)),
+            Arc::new(Mutex::new(DnsRuntimeConfig::from_options(&options))),
+            Arc::new(LocalNameRegistry::new()),
+        )
+    }
+
+    fn answer(timestamp: u64, domain: &str, ip: &str) -> DnsQueryRecord {
+        DnsQueryRecord {
+            timestamp,
+            domain: domain.to_string(),
+            query_type: "A".to_string(),
+            response_code: "Success".to_string(),
+            source_ip: "192.168.1.1".to_string(),
+            destination_ip: "192.168.1.10".to_string(),
+            source_port: 53,
+            destination_port: 40000,
+            transaction_id: 0,
+            is_query: false,
+            response_ips: vec![ip.to_string()],
+            response_records: Vec::new(),
+            response_time_ms: None,
+            device_mac: "00:11:22:33:44:55".to_string(),
+            device_name: "phone".to_string(),
+            categories: Vec::new(),
+            blocked: false,
+        }
+    }
+
+    fn set_config_request(body: &str) -> HttpRequest {
+        HttpRequest {
+            method: "POST".to_string(),
+            path: "/api/dns/config".to_string(),
+            query_params: HashMap::new(),
+            body: Some(body.to_string()),
+        }
+    }
+
+    fn lookup_total(response: &HttpResponse) -> u64 {
+        let body: serde_json::Value = serde_json::from_str(&response.body).unwrap();
+        body["data"]["total"].as_u64().unwrap()
+    }
+
+    fn lookup_request(ip: Option<&str>) -> HttpRequest {
+        let mut query_params = HashMap::new();
+        if let Some(ip) = ip {
+            query_params.insert("ip".to_string(), ip.to_string());
+        }
+        HttpRequest {
+            method: "GET".to_string(),
+            path: "/api/dns/lookup".to_string(),
+            query_params,
+            body: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lookup_rejects_missing_and_invalid_ip() {
+        let dir = temp_dir("invalid");
+        let handler = handler(&dir, Vec::new());
+
+        let response = handler.handle_lookup(&lookup_request(None)).await.unwrap();
+        assert_eq!(response.status, 400);
+        assert!(response.body.contains("Missing required parameter: ip"));
+
+        let response = handler.handle_lookup(&lookup_request(Some("  "))).await.unwrap();
+        assert_eq!(response.status, 400);
+
+        let response = handler.handle_lookup(&lookup_request(Some("192.168.1.300"))).await.unwrap();
+        assert_eq!(response.status, 400);
+        assert!(response.body.contains("Invalid IP address: 192.168.1.300"));
+    }
+
+    #[tokio::test]
+    async fn test_lookup_empty_and_normalized_ip() {
+        let dir = temp_dir("empty");
+        let handler = handler(&dir, vec![answer(1, "example.com", "2001:db8::1")]);
+
+        // 没有记录的 IP 返回空结果而不是错误
+        let response = handler.handle_lookup(&lookup_request(Some("10.0.0.1"))).await.unwrap();
+        assert_eq!(response.status, 200);
+        let body: serde_json::Value = serde_json::from_str(&response.body).unwrap();
+        assert_eq!(body["data"]["ip"], "10.0.0.1");
+        assert_eq!(body["data"]["total"], 0);
+        assert_eq!(body["data"]["domains"], serde_json::json!([]));
+        assert_eq!(body["data"]["records"], serde_json::json!([]));
+
+        // 非规范写法的 IPv6 地址按规范格式匹配
+        let response = handler.handle_lookup(&lookup_request(Some("2001:0db8:0::1"))).await.unwrap();
+        assert_eq!(response.status, 200);
+        let body: serde_json::Value = serde_json::from_str(&response.body).unwrap();
+        assert_eq!(body["data"]["ip"], "2001:db8::1");
+        assert_eq!(body["data"]["total"], 1);
+        assert_eq!(body["data"]["domains"], serde_json::json!(["example.com"]));
+        assert_eq!(body["data"]["devices"], serde_json::json!(["00:11:22:33:44:55"]));
+    }
+
+    #[tokio::test]
+    async fn test_set_config_trims_records_and_index() {
+        let dir = temp_dir("trim");
+        let handler = handler(
+            &dir,
+            vec![
+                answer(1, "a.com", "10.0.0.1"),
+                answer(2, "b.com", "10.0.0.2"),
+                answer(3, "c.com", "10.0.0.3"),
+            ],
+        );
+
+        let request = set_config_request(r#"{"max_records": 2}"#);
+        let response = handler.handle_set_config(&request).await.unwrap();
+        assert_eq!(response.status, 200);
+        assert_eq!(handler.dns_queries.lock().unwrap().len(), 2);
+
+        // 淘汰的记录不再能反查，保留的记录仍可反查
+        let response = handler.handle_lookup(&lookup_request(Some("10.0.0.1"))).await.unwrap();
+        assert_eq!(lookup_total(&response), 0);
+        let response = handler.handle_lookup(&lookup_request(Some("10.0.0.3"))).await.unwrap();
+        assert_eq!(lookup_total(&response), 1);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_lookup_survives_store_responses_toggle() {
+        let dir = temp_dir("toggle");
+        let mut query = answer(1, "a.com", "10.0.0.1");
+        query.is_query = true;
+        query.response_ips.clear();
+        let handler = handler(
+            &dir,
+            vec![query, answer(2, "a.com", "10.0.0.1"), answer(3, "b.com", "10.0.0.2")],
+        );
+
+        // 关闭 store_responses 后只保存查询，但已有响应的应答 IP 仍可反查
+        let request = set_config_request(r#"{"store_responses": false}"#);
+        assert_eq!(handler.handle_set_config(&request).await.unwrap().status, 200);
+        assert_eq!(handler.dns_queries.lock().unwrap().len(), 1);
+        assert_eq!(handler.unstored_responses.lock().unwrap().len(), 2);
+        let response = handler.handle_lookup(&lookup_request(Some("10.0.0.1"))).await.unwrap();
+        assert_eq!(lookup_total(&response), 1);
+
+        // 重新打开后仍可反查
+        let request = set_config_request(r#"{"store_responses": true}"#);
+        assert_eq!(handler.handle_set_config(&request).await.unwrap().status, 200);
+        let response = handler.handle_lookup(&lookup_request(Some("10.0.0.2"))).await.unwrap();
+        assert_eq!(lookup_total(&response), 1);
+
+        // 忽略设备后其响应从索引中移除
+        let request = set_config_request(r#"{"ignored_devices": ["00:11:22:33:44:55"]}"#);
+        assert_eq!(handler.handle_set_config(&request).await.unwrap().status, 200);
+        assert!(handler.unstored_responses.lock().unwrap().is_empty());
+        assert!(handler.dns_ip_index.lock().unwrap().is_empty());
+
+        std::fs::remove_dir_all(&dir).ok();
     }
 }
@@ -174,7 +174,12 @@ impl ApiRouter {
         for handler in self.handlers.values() {
             for route in handler.supported_routes() {
                 if request.path.starts_with(route) {
-                    return handler.handle_request(request).await;
+                    let mut response = handler.handle_request(request).await?;
+                    // 开启匿名化时统一改写响应中的 MAC / IP / 域名
+                    if response.status == 200 && response.content_type == "application/json" {
+                        response.body = crate::privacy::anonymize_json_body(response.body);
+                    }
+                    return Ok(response);
                 }
             }
         }
@@ -1,5 +1,11 @@
 use super::{ApiResponse, HttpRequest, HttpResponse};
 use crate::command::Options;
+use crate::monitor::dhcp::DhcpSnooper;
+use crate::monitor::domain::DomainTracker;
+use crate::monitor::fingerprint::DeviceType;
+use crate::storage::groups::GroupRateLimit;
+use crate::storage::history::HistoryKind;
+use crate::storage::meta::DeviceMeta;
 use crate::storage::traffic::{self, LongTermRingManager, RealtimeRingManager, ScheduledRateLimit, TimeSlot};
 use crate::utils::format_utils::{format_bytes, format_mac};
 use chrono::{DateTime, Utc};
@@ -10,6 +16,9 @@ use std::sync::{Arc, Mutex};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+// 按身份合并的增量：WAN 接收、WAN 发送、LAN 接收、LAN 发送字节数与身份下的 MAC
+type IdentityIncrements = (u64, u64, u64, u64, Vec<[u8; 6]>);
+
 /// 设备信息，用于 API 响应
 #[derive(Serialize, Deserialize)]
 pub struct DeviceInfo {
@@ -17,6 +26,8 @@ pub struct DeviceInfo {
     pub ipv6_addresses: Vec<String>,
     pub mac: String,
     pub hostname: String,
+    pub vendor: Option<String>,
+    pub randomized_mac: bool,
     pub connection_type: String,
 
     pub total_rx_bytes: u64,
@@ -38,6 +49,176 @@ pub struct DeviceInfo {
     pub wan_tx_rate: u64,
 
     pub last_online_ts: u64,
+
+    pub wireless: Option<DeviceWirelessInfo>, // 仅 Wi-Fi 客户端有值
+    pub device_type: Option<DeviceTypeInfo>,  // 设备类型识别结果，没有可用信号时为 None
+    pub identity: Option<String>,             // 所属合并身份的主 MAC，未与其他 MAC 合并时为 None
+    pub meta: Option<DeviceMeta>,             // 用户填写的元数据，未填写时为 None
+    pub groups: Vec<String>,                  // 所在的设备组
+}
+
+/// 设备类型识别结果
+#[derive(Serialize, Deserialize)]
+pub struct DeviceTypeInfo {
+    pub label: String,   // "phone"、"tablet"、"computer"、"tv"、"console"、"speaker"、"printer"、"camera"、"iot"、"network"
+    pub confidence: f32, // 0.0 ~ 1.0
+    pub manual: bool,    // 是否为手动设置
+    pub signals: Vec<String>, // 支持该结果的信号
+}
+
+impl From<crate::monitor::fingerprint::Classification> for DeviceTypeInfo {
+    fn from(classification: crate::monitor::fingerprint::Classification) -> Self {
+        Self {
+            label: classification.device_type.as_str().to_string(),
+            confidence: classification.confidence,
+            manual: classification.manual,
+            signals: classification.signals,
+        }
+    }
+}
+
+/// Wi-Fi 客户端信息，字节数为 AP 视角（rx 为从客户端接收）
+#[derive(Serialize, Deserialize)]
+pub struct DeviceWirelessInfo {
+    pub interface: String,
+    pub ssid: Option<String>,
+    pub band: Option<String>, // "2.4GHz"、"5GHz"、"6GHz" 或 "60GHz"
+    pub frequency_mhz: Option<u32>,
+    pub signal_dbm: Option<i32>,
+    pub signal_avg_dbm: Option<i32>,
+    pub tx_bitrate_kbps: Option<u32>,
+    pub rx_bitrate_kbps: Option<u32>,
+    pub rx_bytes: Option<u64>,
+    pub tx_bytes: Option<u64>,
+    pub connected_time_secs: Option<u32>,
+    pub inactive_time_ms: Option<u32>,
+    pub source: String, // "nl80211" 或 "ubus"
+}
+
+impl From<crate::wireless::WirelessStation> for DeviceWirelessInfo {
+    fn from(station: crate::wireless::WirelessStation) -> Self {
+        Self {
+            band: station.band().map(|band| band.to_string()),
+            source: station.source.as_str().to_string(),
+            interface: station.interface,
+            ssid: station.ssid,
+            frequency_mhz: station.frequency_mhz,
+            signal_dbm: station.signal_dbm,
+            signal_avg_dbm: station.signal_avg_dbm,
+            tx_bitrate_kbps: station.tx_bitrate_kbps,
+            rx_bitrate_kbps: station.rx_bitrate_kbps,
+            rx_bytes: station.rx_bytes,
+            tx_bytes: station.tx_bytes,
+            connected_time_secs: station.connected_time_secs,
+            inactive_time_ms: station.inactive_time_ms,
+        }
+    }
+}
+
+/// 单个设备的 Wi-Fi 信息响应
+#[derive(Serialize, Deserialize)]
+pub struct DeviceWirelessResponse {
+    pub mac: String,
+    pub wireless: DeviceWirelessInfo,
+}
+
+/// 设备在线会话条目
+#[derive(Serialize, Deserialize)]
+pub struct DeviceSessionInfo {
+    pub start_ms: u64,
+    pub end_ms: Option<u64>, // 仍在线时为 None
+    pub duration_ms: u64,    // 会话时长，仍在线时计算到当前时间
+}
+
+/// /api/devices/{mac}/sessions 响应
+#[derive(Serialize, Deserialize)]
+pub struct DeviceSessionsResponse {
+    pub mac: String,
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub online_ms: u64, // 时间范围内的在线总时长
+    pub sessions: Vec<DeviceSessionInfo>,
+}
+
+/// 设备用过的一个地址及其出现的时间段
+#[derive(Serialize, Deserialize)]
+pub struct HistoryAddressInfo {
+    pub ip: String,
+    pub first_seen_ms: u64,
+    pub last_seen_ms: u64,
+}
+
+/// 设备用过的一个主机名及其出现的时间段
+#[derive(Serialize, Deserialize)]
+pub struct HistoryHostnameInfo {
+    pub hostname: String,
+    pub first_seen_ms: u64,
+    pub last_seen_ms: u64,
+}
+
+/// /api/devices/{mac}/history 响应，各列表按首次出现时间升序
+#[derive(Serialize, Deserialize)]
+pub struct DeviceHistoryResponse {
+    pub mac: String,
+    pub ipv4: Vec<HistoryAddressInfo>,
+    pub ipv6: Vec<HistoryAddressInfo>,
+    pub hostnames: Vec<HistoryHostnameInfo>,
+}
+
+/// /api/devices/{mac}/meta 响应
+#[derive(Serialize, Deserialize)]
+pub struct DeviceMetaResponse {
+    pub mac: String,
+    pub owner: String,
+    pub tags: Vec<String>,
+    pub notes: String,
+    pub icon: String,
+    pub device_type: Option<String>, // 手动设置的设备类型，未设置时为 None
+}
+
+/// 设置设备元数据请求结构，未提供的字段保持不变
+#[derive(Serialize, Deserialize)]
+pub struct SetDeviceMetaRequest {
+    #[serde(default)]
+    pub owner: Option<String>,
+    #[serde(default)]
+    pub tags: Option<Vec<String>>,
+    #[serde(default)]
+    pub notes: Option<String>,
+    #[serde(default)]
+    pub icon: Option<String>,
+    #[serde(default)]
+    pub device_type: Option<String>, // 空字符串恢复自动识别
+}
+
+/// /api/events 响应
+#[derive(Serialize)]
+pub struct EventsResponse {
+    pub total: usize, // 满足条件的事件总数
+    pub events: Vec<crate::storage::events::DeviceEvent>,
+}
+
+/// /api/security/alerts 响应
+#[derive(Serialize)]
+pub struct SecurityAlertsResponse {
+    pub total: usize, // 满足条件的告警总数
+    pub alerts: Vec<crate::storage::events::DeviceEvent>,
+}
+
+/// 某一天全网的在线时长
+#[derive(Serialize, Deserialize)]
+pub struct DailyOnlineHours {
+    pub date: String,      // 本地日期，YYYY-MM-DD
+    pub online_hours: f64, // 所有设备在线小时数之和
+    pub device_count: usize,
+}
+
+/// /api/traffic/usage/online_hours 响应
+#[derive(Serialize, Deserialize)]
+pub struct OnlineHoursResponse {
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub days: Vec<DailyOnlineHours>,
 }
 
 /// 设备响应结构
@@ -61,7 +242,8 @@ pub struct MetricsResponse {
 /// 设备使用排名条目
 #[derive(Serialize, Deserialize)]
 pub struct DeviceUsageRanking {
-    pub mac: String,
+    pub mac: String,       // 设备身份的主 MAC
+    pub macs: Vec<String>, // 计入该条目的全部 MAC（同一身份下轮换使用的地址）
     pub hostname: String,
     pub ip: String,
     pub total_bytes: u64, // 时间范围内总字节数（rx + tx）
@@ -81,6 +263,8 @@ pub struct DeviceUsageRankingResponse {
     pub total_rx_bytes: u64,  // 所有设备的总接收字节数
     pub total_tx_bytes: u64,  // 所有设备的总发送字节数
     pub device_count: usize,  // 设备数量
+    // 按设备组查询时的组名
+    pub group: Option<String>,
     pub rankings: Vec<DeviceUsageRanking>,
 }
 
@@ -126,18 +310,45 @@ pub struct TimeSeriesIncrementResponse {
     pub end_ms: u64,
     pub aggregation: String,  // "hourly" 或 "daily"
     pub mac: String,          // MAC 地址（或 "all" 表示聚合）
+    pub macs: Vec<String>,    // 计入结果的全部 MAC（mac 所属身份下的地址或组内地址），"all" 时为空
     pub network_type: String, // "wan" 或 "lan"
+    // 按设备组查询时的组名
+    pub group: Option<String>,
     pub increments: Vec<TimeSeriesIncrement>,
     pub total_rx_bytes: u64, // 范围内的总 RX 字节数
     pub total_tx_bytes: u64, // 范围内的总 TX 字节数
     pub total_bytes: u64,    // 范围内的总字节数
 }
 
+/// 域名流量排名条目
+#[derive(Serialize, Deserialize)]
+pub struct DomainUsageRanking {
+    pub domain: String,
+    pub total_bytes: u64, // 时间范围内总字节数（rx + tx）
+    pub rx_bytes: u64,    // 接收字节数
+    pub tx_bytes: u64,    // 发送字节数
+    pub percentage: f64,  // 已归属流量的百分比
+    pub rank: usize,      // 排名位置（从1开始）
+}
+
+/// 域名流量排名响应结构
+#[derive(Serialize, Deserialize)]
+pub struct DomainUsageResponse {
+    pub start_ms: u64,
+    pub end_ms: u64,
+    pub mac: String,                // MAC 地址（或 "all" 表示所有设备）
+    pub total_bytes: u64,           // 已归属到域名的总字节数
+    pub unattributed_bytes: u64,    // 无法归属到域名的 WAN 字节数
+    pub domain_count: usize,
+    pub domains: Vec<DomainUsageRanking>,
+}
+
 /// 主机名绑定信息，用于 API 响应
 #[derive(Serialize, Deserialize)]
 pub struct HostnameBinding {
     pub mac: String,
-    pub hostname: String,
+    pub hostname: String,            // 只设置了设备类型时为空
+    pub device_type: Option<String>, // 手动设置的设备类型
 }
 
 /// 主机名绑定响应结构
@@ -150,7 +361,114 @@ pub struct HostnameBindingsResponse {
 #[derive(Serialize, Deserialize)]
 pub struct SetHostnameBindingRequest {
     pub mac: String,
+    #[serde(default)]
+    pub hostname: Option<String>, // 不提供则保持不变，空字符串清除绑定
+    #[serde(default)]
+    pub device_type: Option<String>, // 不提供则保持不变，空字符串恢复自动识别
+}
+
+/// 合并身份中关联到主 MAC 的成员
+#[derive(Serialize, Deserialize)]
+pub struct IdentityMemberInfo {
+    pub mac: String,
+    pub source: String, // 关联依据："client_id"、"hostname" 或 "manual"
+}
+
+/// 合并后的设备身份
+#[derive(Serialize, Deserialize)]
+pub struct DeviceIdentityInfo {
+    pub mac: String, // 主 MAC
     pub hostname: String,
+    pub members: Vec<IdentityMemberInfo>,
+}
+
+/// 设备身份列表响应
+#[derive(Serialize, Deserialize)]
+pub struct DeviceIdentitiesResponse {
+    pub identities: Vec<DeviceIdentityInfo>,
+}
+
+/// 合并设备身份请求：将 mac 所属的身份合并到 into 所属的身份
+#[derive(Serialize, Deserialize)]
+pub struct MergeIdentityRequest {
+    pub mac: String,
+    pub into: String,
+}
+
+/// 拆分设备身份请求
+#[derive(Serialize, Deserialize)]
+pub struct SplitIdentityRequest {
+    pub mac: String,
+}
+
+/// 设备组的预定速率限制规则
+#[derive(Serialize, Deserialize)]
+pub struct GroupRateLimitInfo {
+    pub time_slot: TimeSlotApi,
+    pub wan_rx_rate_limit: u64,
+    pub wan_tx_rate_limit: u64,
+}
+
+/// 设备组
+#[derive(Serialize, Deserialize)]
+pub struct DeviceGroupInfo {
+    pub name: String,
+    pub macs: Vec<String>,
+    pub rate_limits: Vec<GroupRateLimitInfo>,
+}
+
+/// /api/traffic/groups 响应
+#[derive(Serialize, Deserialize)]
+pub struct DeviceGroupsResponse {
+    pub groups: Vec<DeviceGroupInfo>,
+}
+
+/// 创建设备组或替换其成员
+#[derive(Serialize, Deserialize)]
+pub struct SetDeviceGroupRequest {
+    pub name: String,
+    pub macs: Vec<String>,
+}
+
+/// 删除设备组请求
+#[derive(Serialize, Deserialize)]
+pub struct DeleteDeviceGroupRequest {
+    pub name: String,
+}
+
+/// 设置设备组预定速率限制请求，时间段相同的规则会被替换
+#[derive(Serialize, Deserialize)]
+pub struct SetGroupLimitRequest {
+    pub group: String,
+    pub time_slot: TimeSlotApi,
+    pub wan_rx_rate_limit: u64,
+    pub wan_tx_rate_limit: u64,
+}
+
+/// 删除设备组预定速率限制请求
+#[derive(Serialize, Deserialize)]
+pub struct DeleteGroupLimitRequest {
+    pub group: String,
+    pub time_slot: TimeSlotApi,
+}
+
+/// 设备的 DHCP 信息，用于 API 响应
+#[derive(Serialize, Deserialize)]
+pub struct DeviceDhcpResponse {
+    pub mac: String,
+    pub ip: Option<String>,              // 服务器最近一次 ACK 确认的地址
+    pub requested_ip: Option<String>,    // 客户端请求的地址（选项 50）
+    pub hostname: Option<String>,        // 客户端上报的主机名（选项 12）
+    pub vendor_class: Option<String>,    // 厂商类别标识（选项 60）
+    pub parameter_request_list: Vec<u8>, // 参数请求列表（选项 55），可用于识别操作系统
+    pub server_ip: Option<String>,
+    pub lease_time_secs: Option<u32>,
+    pub lease_start_ms: Option<u64>,
+    pub lease_expires_ms: Option<u64>,
+    pub released: bool,
+    pub last_message_type: String,
+    pub first_seen_ms: u64,
+    pub last_seen_ms: u64,
 }
 
 /// 时间段，用于 API 请求/响应
@@ -242,6 +560,8 @@ pub struct TrafficApiHandler {
     realtime_manager: Arc<RealtimeRingManager>,
     long_term_manager: Arc<LongTermRingManager>,
     device_manager: Arc<crate::device::DeviceManager>,
+    domain_tracker: Arc<DomainTracker>,
+    dhcp_snooper: Arc<DhcpSnooper>,
     options: Options,
 }
 
@@ -255,6 +575,8 @@ impl TrafficApiHandler {
         realtime_manager: Arc<RealtimeRingManager>,
         long_term_manager: Arc<LongTermRingManager>,
         device_manager: Arc<crate::device::DeviceManager>,
+        domain_tracker: Arc<DomainTracker>,
+        dhcp_snooper: Arc<DhcpSnooper>,
         options: Options,
     ) -> Self {
         Self {
@@ -266,6 +588,8 @@ impl TrafficApiHandler {
             realtime_manager,
             long_term_manager,
             device_manager,
+            domain_tracker,
+            dhcp_snooper,
             options,
         }
     }
@@ -278,16 +602,41 @@ impl TrafficApiHandler {
             "/api/traffic/limits/schedule",
             "/api/traffic/metrics",
             "/api/traffic/bindings",
+            "/api/traffic/identities",
+            "/api/traffic/groups",
             "/api/traffic/usage/ranking",
             "/api/traffic/usage/increments",
+            "/api/traffic/usage/online_hours",
+            "/api/traffic/domains",
             "/api/traffic/rate_limit/whitelist",
             "/api/traffic/rate_limit/whitelist/enabled",
             "/api/traffic/rate_limit/default",
+            "/api/devices/",
+            "/api/events",
+            "/api/security/alerts",
         ]
     }
 
     pub async fn handle_request(&self, request: &HttpRequest) -> Result<HttpResponse, anyhow::Error> {
         match request.path.as_str() {
+            path if path.starts_with("/api/devices/") => match request.method.as_str() {
+                "GET" | "POST" => self.handle_device_resource(request).await,
+                _ => Ok(HttpResponse::error(405, "Method not allowed".to_string())),
+            },
+            "/api/events" => {
+                if request.method == "GET" {
+                    self.handle_events(request).await
+                } else {
+                    Ok(HttpResponse::error(405, "Method not allowed".to_string()))
+                }
+            }
+            "/api/security/alerts" => {
+                if request.method == "GET" {
+                    self.handle_security_alerts(request).await
+                } else {
+                    Ok(HttpResponse::error(405, "Method not allowed".to_string()))
+                }
+            }
             "/api/traffic/devices" => {
                 if request.method == "GET" {
                     self.handle_devices(request).await
@@ -300,6 +649,29 @@ impl TrafficApiHandler {
                 "POST" => self.handle_set_hostname_binding(request).await,
                 _ => Ok(HttpResponse::error(405, "Method not allowed".to_string())),
             },
+            "/api/traffic/identities" => match request.method.as_str() {
+                "GET" => self.handle_identities().await,
+                _ => Ok(HttpResponse::error(405, "Method not allowed".to_string())),
+            },
+            "/api/traffic/identities/merge" => match request.method.as_str() {
+                "POST" => self.handle_merge_identity(request).await,
+                _ => Ok(HttpResponse::error(405, "Method not allowed".to_string())),
+            },
+            "/api/traffic/groups" => match request.method.as_str() {
+                "GET" => self.handle_groups().await,
+                "POST" => self.handle_set_group(request).await,
+                "DELETE" => self.handle_delete_group(request).await,
+                _ => Ok(HttpResponse::error(405, "Method not allowed".to_string())),
+            },
+            "/api/traffic/groups/limits" => match request.method.as_str() {
+                "POST" => self.handle_set_group_limit(request).await,
+                "DELETE" => self.handle_delete_group_limit(request).await,
+                _ => Ok(HttpResponse::error(405, "Method not allowed".to_string())),
+            },
+            "/api/traffic/identities/split" => match request.method.as_str() {
+                "POST" => self.handle_split_identity(request).await,
+                _ => Ok(HttpResponse::error(405, "Method not allowed".to_string())),
+            },
             "/api/traffic/metrics" => {
                 if request.method == "GET" {
                     self.handle_metrics(request).await
@@ -321,6 +693,20 @@ impl TrafficApiHandler {
                     Ok(HttpResponse::error(405, "Method not allowed".to_string()))
                 }
             }
+            "/api/traffic/usage/online_hours" => {
+                if request.method == "GET" {
+                    self.handle_online_hours(request).await
+                } else {
+                    Ok(HttpResponse::error(405, "Method not allowed".to_string()))
+                }
+            }
+            "/api/traffic/domains" => {
+                if request.method == "GET" {
+                    self.handle_domain_usage(request).await
+                } else {
+                    Ok(HttpResponse::error(405, "Method not allowed".to_string()))
+                }
+            }
             "/api/traffic/limits/schedule" => match request.method.as_str() {
                 "GET" => self.handle_scheduled_limits().await,
                 "POST" => self.handle_set_scheduled_limit(request).await,
@@ -473,6 +859,7 @@ impl TrafficApiHandler {
     /// 查询参数：
     ///   - start_ms: 开始时间戳，毫秒（可选）
     ///   - end_ms: 结束时间戳，毫秒（可选）
+    ///   - tag: 只返回带该标签的设备，不区分大小写（可选）
     ///   如果都为空，则默认返回所有设备的累积流量（period=all）
     async fn handle_devices(&self, request: &HttpRequest) -> Result<HttpResponse, anyhow::Error> {
         let start_ms_param = request.query_params.get("start_ms").and_then(|s| s.parse::<u64>().ok());
@@ -508,8 +895,13 @@ impl TrafficApiHandler {
         let bindings_map = self.hostname_bindings.lock().unwrap();
         let wifi_set = self.device_manager.get_wifi_macs_snapshot();
 
-        // 从设备管理器收集所有设备（包括在线和离线设备）
-        let all_devices = self.device_manager.get_all_devices_with_mac();
+        // 从设备管理器收集所有设备（包括在线和离线设备），指定 tag 时只保留带该标签的设备
+        let meta_store = self.device_manager.device_meta();
+        let tag = request.query_params.get("tag").map(|s| s.trim()).filter(|s| !s.is_empty());
+        let mut all_devices = self.device_manager.get_all_devices_with_mac();
+        if let Some(tag) = tag {
+            all_devices.retain(|(mac, _)| meta_store.has_tag(mac, tag));
+        }
 
         let devices: Vec<DeviceInfo> = all_devices
             .into_iter()
@@ -654,6 +1046,8 @@ impl TrafficApiHandler {
                     ipv6_addresses,
                     mac: mac_str,
                     hostname,
+                    vendor: device.vendor.clone(),
+                    randomized_mac: device.randomized_mac,
                     connection_type,
                     total_rx_bytes: final_total_rx_bytes,
                     total_tx_bytes: final_total_tx_bytes,
@@ -670,6 +1064,18 @@ impl TrafficApiHandler {
                     wan_rx_rate: device.wan_rx_rate,
                     wan_tx_rate: device.wan_tx_rate,
                     last_online_ts: device.last_online_ts,
+                    wireless: self.device_manager.get_wireless_station(&mac).map(DeviceWirelessInfo::from),
+                    device_type: device.device_type.clone().map(DeviceTypeInfo::from),
+                    identity: {
+                        let macs = self.device_manager.identity_registry().group_macs(&mac);
+                        if macs.len() > 1 {
+                            Some(format_mac(&macs[0]))
+                        } else {
+                            None
+                        }
+                    },
+                    meta: meta_store.get(&mac),
+                    groups: self.device_manager.groups_of(&mac),
                 };
 
                 log::debug!(
@@ -776,20 +1182,395 @@ impl TrafficApiHandler {
         }
     }
 
+    /// 处理 /api/devices/{mac}/... 形式的单设备资源
+    async fn handle_device_resource(&self, request: &HttpRequest) -> Result<HttpResponse, anyhow::Error> {
+        let rest = request.path.trim_start_matches("/api/devices/");
+        let (mac_str, resource) = match rest.split_once('/') {
+            Some(parts) => parts,
+            None => return Ok(HttpResponse::not_found()),
+        };
+        let mac = match crate::utils::network_utils::parse_mac_address(mac_str) {
+            Ok(mac) => mac,
+            Err(e) => return Ok(HttpResponse::error(400, format!("Invalid MAC address: {}", e))),
+        };
+
+        match (request.method.as_str(), resource) {
+            ("GET", "dhcp") => self.handle_device_dhcp(mac).await,
+            ("GET", "wireless") => self.handle_device_wireless(mac).await,
+            ("GET", "sessions") => self.handle_device_sessions(mac, request).await,
+            ("GET", "history") => self.handle_device_history(mac, request).await,
+            ("GET", "meta") => self.handle_device_meta(mac).await,
+            ("POST", "meta") => self.handle_set_device_meta(mac, request).await,
+            ("GET", _) => Ok(HttpResponse::not_found()),
+            _ => Ok(HttpResponse::error(405, "Method not allowed".to_string())),
+        }
+    }
+
+    /// 从查询参数解析 start_ms / end_ms，缺省时为最近 default_days 天
+    fn parse_time_range(request: &HttpRequest, now_ms: u64, default_days: u64) -> Result<(u64, u64), HttpResponse> {
+        let start_ms = request
+            .query_params
+            .get("start_ms")
+            .and_then(|s| s.parse::<u64>().ok())
+            .unwrap_or_else(|| now_ms.saturating_sub(default_days * 24 * 3600 * 1000));
+        let end_ms = request
+            .query_params
+            .get("end_ms")
+            .and_then(|s| s.parse::<u64>().ok())
+            .unwrap_or(now_ms);
+        if start_ms >= end_ms {
+            return Err(HttpResponse::error(
+                400,
+                "Invalid time range: start_ms must be less than end_ms".to_string(),
+            ));
+        }
+        Ok((start_ms, end_ms))
+    }
+
+    /// 处理 /api/devices/{mac}/sessions endpoint
+    /// 查询参数：
+    ///   - start_ms: 开始时间戳，毫秒（可选，默认为 7 天前）
+    ///   - end_ms: 结束时间戳，毫秒（可选，默认为现在）
+    async fn handle_device_sessions(&self, mac: [u8; 6], request: &HttpRequest) -> Result<HttpResponse, anyhow::Error> {
+        let now_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+        let (start_ms, end_ms) = match Self::parse_time_range(request, now_ms, 7) {
+            Ok(range) => range,
+            Err(response) => return Ok(response),
+        };
+
+        let sessions: Vec<DeviceSessionInfo> = self
+            .device_manager
+            .presence_tracker()
+            .sessions(&mac, start_ms, end_ms)
+            .into_iter()
+            .map(|s| DeviceSessionInfo {
+                start_ms: s.start_ms,
+                end_ms: s.end_ms,
+                duration_ms: s.end_ms.unwrap_or(now_ms).saturating_sub(s.start_ms),
+            })
+            .collect();
+        let online_ms = sessions
+            .iter()
+            .map(|s| s.end_ms.unwrap_or(now_ms).min(end_ms).saturating_sub(s.start_ms.max(start_ms)))
+            .sum();
+
+        let response = DeviceSessionsResponse {
+            mac: format_mac(&mac),
+            start_ms,
+            end_ms,
+            online_ms,
+            sessions,
+        };
+        let api_response = ApiResponse::success(response);
+        let body = serde_json::to_string(&api_response)?;
+        Ok(HttpResponse::ok(body))
+    }
+
+    /// 处理 /api/devices/{mac}/history endpoint
+    /// 查询参数：
+    ///   - start_ms / end_ms: 只返回在此时间范围内出现过的条目，毫秒（可选，默认全部）
+    async fn handle_device_history(&self, mac: [u8; 6], request: &HttpRequest) -> Result<HttpResponse, anyhow::Error> {
+        let start_ms = request
+            .query_params
+            .get("start_ms")
+            .and_then(|s| s.parse::<u64>().ok())
+            .unwrap_or(0);
+        let end_ms = request
+            .query_params
+            .get("end_ms")
+            .and_then(|s| s.parse::<u64>().ok())
+            .unwrap_or(u64::MAX);
+        if start_ms >= end_ms {
+            return Ok(HttpResponse::error(
+                400,
+                "Invalid time range: start_ms must be less than end_ms".to_string(),
+            ));
+        }
+
+        let mut response = DeviceHistoryResponse {
+            mac: format_mac(&mac),
+            ipv4: Vec::new(),
+            ipv6: Vec::new(),
+            hostnames: Vec::new(),
+        };
+        for entry in self.device_manager.device_history().entries(&mac, start_ms, end_ms) {
+            let (first_seen_ms, last_seen_ms) = (entry.first_seen_ms, entry.last_seen_ms);
+            match entry.kind {
+                HistoryKind::Ipv4 => response.ipv4.push(HistoryAddressInfo {
+                    ip: entry.value,
+                    first_seen_ms,
+                    last_seen_ms,
+                }),
+                HistoryKind::Ipv6 => response.ipv6.push(HistoryAddressInfo {
+                    ip: entry.value,
+                    first_seen_ms,
+                    last_seen_ms,
+                }),
+                HistoryKind::Hostname => response.hostnames.push(HistoryHostnameInfo {
+                    hostname: entry.value,
+                    first_seen_ms,
+                    last_seen_ms,
+                }),
+            }
+        }
+
+        let api_response = ApiResponse::success(response);
+        let body = serde_json::to_string(&api_response)?;
+        Ok(HttpResponse::ok(body))
+    }
+
+    fn device_meta_response(&self, mac: [u8; 6]) -> DeviceMetaResponse {
+        let meta = self.device_manager.device_meta().get(&mac).unwrap_or_default();
+        DeviceMetaResponse {
+            mac: format_mac(&mac),
+            owner: meta.owner,
+            tags: meta.tags,
+            notes: meta.notes,
+            icon: meta.icon,
+            device_type: self
+                .device_manager
+                .device_classifier()
+                .get_override(&mac)
+                .map(|t| t.as_str().to_string()),
+        }
+    }
+
+    /// 处理 /api/devices/{mac}/meta endpoint (GET)
+    async fn handle_device_meta(&self, mac: [u8; 6]) -> Result<HttpResponse, anyhow::Error> {
+        let api_response = ApiResponse::success(self.device_meta_response(mac));
+        let body = serde_json::to_string(&api_response)?;
+        Ok(HttpResponse::ok(body))
+    }
+
+    /// 处理 /api/devices/{mac}/meta endpoint (POST)，未提供的字段保持不变
+    async fn handle_set_device_meta(&self, mac: [u8; 6], request: &HttpRequest) -> Result<HttpResponse, anyhow::Error> {
+        let body = request.body.as_ref().ok_or_else(|| anyhow::anyhow!("Missing request body"))?;
+        let set_meta_request: SetDeviceMetaRequest = match serde_json::from_str(body) {
+            Ok(req) => req,
+            Err(e) => return Ok(HttpResponse::error(400, format!("Invalid request body: {}", e))),
+        };
+
+        // 设备类型：先校验，避免部分生效
+        let device_type = match set_meta_request.device_type.as_deref().map(str::trim) {
+            None => None,
+            Some("") => Some(None),
+            Some(s) => match DeviceType::parse(s) {
+                Some(t) => Some(Some(t)),
+                None => return Ok(HttpResponse::error(400, format!("Invalid device type: {}", s))),
+            },
+        };
+
+        let store = self.device_manager.device_meta();
+        let mut meta = store.get(&mac).unwrap_or_default();
+        if let Some(owner) = set_meta_request.owner {
+            meta.owner = owner;
+        }
+        if let Some(tags) = set_meta_request.tags {
+            meta.tags = tags;
+        }
+        if let Some(notes) = set_meta_request.notes {
+            meta.notes = notes;
+        }
+        if let Some(icon) = set_meta_request.icon {
+            meta.icon = icon;
+        }
+        let meta = match crate::meta::normalize(meta) {
+            Ok(meta) => meta,
+            Err(e) => return Ok(HttpResponse::error(400, e.to_string())),
+        };
+
+        store.set(mac, meta)?;
+        if let Some(device_type) = device_type {
+            traffic::upsert_device_type_override(self.options.data_dir(), &mac, device_type)?;
+            self.device_manager.set_device_type_override(mac, device_type);
+        }
+        log::info!("Device metadata updated for MAC: {}", format_mac(&mac));
+
+        let api_response = ApiResponse::success(self.device_meta_response(mac));
+        let body = serde_json::to_string(&api_response)?;
+        Ok(HttpResponse::ok(body))
+    }
+
+    /// 处理 /api/events endpoint，最新的事件在前
+    /// 查询参数：
+    ///   - type: 事件类型（可选）
+    ///   - mac: MAC 地址（可选）
+    ///   - start_ms / end_ms: 时间范围，毫秒（可选）
+    ///   - limit: 最多返回的事件数（可选，默认 100，最大 1000）
+    async fn handle_events(&self, request: &HttpRequest) -> Result<HttpResponse, anyhow::Error> {
+        let (filter, limit) = match Self::parse_event_query(request, false) {
+            Ok(query) => query,
+            Err(response) => return Ok(response),
+        };
+
+        let (total, events) = self.device_manager.event_journal().query(&filter, limit);
+        let api_response = ApiResponse::success(EventsResponse { total, events });
+        let body = serde_json::to_string(&api_response)?;
+        Ok(HttpResponse::ok(body))
+    }
+
+    /// 处理 /api/security/alerts endpoint，只返回 ARP/NDP 欺骗检测产生的安全事件，最新的在前
+    /// 查询参数与 /api/events 相同，type 只能是 gateway_impersonation、duplicate_ip 或 mac_flip
+    async fn handle_security_alerts(&self, request: &HttpRequest) -> Result<HttpResponse, anyhow::Error> {
+        let (filter, limit) = match Self::parse_event_query(request, true) {
+            Ok(query) => query,
+            Err(response) => return Ok(response),
+        };
+
+        let (total, alerts) = self.device_manager.event_journal().query(&filter, limit);
+        let api_response = ApiResponse::success(SecurityAlertsResponse { total, alerts });
+        let body = serde_json::to_string(&api_response)?;
+        Ok(HttpResponse::ok(body))
+    }
+
+    /// 解析事件查询参数，security_only 时只接受安全事件类型
+    fn parse_event_query(request: &HttpRequest, security_only: bool) -> Result<(crate::events::EventFilter, usize), HttpResponse> {
+        let param = |name: &str| request.query_params.get(name).map(|s| s.trim()).filter(|s| !s.is_empty());
+
+        let kind = match param("type") {
+            Some(t) => match crate::events::EventKind::parse(t) {
+                Some(kind) if !security_only || kind.is_security() => Some(kind),
+                _ => return Err(HttpResponse::error(400, format!("Invalid event type: {}", t))),
+            },
+            None => None,
+        };
+        let mac = match param("mac") {
+            Some(m) => match crate::utils::network_utils::parse_mac_address(m) {
+                Ok(mac) => Some(format_mac(&mac)),
+                Err(e) => return Err(HttpResponse::error(400, format!("Invalid MAC address: {}", e))),
+            },
+            None => None,
+        };
+        let filter = crate::events::EventFilter {
+            kind,
+            mac,
+            start_ms: param("start_ms").and_then(|s| s.parse().ok()),
+            end_ms: param("end_ms").and_then(|s| s.parse().ok()),
+            security_only,
+        };
+        let limit = param("limit").and_then(|s| s.parse::<usize>().ok()).unwrap_or(100).min(1000);
+        Ok((filter, limit))
+    }
+
+    /// 处理 /api/traffic/usage/online_hours endpoint，按本地日期统计全网在线时长
+    /// 查询参数：
+    ///   - start_ms: 开始时间戳，毫秒（可选，默认为 7 天前）
+    ///   - end_ms: 结束时间戳，毫秒（可选，默认为现在）
+    async fn handle_online_hours(&self, request: &HttpRequest) -> Result<HttpResponse, anyhow::Error> {
+        let now_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+        let (start_ms, end_ms) = match Self::parse_time_range(request, now_ms, 7) {
+            Ok(range) => range,
+            Err(response) => return Ok(response),
+        };
+
+        let days = self
+            .device_manager
+            .presence_tracker()
+            .daily_online(start_ms, end_ms, now_ms)
+            .into_iter()
+            .map(|day| DailyOnlineHours {
+                date: day.date.format("%Y-%m-%d").to_string(),
+                online_hours: (day.online_ms as f64 / 36_000.0).round() / 100.0,
+                device_count: day.device_count,
+            })
+            .collect();
+
+        let response = OnlineHoursResponse { start_ms, end_ms, days };
+        let api_response = ApiResponse::success(response);
+        let body = serde_json::to_string(&api_response)?;
+        Ok(HttpResponse::ok(body))
+    }
+
+    /// 处理 /api/devices/{mac}/dhcp endpoint
+    async fn handle_device_dhcp(&self, mac: [u8; 6]) -> Result<HttpResponse, anyhow::Error> {
+        if !self.options.traffic_dhcp_snooping() {
+            return Ok(HttpResponse::error(
+                400,
+                "DHCP snooping is disabled (--traffic-dhcp-snooping)".to_string(),
+            ));
+        }
+        let lease = match self.dhcp_snooper.get(&mac) {
+            Some(lease) => lease,
+            None => {
+                return Ok(HttpResponse::error(
+                    404,
+                    format!("No DHCP traffic seen for {}", format_mac(&mac)),
+                ))
+            }
+        };
+
+        let format_ip = |ip: [u8; 4]| std::net::Ipv4Addr::from(ip).to_string();
+        let response = DeviceDhcpResponse {
+            mac: format_mac(&mac),
+            ip: lease.ip.map(format_ip),
+            requested_ip: lease.requested_ip.map(format_ip),
+            hostname: lease.hostname.clone(),
+            vendor_class: lease.vendor_class.clone(),
+            parameter_request_list: lease.parameter_request_list.clone(),
+            server_ip: lease.server_ip.map(format_ip),
+            lease_time_secs: lease.lease_time_secs,
+            lease_start_ms: lease.lease_start_ms,
+            lease_expires_ms: lease.lease_expires_ms(),
+            released: lease.released,
+            last_message_type: crate::monitor::dhcp::message_type_name(lease.last_message_type).to_string(),
+            first_seen_ms: lease.first_seen_ms,
+            last_seen_ms: lease.last_seen_ms,
+        };
+
+        let api_response = ApiResponse::success(response);
+        let body = serde_json::to_string(&api_response)?;
+        Ok(HttpResponse::ok(body))
+    }
+
+    /// 处理 /api/devices/{mac}/wireless endpoint
+    async fn handle_device_wireless(&self, mac: [u8; 6]) -> Result<HttpResponse, anyhow::Error> {
+        let station = match self.device_manager.get_wireless_station(&mac) {
+            Some(station) => station,
+            None => {
+                return Ok(HttpResponse::error(
+                    404,
+                    format!("{} is not an associated Wi-Fi client", format_mac(&mac)),
+                ))
+            }
+        };
+
+        let response = DeviceWirelessResponse {
+            mac: format_mac(&mac),
+            wireless: DeviceWirelessInfo::from(station),
+        };
+
+        let api_response = ApiResponse::success(response);
+        let body = serde_json::to_string(&api_response)?;
+        Ok(HttpResponse::ok(body))
+    }
+
     /// 处理/api/traffic/bindings endpoint (GET)
     async fn handle_hostname_bindings(&self) -> Result<HttpResponse, anyhow::Error> {
         let bindings_map = self.hostname_bindings.lock().unwrap();
+        let device_types = self.device_manager.device_classifier().overrides();
 
-        let bindings: Vec<HostnameBinding> = bindings_map
+        let mut bindings: Vec<HostnameBinding> = bindings_map
             .iter()
             .map(|(mac, hostname)| {
                 let mac_str = format_mac(mac);
                 HostnameBinding {
                     mac: mac_str,
                     hostname: hostname.clone(),
+                    device_type: device_types.get(mac).map(|t| t.as_str().to_string()),
                 }
             })
             .collect();
+        // 只设置了设备类型的设备
+        bindings.extend(
+            device_types
+                .iter()
+                .filter(|(mac, _)| !bindings_map.contains_key(*mac))
+                .map(|(mac, t)| HostnameBinding {
+                    mac: format_mac(mac),
+                    hostname: String::new(),
+                    device_type: Some(t.as_str().to_string()),
+                }),
+        );
 
         let response = HostnameBindingsResponse { bindings };
         let api_response = ApiResponse::success(response);
@@ -806,9 +1587,35 @@ impl TrafficApiHandler {
 
         let mac = crate::utils::network_utils::parse_mac_address(&set_binding_request.mac)?;
 
-        // Allow empty hostname (for clearing bindings)
-        let hostname = set_binding_request.hostname.trim();
+        // 设备类型：先校验，避免部分生效
+        let device_type = match set_binding_request.device_type.as_deref().map(str::trim) {
+            None => None,
+            Some("") => Some(None),
+            Some(s) => match DeviceType::parse(s) {
+                Some(t) => Some(Some(t)),
+                None => return Ok(HttpResponse::error(400, format!("Invalid device type: {}", s))),
+            },
+        };
+
+        if let Some(device_type) = device_type {
+            traffic::upsert_device_type_override(self.options.data_dir(), &mac, device_type)?;
+            self.device_manager.set_device_type_override(mac, device_type);
+            match device_type {
+                Some(t) => log::info!("Device type set for MAC: {} -> {}", format_mac(&mac), t.as_str()),
+                None => log::info!("Device type override cleared for MAC: {}", format_mac(&mac)),
+            }
+        }
 
+        let hostname = match set_binding_request.hostname.as_deref() {
+            Some(hostname) => hostname.trim(),
+            None => {
+                let api_response = ApiResponse::success(());
+                let body = serde_json::to_string(&api_response)?;
+                return Ok(HttpResponse::ok(body));
+            }
+        };
+
+        // Allow empty hostname (for clearing bindings)
         // 更新in-memory hostname bindings
         {
             let mut bindings = self.hostname_bindings.lock().unwrap();
@@ -836,6 +1643,199 @@ impl TrafficApiHandler {
         Ok(HttpResponse::ok(body))
     }
 
+    /// 处理/api/traffic/identities endpoint (GET)
+    async fn handle_identities(&self) -> Result<HttpResponse, anyhow::Error> {
+        let groups = self.device_manager.identity_registry().groups();
+        let bindings_map = self.hostname_bindings.lock().unwrap();
+
+        let identities: Vec<DeviceIdentityInfo> = groups
+            .into_iter()
+            .map(|group| {
+                let hostname = std::iter::once(&group.primary)
+                    .chain(group.members.iter().map(|(mac, _)| mac))
+                    .find_map(|mac| self.device_manager.get_device_by_mac(mac))
+                    .map(|d| d.hostname)
+                    .filter(|h| !h.is_empty())
+                    .or_else(|| bindings_map.get(&group.primary).cloned())
+                    .unwrap_or_default();
+                DeviceIdentityInfo {
+                    mac: format_mac(&group.primary),
+                    hostname,
+                    members: group
+                        .members
+                        .iter()
+                        .map(|(mac, source)| IdentityMemberInfo {
+                            mac: format_mac(mac),
+                            source: source.as_str().to_string(),
+                        })
+                        .collect(),
+                }
+            })
+            .collect();
+
+        let response = DeviceIdentitiesResponse { identities };
+        let api_response = ApiResponse::success(response);
+        let body = serde_json::to_string(&api_response)?;
+        Ok(HttpResponse::ok(body))
+    }
+
+    /// 处理/api/traffic/identities/merge endpoint (POST)
+    async fn handle_merge_identity(&self, request: &HttpRequest) -> Result<HttpResponse, anyhow::Error> {
+        let body = request.body.as_ref().ok_or_else(|| anyhow::anyhow!("Missing request body"))?;
+        let merge_request: MergeIdentityRequest = serde_json::from_str(body)?;
+
+        let mac = crate::utils::network_utils::parse_mac_address(&merge_request.mac)?;
+        let into = crate::utils::network_utils::parse_mac_address(&merge_request.into)?;
+
+        match self.device_manager.identity_registry().merge(mac, into) {
+            Ok(primary) => log::info!("Merged device {} into identity {}", format_mac(&mac), format_mac(&primary)),
+            Err(e) => return Ok(HttpResponse::error(400, e.to_string())),
+        }
+
+        self.handle_identities().await
+    }
+
+    /// 处理/api/traffic/identities/split endpoint (POST)
+    async fn handle_split_identity(&self, request: &HttpRequest) -> Result<HttpResponse, anyhow::Error> {
+        let body = request.body.as_ref().ok_or_else(|| anyhow::anyhow!("Missing request body"))?;
+        let split_request: SplitIdentityRequest = serde_json::from_str(body)?;
+
+        let mac = crate::utils::network_utils::parse_mac_address(&split_request.mac)?;
+
+        if let Err(e) = self.device_manager.identity_registry().split(mac) {
+            return Ok(HttpResponse::error(400, e.to_string()));
+        }
+        log::info!("Split device {} from its identity", format_mac(&mac));
+
+        self.handle_identities().await
+    }
+
+    /// 解析 group 查询参数，返回组名与组内全部 MAC；未指定时返回 (None, [])
+    fn resolve_group_param(&self, request: &HttpRequest) -> Result<(Option<String>, Vec<[u8; 6]>), HttpResponse> {
+        let name = match request.query_params.get("group").map(|s| s.trim()).filter(|s| !s.is_empty()) {
+            Some(name) => name,
+            None => return Ok((None, Vec::new())),
+        };
+        match self.device_manager.device_groups().get(name) {
+            Some(group) => {
+                let macs = self.device_manager.group_members(&group.name).unwrap_or_default();
+                Ok((Some(group.name), macs))
+            }
+            None => Err(HttpResponse::error(404, format!("Group not found: {}", name))),
+        }
+    }
+
+    /// 处理/api/traffic/groups endpoint (GET)
+    async fn handle_groups(&self) -> Result<HttpResponse, anyhow::Error> {
+        let groups: Vec<DeviceGroupInfo> = self
+            .device_manager
+            .device_groups()
+            .groups()
+            .into_iter()
+            .map(|group| DeviceGroupInfo {
+                name: group.name,
+                macs: group.macs.iter().map(format_mac).collect(),
+                rate_limits: group
+                    .rate_limits
+                    .iter()
+                    .map(|rule| GroupRateLimitInfo {
+                        time_slot: TimeSlotApi::from(&rule.time_slot),
+                        wan_rx_rate_limit: rule.wan_rx_rate_limit,
+                        wan_tx_rate_limit: rule.wan_tx_rate_limit,
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        let api_response = ApiResponse::success(DeviceGroupsResponse { groups });
+        let body = serde_json::to_string(&api_response)?;
+        Ok(HttpResponse::ok(body))
+    }
+
+    /// 处理/api/traffic/groups endpoint (POST)，组不存在时创建
+    async fn handle_set_group(&self, request: &HttpRequest) -> Result<HttpResponse, anyhow::Error> {
+        let body = request.body.as_ref().ok_or_else(|| anyhow::anyhow!("Missing request body"))?;
+        let set_request: SetDeviceGroupRequest = serde_json::from_str(body)?;
+
+        let mut macs = Vec::with_capacity(set_request.macs.len());
+        for mac_str in &set_request.macs {
+            match crate::utils::network_utils::parse_mac_address(mac_str) {
+                Ok(mac) => macs.push(mac),
+                Err(e) => return Ok(HttpResponse::error(400, format!("Invalid MAC address: {}", e))),
+            }
+        }
+
+        let count = macs.len();
+        if let Err(e) = self.device_manager.device_groups().set_members(&set_request.name, macs) {
+            return Ok(HttpResponse::error(400, e.to_string()));
+        }
+        log::info!("Device group {} set with {} device(s)", set_request.name.trim(), count);
+
+        self.handle_groups().await
+    }
+
+    /// 处理/api/traffic/groups endpoint (DELETE)
+    async fn handle_delete_group(&self, request: &HttpRequest) -> Result<HttpResponse, anyhow::Error> {
+        let body = request.body.as_ref().ok_or_else(|| anyhow::anyhow!("Missing request body"))?;
+        let delete_request: DeleteDeviceGroupRequest = serde_json::from_str(body)?;
+
+        if let Err(e) = self.device_manager.device_groups().remove(&delete_request.name) {
+            return Ok(HttpResponse::error(404, e.to_string()));
+        }
+        log::info!("Device group {} deleted", delete_request.name.trim());
+
+        self.handle_groups().await
+    }
+
+    /// 处理/api/traffic/groups/limits endpoint (POST)
+    async fn handle_set_group_limit(&self, request: &HttpRequest) -> Result<HttpResponse, anyhow::Error> {
+        let body = request.body.as_ref().ok_or_else(|| anyhow::anyhow!("Missing request body"))?;
+        let set_request: SetGroupLimitRequest = serde_json::from_str(body)?;
+        let time_slot = TimeSlot::try_from(&set_request.time_slot).map_err(|e| anyhow::anyhow!("Invalid time slot: {}", e))?;
+
+        let rule = GroupRateLimit {
+            time_slot,
+            wan_rx_rate_limit: set_request.wan_rx_rate_limit,
+            wan_tx_rate_limit: set_request.wan_tx_rate_limit,
+        };
+        if let Err(e) = self.device_manager.device_groups().set_rate_limit(&set_request.group, rule) {
+            return Ok(HttpResponse::error(400, e.to_string()));
+        }
+        log::info!(
+            "Scheduled rate limit set for group: {} - Time: {} to {} (days: {})",
+            set_request.group.trim(),
+            TimeSlot::format_time(time_slot.start_hour, time_slot.start_minute),
+            TimeSlot::format_time(time_slot.end_hour, time_slot.end_minute),
+            TimeSlot::format_days(time_slot.days_of_week)
+        );
+
+        self.handle_groups().await
+    }
+
+    /// 处理/api/traffic/groups/limits endpoint (DELETE)
+    async fn handle_delete_group_limit(&self, request: &HttpRequest) -> Result<HttpResponse, anyhow::Error> {
+        let body = request.body.as_ref().ok_or_else(|| anyhow::anyhow!("Missing request body"))?;
+        let delete_request: DeleteGroupLimitRequest = serde_json::from_str(body)?;
+        let time_slot = TimeSlot::try_from(&delete_request.time_slot).map_err(|e| anyhow::anyhow!("Invalid time slot: {}", e))?;
+
+        if let Err(e) = self
+            .device_manager
+            .device_groups()
+            .remove_rate_limit(&delete_request.group, &time_slot)
+        {
+            return Ok(HttpResponse::error(404, e.to_string()));
+        }
+        log::info!(
+            "Scheduled rate limit deleted for group: {} - Time: {} to {} (days: {})",
+            delete_request.group.trim(),
+            TimeSlot::format_time(time_slot.start_hour, time_slot.start_minute),
+            TimeSlot::format_time(time_slot.end_hour, time_slot.end_minute),
+            TimeSlot::format_days(time_slot.days_of_week)
+        );
+
+        self.handle_groups().await
+    }
+
     /// 处理/api/traffic/limits/schedule endpoint (GET)
     async fn handle_scheduled_limits(&self) -> Result<HttpResponse, anyhow::Error> {
         let scheduled_limits = self.scheduled_rate_limits.lock().unwrap();
@@ -964,6 +1964,7 @@ impl TrafficApiHandler {
     ///   - start_ms: 开始时间戳，毫秒（可选，默认为 365 天前）
     ///   - end_ms: 结束时间戳，毫秒（可选，默认为现在）
     ///   - network_type: "wan", "lan", 或 "all"（可选，默认为 "wan"）
+    ///   - group: 只统计该设备组内的设备（可选）
     async fn handle_usage_ranking(&self, request: &HttpRequest) -> Result<HttpResponse, anyhow::Error> {
         // 解析 network_type 参数
         let network_type = request
@@ -1008,6 +2009,11 @@ impl TrafficApiHandler {
             ));
         }
 
+        let (group, group_macs) = match self.resolve_group_param(request) {
+            Ok(resolved) => resolved,
+            Err(response) => return Ok(response),
+        };
+
         // 查询所有设备的统计信息
         let mut device_stats = match self.long_term_manager.query_stats_by_device(start_ms, end_ms) {
             Ok(stats) => stats,
@@ -1071,7 +2077,23 @@ impl TrafficApiHandler {
             }
         }
 
-        if device_stats.is_empty() {
+        if group.is_some() {
+            device_stats.retain(|mac, _| group_macs.contains(mac));
+        }
+
+        // 按设备身份合并：同一身份下的多个 MAC 计为一个设备，以主 MAC 展示
+        let registry = self.device_manager.identity_registry();
+        let mut identity_stats: HashMap<[u8; 6], IdentityIncrements> = HashMap::new();
+        for (mac, stats) in device_stats.iter() {
+            let entry = identity_stats.entry(registry.primary_of(mac)).or_default();
+            entry.0 = entry.0.saturating_add(stats.wan_rx_bytes_inc);
+            entry.1 = entry.1.saturating_add(stats.wan_tx_bytes_inc);
+            entry.2 = entry.2.saturating_add(stats.lan_rx_bytes_inc);
+            entry.3 = entry.3.saturating_add(stats.lan_tx_bytes_inc);
+            entry.4.push(*mac);
+        }
+
+        if identity_stats.is_empty() {
             let response = DeviceUsageRankingResponse {
                 start_ms,
                 end_ms,
@@ -1080,6 +2102,7 @@ impl TrafficApiHandler {
                 total_rx_bytes: 0,
                 total_tx_bytes: 0,
                 device_count: 0,
+                group,
                 rankings: Vec::new(),
             };
             let api_response = ApiResponse::success(response);
@@ -1091,24 +2114,24 @@ impl TrafficApiHandler {
 
         let mut rankings: Vec<DeviceUsageRanking> = Vec::new();
 
-        for (mac, stats) in device_stats.iter() {
+        for (mac, (wan_rx_bytes, wan_tx_bytes, lan_rx_bytes, lan_tx_bytes, macs)) in identity_stats.iter_mut() {
             // 根据 network_type 选择相应的流量数据
             let (device_rx_bytes, device_tx_bytes) = match network_type.as_str() {
-                "wan" => (stats.wan_rx_bytes_inc, stats.wan_tx_bytes_inc),
-                "lan" => (stats.lan_rx_bytes_inc, stats.lan_tx_bytes_inc),
-                "all" => (
-                    stats.wan_rx_bytes_inc + stats.lan_rx_bytes_inc,
-                    stats.wan_tx_bytes_inc + stats.lan_tx_bytes_inc,
-                ),
-                _ => (stats.wan_rx_bytes_inc, stats.wan_tx_bytes_inc), // 默认为 WAN
+                "wan" => (*wan_rx_bytes, *wan_tx_bytes),
+                "lan" => (*lan_rx_bytes, *lan_tx_bytes),
+                "all" => (*wan_rx_bytes + *lan_rx_bytes, *wan_tx_bytes + *lan_tx_bytes),
+                _ => (*wan_rx_bytes, *wan_tx_bytes), // 默认为 WAN
             };
+            macs.sort();
 
             let total_device_bytes = device_rx_bytes + device_tx_bytes;
 
             let mac_str = format_mac(mac);
 
-            // 从设备管理器获取设备信息
-            let device = self.device_manager.get_device_by_mac(mac);
+            // 从设备管理器获取设备信息，主 MAC 不在线时使用身份下其他 MAC 的信息
+            let device = std::iter::once(mac)
+                .chain(macs.iter())
+                .find_map(|m| self.device_manager.get_device_by_mac(m));
             let hostname = device
                 .as_ref()
                 .map(|d| d.hostname.clone())
@@ -1122,6 +2145,7 @@ impl TrafficApiHandler {
 
             rankings.push(DeviceUsageRanking {
                 mac: mac_str,
+                macs: macs.iter().map(format_mac).collect(),
                 hostname,
                 ip: ip_str,
                 total_bytes: total_device_bytes,
@@ -1156,6 +2180,7 @@ impl TrafficApiHandler {
             total_rx_bytes,
             total_tx_bytes,
             device_count: rankings.len(),
+            group,
             rankings,
         };
 
@@ -1164,6 +2189,88 @@ impl TrafficApiHandler {
         Ok(HttpResponse::ok(body))
     }
 
+    /// 处理/api/traffic/domains endpoint
+    /// 按域名统计 WAN 流量（基于 DNS 应答的远端 IP 归属，仅内存保留最近 7 天）
+    /// 查询参数：
+    ///   - mac: MAC 地址（可选，默认为 "all"）
+    ///   - start_ms: 开始时间戳，毫秒（可选，默认为 24 小时前）
+    ///   - end_ms: 结束时间戳，毫秒（可选，默认为现在）
+    async fn handle_domain_usage(&self, request: &HttpRequest) -> Result<HttpResponse, anyhow::Error> {
+        let now_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or(Duration::from_secs(0))
+            .as_millis() as u64;
+
+        let start_ms = request
+            .query_params
+            .get("start_ms")
+            .and_then(|s| s.parse::<u64>().ok())
+            .unwrap_or_else(|| now_ms.saturating_sub(24 * 3600 * 1000));
+
+        let end_ms = request
+            .query_params
+            .get("end_ms")
+            .and_then(|s| s.parse::<u64>().ok())
+            .unwrap_or(now_ms);
+
+        if start_ms >= end_ms {
+            return Ok(HttpResponse::error(
+                400,
+                "Invalid time range: start_ms must be less than end_ms".to_string(),
+            ));
+        }
+
+        let query_mac = match request.query_params.get("mac") {
+            Some(mac_str) if !mac_str.trim().is_empty() && !mac_str.eq_ignore_ascii_case("all") => {
+                match crate::utils::network_utils::parse_mac_address(mac_str) {
+                    Ok(mac) => Some(mac),
+                    Err(e) => return Ok(HttpResponse::error(400, format!("Invalid MAC address: {}", e))),
+                }
+            }
+            _ => None,
+        };
+
+        let (by_domain, unattributed) = self.domain_tracker.query_usage(query_mac, start_ms, end_ms);
+
+        let mut domains: Vec<DomainUsageRanking> = by_domain
+            .into_iter()
+            .map(|(domain, usage)| DomainUsageRanking {
+                domain,
+                total_bytes: usage.rx_bytes.saturating_add(usage.tx_bytes),
+                rx_bytes: usage.rx_bytes,
+                tx_bytes: usage.tx_bytes,
+                percentage: 0.0,
+                rank: 0,
+            })
+            .collect();
+
+        let total_bytes: u64 = domains.iter().map(|d| d.total_bytes).sum();
+
+        // 排序by total_bytes descending
+        domains.sort_by(|a, b| b.total_bytes.cmp(&a.total_bytes).then_with(|| a.domain.cmp(&b.domain)));
+
+        for (idx, d) in domains.iter_mut().enumerate() {
+            d.rank = idx + 1;
+            if total_bytes > 0 {
+                d.percentage = (d.total_bytes as f64 / total_bytes as f64) * 100.0;
+            }
+        }
+
+        let response = DomainUsageResponse {
+            start_ms,
+            end_ms,
+            mac: query_mac.map(|m| format_mac(&m)).unwrap_or_else(|| "all".to_string()),
+            total_bytes,
+            unattributed_bytes: unattributed.rx_bytes.saturating_add(unattributed.tx_bytes),
+            domain_count: domains.len(),
+            domains,
+        };
+
+        let api_response = ApiResponse::success(response);
+        let body = serde_json::to_string(&api_response)?;
+        Ok(HttpResponse::ok(body))
+    }
+
     /// 处理/api/traffic/usage/increments endpoint
     /// 从长期数据查询时间序列增量（每小时或每日）
     /// 查询参数：
@@ -1172,6 +2279,7 @@ impl TrafficApiHandler {
     ///   - end_ms: 结束时间戳，毫秒（可选，默认为现在）
     ///   - aggregation: "hourly" 或 "daily"（可选，默认为 "hourly"）
     ///   - network_type: "wan" 或 "lan"（可选，默认为 "wan"）
+    ///   - group: 设备组名，汇总组内全部设备（可选，不能与 mac 同时使用）
     async fn handle_usage_increments(&self, request: &HttpRequest) -> Result<HttpResponse, anyhow::Error> {
         let aggregation = request
             .query_params
@@ -1239,8 +2347,28 @@ impl TrafficApiHandler {
             None
         };
 
-        let rows_result = if let Some(mac) = query_mac {
+        let (group, member_macs) = match self.resolve_group_param(request) {
+            Ok(resolved) => resolved,
+            Err(response) => return Ok(response),
+        };
+        if group.is_some() && query_mac.is_some() {
+            return Ok(HttpResponse::error(
+                400,
+                "Invalid query: mac and group cannot be used together".to_string(),
+            ));
+        }
+
+        // 指定 MAC 时查询其所属身份下的全部 MAC，指定组时查询组内全部 MAC
+        let group_macs = match query_mac {
+            Some(mac) => self.device_manager.identity_registry().group_macs(&mac),
+            None => member_macs,
+        };
+        let filter_macs = query_mac.is_some() || group.is_some();
+
+        let rows_result = if let Some(mac) = query_mac.filter(|_| group_macs.len() == 1) {
             self.long_term_manager.query_stats_by_mac(&mac, start_ms, end_ms)
+        } else if filter_macs {
+            self.long_term_manager.query_stats_aggregate_macs(&group_macs, start_ms, end_ms)
         } else {
             self.long_term_manager.query_stats_aggregate_all(start_ms, end_ms)
         };
@@ -1299,9 +2427,9 @@ impl TrafficApiHandler {
             // 获取活跃的 accumulator 数据（包含速率统计）
             let active_accumulators = self.long_term_manager.get_active_accumulators_with_stats();
 
-            if let Some(ref mac) = query_mac {
-                // 查询特定 MAC 地址
-                if let Some(acc) = active_accumulators.get(mac) {
+            if filter_macs {
+                // 查询特定 MAC 地址（及同一身份下的其他 MAC）或设备组
+                for acc in group_macs.iter().filter_map(|m| active_accumulators.get(m)) {
                     // 为当前小时创建 TimeSeriesIncrement 条目（包含速率统计）
                     let current_hour_increment = TimeSeriesIncrement {
                         start_ts_ms: current_hour_start,
@@ -1660,6 +2788,8 @@ impl TrafficApiHandler {
             end_ms,
             aggregation: aggregation.clone(),
             mac: mac_label,
+            macs: group_macs.iter().map(format_mac).collect(),
+            group,
             network_type,
             increments,
             total_rx_bytes,
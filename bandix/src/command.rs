@@ -39,6 +39,20 @@ pub struct CommonArgs {
         help = "Log level: trace, debug, info, warn, error (default: info). Web and DNS logs are always at DEBUG level."
     )]
     pub log_level: String,
+
+    #[clap(
+        long,
+        default_value = "false",
+        help = "Anonymise API responses and webhook exports: MACs and IPs are replaced by salted hashes, domains are truncated to the registrable domain"
+    )]
+    pub privacy_anonymize: bool,
+
+    #[clap(
+        long,
+        default_value = "ubus,dnsmasq,dhcpd,odhcpd,ethers",
+        help = "Hostname sources in priority order (comma-separated): ubus, dnsmasq, dhcpd, odhcpd, ethers. Use kind:/path to read a specific file."
+    )]
+    pub hostname_sources: String,
 }
 
 /// 流量模块参数
@@ -79,7 +93,7 @@ pub struct TrafficArgs {
     #[clap(
         long,
         default_value = "",
-        help = "Export device online/offline events to a remote HTTP endpoint (POST JSON on state change). Empty = disabled."
+        help = "Deliver device events from the event journal to a remote HTTP endpoint (POST JSON per event, retried until accepted). Empty = disabled."
     )]
     pub traffic_event_url: String,
 
@@ -89,6 +103,20 @@ pub struct TrafficArgs {
         help = "Additional local subnets (comma-separated CIDR notation, e.g. '192.168.2.0/24,10.0.0.0/8'). Empty = only interface subnet."
     )]
     pub traffic_additional_subnets: String,
+
+    #[clap(
+        long,
+        default_value = "false",
+        help = "Learn device IPs, hostnames and vendor classes from DHCP traffic (UDP 67/68) seen on the interface"
+    )]
+    pub traffic_dhcp_snooping: bool,
+
+    #[clap(
+        long,
+        default_value = "false",
+        help = "Watch ARP and NDP traffic for gateway impersonation, duplicate IPs and rapid MAC changes (reported as security events)"
+    )]
+    pub traffic_spoof_detection: bool,
 }
 
 /// DNS 模块参数
@@ -103,6 +131,83 @@ pub struct DnsArgs {
         help = "Maximum number of DNS records to keep in memory (default: 10000)"
     )]
     pub dns_max_records: usize,
+
+    #[clap(
+        long,
+        default_value = "",
+        help = "Approved DNS resolvers (comma-separated IPs). Plain DNS to other servers is reported as resolver bypass. Empty = interface addresses."
+    )]
+    pub dns_approved_resolvers: String,
+
+    #[clap(
+        long,
+        default_value = "false",
+        help = "Send resolver bypass events (external DNS, DoT, DoH) to --traffic-event-url"
+    )]
+    pub dns_bypass_events: bool,
+
+    #[clap(
+        long,
+        default_value = "",
+        help = "Stream captured DNS messages in dnstap format to this Frame Streams Unix socket. Empty = disabled."
+    )]
+    pub dns_dnstap_socket: String,
+
+    #[clap(
+        long,
+        default_value = "",
+        help = "Write captured DNS messages in dnstap format to this file (rotated by size). Empty = disabled."
+    )]
+    pub dns_dnstap_file: String,
+
+    #[clap(
+        long,
+        default_value = "64",
+        help = "Rotate the dnstap file after this many megabytes (default: 64)"
+    )]
+    pub dns_dnstap_file_max_mb: u64,
+
+    #[clap(
+        long,
+        default_value = "3.8",
+        help = "Alert when a query label (12+ chars) has Shannon entropy at or above this value, in bits per char (default: 3.8)"
+    )]
+    pub dns_alert_entropy: f64,
+
+    #[clap(
+        long,
+        default_value = "100",
+        help = "Alert when a query name is at least this many characters long (default: 100)"
+    )]
+    pub dns_alert_qname_length: usize,
+
+    #[clap(
+        long,
+        default_value = "30",
+        help = "Alert when a device sends at least this many TXT queries per minute (default: 30)"
+    )]
+    pub dns_alert_txt_per_minute: usize,
+
+    #[clap(
+        long,
+        default_value = "20",
+        help = "Alert when a device receives at least this many NXDOMAIN responses per minute (default: 20)"
+    )]
+    pub dns_alert_nxdomain_per_minute: usize,
+
+    #[clap(
+        long,
+        default_value = "false",
+        help = "Send suspicious DNS alerts to --traffic-event-url"
+    )]
+    pub dns_alert_events: bool,
+
+    #[clap(
+        long,
+        default_value = "",
+        help = "Devices whose DNS activity must never be recorded (comma-separated MAC addresses). Empty = none."
+    )]
+    pub dns_privacy_exclude: String,
 }
 
 /// 连接模块参数
@@ -156,6 +261,16 @@ impl Options {
         &self.common.log_level
     }
 
+    /// 从通用参数获取是否匿名化输出
+    pub fn privacy_anonymize(&self) -> bool {
+        self.common.privacy_anonymize
+    }
+
+    /// 从通用参数获取主机名来源列表
+    pub fn hostname_sources(&self) -> &str {
+        &self.common.hostname_sources
+    }
+
     /// 从流量参数获取启用流量
     pub fn enable_traffic(&self) -> bool {
         self.traffic.enable_traffic
@@ -188,6 +303,16 @@ impl Options {
         &self.traffic.traffic_additional_subnets
     }
 
+    /// 从流量参数获取是否开启 DHCP 监听
+    pub fn traffic_dhcp_snooping(&self) -> bool {
+        self.traffic.traffic_dhcp_snooping
+    }
+
+    /// 从流量参数获取是否开启 ARP/NDP 欺骗检测
+    pub fn traffic_spoof_detection(&self) -> bool {
+        self.traffic.traffic_spoof_detection
+    }
+
     /// 从 DNS 参数获取启用 DNS
     pub fn enable_dns(&self) -> bool {
         self.dns.enable_dns
@@ -198,6 +323,61 @@ impl Options {
         self.dns.dns_max_records
     }
 
+    /// 从 DNS 参数获取批准的解析器列表
+    pub fn dns_approved_resolvers(&self) -> &str {
+        &self.dns.dns_approved_resolvers
+    }
+
+    /// 从 DNS 参数获取是否发送解析器绕过事件
+    pub fn dns_bypass_events(&self) -> bool {
+        self.dns.dns_bypass_events
+    }
+
+    /// 从 DNS 参数获取 dnstap Unix socket 路径
+    pub fn dns_dnstap_socket(&self) -> &str {
+        &self.dns.dns_dnstap_socket
+    }
+
+    /// 从 DNS 参数获取 dnstap 文件路径
+    pub fn dns_dnstap_file(&self) -> &str {
+        &self.dns.dns_dnstap_file
+    }
+
+    /// 从 DNS 参数获取 dnstap 文件轮转大小（MB）
+    pub fn dns_dnstap_file_max_mb(&self) -> u64 {
+        self.dns.dns_dnstap_file_max_mb
+    }
+
+    /// 从 DNS 参数获取标签熵告警阈值
+    pub fn dns_alert_entropy(&self) -> f64 {
+        self.dns.dns_alert_entropy
+    }
+
+    /// 从 DNS 参数获取查询名长度告警阈值
+    pub fn dns_alert_qname_length(&self) -> usize {
+        self.dns.dns_alert_qname_length
+    }
+
+    /// 从 DNS 参数获取每分钟 TXT 查询告警阈值
+    pub fn dns_alert_txt_per_minute(&self) -> usize {
+        self.dns.dns_alert_txt_per_minute
+    }
+
+    /// 从 DNS 参数获取每分钟 NXDOMAIN 告警阈值
+    pub fn dns_alert_nxdomain_per_minute(&self) -> usize {
+        self.dns.dns_alert_nxdomain_per_minute
+    }
+
+    /// 从 DNS 参数获取是否发送 DNS 告警事件
+    pub fn dns_alert_events(&self) -> bool {
+        self.dns.dns_alert_events
+    }
+
+    /// 从 DNS 参数获取不记录 DNS 活动的设备列表
+    pub fn dns_privacy_exclude(&self) -> &str {
+        &self.dns.dns_privacy_exclude
+    }
+
     /// 从连接参数获取启用连接
     pub fn enable_connection(&self) -> bool {
         self.connection.enable_connection
@@ -268,6 +448,9 @@ fn validate_arguments(opt: &Options) -> Result<(), anyhow::Error> {
         return Err(anyhow::anyhow!("Port number cannot be 0"));
     }
 
+    crate::storage::hostname::parse_hostname_sources(opt.hostname_sources())
+        .map_err(|e| anyhow::anyhow!("Invalid --hostname-sources: {}", e))?;
+
     // 仅在启用流量模块时验证流量特定参数
     if opt.enable_traffic() {
         if opt.traffic_retention_seconds() == 0 {
@@ -294,6 +477,50 @@ fn validate_arguments(opt: &Options) -> Result<(), anyhow::Error> {
         }
     }
 
+    // DHCP 监听的结果由流量模块的设备管理器使用
+    if opt.traffic_dhcp_snooping() && !opt.enable_traffic() {
+        return Err(anyhow::anyhow!("--traffic-dhcp-snooping requires --enable-traffic"));
+    }
+
+    // 欺骗检测的告警写入流量模块设备管理器的事件日志
+    if opt.traffic_spoof_detection() && !opt.enable_traffic() {
+        return Err(anyhow::anyhow!("--traffic-spoof-detection requires --enable-traffic"));
+    }
+
+    // 验证批准的 DNS 解析器地址
+    if opt.enable_dns() {
+        for resolver in opt.dns_approved_resolvers().split(',') {
+            let resolver = resolver.trim();
+            if resolver.is_empty() {
+                continue;
+            }
+            resolver
+                .parse::<std::net::IpAddr>()
+                .map_err(|_| anyhow::anyhow!("Invalid approved DNS resolver address '{}'", resolver))?;
+        }
+
+        if !opt.dns_dnstap_socket().trim().is_empty() && !opt.dns_dnstap_file().trim().is_empty() {
+            return Err(anyhow::anyhow!("--dns-dnstap-socket and --dns-dnstap-file cannot be used together"));
+        }
+
+        if opt.dns_alert_entropy() <= 0.0
+            || opt.dns_alert_qname_length() == 0
+            || opt.dns_alert_txt_per_minute() == 0
+            || opt.dns_alert_nxdomain_per_minute() == 0
+        {
+            return Err(anyhow::anyhow!("DNS alert thresholds must be greater than 0"));
+        }
+
+        for mac in opt.dns_privacy_exclude().split(',') {
+            let mac = mac.trim();
+            if mac.is_empty() {
+                continue;
+            }
+            crate::utils::network_utils::parse_mac_address(mac)
+                .map_err(|_| anyhow::anyhow!("Invalid MAC address '{}' in --dns-privacy-exclude", mac))?;
+        }
+    }
+
     Ok(())
 }
 
@@ -466,6 +693,34 @@ use crate::device::DeviceManager;
         module_flags.set(1, &dns_flag, 0)?;
         log::info!("DNS module enabled: {}", dns_flag != 0);
 
+        // 设置DHCP snooping flag (index 2)
+        let dhcp_flag = if options.traffic_dhcp_snooping() { 1u8 } else { 0u8 };
+        module_flags.set(2, dhcp_flag, 0)?;
+        log::info!("DHCP snooping enabled: {}", dhcp_flag != 0);
+
+        let dhcp_map = if options.traffic_dhcp_snooping() {
+            Some(
+                ebpf.take_map("DHCP_DATA")
+                    .ok_or_else(|| anyhow::anyhow!("Cannot find DHCP_DATA map. Make sure eBPF programs are loaded correctly."))?,
+            )
+        } else {
+            None
+        };
+
+        // 设置ARP/NDP spoof detection flag (index 3)
+        let arp_flag = if options.traffic_spoof_detection() { 1u8 } else { 0u8 };
+        module_flags.set(3, arp_flag, 0)?;
+        log::info!("ARP/NDP spoof detection enabled: {}", arp_flag != 0);
+
+        let arp_map = if options.traffic_spoof_detection() {
+            Some(
+                ebpf.take_map("ARP_DATA")
+                    .ok_or_else(|| anyhow::anyhow!("Cannot find ARP_DATA map. Make sure eBPF programs are loaded correctly."))?,
+            )
+        } else {
+            None
+        };
+
         let dns_map = if options.enable_dns() {
             // 在包装到 Arc 之前获取 DNS_DATA RingBuf 映射
             // 这是必要的，因为 take_map 需要独占访问
@@ -481,6 +736,9 @@ use crate::device::DeviceManager;
         // 现在包装到 Arc 中，以便两个模块可以共享
         let shared_ebpf_arc = Arc::new(ebpf);
 
+        // DNS 应答与流量之间共享的域名归属状态
+        let domain_tracker = Arc::new(crate::monitor::domain::DomainTracker::new());
+
         // 初始化流量模块
         if options.enable_traffic() {
             log::info!("Initializing traffic module...");
@@ -492,6 +750,9 @@ use crate::device::DeviceManager;
             // 创建流量模块上下文（使用共享的 device_manager）
             let mut traffic_ctx = TrafficModuleContext::new(options.clone(), ingress, egress, Arc::clone(&device_manager));
             traffic_ctx.hostname_bindings = Arc::clone(shared_hostname_bindings);
+            traffic_ctx.domain_tracker = Arc::clone(&domain_tracker);
+            traffic_ctx.dhcp_map = dhcp_map;
+            traffic_ctx.arp_map = arp_map;
 
             module_contexts.push(ModuleContext::Traffic(traffic_ctx));
         }
@@ -511,6 +772,8 @@ use crate::device::DeviceManager;
                 egress,
                 dns_map.unwrap(),
                 Arc::clone(shared_hostname_bindings),
+                Arc::clone(&domain_tracker),
+                device_manager.device_classifier(),
             );
             module_contexts.push(ModuleContext::Dns(dns_ctx));
         }
@@ -533,7 +796,7 @@ use crate::device::DeviceManager;
     Ok(module_contexts)
 }
 
-// 启动主机名刷新任务：定期从 ubus 更新主机名绑定
+// 启动主机名刷新任务：定期从 --hostname-sources 配置的来源更新主机名绑定
 fn start_hostname_refresh_task(
     shared_hostname_bindings: std::sync::Arc<std::sync::Mutex<std::collections::HashMap<[u8; 6], String>>>,
     options: Options,
@@ -541,12 +804,15 @@ fn start_hostname_refresh_task(
 ) -> tokio::task::JoinHandle<()> {
     tokio::spawn(async move {
         let mut interval = tokio::time::interval(tokio::time::Duration::from_secs(600));
+        // 参数已在启动时校验
+        let sources = crate::storage::hostname::parse_hostname_sources(options.hostname_sources()).unwrap_or_default();
 
         loop {
             tokio::select! {
                 _ = interval.tick() => {
-                    // 从 ubus 加载主机名绑定（如果 ubus 不可用则静默失败）
-                    if let Ok(ubus_bindings) = crate::storage::hostname::load_hostname_from_ubus() {
+                    // 按优先级从各来源加载主机名（不可用的来源静默跳过）
+                    let source_bindings = crate::storage::hostname::load_hostname_from_sources(&sources);
+                    if !source_bindings.is_empty() {
                         let mut bindings_map = shared_hostname_bindings.lock().unwrap();
                         let mut updated_count = 0;
 
@@ -556,7 +822,7 @@ fn start_hostname_refresh_task(
                         let saved_macs: std::collections::HashSet<[u8; 6]> =
                             saved_bindings.iter().map(|(mac, _)| *mac).collect();
 
-                        for (mac, hostname) in ubus_bindings {
+                        for (mac, hostname) in source_bindings {
                             // 仅在未手动设置时更新（不在保存的绑定中）
                             if !saved_macs.contains(&mac) {
                                 if let Some(existing) = bindings_map.get(&mac) {
@@ -572,7 +838,7 @@ fn start_hostname_refresh_task(
                         }
 
                         if updated_count > 0 {
-                            log::debug!("Updated {} hostname bindings from ubus", updated_count);
+                            log::debug!("Updated {} hostname bindings from hostname sources", updated_count);
                         }
                     }
                 }
@@ -606,10 +872,24 @@ async fn run_service(options: &Options) -> Result<(), anyhow::Error> {
     let shared_hostname_bindings: Arc<Mutex<std::collections::HashMap<[u8; 6], String>>> =
         Arc::new(Mutex::new(hostname_bindings_vec.into_iter().collect()));
 
+    let device_type_overrides = crate::storage::hostname::load_device_type_overrides(options.data_dir()).unwrap_or_default();
+    let device_classifier = Arc::new(crate::monitor::fingerprint::DeviceClassifier::new(
+        device_type_overrides.into_iter().collect(),
+    ));
+
+    let event_journal = Arc::new(crate::events::EventJournal::load(options.data_dir()));
+
     let device_manager = Arc::new(DeviceManager::new(
         options.iface().to_string(),
         subnet_info.clone(),
         Arc::clone(&shared_hostname_bindings),
+        device_classifier,
+        Arc::new(crate::identity::IdentityRegistry::load(options.data_dir())),
+        Arc::new(crate::presence::PresenceTracker::load(options.data_dir())),
+        Arc::clone(&event_journal),
+        Arc::new(crate::history::DeviceHistory::load(options.data_dir())),
+        Arc::new(crate::meta::DeviceMetaStore::load(options.data_dir())),
+        Arc::new(crate::groups::GroupRegistry::load(options.data_dir())),
     ));
 
     // 首次刷新邻居表，获取局域网设备
@@ -618,10 +898,15 @@ async fn run_service(options: &Options) -> Result<(), anyhow::Error> {
     }
 
     // 启动设备管理器的后台任务
+    let device_refresh_task = Arc::clone(&device_manager).start_background_task(Duration::from_secs(30), shutdown_notify.clone());
+
+    // 从事件日志向 --traffic-event-url 投递事件
     let event_url = options.traffic_event_url().trim();
-    let event_url = if event_url.is_empty() { None } else { Some(event_url.to_string()) };
-    let device_refresh_task =
-        Arc::clone(&device_manager).start_background_task(Duration::from_secs(30), shutdown_notify.clone(), event_url);
+    let event_webhook_task = if event_url.is_empty() {
+        None
+    } else {
+        Some(Arc::clone(&event_journal).start_webhook_task(event_url.to_string(), shutdown_notify.clone()))
+    };
 
     // 创建模块上下文：加载 eBPF 程序并配置内核映射
     let module_contexts = create_module_contexts(options, &subnet_info, &shared_hostname_bindings, Arc::clone(&device_manager)).await?;
@@ -650,6 +935,7 @@ async fn run_service(options: &Options) -> Result<(), anyhow::Error> {
     tasks.push(web_task);
     tasks.push(hostname_refresh_task);
     tasks.push(device_refresh_task);
+    tasks.extend(event_webhook_task);
 
     // 等待关闭信号
     shutdown_notify.notified().await;
@@ -662,6 +948,12 @@ async fn run_service(options: &Options) -> Result<(), anyhow::Error> {
         }
     }
 
+    // 等待事件日志写入完成
+    let journal = Arc::clone(&event_journal);
+    if let Err(e) = tokio::task::spawn_blocking(move || journal.flush()).await {
+        log::error!("Event journal flush error: {}", e);
+    }
+
     info!("All modules stopped, program exiting");
 
     Ok(())
@@ -692,6 +984,9 @@ pub async fn run(options: Options) -> Result<(), anyhow::Error> {
 
     log_startup_info(&options);
 
+    crate::privacy::init(options.privacy_anonymize(), options.data_dir())?;
+    crate::oui::init(options.data_dir());
+
     // 检查是否至少启用了 一个模块
     if !options.enable_traffic() && !options.enable_dns() && !options.enable_connection() {
         return Err(anyhow::anyhow!(
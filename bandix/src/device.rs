@@ -1,12 +1,26 @@
 use crate::command::SubnetInfo;
+use crate::events::{EventJournal, EventKind};
+use crate::groups::GroupRegistry;
+use crate::history::DeviceHistory;
+use crate::identity::IdentityRegistry;
+use crate::meta::DeviceMetaStore;
+use crate::monitor::fingerprint::{Classification, DeviceClassifier, DeviceType};
+use crate::monitor::spoof::SpoofAlert;
+use crate::presence::PresenceTracker;
+use crate::storage::events::DeviceEvent;
+use crate::storage::history::HistoryKind;
 use anyhow::Result;
 use log;
-use serde_json::Value;
 use std::collections::{HashMap, HashSet};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+// 邻居表中的一个设备：MAC、IPv4 地址与 IPv6 地址列表
+type NeighborDevice = ([u8; 6], Option<[u8; 4]>, Vec<[u8; 16]>);
+// 每个 MAC 的各 IPv4 邻居是否在线
+type Ipv4NeighborStates = HashMap<[u8; 6], HashMap<[u8; 4], bool>>;
+
 /// 统一的设备结构，包含设备信息和流量统计
 #[derive(Debug, Clone)]
 pub struct UnifiedDevice {
@@ -18,6 +32,9 @@ pub struct UnifiedDevice {
     pub current_ipv6: Vec<[u8; 16]>,
     pub historical_ipv6: Vec<[u8; 16]>,
     pub hostname: String,
+    pub vendor: Option<String>,              // 由 OUI 解析的厂商名称，随机化地址为 None
+    pub randomized_mac: bool,                // 是否为本地管理（随机化）地址
+    pub device_type: Option<Classification>, // 设备类型识别结果，由后台任务定期更新
 
     // 流量统计数据（从 DeviceTrafficStats 拆分而来）
     // 速率限制
@@ -58,6 +75,9 @@ impl UnifiedDevice {
             current_ipv6: Vec::new(),
             historical_ipv6: Vec::new(),
             hostname: String::new(),
+            vendor: crate::oui::lookup_vendor(&mac),
+            randomized_mac: crate::oui::is_randomized_mac(&mac),
+            device_type: None,
             wan_rx_rate_limit: 0,
             wan_tx_rate_limit: 0,
             lan_rx_bytes: 0,
@@ -97,8 +117,10 @@ impl UnifiedDevice {
         self.lan_tx_rate + self.wan_tx_rate
     }
 
-    pub fn update_ipv4(&mut self, ip: [u8; 4]) {
+    /// 更新当前 IPv4 地址，地址发生变化时返回原地址
+    pub fn update_ipv4(&mut self, ip: [u8; 4]) -> Option<[u8; 4]> {
         let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+        let mut previous = None;
 
         if let Some(current) = self.current_ipv4 {
             if current != ip {
@@ -106,12 +128,14 @@ impl UnifiedDevice {
                     self.historical_ipv4.push(current);
                 }
                 self.current_ipv4 = Some(ip);
+                previous = Some(current);
             }
         } else {
             self.current_ipv4 = Some(ip);
         }
 
         self.last_online_ts = now;
+        previous
     }
 
     pub fn update_ipv6(&mut self, ipv6_list: &[[u8; 16]]) {
@@ -126,10 +150,13 @@ impl UnifiedDevice {
         self.current_ipv6 = ipv6_list.to_vec();
     }
 
-    pub fn update_hostname(&mut self, hostname: String) {
-        if !hostname.is_empty() {
-            self.hostname = hostname;
+    /// 更新主机名，原主机名非空且发生变化时返回原主机名
+    pub fn update_hostname(&mut self, hostname: String) -> Option<String> {
+        if hostname.is_empty() || hostname == self.hostname {
+            return None;
         }
+        let previous = std::mem::replace(&mut self.hostname, hostname);
+        Some(previous).filter(|previous| !previous.is_empty())
     }
 
     #[allow(dead_code)]
@@ -152,20 +179,57 @@ pub struct DeviceManager {
     subnet_info: SubnetInfo,
     hostname_bindings: Arc<Mutex<HashMap<[u8; 6], String>>>,
     neighbor_ipv4_online: Arc<Mutex<HashMap<[u8; 6], bool>>>,
+    neighbor_ipv4_states: Arc<Mutex<Ipv4NeighborStates>>, // 增量更新时据此重新计算 MAC 的在线状态
     neighbor_initialized: Arc<AtomicBool>,
-    wifi_macs: Arc<Mutex<HashSet<[u8; 6]>>>,
+    ifindex: AtomicU32, // 监控接口的 ifindex 缓存，0 表示尚未解析
+    wireless_stations: Arc<Mutex<HashMap<[u8; 6], crate::wireless::WirelessStation>>>,
+    device_classifier: Arc<DeviceClassifier>,
+    identity_registry: Arc<IdentityRegistry>,
+    presence_tracker: Arc<PresenceTracker>,
+    event_journal: Arc<EventJournal>,
+    device_history: Arc<DeviceHistory>,
+    device_meta: Arc<DeviceMetaStore>,
+    device_groups: Arc<GroupRegistry>,
+}
+
+/// 设备信息变化，释放设备表锁后再写入事件日志
+enum DeviceChange {
+    New,
+    Ip([u8; 4]),      // 原 IPv4 地址
+    Hostname(String), // 原主机名
 }
 
 impl DeviceManager {
-    pub fn new(iface: String, subnet_info: SubnetInfo, hostname_bindings: Arc<Mutex<HashMap<[u8; 6], String>>>) -> Self {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        iface: String,
+        subnet_info: SubnetInfo,
+        hostname_bindings: Arc<Mutex<HashMap<[u8; 6], String>>>,
+        device_classifier: Arc<DeviceClassifier>,
+        identity_registry: Arc<IdentityRegistry>,
+        presence_tracker: Arc<PresenceTracker>,
+        event_journal: Arc<EventJournal>,
+        device_history: Arc<DeviceHistory>,
+        device_meta: Arc<DeviceMetaStore>,
+        device_groups: Arc<GroupRegistry>,
+    ) -> Self {
         Self {
             devices: Arc::new(Mutex::new(HashMap::new())),
             iface,
             subnet_info,
             hostname_bindings,
             neighbor_ipv4_online: Arc::new(Mutex::new(HashMap::new())),
+            neighbor_ipv4_states: Arc::new(Mutex::new(HashMap::new())),
             neighbor_initialized: Arc::new(AtomicBool::new(false)),
-            wifi_macs: Arc::new(Mutex::new(HashSet::new())),
+            ifindex: AtomicU32::new(0),
+            wireless_stations: Arc::new(Mutex::new(HashMap::new())),
+            device_classifier,
+            identity_registry,
+            presence_tracker,
+            event_journal,
+            device_history,
+            device_meta,
+            device_groups,
         }
     }
 
@@ -173,58 +237,37 @@ impl DeviceManager {
         self: Arc<Self>,
         refresh_interval: Duration,
         shutdown_notify: Arc<tokio::sync::Notify>,
-        event_url: Option<String>,
     ) -> tokio::task::JoinHandle<()> {
         tokio::spawn(async move {
             let mut interval = tokio::time::interval(refresh_interval);
-            let http = reqwest::Client::builder()
-                .timeout(Duration::from_millis(800))
-                .build()
-                .unwrap();
 
-            if let Some(url) = event_url.as_ref() {
-                log::debug!("Traffic event notifier enabled: {} (interval {:?})", url, refresh_interval);
-            }
+            // 订阅内核邻居变化，上线/离线事件即时触发；周期性全量刷新仅用于校准
+            let mut subscription = match crate::neighbor::NeighborSubscription::new() {
+                Ok(subscription) => Some(subscription),
+                Err(e) => {
+                    log::warn!(
+                        "Failed to subscribe to neighbor events, falling back to periodic refresh: {}",
+                        e
+                    );
+                    None
+                }
+            };
 
             // Best-effort initial refresh so API / events have wifi/wired classification early.
-            self.refresh_wifi_macs_cache();
+            self.refresh_wireless_stations();
 
             loop {
                 tokio::select! {
                     _ = interval.tick() => {
-                        // Refresh Wi-Fi MAC cache (hostapd get_clients) once per refresh cycle.
-                        self.refresh_wifi_macs_cache();
+                        // Refresh Wi-Fi station cache (nl80211, hostapd get_clients as fallback) once per refresh cycle.
+                        self.refresh_wireless_stations();
+                        self.classify_devices();
 
                         tokio::select! {
                             result = self.refresh_devices_with_neighbor_events() => {
                                 match result {
                                     Ok(events) => {
-                                        if let Some(url) = event_url.as_ref() {
-                                            log::debug!("Neighbor refresh generated {} event(s)", events.len());
-                                            if !events.is_empty() {
-                                                log::debug!("Emitting {} neighbor event(s) to {}", events.len(), url);
-                                            }
-                                            for payload in events {
-                                                let url = url.clone();
-                                                let client = http.clone();
-                                                tokio::spawn(async move {
-                                                    match client.post(&url).json(&payload).send().await {
-                                                        Ok(resp) => {
-                                                            if !resp.status().is_success() {
-                                                                log::warn!(
-                                                                    "Traffic event export got HTTP {} from {}",
-                                                                    resp.status(),
-                                                                    url
-                                                                );
-                                                            }
-                                                        }
-                                                        Err(e) => {
-                                                            log::warn!("Traffic event export failed: {}", e);
-                                                        }
-                                                    }
-                                                });
-                                            }
-                                        }
+                                        log::debug!("Neighbor refresh generated {} event(s)", events);
                                     }
                                     Err(e) => {
                                         log::warn!("Failed to refresh devices: {}", e);
@@ -237,16 +280,131 @@ impl DeviceManager {
                             }
                         }
                     }
+                    result = async { subscription.as_mut().unwrap().recv().await }, if subscription.is_some() => {
+                        match result {
+                            Ok(messages) => {
+                                for message in messages.iter() {
+                                    self.apply_neighbor_message(message);
+                                }
+                            }
+                            Err(e) => {
+                                // 通常为接收缓冲区溢出，丢失的变化由下一次全量刷新补齐
+                                log::debug!("Neighbor event subscription error: {}", e);
+                                interval.reset_immediately();
+                            }
+                        }
+                    }
                     _ = shutdown_notify.notified() => {
                         log::info!("Device manager background task received shutdown signal, stopping...");
                         break;
                     }
                 }
             }
+
+            let now_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+            self.presence_tracker.flush(now_ms);
+            self.device_history.flush(now_ms);
+            self.identity_registry.flush();
         })
     }
 
-    fn apply_neighbor_devices(&self, neighbor_devices: Vec<([u8; 6], Option<[u8; 4]>, Vec<[u8; 16]>)>) -> Result<()> {
+    /// 处理一条内核推送的邻居变化，必要时记录上线/离线等事件
+    fn apply_neighbor_message(&self, message: &crate::neighbor::NeighborMessage) {
+        let entry = message.entry();
+        if Some(entry.ifindex) != self.interface_index() {
+            return;
+        }
+
+        let deleted = matches!(message, crate::neighbor::NeighborMessage::Del(_));
+        let entry_online = !deleted && entry.is_online();
+
+        // RTM_DELNEIGH 可能不携带 lladdr，此时按 IPv4 地址反查设备
+        let mac = match entry.mac {
+            Some(mac) => mac,
+            None => {
+                let ipv4 = match entry.ip {
+                    std::net::IpAddr::V4(ip) => ip.octets(),
+                    std::net::IpAddr::V6(_) => return,
+                };
+                let devices = self.devices.lock().unwrap();
+                let Some((mac, _)) = devices.iter().find(|(_, device)| device.current_ipv4 == Some(ipv4)) else {
+                    return;
+                };
+                *mac
+            }
+        };
+        if Self::is_special_mac_address(&mac) {
+            return;
+        }
+
+        if entry_online {
+            let mut changes = Vec::new();
+            let hostname_bindings = self.hostname_bindings.lock().unwrap();
+            let mut devices = self.devices.lock().unwrap();
+            if self.event_journal.mark_known(mac) {
+                changes.push((mac, DeviceChange::New));
+            }
+            let device = devices.entry(mac).or_insert_with(|| UnifiedDevice::new(mac));
+            match entry.ip {
+                std::net::IpAddr::V4(ip) => {
+                    if let Some(previous) = device.update_ipv4(ip.octets()) {
+                        changes.push((mac, DeviceChange::Ip(previous)));
+                    }
+                }
+                std::net::IpAddr::V6(ip) => {
+                    if !device.current_ipv6.contains(&ip.octets()) {
+                        let mut ipv6_list = device.current_ipv6.clone();
+                        ipv6_list.push(ip.octets());
+                        device.update_ipv6(&ipv6_list);
+                    }
+                }
+            }
+            if let Some(previous) = hostname_bindings
+                .get(&mac)
+                .and_then(|hostname| device.update_hostname(hostname.clone()))
+            {
+                changes.push((mac, DeviceChange::Hostname(previous)));
+            }
+            drop(devices);
+            drop(hostname_bindings);
+            self.record_changes(changes);
+        }
+
+        // 在线状态只由 IPv4 邻居决定，任一 IPv4 邻居在线即在线，与全量刷新保持一致
+        let std::net::IpAddr::V4(ipv4) = entry.ip else {
+            return;
+        };
+        let (prev_online, online) = {
+            let mut guard = self.neighbor_ipv4_online.lock().unwrap();
+            let mut states = self.neighbor_ipv4_states.lock().unwrap();
+            let prev = guard.get(&mac).copied().unwrap_or(false);
+            let state = if deleted { None } else { Some(entry_online) };
+            let online = match Self::update_ipv4_neighbor_state(&mut states, mac, ipv4.octets(), state) {
+                Some(online) => {
+                    guard.insert(mac, online);
+                    online
+                }
+                None => {
+                    guard.remove(&mac);
+                    false
+                }
+            };
+            (prev, online)
+        };
+
+        let now_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+        self.presence_tracker.record(mac, online, now_ms);
+
+        // 首次全量刷新之前不产生事件
+        if !self.neighbor_initialized.load(Ordering::Relaxed) || prev_online == online {
+            return;
+        }
+
+        let kind = if online { EventKind::Online } else { EventKind::Offline };
+        self.event_journal.record(self.device_event(&mac, kind));
+    }
+
+    fn apply_neighbor_devices(&self, neighbor_devices: Vec<NeighborDevice>) -> Result<()> {
         log::debug!("Starting device refresh for interface: {}", self.iface);
 
         log::debug!("Found {} devices in neighbor table", neighbor_devices.len());
@@ -257,8 +415,12 @@ impl DeviceManager {
 
         let mut updated_count = 0;
         let mut new_count = 0;
+        let mut changes = Vec::new();
 
         for (mac, ipv4, ipv6_list) in neighbor_devices {
+            if self.event_journal.mark_known(mac) {
+                changes.push((mac, DeviceChange::New));
+            }
             let is_new = !devices.contains_key(&mac);
             let device = devices.entry(mac).or_insert_with(|| UnifiedDevice::new(mac));
 
@@ -268,16 +430,19 @@ impl DeviceManager {
                 updated_count += 1;
             }
 
-            if let Some(ip) = ipv4 {
-                device.update_ipv4(ip);
+            if let Some(previous) = ipv4.and_then(|ip| device.update_ipv4(ip)) {
+                changes.push((mac, DeviceChange::Ip(previous)));
             }
 
             if !ipv6_list.is_empty() {
                 device.update_ipv6(&ipv6_list);
             }
 
-            if let Some(hostname) = hostname_bindings.get(&mac) {
-                device.update_hostname(hostname.clone());
+            if let Some(previous) = hostname_bindings
+                .get(&mac)
+                .and_then(|hostname| device.update_hostname(hostname.clone()))
+            {
+                changes.push((mac, DeviceChange::Hostname(previous)));
             }
         }
 
@@ -288,19 +453,29 @@ impl DeviceManager {
             updated_count
         );
 
+        drop(devices);
+        drop(hostname_bindings);
+        self.record_changes(changes);
+
         Ok(())
     }
 
-    async fn refresh_devices_with_neighbor_events(&self) -> Result<Vec<NeighborEventPayload>> {
-        let neighbor_devices = self.read_neighbor_table()?;
-        self.apply_neighbor_devices(neighbor_devices)?;
+    /// 全量刷新邻居表，记录上线/离线事件，返回上线/离线事件数
+    async fn refresh_devices_with_neighbor_events(&self) -> Result<usize> {
+        let entries = self.dump_interface_neighbors()?;
+        self.apply_neighbor_devices(self.build_neighbor_table(&entries))?;
+        self.event_journal.end_seeding();
 
         let now_ms = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .unwrap_or_default()
             .as_millis() as u64;
 
-        let new_ipv4_online = self.read_ipv4_neighbor_online_map()?;
+        let new_ipv4_states = Self::build_ipv4_neighbor_states(&entries);
+        let new_ipv4_online: HashMap<[u8; 6], bool> = new_ipv4_states
+            .iter()
+            .map(|(mac, ips)| (*mac, ips.values().any(|online| *online)))
+            .collect();
         let old_ipv4_online = {
             let guard = self.neighbor_ipv4_online.lock().unwrap();
             guard.clone()
@@ -309,24 +484,24 @@ impl DeviceManager {
         {
             let mut guard = self.neighbor_ipv4_online.lock().unwrap();
             *guard = new_ipv4_online.clone();
+            *self.neighbor_ipv4_states.lock().unwrap() = new_ipv4_states;
         }
+        self.presence_tracker.reconcile(&new_ipv4_online, now_ms);
+        self.record_history(&new_ipv4_online, now_ms);
 
         let already = self.neighbor_initialized.swap(true, Ordering::Relaxed);
         if !already {
-            return Ok(Vec::new());
+            return Ok(0);
         }
 
-        let wifi_set = self.get_wifi_macs_snapshot();
-        let mut out = Vec::new();
+        let mut count = 0;
 
         for (mac, now_online) in new_ipv4_online.iter() {
             let prev_online = old_ipv4_online.get(mac).copied().unwrap_or(false);
             if prev_online != *now_online {
-                let event = if *now_online { "online" } else { "offline" };
-                let ct = if wifi_set.contains(mac) { "wifi".to_string() } else { "wired".to_string() };
-                if let Some(p) = self.build_neighbor_event_payload(mac, event, now_ms, ct) {
-                    out.push(p);
-                }
+                let kind = if *now_online { EventKind::Online } else { EventKind::Offline };
+                self.event_journal.record(self.device_event(mac, kind));
+                count += 1;
             }
         }
 
@@ -334,159 +509,87 @@ impl DeviceManager {
             if !new_ipv4_online.contains_key(mac) {
                 let prev_online = old_ipv4_online.get(mac).copied().unwrap_or(false);
                 if prev_online {
-                    let ct = if wifi_set.contains(mac) { "wifi".to_string() } else { "wired".to_string() };
-                    if let Some(p) = self.build_neighbor_event_payload(mac, "offline", now_ms, ct) {
-                        out.push(p);
-                    }
+                    self.event_journal.record(self.device_event(mac, EventKind::Offline));
+                    count += 1;
                 }
             }
         }
 
-        Ok(out)
+        Ok(count)
     }
 
-    fn read_ipv4_neighbor_online_map(&self) -> Result<HashMap<[u8; 6], bool>> {
-        let mut out: HashMap<[u8; 6], bool> = HashMap::new();
-
-        let output = std::process::Command::new("ip")
-            .args(["-4", "neigh", "show", "dev", &self.iface])
-            .output()?;
-
-        let output_str = String::from_utf8_lossy(&output.stdout);
-        for line in output_str.lines() {
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() < 4 {
-                continue;
-            }
-
-            let state = Self::extract_neighbor_state(&parts).unwrap_or("UNKNOWN");
-            let online = matches!(state, "REACHABLE" | "STALE" | "DELAY" | "PROBE");
-
-            if let Some(lladdr_pos) = parts.iter().position(|&x| x == "lladdr") {
-                if lladdr_pos + 1 < parts.len() {
-                    if let Ok(mac) = crate::utils::network_utils::parse_mac_address(parts[lladdr_pos + 1]) {
-                        if Self::is_special_mac_address(&mac) {
-                            continue;
-                        }
-                        out.insert(mac, online);
-                    }
-                }
+    /// 监控接口的 ifindex，首次使用时解析并缓存
+    fn interface_index(&self) -> Option<u32> {
+        match self.ifindex.load(Ordering::Relaxed) {
+            0 => {
+                let index = crate::neighbor::interface_index(&self.iface)?;
+                self.ifindex.store(index, Ordering::Relaxed);
+                Some(index)
             }
+            index => Some(index),
         }
-
-        Ok(out)
     }
 
-    fn extract_neighbor_state(parts: &[&str]) -> Option<&'static str> {
-        fn normalize_token(s: &str) -> String {
-            s.trim_matches(|c: char| !c.is_ascii_alphabetic())
-                .to_ascii_uppercase()
-        }
-
-        for p in parts.iter().rev() {
-            match normalize_token(p).as_str() {
-                "REACHABLE" => return Some("REACHABLE"),
-                "STALE" => return Some("STALE"),
-                "DELAY" => return Some("DELAY"),
-                "PROBE" => return Some("PROBE"),
-                "FAILED" => return Some("FAILED"),
-                "NOARP" => return Some("NOARP"),
-                "INCOMPLETE" => return Some("INCOMPLETE"),
-                "INVALID" => return Some("INVALID"),
-                _ => {}
-            }
-        }
-        None
+    /// 通过 rtnetlink 获取当前接口的邻居表；每次全量刷新重新解析 ifindex（接口可能被重建）
+    fn dump_interface_neighbors(&self) -> Result<Vec<crate::neighbor::NeighborEntry>> {
+        let ifindex = crate::neighbor::interface_index(&self.iface).ok_or_else(|| anyhow::anyhow!("Interface {} not found", self.iface))?;
+        self.ifindex.store(ifindex, Ordering::Relaxed);
+        let mut entries = crate::neighbor::dump_neighbors()?;
+        entries.retain(|entry| entry.ifindex == ifindex);
+        Ok(entries)
     }
 
-    fn read_neighbor_table(&self) -> Result<Vec<([u8; 6], Option<[u8; 4]>, Vec<[u8; 16]>)>> {
-        let mut devices_map: HashMap<[u8; 6], (Option<[u8; 4]>, Vec<[u8; 16]>)> = HashMap::new();
+    fn build_ipv4_neighbor_states(entries: &[crate::neighbor::NeighborEntry]) -> Ipv4NeighborStates {
+        let mut out: Ipv4NeighborStates = HashMap::new();
 
-        // 读取 IPv4 邻居表
-        if let Ok(output) = std::process::Command::new("ip")
-            .args(["-4", "neigh", "show", "dev", &self.iface])
-            .output()
-        {
-            let output_str = String::from_utf8_lossy(&output.stdout);
-            for line in output_str.lines() {
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                if parts.len() < 4 {
-                    continue;
-                }
-
-                // 检查邻居状态，只保留有效状态
-                let state = match Self::extract_neighbor_state(&parts) {
-                    Some(s) => s,
-                    None => continue,
-                };
-                if matches!(state, "FAILED" | "NOARP" | "INCOMPLETE" | "INVALID") {
-                    continue;
-                }
-
-                // 解析 IPv4 地址
-                let ipv4 = match parts[0].parse::<std::net::Ipv4Addr>() {
-                    Ok(ip) => ip,
-                    Err(_) => continue,
-                };
+        for entry in entries.iter() {
+            let std::net::IpAddr::V4(ip) = entry.ip else {
+                continue;
+            };
+            let mac = match entry.mac {
+                Some(mac) if !Self::is_special_mac_address(&mac) => mac,
+                _ => continue,
+            };
+            // 同一 MAC 可能对应多个 IPv4 邻居，任一在线即视为在线
+            *out.entry(mac).or_default().entry(ip.octets()).or_insert(false) |= entry.is_online();
+        }
 
-                // 查找 MAC 地址 (lladdr)
-                if let Some(lladdr_pos) = parts.iter().position(|&x| x == "lladdr") {
-                    if lladdr_pos + 1 < parts.len() {
-                        if let Ok(mac) = crate::utils::network_utils::parse_mac_address(parts[lladdr_pos + 1]) {
-                            if Self::is_special_mac_address(&mac) {
-                                continue;
-                            }
+        out
+    }
 
-                            devices_map.entry(mac).or_insert_with(|| (None, Vec::new())).0 = Some(ipv4.octets());
-                        }
-                    }
-                }
+    /// 更新一个 IPv4 邻居的状态（None 表示邻居被删除），返回该 MAC 的整体在线状态；
+    /// MAC 已没有任何 IPv4 邻居时返回 None
+    fn update_ipv4_neighbor_state(states: &mut Ipv4NeighborStates, mac: [u8; 6], ip: [u8; 4], state: Option<bool>) -> Option<bool> {
+        let ips = states.entry(mac).or_default();
+        match state {
+            Some(online) => {
+                ips.insert(ip, online);
+            }
+            None => {
+                ips.remove(&ip);
             }
         }
+        if ips.is_empty() {
+            states.remove(&mac);
+            return None;
+        }
+        Some(ips.values().any(|online| *online))
+    }
 
-        // 读取 IPv6 邻居表
-        if let Ok(output) = std::process::Command::new("ip")
-            .args(["-6", "neigh", "show", "dev", &self.iface])
-            .output()
-        {
-            let output_str = String::from_utf8_lossy(&output.stdout);
-            for line in output_str.lines() {
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                if parts.len() < 4 {
-                    continue;
-                }
-
-                // 检查邻居状态，只保留有效状态
-                let state = match Self::extract_neighbor_state(&parts) {
-                    Some(s) => s,
-                    None => continue,
-                };
-                if matches!(state, "FAILED" | "NOARP" | "INCOMPLETE" | "INVALID") {
-                    continue;
-                }
-
-                // 解析 IPv6 地址
-                let ipv6 = match parts[0].parse::<std::net::Ipv6Addr>() {
-                    Ok(ip) => ip,
-                    Err(_) => continue,
-                };
-
-                // 查找 MAC 地址 (lladdr)
-                if let Some(lladdr_pos) = parts.iter().position(|&x| x == "lladdr") {
-                    if lladdr_pos + 1 < parts.len() {
-                        if let Ok(mac) = crate::utils::network_utils::parse_mac_address(parts[lladdr_pos + 1]) {
-                            if Self::is_special_mac_address(&mac) {
-                                continue;
-                            }
+    fn build_neighbor_table(&self, entries: &[crate::neighbor::NeighborEntry]) -> Vec<NeighborDevice> {
+        let mut devices_map: HashMap<[u8; 6], (Option<[u8; 4]>, Vec<[u8; 16]>)> = HashMap::new();
 
-                            devices_map
-                                .entry(mac)
-                                .or_insert_with(|| (None, Vec::new()))
-                                .1
-                                .push(ipv6.octets());
-                        }
-                    }
-                }
+        // 只保留有效状态且带有 MAC 地址的邻居
+        for entry in entries.iter().filter(|entry| entry.is_online()) {
+            let mac = match entry.mac {
+                Some(mac) if !Self::is_special_mac_address(&mac) => mac,
+                _ => continue,
+            };
+
+            let slot = devices_map.entry(mac).or_insert_with(|| (None, Vec::new()));
+            match entry.ip {
+                std::net::IpAddr::V4(ip) => slot.0 = Some(ip.octets()),
+                std::net::IpAddr::V6(ip) => slot.1.push(ip.octets()),
             }
         }
 
@@ -501,15 +604,14 @@ impl DeviceManager {
         devices_map.insert(interface_mac, (Some(interface_ipv4), interface_ipv6_addresses));
 
         // 转换为向量格式
-        let devices: Vec<([u8; 6], Option<[u8; 4]>, Vec<[u8; 16]>)> =
-            devices_map.into_iter().map(|(mac, (ipv4, ipv6))| (mac, ipv4, ipv6)).collect();
-
-        Ok(devices)
+        devices_map.into_iter().map(|(mac, (ipv4, ipv6))| (mac, ipv4, ipv6)).collect()
     }
 
     pub async fn refresh_devices(&self) -> Result<()> {
-        let neighbor_devices = self.read_neighbor_table()?;
-        self.apply_neighbor_devices(neighbor_devices)
+        let entries = self.dump_interface_neighbors()?;
+        self.apply_neighbor_devices(self.build_neighbor_table(&entries))?;
+        self.event_journal.end_seeding();
+        Ok(())
     }
 
     pub fn get_device_by_mac(&self, mac: &[u8; 6]) -> Option<UnifiedDevice> {
@@ -536,7 +638,12 @@ impl DeviceManager {
     }
 
     pub fn get_wifi_macs_snapshot(&self) -> HashSet<[u8; 6]> {
-        self.wifi_macs.lock().unwrap().clone()
+        self.wireless_stations.lock().unwrap().keys().copied().collect()
+    }
+
+    /// 获取 Wi-Fi 客户端的信号、速率等信息，有线设备返回 None
+    pub fn get_wireless_station(&self, mac: &[u8; 6]) -> Option<crate::wireless::WirelessStation> {
+        self.wireless_stations.lock().unwrap().get(mac).cloned()
     }
 
     /// 添加离线设备（从 ring 文件恢复的设备）
@@ -545,6 +652,8 @@ impl DeviceManager {
         let hostname_bindings = self.hostname_bindings.lock().unwrap();
         let mut devices = self.devices.lock().unwrap();
 
+        // 历史设备视为已见过，不产生 new_device 事件
+        self.event_journal.mark_known(mac);
         let device = devices.entry(mac).or_insert_with(|| UnifiedDevice::new(mac));
 
         if let Some(hostname) = hostname_bindings.get(&mac) {
@@ -561,6 +670,52 @@ impl DeviceManager {
         }
     }
 
+    /// 应用 DHCP 监听到的信息
+    /// ipv4 为服务器确认（ACK）的地址，此时设备不存在则创建；没有主机名绑定时使用 DHCP 主机名
+    pub fn apply_dhcp_lease(&self, mac: [u8; 6], ipv4: Option<[u8; 4]>, hostname: Option<&str>) {
+        if Self::is_special_mac_address(&mac) {
+            return;
+        }
+
+        let hostname_bindings = self.hostname_bindings.lock().unwrap();
+        let mut devices = self.devices.lock().unwrap();
+
+        let mut changes = Vec::new();
+        let device = match (devices.contains_key(&mac), ipv4) {
+            (true, _) => devices.get_mut(&mac).unwrap(),
+            (false, Some(_)) => {
+                if self.event_journal.mark_known(mac) {
+                    changes.push((mac, DeviceChange::New));
+                }
+                devices.entry(mac).or_insert_with(|| UnifiedDevice::new(mac))
+            }
+            (false, None) => return,
+        };
+
+        if let Some(previous) = ipv4.and_then(|ip| device.update_ipv4(ip)) {
+            changes.push((mac, DeviceChange::Ip(previous)));
+        }
+
+        let previous_hostname = match (hostname_bindings.get(&mac), hostname) {
+            (Some(bound), _) => device.update_hostname(bound.clone()),
+            (None, Some(name)) => device.update_hostname(name.to_string()),
+            (None, None) => None,
+        };
+        if let Some(previous) = previous_hostname {
+            changes.push((mac, DeviceChange::Hostname(previous)));
+        }
+
+        drop(devices);
+        drop(hostname_bindings);
+        self.record_changes(changes);
+
+        if let Some(ip) = ipv4 {
+            let now_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+            let ip = std::net::Ipv4Addr::from(ip).to_string();
+            self.device_history.record(vec![(mac, HistoryKind::Ipv4, ip)], now_ms);
+        }
+    }
+
     fn is_special_mac_address(mac: &[u8; 6]) -> bool {
         if mac == &[0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF] {
             return true;
@@ -577,99 +732,167 @@ impl DeviceManager {
         false
     }
 
-    fn refresh_wifi_macs_cache(&self) {
-        let ifaces = Self::read_hostapd_interfaces();
-        if ifaces.is_empty() {
-            let mut guard = self.wifi_macs.lock().unwrap();
-            guard.clear();
-            return;
-        }
+    pub fn device_classifier(&self) -> Arc<DeviceClassifier> {
+        Arc::clone(&self.device_classifier)
+    }
+
+    pub fn identity_registry(&self) -> Arc<IdentityRegistry> {
+        Arc::clone(&self.identity_registry)
+    }
+
+    pub fn presence_tracker(&self) -> Arc<PresenceTracker> {
+        Arc::clone(&self.presence_tracker)
+    }
+
+    pub fn event_journal(&self) -> Arc<EventJournal> {
+        Arc::clone(&self.event_journal)
+    }
+
+    pub fn subnet_info(&self) -> &SubnetInfo {
+        &self.subnet_info
+    }
+
+    pub fn device_history(&self) -> Arc<DeviceHistory> {
+        Arc::clone(&self.device_history)
+    }
+
+    pub fn device_meta(&self) -> Arc<DeviceMetaStore> {
+        Arc::clone(&self.device_meta)
+    }
+
+    pub fn device_groups(&self) -> Arc<GroupRegistry> {
+        Arc::clone(&self.device_groups)
+    }
+
+    /// 设备所在的组，同一身份下任一 MAC 在组内即算作成员
+    pub fn groups_of(&self, mac: &[u8; 6]) -> Vec<String> {
+        self.device_groups.groups_of(&self.identity_registry.group_macs(mac))
+    }
 
-        let mut out: HashSet<[u8; 6]> = HashSet::new();
-        for obj in ifaces {
-            for mac in Self::read_hostapd_clients(&obj) {
-                if Self::is_special_mac_address(&mac) {
+    /// 组内全部 MAC，包括成员所属身份下的其他 MAC；组不存在时返回 None
+    pub fn group_members(&self, name: &str) -> Option<Vec<[u8; 6]>> {
+        let group = self.device_groups.get(name)?;
+        let mut macs: Vec<[u8; 6]> = group
+            .macs
+            .iter()
+            .flat_map(|mac| self.identity_registry.group_macs(mac))
+            .collect();
+        macs.sort();
+        macs.dedup();
+        Some(macs)
+    }
+
+    /// 将在线设备当前的地址与主机名记入历史
+    fn record_history(&self, online: &HashMap<[u8; 6], bool>, now_ms: u64) {
+        let mut observations = Vec::new();
+        {
+            let devices = self.devices.lock().unwrap();
+            for (mac, _) in online.iter().filter(|(_, online)| **online) {
+                let Some(device) = devices.get(mac) else {
                     continue;
+                };
+                if let Some(ip) = device.current_ipv4 {
+                    observations.push((*mac, HistoryKind::Ipv4, std::net::Ipv4Addr::from(ip).to_string()));
+                }
+                for ip in device.current_ipv6.iter() {
+                    observations.push((*mac, HistoryKind::Ipv6, std::net::Ipv6Addr::from(*ip).to_string()));
+                }
+                if !device.hostname.is_empty() {
+                    observations.push((*mac, HistoryKind::Hostname, device.hostname.clone()));
                 }
-                out.insert(mac);
             }
         }
-
-        let mut guard = self.wifi_macs.lock().unwrap();
-        *guard = out;
+        self.device_history.record(observations, now_ms);
     }
 
-    fn read_hostapd_interfaces() -> Vec<String> {
-        let output = match std::process::Command::new("ubus").args(["list"]).output() {
-            Ok(o) => o,
-            Err(_) => return Vec::new(),
-        };
-        if !output.status.success() {
-            return Vec::new();
-        }
+    /// 记录预定限速规则的生效（Some，附带生效的限速值）或失效（None）
+    pub fn record_rate_limit_change(&self, mac: &[u8; 6], limits: Option<[u64; 2]>) {
+        let kind = if limits.is_some() { EventKind::RateLimitOn } else { EventKind::RateLimitOff };
+        let mut event = self.device_event(mac, kind);
+        event.wan_rx_rate_limit = limits.map(|l| l[0]);
+        event.wan_tx_rate_limit = limits.map(|l| l[1]);
+        self.event_journal.record(event);
+    }
 
-        let s = String::from_utf8_lossy(&output.stdout);
-        s.lines()
-            .map(|l| l.trim().to_string())
-            .filter(|l| l.starts_with("hostapd."))
-            .collect()
+    /// 记录 ARP/NDP 监听产生的安全告警，ip 为报文中被声明的地址
+    pub fn record_security_alert(&self, alert: &SpoofAlert) {
+        let mut event = self.device_event(&alert.mac, alert.kind);
+        event.ip = alert.ip.to_string();
+        event.conflicting_mac = Some(crate::utils::format_utils::format_mac(&alert.conflicting_mac));
+        event.flip_count = alert.flip_count;
+        self.event_journal.record(event);
     }
 
-    fn read_hostapd_clients(obj: &str) -> Vec<[u8; 6]> {
-        let output = match std::process::Command::new("ubus").args(["call", obj, "get_clients"]).output() {
-            Ok(o) => o,
-            Err(_) => return Vec::new(),
-        };
-        if !output.status.success() {
-            return Vec::new();
+    /// 根据已收集的信号重新识别所有设备的类型
+    pub fn classify_devices(&self) {
+        let mut devices = self.devices.lock().unwrap();
+        for (mac, device) in devices.iter_mut() {
+            device.device_type = self.device_classifier.classify(mac, device.vendor.as_deref(), &device.hostname);
         }
+    }
 
-        let v: Value = serde_json::from_slice(&output.stdout).unwrap_or(Value::Null);
-        let clients = match v.get("clients").and_then(|x| x.as_object()) {
-            Some(o) => o,
-            None => return Vec::new(),
-        };
-
-        let mut macs = Vec::new();
-        for (mac_str, _info) in clients.iter() {
-            if let Ok(mac) = crate::utils::network_utils::parse_mac_address(mac_str) {
-                macs.push(mac);
-            }
+    /// 手动设置或清除设备类型，立即生效
+    pub fn set_device_type_override(&self, mac: [u8; 6], device_type: Option<DeviceType>) {
+        self.device_classifier.set_override(mac, device_type);
+        let mut devices = self.devices.lock().unwrap();
+        if let Some(device) = devices.get_mut(&mac) {
+            device.device_type = self
+                .device_classifier
+                .classify(&mac, device.vendor.as_deref(), &device.hostname);
         }
-        macs
     }
 
-}
+    fn refresh_wireless_stations(&self) {
+        let mut stations = crate::wireless::read_stations();
+        stations.retain(|mac, _| !Self::is_special_mac_address(mac));
 
-#[derive(serde::Serialize)]
-struct NeighborEventPayload {
-    ts_ms: u64,
-    event: String,
-    mac: String,
-    ip: String,
-    hostname: String,
-    connection_type: String,
+        let mut guard = self.wireless_stations.lock().unwrap();
+        *guard = stations;
+    }
 }
 
 impl DeviceManager {
-    fn build_neighbor_event_payload(
-        &self,
-        mac: &[u8; 6],
-        event: &str,
-        ts_ms: u64,
-        connection_type: String,
-    ) -> Option<NeighborEventPayload> {
-        let device = self.get_device_by_mac(mac)?;
+    /// 以设备当前的 IP、主机名与接入方式构造一条事件，编号由事件日志分配
+    fn device_event(&self, mac: &[u8; 6], kind: EventKind) -> DeviceEvent {
+        let device = self.get_device_by_mac(mac).unwrap_or_else(|| UnifiedDevice::new(*mac));
         let ipv4 = device.get_current_ipv4();
-        let ip = format!("{}.{}.{}.{}", ipv4[0], ipv4[1], ipv4[2], ipv4[3]);
-        Some(NeighborEventPayload {
-            ts_ms,
-            event: event.to_string(),
+        let connection_type = if self.wireless_stations.lock().unwrap().contains_key(mac) {
+            "wifi"
+        } else {
+            "wired"
+        };
+        DeviceEvent {
+            id: 0,
+            ts_ms: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64,
+            event: kind.as_str().to_string(),
             mac: crate::utils::format_utils::format_mac(mac),
-            ip,
+            ip: format!("{}.{}.{}.{}", ipv4[0], ipv4[1], ipv4[2], ipv4[3]),
             hostname: device.hostname,
-            connection_type,
-        })
+            connection_type: connection_type.to_string(),
+            previous_ip: None,
+            previous_hostname: None,
+            wan_rx_rate_limit: None,
+            wan_tx_rate_limit: None,
+            conflicting_mac: None,
+            flip_count: None,
+        }
+    }
+
+    fn record_changes(&self, changes: Vec<([u8; 6], DeviceChange)>) {
+        for (mac, change) in changes {
+            let event = match change {
+                DeviceChange::New => self.device_event(&mac, EventKind::NewDevice),
+                DeviceChange::Ip(previous) => DeviceEvent {
+                    previous_ip: Some(std::net::Ipv4Addr::from(previous).to_string()),
+                    ..self.device_event(&mac, EventKind::IpChange)
+                },
+                DeviceChange::Hostname(previous) => DeviceEvent {
+                    previous_hostname: Some(previous),
+                    ..self.device_event(&mac, EventKind::HostnameChange)
+                },
+            };
+            self.event_journal.record(event);
+        }
     }
 }
 
@@ -684,4 +907,22 @@ mod tests {
         assert!(DeviceManager::is_special_mac_address(&[0x00, 0x00, 0x00, 0x00, 0x00, 0x00]));
         assert!(!DeviceManager::is_special_mac_address(&[0x00, 0x11, 0x22, 0x33, 0x44, 0x55]));
     }
+
+    #[test]
+    fn test_ipv4_neighbor_state_per_mac() {
+        let mac = [0x00, 0x11, 0x22, 0x33, 0x44, 0x55];
+        let mut states = HashMap::new();
+        let mut update = |ip, state| DeviceManager::update_ipv4_neighbor_state(&mut states, mac, ip, state);
+
+        assert_eq!(update([192, 168, 1, 20], Some(true)), Some(true));
+        assert_eq!(update([192, 168, 1, 21], Some(true)), Some(true));
+
+        // 另一个 IPv4 邻居仍在线时，单个邻居失效或删除不算离线
+        assert_eq!(update([192, 168, 1, 21], Some(false)), Some(true));
+        assert_eq!(update([192, 168, 1, 21], None), Some(true));
+
+        assert_eq!(update([192, 168, 1, 20], Some(false)), Some(false));
+        assert_eq!(update([192, 168, 1, 20], None), None);
+        assert!(states.is_empty());
+    }
 }
@@ -0,0 +1,464 @@
+// 设备事件日志：持久化、有上限的事件记录，供 API 查询并作为 webhook 推送的来源
+//
+// 设备上线/离线、首次出现、IP 或主机名变化、预定限速规则生效/失效等事件先写入日志（data_dir/events.jsonl），
+// 再由 webhook 任务按编号顺序投递到 --traffic-event-url。接收方不可用时事件保留在日志中，恢复后继续投递；
+// 已投递到的编号保存在 events_webhook_cursor，重启后从断点继续。
+// 文件写入由独立的写入线程按顺序执行，记录事件时不在状态锁内或异步任务中做文件 I/O。
+
+use crate::storage::events::DeviceEvent;
+use std::collections::{HashSet, VecDeque};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// 日志中最多保留的事件数
+const MAX_EVENTS: usize = 10_000;
+// 文件中累计的行数超过 MAX_EVENTS 的这一比例后重写文件，丢弃旧事件
+const COMPACT_SLACK: usize = MAX_EVENTS / 2;
+// 每次投递的最大事件数，之后检查一次关闭信号
+const WEBHOOK_BATCH: usize = 100;
+// 投递失败后的重试间隔上限
+const WEBHOOK_MAX_BACKOFF: Duration = Duration::from_secs(60);
+// 退出前等待写入线程完成的最长时间
+const WRITER_FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 设备事件类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EventKind {
+    Online,
+    Offline,
+    NewDevice,      // 首次见到的设备
+    IpChange,       // IPv4 地址变化
+    HostnameChange, // 主机名变化
+    RateLimitOn,    // 预定限速规则开始生效或限速值变化
+    RateLimitOff,   // 预定限速规则失效
+    // 以下为 ARP/NDP 监听产生的安全事件
+    GatewayImpersonation, // 其他 MAC 声明网关的 IP
+    DuplicateIp,          // 同一 IP 同时被多个 MAC 声明
+    MacFlip,              // 同一 IP 的 MAC 在短时间内频繁变化
+}
+
+impl EventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventKind::Online => "online",
+            EventKind::Offline => "offline",
+            EventKind::NewDevice => "new_device",
+            EventKind::IpChange => "ip_change",
+            EventKind::HostnameChange => "hostname_change",
+            EventKind::RateLimitOn => "rate_limit_on",
+            EventKind::RateLimitOff => "rate_limit_off",
+            EventKind::GatewayImpersonation => "gateway_impersonation",
+            EventKind::DuplicateIp => "duplicate_ip",
+            EventKind::MacFlip => "mac_flip",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "online" => Some(EventKind::Online),
+            "offline" => Some(EventKind::Offline),
+            "new_device" => Some(EventKind::NewDevice),
+            "ip_change" => Some(EventKind::IpChange),
+            "hostname_change" => Some(EventKind::HostnameChange),
+            "rate_limit_on" => Some(EventKind::RateLimitOn),
+            "rate_limit_off" => Some(EventKind::RateLimitOff),
+            "gateway_impersonation" => Some(EventKind::GatewayImpersonation),
+            "duplicate_ip" => Some(EventKind::DuplicateIp),
+            "mac_flip" => Some(EventKind::MacFlip),
+            _ => None,
+        }
+    }
+
+    /// 是否为安全告警事件
+    pub fn is_security(&self) -> bool {
+        matches!(
+            self,
+            EventKind::GatewayImpersonation | EventKind::DuplicateIp | EventKind::MacFlip
+        )
+    }
+}
+
+/// 事件查询条件
+#[derive(Debug, Clone, Default)]
+pub struct EventFilter {
+    pub kind: Option<EventKind>,
+    pub mac: Option<String>, // 冒号分隔的小写 MAC
+    pub start_ms: Option<u64>,
+    pub end_ms: Option<u64>,
+    pub security_only: bool, // 只匹配安全告警事件
+}
+
+impl EventFilter {
+    fn matches(&self, event: &DeviceEvent) -> bool {
+        if matches!(self.kind, Some(kind) if event.event != kind.as_str()) {
+            return false;
+        }
+        if self.security_only && !matches!(EventKind::parse(&event.event), Some(kind) if kind.is_security()) {
+            return false;
+        }
+        if matches!(&self.mac, Some(mac) if &event.mac != mac) {
+            return false;
+        }
+        event.ts_ms >= self.start_ms.unwrap_or(0) && event.ts_ms < self.end_ms.unwrap_or(u64::MAX)
+    }
+}
+
+#[derive(Debug, Default)]
+struct JournalState {
+    events: VecDeque<DeviceEvent>, // 按编号升序
+    next_id: u64,
+    file_lines: usize,       // 事件文件中的行数
+    known: HashSet<[u8; 6]>, // 见过的设备
+    seeding: bool,           // 首次运行时先静默记录现有设备，不产生 new_device 事件
+}
+
+/// 交给写入线程的文件操作，按发送顺序执行
+enum JournalWrite {
+    Append(Box<DeviceEvent>),
+    Compact(Vec<DeviceEvent>), // 用当前保留的事件重写文件
+    KnownDevices(Vec<[u8; 6]>),
+    Flush(mpsc::Sender<()>), // 之前的操作全部完成后应答
+}
+
+/// 设备事件日志，在设备管理器、流量模块与 API 之间共享
+pub struct EventJournal {
+    data_dir: Option<String>, // 为 None 时不持久化
+    state: Mutex<JournalState>,
+    appended: tokio::sync::Notify,                     // 有新事件时唤醒 webhook 任务
+    writer: Option<Mutex<mpsc::Sender<JournalWrite>>>, // 写入线程，未持久化时为 None
+}
+
+impl EventJournal {
+    /// 从数据目录加载事件日志与已见过的设备
+    pub fn load(data_dir: &str) -> Self {
+        let events = crate::storage::events::load_events(data_dir).unwrap_or_else(|e| {
+            log::warn!("Failed to load event journal: {}", e);
+            Vec::new()
+        });
+        let known = crate::storage::events::load_known_devices(data_dir).unwrap_or_else(|e| {
+            log::warn!("Failed to load known devices: {}", e);
+            None
+        });
+        Self::from_parts(Some(data_dir.to_string()), events, known)
+    }
+
+    fn from_parts(data_dir: Option<String>, events: Vec<DeviceEvent>, known: Option<Vec<[u8; 6]>>) -> Self {
+        let file_lines = events.len();
+        let mut events: VecDeque<DeviceEvent> = events.into();
+        while events.len() > MAX_EVENTS {
+            events.pop_front();
+        }
+        let state = JournalState {
+            next_id: events.back().map_or(1, |e| e.id + 1),
+            events,
+            file_lines,
+            seeding: known.is_none(),
+            known: known.unwrap_or_default().into_iter().collect(),
+        };
+        let writer = data_dir.clone().map(|dir| Mutex::new(spawn_writer(dir)));
+        Self {
+            data_dir,
+            state: Mutex::new(state),
+            appended: tokio::sync::Notify::new(),
+            writer,
+        }
+    }
+
+    /// 将文件操作交给写入线程；调用方持有状态锁，保证操作顺序与编号顺序一致
+    fn write(&self, op: JournalWrite) {
+        if let Some(writer) = &self.writer {
+            if writer.lock().unwrap().send(op).is_err() {
+                log::warn!("Event journal writer is not running, event not persisted");
+            }
+        }
+    }
+
+    /// 写入一条事件，编号由日志分配；返回分配的编号
+    pub fn record(&self, mut event: DeviceEvent) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        event.id = state.next_id;
+        state.next_id += 1;
+        log::debug!("Event {} {} {}", event.id, event.event, event.mac);
+
+        if self.writer.is_some() {
+            self.write(JournalWrite::Append(Box::new(event.clone())));
+        }
+        state.file_lines += 1;
+
+        let id = event.id;
+        state.events.push_back(event);
+        while state.events.len() > MAX_EVENTS {
+            state.events.pop_front();
+        }
+
+        if state.file_lines > MAX_EVENTS + COMPACT_SLACK {
+            if self.writer.is_some() {
+                self.write(JournalWrite::Compact(state.events.iter().cloned().collect()));
+            }
+            state.file_lines = state.events.len();
+        }
+        drop(state);
+
+        self.appended.notify_one();
+        id
+    }
+
+    /// 记录见到的设备，返回是否为首次见到（首次运行的初始阶段始终返回 false）
+    pub fn mark_known(&self, mac: [u8; 6]) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if !state.known.insert(mac) {
+            return false;
+        }
+        self.write(JournalWrite::KnownDevices(vec![mac]));
+        !state.seeding
+    }
+
+    /// 结束初始阶段，之后新出现的设备会产生 new_device 事件
+    pub fn end_seeding(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.seeding {
+            state.seeding = false;
+            // 确保文件存在，重启后不再进入初始阶段
+            self.write(JournalWrite::KnownDevices(Vec::new()));
+        }
+    }
+
+    /// 等待已记录的事件全部写入文件（退出前调用，会阻塞当前线程）
+    pub fn flush(&self) {
+        let (ack_tx, ack_rx) = mpsc::channel();
+        if self.writer.is_some() {
+            self.write(JournalWrite::Flush(ack_tx));
+            if ack_rx.recv_timeout(WRITER_FLUSH_TIMEOUT).is_err() {
+                log::warn!("Timed out waiting for the event journal to be written");
+            }
+        }
+    }
+
+    /// 按条件查询事件，最新的在前；返回 (匹配总数, 最多 limit 条事件)
+    pub fn query(&self, filter: &EventFilter, limit: usize) -> (usize, Vec<DeviceEvent>) {
+        let state = self.state.lock().unwrap();
+        let mut total = 0;
+        let mut out = Vec::new();
+        for event in state.events.iter().rev().filter(|e| filter.matches(e)) {
+            total += 1;
+            if out.len() < limit {
+                out.push(event.clone());
+            }
+        }
+        (total, out)
+    }
+
+    /// 编号大于 after_id 的事件，按编号升序，最多 limit 条
+    fn events_after(&self, after_id: u64, limit: usize) -> Vec<DeviceEvent> {
+        let state = self.state.lock().unwrap();
+        state.events.iter().filter(|e| e.id > after_id).take(limit).cloned().collect()
+    }
+
+    fn last_id(&self) -> u64 {
+        self.state.lock().unwrap().next_id - 1
+    }
+
+    /// 启动 webhook 投递任务：按编号顺序逐条 POST，失败时退避重试，不跳过事件
+    pub fn start_webhook_task(self: Arc<Self>, url: String, shutdown_notify: Arc<tokio::sync::Notify>) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let http = reqwest::Client::builder().timeout(Duration::from_secs(3)).build().unwrap();
+            log::debug!("Traffic event notifier enabled: {}", url);
+
+            // 首次启用时不补发历史事件
+            let data_dir = self.data_dir.clone();
+            let mut cursor = match data_dir.as_deref().map(crate::storage::events::load_webhook_cursor) {
+                Some(Ok(Some(cursor))) => cursor,
+                Some(Err(e)) => {
+                    log::warn!("Failed to load event webhook cursor: {}", e);
+                    self.last_id()
+                }
+                _ => self.last_id(),
+            };
+            // 事件文件被删除后编号会重新开始
+            cursor = cursor.min(self.last_id());
+            let mut backoff = Duration::from_secs(1);
+
+            // 提前注册关闭通知，避免投递期间错过
+            let shutdown = shutdown_notify.notified();
+            tokio::pin!(shutdown);
+            shutdown.as_mut().enable();
+
+            loop {
+                let pending = self.events_after(cursor, WEBHOOK_BATCH);
+                if pending.is_empty() {
+                    tokio::select! {
+                        _ = self.appended.notified() => continue,
+                        _ = &mut shutdown => break,
+                    }
+                }
+
+                let delivered_before = cursor;
+                for event in pending {
+                    match http.post(&url).json(&crate::privacy::export_value(&event)).send().await {
+                        Ok(resp) if resp.status().is_success() => cursor = event.id,
+                        Ok(resp) => {
+                            log::warn!("Traffic event export got HTTP {} from {}", resp.status(), url);
+                            break;
+                        }
+                        Err(e) => {
+                            log::warn!("Traffic event export failed: {}", e);
+                            break;
+                        }
+                    }
+                }
+
+                if cursor != delivered_before {
+                    backoff = Duration::from_secs(1);
+                    if let Some(data_dir) = data_dir.as_deref() {
+                        if let Err(e) = crate::storage::events::save_webhook_cursor(data_dir, cursor) {
+                            log::warn!("Failed to save event webhook cursor: {}", e);
+                        }
+                    }
+                    continue;
+                }
+
+                // 整批都未投递成功，稍后重试
+                tokio::select! {
+                    _ = tokio::time::sleep(backoff) => {}
+                    _ = &mut shutdown => break,
+                }
+                backoff = (backoff * 2).min(WEBHOOK_MAX_BACKOFF);
+            }
+        })
+    }
+}
+
+/// 启动写入线程，按顺序执行文件操作；发送端全部释放后线程退出
+fn spawn_writer(data_dir: String) -> mpsc::Sender<JournalWrite> {
+    let (tx, rx) = mpsc::channel::<JournalWrite>();
+    let spawned = std::thread::Builder::new().name("event-journal".to_string()).spawn(move || {
+        for op in rx {
+            match op {
+                JournalWrite::Append(event) => {
+                    if let Err(e) = crate::storage::events::append_events(&data_dir, std::slice::from_ref(&event)) {
+                        log::warn!("Failed to append to event journal: {}", e);
+                    }
+                }
+                JournalWrite::Compact(events) => {
+                    if let Err(e) = crate::storage::events::save_events(&data_dir, events.iter()) {
+                        log::warn!("Failed to compact event journal: {}", e);
+                    }
+                }
+                JournalWrite::KnownDevices(macs) => {
+                    if let Err(e) = crate::storage::events::append_known_devices(&data_dir, &macs) {
+                        log::warn!("Failed to save known devices: {}", e);
+                    }
+                }
+                JournalWrite::Flush(ack) => {
+                    let _ = ack.send(());
+                }
+            }
+        }
+    });
+    if let Err(e) = spawned {
+        log::warn!("Failed to start event journal writer: {}", e);
+    }
+    tx
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(kind: EventKind, mac: &str, ts_ms: u64) -> DeviceEvent {
+        DeviceEvent {
+            id: 0,
+            ts_ms,
+            event: kind.as_str().to_string(),
+            mac: mac.to_string(),
+            ip: String::new(),
+            hostname: String::new(),
+            connection_type: "wired".to_string(),
+            previous_ip: None,
+            previous_hostname: None,
+            wan_rx_rate_limit: None,
+            wan_tx_rate_limit: None,
+            conflicting_mac: None,
+            flip_count: None,
+        }
+    }
+
+    #[test]
+    fn test_record_and_query() {
+        let journal = EventJournal::from_parts(None, Vec::new(), Some(Vec::new()));
+        journal.record(event(EventKind::Online, "00:11:22:33:44:55", 1_000));
+        journal.record(event(EventKind::Online, "00:11:22:33:44:66", 2_000));
+        let id = journal.record(event(EventKind::Offline, "00:11:22:33:44:55", 3_000));
+        assert_eq!(id, 3);
+
+        let filter = EventFilter {
+            mac: Some("00:11:22:33:44:55".to_string()),
+            ..Default::default()
+        };
+        let (total, events) = journal.query(&filter, 1);
+        assert_eq!(total, 2);
+        assert_eq!(events[0].event, "offline");
+
+        let filter = EventFilter {
+            kind: Some(EventKind::Online),
+            start_ms: Some(1_500),
+            ..Default::default()
+        };
+        assert_eq!(journal.query(&filter, 10).0, 1);
+        assert_eq!(journal.events_after(1, 10).len(), 2);
+
+        // 重启后编号继续递增
+        let restored = EventJournal::from_parts(None, journal.events_after(0, 10), Some(Vec::new()));
+        assert_eq!(restored.record(event(EventKind::Online, "00:11:22:33:44:55", 4_000)), 4);
+    }
+
+    #[test]
+    fn test_mark_known_seeding() {
+        let journal = EventJournal::from_parts(None, Vec::new(), None);
+        assert!(!journal.mark_known([0, 1, 2, 3, 4, 5]));
+        journal.end_seeding();
+        assert!(!journal.mark_known([0, 1, 2, 3, 4, 5]));
+        assert!(journal.mark_known([0, 1, 2, 3, 4, 6]));
+    }
+
+    #[test]
+    fn test_security_filter() {
+        let journal = EventJournal::from_parts(None, Vec::new(), Some(Vec::new()));
+        journal.record(event(EventKind::Online, "00:11:22:33:44:55", 1_000));
+        journal.record(event(EventKind::DuplicateIp, "00:11:22:33:44:55", 2_000));
+        journal.record(event(EventKind::MacFlip, "00:11:22:33:44:66", 3_000));
+
+        let filter = EventFilter {
+            security_only: true,
+            ..Default::default()
+        };
+        let (total, events) = journal.query(&filter, 10);
+        assert_eq!(total, 2);
+        assert_eq!(events[0].event, "mac_flip");
+        assert!(!EventKind::Online.is_security());
+        assert_eq!(EventKind::parse("gateway_impersonation"), Some(EventKind::GatewayImpersonation));
+    }
+
+    #[test]
+    fn test_journal_persists_through_writer() {
+        let dir = std::env::temp_dir().join(format!("bandix-events-test-{}", std::process::id()));
+        let data_dir = dir.to_str().unwrap().to_string();
+
+        let journal = EventJournal::load(&data_dir);
+        journal.mark_known([0, 1, 2, 3, 4, 5]);
+        journal.end_seeding();
+        journal.record(event(EventKind::Online, "00:11:22:33:44:55", 1_000));
+        journal.record(event(EventKind::Offline, "00:11:22:33:44:55", 2_000));
+        journal.flush();
+
+        let restored = EventJournal::load(&data_dir);
+        assert_eq!(restored.last_id(), 2);
+        assert_eq!(restored.events_after(0, 10)[1].event, "offline");
+        // 已见过的设备与初始阶段状态也已写入
+        assert!(!restored.mark_known([0, 1, 2, 3, 4, 5]));
+        assert!(restored.mark_known([0, 1, 2, 3, 4, 6]));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
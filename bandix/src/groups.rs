@@ -0,0 +1,229 @@
+// 设备组：将设备归入命名的组（例如 "Kids"、"IoT"、"Work"）
+//
+// 组用于按组汇总长期流量，并为组内设备统一设置预定速率限制。组规则对组内每台设备分别生效，
+// 设备自身的预定规则优先于组规则。合并身份下的任一 MAC 在组内时，该身份的全部 MAC 都视为组成员。
+
+use crate::storage::groups::{DeviceGroup, GroupRateLimit};
+use crate::storage::traffic::TimeSlot;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Local};
+use std::sync::Mutex;
+
+// 组名的长度上限（字符数）
+const MAX_NAME_LEN: usize = 32;
+// 最多的组数
+const MAX_GROUPS: usize = 64;
+// 每个组最多的成员数
+const MAX_MEMBERS: usize = 256;
+// 每个组最多的速率限制规则数
+const MAX_RATE_LIMITS: usize = 32;
+
+/// 校验并规范化组名
+pub fn normalize_name(name: &str) -> Result<String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(anyhow!("group name must not be empty"));
+    }
+    if name.contains(char::is_control) {
+        return Err(anyhow!("group name must not contain control characters"));
+    }
+    if name.chars().count() > MAX_NAME_LEN {
+        return Err(anyhow!("group name is longer than {} characters", MAX_NAME_LEN));
+    }
+    Ok(name.to_string())
+}
+
+/// 设备组注册表，在 API、设备管理器与限速模块之间共享
+pub struct GroupRegistry {
+    data_dir: Option<String>,        // 为 None 时不持久化
+    groups: Mutex<Vec<DeviceGroup>>, // 按组名排序
+}
+
+impl GroupRegistry {
+    /// 从数据目录加载已保存的设备组
+    pub fn load(data_dir: &str) -> Self {
+        let groups = crate::storage::groups::load_groups(data_dir).unwrap_or_else(|e| {
+            log::warn!("Failed to load device groups: {}", e);
+            Vec::new()
+        });
+        Self::from_groups(Some(data_dir.to_string()), groups)
+    }
+
+    fn from_groups(data_dir: Option<String>, mut groups: Vec<DeviceGroup>) -> Self {
+        groups.sort_by_key(|g| g.name.to_lowercase());
+        Self {
+            data_dir,
+            groups: Mutex::new(groups),
+        }
+    }
+
+    fn persist(&self, groups: &[DeviceGroup]) {
+        if let Some(data_dir) = &self.data_dir {
+            if let Err(e) = crate::storage::groups::save_groups(data_dir, groups) {
+                log::warn!("Failed to save device groups: {}", e);
+            }
+        }
+    }
+
+    /// 所有设备组，按组名排序
+    pub fn groups(&self) -> Vec<DeviceGroup> {
+        self.groups.lock().unwrap().clone()
+    }
+
+    /// 按组名查找（不区分大小写）
+    pub fn get(&self, name: &str) -> Option<DeviceGroup> {
+        let groups = self.groups.lock().unwrap();
+        groups.iter().find(|g| g.name.eq_ignore_ascii_case(name.trim())).cloned()
+    }
+
+    /// 设置组成员，组不存在时创建
+    pub fn set_members(&self, name: &str, mut macs: Vec<[u8; 6]>) -> Result<()> {
+        let name = normalize_name(name)?;
+        macs.sort();
+        macs.dedup();
+        if macs.len() > MAX_MEMBERS {
+            return Err(anyhow!("a group can have at most {} devices", MAX_MEMBERS));
+        }
+
+        let mut groups = self.groups.lock().unwrap();
+        match groups.iter_mut().find(|g| g.name.eq_ignore_ascii_case(&name)) {
+            Some(group) => {
+                group.name = name;
+                group.macs = macs;
+            }
+            None => {
+                if groups.len() >= MAX_GROUPS {
+                    return Err(anyhow!("at most {} groups are allowed", MAX_GROUPS));
+                }
+                groups.push(DeviceGroup {
+                    name,
+                    macs,
+                    rate_limits: Vec::new(),
+                });
+                groups.sort_by_key(|g| g.name.to_lowercase());
+            }
+        }
+        self.persist(&groups);
+        Ok(())
+    }
+
+    /// 删除设备组及其规则
+    pub fn remove(&self, name: &str) -> Result<()> {
+        let mut groups = self.groups.lock().unwrap();
+        let before = groups.len();
+        groups.retain(|g| !g.name.eq_ignore_ascii_case(name.trim()));
+        if groups.len() == before {
+            return Err(anyhow!("group not found: {}", name));
+        }
+        self.persist(&groups);
+        Ok(())
+    }
+
+    /// 添加或替换组的速率限制规则，时间段相同的规则会被替换
+    pub fn set_rate_limit(&self, name: &str, rule: GroupRateLimit) -> Result<()> {
+        let mut groups = self.groups.lock().unwrap();
+        let group = groups
+            .iter_mut()
+            .find(|g| g.name.eq_ignore_ascii_case(name.trim()))
+            .ok_or_else(|| anyhow!("group not found: {}", name))?;
+        group.rate_limits.retain(|r| r.time_slot != rule.time_slot);
+        if group.rate_limits.len() >= MAX_RATE_LIMITS {
+            return Err(anyhow!("a group can have at most {} rate limit rules", MAX_RATE_LIMITS));
+        }
+        group.rate_limits.push(rule);
+        self.persist(&groups);
+        Ok(())
+    }
+
+    /// 删除组中指定时间段的速率限制规则
+    pub fn remove_rate_limit(&self, name: &str, time_slot: &TimeSlot) -> Result<()> {
+        let mut groups = self.groups.lock().unwrap();
+        let group = groups
+            .iter_mut()
+            .find(|g| g.name.eq_ignore_ascii_case(name.trim()))
+            .ok_or_else(|| anyhow!("group not found: {}", name))?;
+        group.rate_limits.retain(|r| r.time_slot != *time_slot);
+        self.persist(&groups);
+        Ok(())
+    }
+
+    /// 包含任一给定 MAC 的组名
+    pub fn groups_of(&self, macs: &[[u8; 6]]) -> Vec<String> {
+        let groups = self.groups.lock().unwrap();
+        groups
+            .iter()
+            .filter(|g| g.macs.iter().any(|m| macs.contains(m)))
+            .map(|g| g.name.clone())
+            .collect()
+    }
+
+    /// 给定 MAC 所在各组在 now 时刻生效的速率限制，多条规则匹配时取最严格的
+    pub fn current_rate_limit(&self, macs: &[[u8; 6]], now: &DateTime<Local>) -> Option<[u64; 2]> {
+        let groups = self.groups.lock().unwrap();
+        crate::storage::traffic::strictest_rate_limit(
+            groups
+                .iter()
+                .filter(|g| g.macs.iter().any(|m| macs.contains(m)))
+                .flat_map(|g| g.rate_limits.iter())
+                .filter(|r| r.time_slot.matches(now))
+                .map(|r| [r.wan_rx_rate_limit, r.wan_tx_rate_limit]),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PHONE: [u8; 6] = [0x00, 0x11, 0x22, 0x33, 0x44, 0x55];
+    const TABLET: [u8; 6] = [0x00, 0x11, 0x22, 0x33, 0x44, 0x66];
+
+    fn rule(rx: u64, tx: u64) -> GroupRateLimit {
+        GroupRateLimit {
+            time_slot: TimeSlot::all_time(),
+            wan_rx_rate_limit: rx,
+            wan_tx_rate_limit: tx,
+        }
+    }
+
+    #[test]
+    fn test_membership() {
+        let registry = GroupRegistry::from_groups(None, Vec::new());
+        registry.set_members("Kids", vec![PHONE, TABLET, PHONE]).unwrap();
+        registry.set_members("IoT", vec![TABLET]).unwrap();
+        assert_eq!(registry.get("kids").unwrap().macs, vec![PHONE, TABLET]);
+        assert_eq!(registry.groups_of(&[TABLET]), vec!["IoT".to_string(), "Kids".to_string()]);
+
+        // 组名不区分大小写，重新设置时替换成员
+        registry.set_members("KIDS", vec![PHONE]).unwrap();
+        assert_eq!(registry.groups().len(), 2);
+        assert_eq!(registry.groups_of(&[TABLET]), vec!["IoT".to_string()]);
+
+        assert!(registry.set_members(" ", vec![PHONE]).is_err());
+        registry.remove("iot").unwrap();
+        assert!(registry.remove("iot").is_err());
+    }
+
+    #[test]
+    fn test_rate_limits() {
+        let registry = GroupRegistry::from_groups(None, Vec::new());
+        registry.set_members("Kids", vec![PHONE]).unwrap();
+        registry.set_members("Work", vec![PHONE]).unwrap();
+        let now = Local::now();
+        assert_eq!(registry.current_rate_limit(&[PHONE], &now), None);
+
+        registry.set_rate_limit("Kids", rule(1000, 0)).unwrap();
+        registry.set_rate_limit("Work", rule(2000, 500)).unwrap();
+        assert_eq!(registry.current_rate_limit(&[PHONE], &now), Some([1000, 500]));
+        assert_eq!(registry.current_rate_limit(&[TABLET], &now), None);
+
+        // 同一时间段的规则被替换
+        registry.set_rate_limit("Kids", rule(3000, 0)).unwrap();
+        assert_eq!(registry.get("Kids").unwrap().rate_limits.len(), 1);
+        assert_eq!(registry.current_rate_limit(&[PHONE], &now), Some([2000, 500]));
+
+        registry.remove_rate_limit("Work", &TimeSlot::all_time()).unwrap();
+        assert_eq!(registry.current_rate_limit(&[PHONE], &now), Some([3000, 0]));
+        assert!(registry.set_rate_limit("Guests", rule(1, 1)).is_err());
+    }
+}
@@ -0,0 +1,199 @@
+// 设备地址与主机名历史：带首次/最后出现时间的时间线
+//
+// UnifiedDevice 中的 historical_ipv4 / historical_ipv6 只是没有时间戳的列表，也不会持久化。
+// 这里记录每个 MAC 用过的 IPv4、IPv6 地址和主机名以及各自出现的时间段，并持久化到数据目录，
+// 使过去的 DNS 与连接记录能够按当时的地址归属到正确的设备。
+
+use crate::storage::history::{HistoryEntry, HistoryKind};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+// 历史保留时长：90 天
+const RETENTION_MS: u64 = 90 * 24 * 3600 * 1000;
+// 每台设备最多保留的条目数
+const MAX_ENTRIES_PER_DEVICE: usize = 256;
+// 最短写盘间隔，减少对闪存的写入
+const SAVE_INTERVAL_MS: u64 = 5 * 60 * 1000;
+// 同一 IPv6 地址两次出现的间隔超过此值时记为新的时间段
+const IPV6_GAP_MS: u64 = 3600 * 1000;
+
+#[derive(Debug, Default)]
+struct HistoryState {
+    entries: HashMap<[u8; 6], Vec<HistoryEntry>>, // 按首次出现时间升序
+    dirty: bool,
+    last_save_ms: u64,
+}
+
+impl HistoryState {
+    fn observe(&mut self, mac: [u8; 6], kind: HistoryKind, value: &str, now_ms: u64) {
+        let entries = self.entries.entry(mac).or_default();
+        // IPv4 与主机名同一时间只有一个：与最近一段相同时延长。IPv6 可同时有多个地址：按地址查找，间隔过长时另起一段
+        let current = match kind {
+            HistoryKind::Ipv6 => entries
+                .iter_mut()
+                .rev()
+                .find(|e| e.kind == kind && e.value == value)
+                .filter(|e| now_ms.saturating_sub(e.last_seen_ms) <= IPV6_GAP_MS),
+            HistoryKind::Ipv4 | HistoryKind::Hostname => entries.iter_mut().rev().find(|e| e.kind == kind).filter(|e| e.value == value),
+        };
+        match current {
+            Some(entry) => entry.last_seen_ms = entry.last_seen_ms.max(now_ms),
+            None => {
+                entries.push(HistoryEntry {
+                    kind,
+                    value: value.to_string(),
+                    first_seen_ms: now_ms,
+                    last_seen_ms: now_ms,
+                });
+                if entries.len() > MAX_ENTRIES_PER_DEVICE {
+                    entries.remove(0);
+                }
+            }
+        }
+        self.dirty = true;
+    }
+
+    fn prune(&mut self, now_ms: u64) {
+        let cutoff = now_ms.saturating_sub(RETENTION_MS);
+        for entries in self.entries.values_mut() {
+            let before = entries.len();
+            entries.retain(|e| e.last_seen_ms >= cutoff);
+            self.dirty |= entries.len() != before;
+        }
+        self.entries.retain(|_, entries| !entries.is_empty());
+    }
+}
+
+/// 设备地址与主机名历史，由设备管理器在刷新邻居表与收到 DHCP 租约时更新
+pub struct DeviceHistory {
+    data_dir: Option<String>, // 为 None 时不持久化
+    state: Mutex<HistoryState>,
+}
+
+impl DeviceHistory {
+    /// 从数据目录加载已保存的历史
+    pub fn load(data_dir: &str) -> Self {
+        let entries = crate::storage::history::load_history(data_dir).unwrap_or_else(|e| {
+            log::warn!("Failed to load device history: {}", e);
+            Vec::new()
+        });
+        Self::from_entries(Some(data_dir.to_string()), entries)
+    }
+
+    fn from_entries(data_dir: Option<String>, entries: Vec<([u8; 6], HistoryEntry)>) -> Self {
+        let mut state = HistoryState::default();
+        for (mac, entry) in entries {
+            state.entries.entry(mac).or_default().push(entry);
+        }
+        for entries in state.entries.values_mut() {
+            entries.sort_by_key(|e| e.first_seen_ms);
+        }
+        Self {
+            data_dir,
+            state: Mutex::new(state),
+        }
+    }
+
+    fn persist(&self, state: &mut HistoryState, now_ms: u64) {
+        if let Some(data_dir) = &self.data_dir {
+            let sorted: BTreeMap<_, _> = state.entries.iter().collect();
+            let entries = sorted
+                .into_iter()
+                .flat_map(|(mac, entries)| entries.iter().map(move |e| (*mac, e)));
+            if let Err(e) = crate::storage::history::save_history(data_dir, entries) {
+                log::warn!("Failed to save device history: {}", e);
+                return;
+            }
+        }
+        state.dirty = false;
+        state.last_save_ms = now_ms;
+    }
+
+    /// 记录一批观察到的地址与主机名，并按间隔写盘
+    pub fn record(&self, observations: Vec<([u8; 6], HistoryKind, String)>, now_ms: u64) {
+        let mut state = self.state.lock().unwrap();
+        for (mac, kind, value) in observations {
+            // 值会写入按空格分隔的文本文件，跳过含控制字符的主机名
+            if value.is_empty() || value.contains(char::is_control) {
+                continue;
+            }
+            state.observe(mac, kind, &value, now_ms);
+        }
+        state.prune(now_ms);
+
+        if state.dirty && now_ms.saturating_sub(state.last_save_ms) >= SAVE_INTERVAL_MS {
+            self.persist(&mut state, now_ms);
+        }
+    }
+
+    /// 立即写盘，用于退出前
+    pub fn flush(&self, now_ms: u64) {
+        let mut state = self.state.lock().unwrap();
+        if state.dirty {
+            self.persist(&mut state, now_ms);
+        }
+    }
+
+    /// 某设备在 [start_ms, end_ms) 内出现过的地址与主机名，按首次出现时间升序
+    pub fn entries(&self, mac: &[u8; 6], start_ms: u64, end_ms: u64) -> Vec<HistoryEntry> {
+        let state = self.state.lock().unwrap();
+        state
+            .entries
+            .get(mac)
+            .map(|entries| {
+                entries
+                    .iter()
+                    .filter(|e| e.first_seen_ms < end_ms && e.last_seen_ms >= start_ms)
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAC: [u8; 6] = [0x00, 0x11, 0x22, 0x33, 0x44, 0x55];
+
+    fn values(history: &DeviceHistory, kind: HistoryKind) -> Vec<(String, u64, u64)> {
+        history
+            .entries(&MAC, 0, u64::MAX)
+            .into_iter()
+            .filter(|e| e.kind == kind)
+            .map(|e| (e.value, e.first_seen_ms, e.last_seen_ms))
+            .collect()
+    }
+
+    #[test]
+    fn test_timeline() {
+        let history = DeviceHistory::from_entries(None, Vec::new());
+        let ip = |v: &str| (MAC, HistoryKind::Ipv4, v.to_string());
+        history.record(vec![ip("192.168.1.10"), (MAC, HistoryKind::Hostname, "tv".to_string())], 1_000);
+        history.record(vec![ip("192.168.1.10")], 2_000);
+        history.record(vec![ip("192.168.1.20")], 3_000);
+        // 换回原地址时另起一段
+        history.record(vec![ip("192.168.1.10")], 4_000);
+        assert_eq!(
+            values(&history, HistoryKind::Ipv4),
+            vec![
+                ("192.168.1.10".to_string(), 1_000, 2_000),
+                ("192.168.1.20".to_string(), 3_000, 3_000),
+                ("192.168.1.10".to_string(), 4_000, 4_000),
+            ]
+        );
+        assert_eq!(history.entries(&MAC, 2_500, 3_500).len(), 1);
+
+        // IPv6 地址可并存，间隔过长时另起一段
+        let v6 = |v: &str| (MAC, HistoryKind::Ipv6, v.to_string());
+        history.record(vec![v6("fd00::1"), v6("fd00::2")], 5_000);
+        history.record(vec![v6("fd00::1"), v6("fd00::2")], 6_000);
+        history.record(vec![v6("fd00::1")], 6_000 + IPV6_GAP_MS + 1);
+        assert_eq!(values(&history, HistoryKind::Ipv6).len(), 3);
+
+        // 含控制字符的主机名被忽略
+        history.record(vec![(MAC, HistoryKind::Hostname, "bad\nname".to_string())], 7_000);
+        assert_eq!(values(&history, HistoryKind::Hostname), vec![("tv".to_string(), 1_000, 1_000)]);
+    }
+}
@@ -0,0 +1,321 @@
+// 设备身份：将同一台物理设备使用过的多个 MAC 关联为一个逻辑设备
+//
+// 手机等设备会轮换随机化 MAC，每个新地址都会产生新的设备与 .ring 文件，历史因此被割裂。
+// 随机化地址在 DHCP 中携带的客户端标识（选项 61）或主机名与此前某个地址相同时，自动关联到该地址所属的身份；
+// 也可以通过 API 手动合并或拆分。每个身份以一个主 MAC 标识，长期流量查询按身份聚合其全部 MAC 的历史。
+
+use crate::storage::identity::{IdentitySnapshot, LinkSource};
+use anyhow::{anyhow, Result};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Mutex;
+
+// 多台设备可能共用的默认主机名，不作为关联依据
+const GENERIC_HOSTNAMES: &[&str] = &[
+    "android",
+    "iphone",
+    "ipad",
+    "galaxy",
+    "localhost",
+    "unknown",
+    "espressif",
+    "macbook",
+    "macbook-air",
+    "macbook-pro",
+];
+// 最多记录的标识数
+const MAX_KEYS: usize = 4096;
+// 只有标识变化时的最短写盘间隔，减少对闪存的写入；关联变化立即写盘
+const SAVE_INTERVAL_MS: u64 = 5 * 60 * 1000;
+
+/// 一个合并后的身份
+#[derive(Debug, Clone, PartialEq)]
+pub struct IdentityGroup {
+    pub primary: [u8; 6],
+    pub members: Vec<([u8; 6], LinkSource)>, // 关联到主 MAC 的其他 MAC
+}
+
+#[derive(Debug, Default)]
+struct IdentityState {
+    links: HashMap<[u8; 6], ([u8; 6], LinkSource)>, // 成员 MAC -> (主 MAC, 依据)；主 MAC 本身不出现在键中
+    keys: HashMap<String, [u8; 6]>,                 // 标识 -> 最近使用该标识的 MAC
+    detached: HashSet<[u8; 6]>,                     // 手动拆分过的 MAC，不再自动关联
+    dirty: bool,                                    // 有尚未写盘的变化
+    last_save_ms: u64,
+}
+
+impl IdentityState {
+    fn primary_of(&self, mac: &[u8; 6]) -> [u8; 6] {
+        self.links.get(mac).map(|(primary, _)| *primary).unwrap_or(*mac)
+    }
+
+    fn members_of(&self, primary: &[u8; 6]) -> Vec<[u8; 6]> {
+        let mut members: Vec<[u8; 6]> = self.links.iter().filter(|(_, (p, _))| p == primary).map(|(m, _)| *m).collect();
+        members.sort();
+        members
+    }
+
+    /// 将 mac（主 MAC）及其全部成员关联到 primary
+    fn link_group(&mut self, mac: [u8; 6], primary: [u8; 6], source: LinkSource) {
+        for member in self.members_of(&mac) {
+            if let Some(link) = self.links.get_mut(&member) {
+                link.0 = primary;
+            }
+        }
+        self.links.insert(mac, (primary, source));
+        self.detached.remove(&mac);
+    }
+
+    fn snapshot(&self) -> IdentitySnapshot {
+        let links: BTreeMap<_, _> = self.links.iter().collect();
+        let keys: BTreeMap<_, _> = self.keys.iter().collect();
+        let mut detached: Vec<[u8; 6]> = self.detached.iter().copied().collect();
+        detached.sort();
+        IdentitySnapshot {
+            links: links.into_iter().map(|(m, (p, s))| (*m, *p, *s)).collect(),
+            keys: keys.into_iter().map(|(k, m)| (k.clone(), *m)).collect(),
+            detached,
+        }
+    }
+}
+
+/// 设备身份注册表，在 DHCP 监听、设备管理器与 API 之间共享
+pub struct IdentityRegistry {
+    data_dir: Option<String>, // 为 None 时不持久化
+    state: Mutex<IdentityState>,
+}
+
+impl IdentityRegistry {
+    /// 从数据目录加载已保存的身份
+    pub fn load(data_dir: &str) -> Self {
+        let snapshot = crate::storage::identity::load_identities(data_dir).unwrap_or_else(|e| {
+            log::warn!("Failed to load device identities: {}", e);
+            IdentitySnapshot::default()
+        });
+        Self::from_snapshot(Some(data_dir.to_string()), snapshot)
+    }
+
+    fn from_snapshot(data_dir: Option<String>, snapshot: IdentitySnapshot) -> Self {
+        let state = IdentityState {
+            links: snapshot.links.into_iter().map(|(m, p, s)| (m, (p, s))).collect(),
+            keys: snapshot.keys.into_iter().collect(),
+            detached: snapshot.detached.into_iter().collect(),
+            ..Default::default()
+        };
+        Self {
+            data_dir,
+            state: Mutex::new(state),
+        }
+    }
+
+    fn persist(&self, state: &mut IdentityState) {
+        if let Some(data_dir) = &self.data_dir {
+            if let Err(e) = crate::storage::identity::save_identities(data_dir, &state.snapshot()) {
+                log::warn!("Failed to save device identities: {}", e);
+                return;
+            }
+        }
+        state.dirty = false;
+    }
+
+    /// 立即写盘，用于退出前
+    pub fn flush(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.dirty {
+            self.persist(&mut state);
+        }
+    }
+
+    /// 记录客户端 DHCP 报文中的标识；随机化地址与此前某个 MAC 标识相同时自动关联，返回关联到的主 MAC
+    /// 新的关联立即写盘，只有标识变化时按间隔写盘
+    pub fn observe_dhcp(&self, mac: [u8; 6], client_id: &[u8], hostname: Option<&str>, now_ms: u64) -> Option<[u8; 6]> {
+        let mut identifiers = Vec::new();
+        if !client_id.is_empty() {
+            let hex: String = client_id.iter().map(|b| format!("{:02x}", b)).collect();
+            identifiers.push((format!("client_id:{}", hex), LinkSource::ClientId));
+        }
+        if let Some(hostname) = hostname.map(|h| h.trim().to_ascii_lowercase()) {
+            if !hostname.is_empty() && !hostname.contains(char::is_whitespace) && !GENERIC_HOSTNAMES.contains(&hostname.as_str()) {
+                identifiers.push((format!("hostname:{}", hostname), LinkSource::Hostname));
+            }
+        }
+        if identifiers.is_empty() {
+            return None;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let mut linked = None;
+        for (key, source) in identifiers {
+            if !state.keys.contains_key(&key) && state.keys.len() >= MAX_KEYS {
+                continue;
+            }
+            let previous = match state.keys.insert(key, mac) {
+                Some(previous) if previous != mac => previous,
+                Some(_) => continue,
+                None => {
+                    state.dirty = true;
+                    continue;
+                }
+            };
+            state.dirty = true;
+
+            // 只自动关联随机化地址，且跳过已关联或被手动拆分过的地址
+            if linked.is_some() || !crate::oui::is_randomized_mac(&mac) || state.detached.contains(&mac) || state.links.contains_key(&mac) {
+                continue;
+            }
+            let primary = state.primary_of(&previous);
+            if primary == mac {
+                continue;
+            }
+            state.link_group(mac, primary, source);
+            linked = Some(primary);
+        }
+
+        if state.dirty && (linked.is_some() || now_ms.saturating_sub(state.last_save_ms) >= SAVE_INTERVAL_MS) {
+            self.persist(&mut state);
+            state.last_save_ms = now_ms;
+        }
+        linked
+    }
+
+    /// 某个 MAC 所属身份的主 MAC（未关联时为自身）
+    pub fn primary_of(&self, mac: &[u8; 6]) -> [u8; 6] {
+        self.state.lock().unwrap().primary_of(mac)
+    }
+
+    /// 某个 MAC 所属身份的全部 MAC，主 MAC 在前
+    pub fn group_macs(&self, mac: &[u8; 6]) -> Vec<[u8; 6]> {
+        let state = self.state.lock().unwrap();
+        let primary = state.primary_of(mac);
+        let mut macs = vec![primary];
+        macs.extend(state.members_of(&primary));
+        macs
+    }
+
+    /// 所有包含多个 MAC 的身份
+    pub fn groups(&self) -> Vec<IdentityGroup> {
+        let state = self.state.lock().unwrap();
+        let mut groups: BTreeMap<[u8; 6], Vec<([u8; 6], LinkSource)>> = BTreeMap::new();
+        for (member, (primary, source)) in state.links.iter() {
+            groups.entry(*primary).or_default().push((*member, *source));
+        }
+        groups
+            .into_iter()
+            .map(|(primary, mut members)| {
+                members.sort_by_key(|(mac, _)| *mac);
+                IdentityGroup { primary, members }
+            })
+            .collect()
+    }
+
+    /// 手动将 mac 所属的身份合并到 into 所属的身份，返回合并后的主 MAC
+    pub fn merge(&self, mac: [u8; 6], into: [u8; 6]) -> Result<[u8; 6]> {
+        if mac == into {
+            return Err(anyhow!("Cannot merge a device into itself"));
+        }
+        let mut state = self.state.lock().unwrap();
+        let primary = state.primary_of(&into);
+        let source_primary = state.primary_of(&mac);
+        if source_primary != primary {
+            state.link_group(source_primary, primary, LinkSource::Manual);
+            self.persist(&mut state);
+        }
+        Ok(primary)
+    }
+
+    /// 将 mac 从其所属身份中拆分出来，之后不再自动关联
+    /// 拆分主 MAC 时，剩余成员中地址最小的成为新的主 MAC
+    pub fn split(&self, mac: [u8; 6]) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.links.remove(&mac).is_none() {
+            let members = state.members_of(&mac);
+            let (new_primary, rest) = members
+                .split_first()
+                .ok_or_else(|| anyhow!("Device is not part of a merged identity"))?;
+            state.links.remove(new_primary);
+            for member in rest {
+                if let Some(link) = state.links.get_mut(member) {
+                    link.0 = *new_primary;
+                }
+            }
+        }
+        state.detached.insert(mac);
+        self.persist(&mut state);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PHONE: [u8; 6] = [0x00, 0x11, 0x22, 0x33, 0x44, 0x55];
+    const RANDOM_A: [u8; 6] = [0xda, 0x00, 0x00, 0x00, 0x00, 0x01];
+    const RANDOM_B: [u8; 6] = [0xda, 0x00, 0x00, 0x00, 0x00, 0x02];
+
+    #[test]
+    fn test_auto_link_randomized_macs() {
+        let registry = IdentityRegistry::from_snapshot(None, IdentitySnapshot::default());
+        assert_eq!(registry.observe_dhcp(PHONE, &[1, 2, 3], Some("Johns-Phone"), 0), None);
+        // 随机化地址使用相同主机名时关联到原设备
+        assert_eq!(registry.observe_dhcp(RANDOM_A, &[9, 9], Some("johns-phone"), 0), Some(PHONE));
+        // 相同客户端标识
+        assert_eq!(registry.observe_dhcp(RANDOM_B, &[9, 9], None, 0), Some(PHONE));
+        assert_eq!(registry.group_macs(&RANDOM_B), vec![PHONE, RANDOM_A, RANDOM_B]);
+
+        // 通用主机名与非随机化地址不会自动关联
+        let other = [0x00, 0x11, 0x22, 0x33, 0x44, 0x66];
+        assert_eq!(registry.observe_dhcp(other, &[], Some("johns-phone"), 0), None);
+        let random_c = [0xda, 0x00, 0x00, 0x00, 0x00, 0x03];
+        registry.observe_dhcp(other, &[], Some("iPhone"), 0);
+        assert_eq!(registry.observe_dhcp(random_c, &[], Some("iPhone"), 0), None);
+    }
+
+    #[test]
+    fn test_merge_and_split() {
+        let registry = IdentityRegistry::from_snapshot(None, IdentitySnapshot::default());
+        assert!(registry.merge(PHONE, PHONE).is_err());
+        assert!(registry.split(PHONE).is_err());
+
+        assert_eq!(registry.merge(RANDOM_A, PHONE).unwrap(), PHONE);
+        assert_eq!(registry.merge(RANDOM_B, RANDOM_A).unwrap(), PHONE);
+        assert_eq!(registry.groups().len(), 1);
+
+        // 拆分主 MAC：剩余成员中地址最小的成为新的主 MAC
+        registry.split(PHONE).unwrap();
+        assert_eq!(registry.group_macs(&RANDOM_B), vec![RANDOM_A, RANDOM_B]);
+        assert_eq!(registry.group_macs(&PHONE), vec![PHONE]);
+
+        // 拆分过的地址不再自动关联
+        registry.split(RANDOM_B).unwrap();
+        registry.observe_dhcp(RANDOM_A, &[], Some("tablet"), 0);
+        assert_eq!(registry.observe_dhcp(RANDOM_B, &[], Some("tablet"), 0), None);
+        assert!(registry.groups().is_empty());
+    }
+
+    #[test]
+    fn test_saves_are_batched() {
+        let dir = std::env::temp_dir().join(format!("bandix-identity-test-{}", std::process::id()));
+        let data_dir = dir.to_str().unwrap().to_string();
+        let saved_keys = || crate::storage::identity::load_identities(&data_dir).unwrap().keys.len();
+        let registry = IdentityRegistry::load(&data_dir);
+        let t0 = SAVE_INTERVAL_MS;
+
+        // 首次写盘后，只有标识变化时在间隔内不再写盘
+        registry.observe_dhcp(PHONE, &[1, 2, 3], None, t0);
+        assert_eq!(saved_keys(), 1);
+        registry.observe_dhcp(PHONE, &[], Some("johns-phone"), t0 + 1_000);
+        assert_eq!(saved_keys(), 1);
+
+        // 新的关联立即写盘
+        assert_eq!(registry.observe_dhcp(RANDOM_A, &[1, 2, 3], None, t0 + 2_000), Some(PHONE));
+        assert_eq!(saved_keys(), 2);
+        assert_eq!(IdentityRegistry::load(&data_dir).primary_of(&RANDOM_A), PHONE);
+
+        registry.observe_dhcp(RANDOM_B, &[4, 5], None, t0 + 3_000);
+        assert_eq!(saved_keys(), 2);
+        registry.flush();
+        assert_eq!(saved_keys(), 3);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
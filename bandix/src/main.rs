@@ -22,11 +22,22 @@ mod api;
 mod command;
 mod device;
 mod ebpf;
+mod events;
+mod groups;
+mod history;
+mod identity;
+mod meta;
 mod monitor;
+mod neighbor;
+mod netlink;
+mod oui;
+mod presence;
+mod privacy;
 mod storage;
 mod system;
 mod utils;
 mod web;
+mod wireless;
 use clap::Parser;
 use command::{run, Options};
 
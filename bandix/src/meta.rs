@@ -0,0 +1,154 @@
+// 设备元数据：所有者、标签、备注与图标
+//
+// 主机名绑定每个 MAC 只有一个字符串。这里为设备保存结构化的元数据，通过 /api/devices/{mac}/meta 编辑，
+// 并可在设备列表中按标签筛选。设备类型的手动设置仍由 DeviceClassifier 保存，元数据接口只是另一个编辑入口。
+
+use crate::storage::meta::DeviceMeta;
+use anyhow::{anyhow, Result};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+// 各字段的长度上限（字符数）
+const MAX_OWNER_LEN: usize = 64;
+const MAX_ICON_LEN: usize = 64;
+const MAX_NOTES_LEN: usize = 1024;
+const MAX_TAG_LEN: usize = 32;
+// 每台设备最多的标签数
+const MAX_TAGS: usize = 16;
+
+/// 校验并规范化元数据：去掉首尾空白，标签去重（不区分大小写）并丢弃空标签
+pub fn normalize(meta: DeviceMeta) -> Result<DeviceMeta> {
+    let owner = check_text("owner", &meta.owner, MAX_OWNER_LEN)?;
+    let icon = check_text("icon", &meta.icon, MAX_ICON_LEN)?;
+    let notes = meta.notes.trim().to_string();
+    if notes.chars().count() > MAX_NOTES_LEN {
+        return Err(anyhow!("notes is longer than {} characters", MAX_NOTES_LEN));
+    }
+
+    let mut tags: Vec<String> = Vec::new();
+    for tag in &meta.tags {
+        let tag = check_text("tag", tag, MAX_TAG_LEN)?;
+        if !tag.is_empty() && !tags.iter().any(|t| t.eq_ignore_ascii_case(&tag)) {
+            tags.push(tag);
+        }
+    }
+    if tags.len() > MAX_TAGS {
+        return Err(anyhow!("at most {} tags are allowed", MAX_TAGS));
+    }
+
+    Ok(DeviceMeta { owner, tags, notes, icon })
+}
+
+fn check_text(field: &str, value: &str, max_len: usize) -> Result<String> {
+    let value = value.trim();
+    if value.contains(char::is_control) {
+        return Err(anyhow!("{} must not contain control characters", field));
+    }
+    if value.chars().count() > max_len {
+        return Err(anyhow!("{} is longer than {} characters", field, max_len));
+    }
+    Ok(value.to_string())
+}
+
+/// 设备元数据存储，在 API 与设备管理器之间共享
+pub struct DeviceMetaStore {
+    data_dir: Option<String>, // 为 None 时不持久化
+    entries: Mutex<HashMap<[u8; 6], DeviceMeta>>,
+}
+
+impl DeviceMetaStore {
+    /// 从数据目录加载已保存的元数据
+    pub fn load(data_dir: &str) -> Self {
+        let entries = crate::storage::meta::load_device_meta(data_dir).unwrap_or_else(|e| {
+            log::warn!("Failed to load device metadata: {}", e);
+            Vec::new()
+        });
+        Self::from_entries(Some(data_dir.to_string()), entries)
+    }
+
+    fn from_entries(data_dir: Option<String>, entries: Vec<([u8; 6], DeviceMeta)>) -> Self {
+        Self {
+            data_dir,
+            entries: Mutex::new(entries.into_iter().filter(|(_, meta)| !meta.is_empty()).collect()),
+        }
+    }
+
+    pub fn get(&self, mac: &[u8; 6]) -> Option<DeviceMeta> {
+        self.entries.lock().unwrap().get(mac).cloned()
+    }
+
+    /// 设置设备的元数据并立即写盘，元数据为空时删除该设备的条目
+    /// 调用方应先用 normalize 校验
+    pub fn set(&self, mac: [u8; 6], meta: DeviceMeta) -> Result<()> {
+        let mut entries = self.entries.lock().unwrap();
+        if meta.is_empty() {
+            entries.remove(&mac);
+        } else {
+            entries.insert(mac, meta);
+        }
+
+        if let Some(data_dir) = &self.data_dir {
+            let sorted: BTreeMap<_, _> = entries.iter().collect();
+            crate::storage::meta::save_device_meta(data_dir, sorted.into_iter().map(|(mac, meta)| (*mac, meta)))?;
+        }
+        Ok(())
+    }
+
+    /// 设备是否带有指定标签（不区分大小写）
+    pub fn has_tag(&self, mac: &[u8; 6], tag: &str) -> bool {
+        let tag = tag.trim();
+        let entries = self.entries.lock().unwrap();
+        match entries.get(mac) {
+            Some(meta) => meta.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAC: [u8; 6] = [0x00, 0x11, 0x22, 0x33, 0x44, 0x55];
+
+    #[test]
+    fn test_normalize() {
+        let meta = normalize(DeviceMeta {
+            owner: " Alice ".to_string(),
+            tags: vec!["Kids".to_string(), "kids".to_string(), " ".to_string(), "tablet".to_string()],
+            notes: "bedroom\n".to_string(),
+            icon: "tablet".to_string(),
+        })
+        .unwrap();
+        assert_eq!(meta.owner, "Alice");
+        assert_eq!(meta.tags, vec!["Kids".to_string(), "tablet".to_string()]);
+        assert_eq!(meta.notes, "bedroom");
+
+        assert!(normalize(DeviceMeta {
+            owner: "a\tb".to_string(),
+            ..Default::default()
+        })
+        .is_err());
+        assert!(normalize(DeviceMeta {
+            tags: (0..=MAX_TAGS).map(|i| i.to_string()).collect(),
+            ..Default::default()
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn test_tags() {
+        let store = DeviceMetaStore::from_entries(None, Vec::new());
+        let meta = DeviceMeta {
+            tags: vec!["IoT".to_string()],
+            ..Default::default()
+        };
+        store.set(MAC, meta).unwrap();
+        assert!(store.has_tag(&MAC, "iot"));
+        assert!(!store.has_tag(&MAC, "kids"));
+
+        // 清空后删除条目
+        store.set(MAC, DeviceMeta::default()).unwrap();
+        assert!(store.get(&MAC).is_none());
+    }
+}
@@ -0,0 +1,391 @@
+use crate::command::Options;
+use crate::monitor::DnsQueryRecord;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+// 内存中保留的告警数
+const MAX_ALERTS: usize = 1000;
+// TXT / NXDOMAIN 计数的滑动窗口
+const RATE_WINDOW_MS: u64 = 60_000;
+// 同一设备、同一类型、同一域名的告警冷却时间
+const ALERT_COOLDOWN_MS: u64 = 300_000;
+// 参与熵计算的最短标签长度，短标签的熵值没有意义
+const MIN_ENTROPY_LABEL_LEN: usize = 12;
+
+/// DNS 告警类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DnsAlertKind {
+    HighEntropy,   // 随机样式的标签（DGA）
+    LongName,      // 超长查询名（隧道）
+    TxtBurst,      // TXT 查询过多（隧道）
+    NxdomainBurst, // NXDOMAIN 突发（DGA 探测）
+}
+
+impl DnsAlertKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DnsAlertKind::HighEntropy => "high_entropy",
+            DnsAlertKind::LongName => "long_name",
+            DnsAlertKind::TxtBurst => "txt_burst",
+            DnsAlertKind::NxdomainBurst => "nxdomain_burst",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "high_entropy" => Some(DnsAlertKind::HighEntropy),
+            "long_name" => Some(DnsAlertKind::LongName),
+            "txt_burst" => Some(DnsAlertKind::TxtBurst),
+            "nxdomain_burst" => Some(DnsAlertKind::NxdomainBurst),
+            _ => None,
+        }
+    }
+}
+
+/// 检测阈值（来自命令行参数）
+#[derive(Debug, Clone, Copy)]
+pub struct DnsAlertThresholds {
+    pub entropy: f64,               // 标签香农熵（bit/字符）
+    pub qname_length: usize,        // 查询名长度
+    pub txt_per_minute: usize,      // 每设备每分钟 TXT 查询数
+    pub nxdomain_per_minute: usize, // 每设备每分钟 NXDOMAIN 响应数
+}
+
+impl DnsAlertThresholds {
+    pub fn from_options(options: &Options) -> Self {
+        Self {
+            entropy: options.dns_alert_entropy(),
+            qname_length: options.dns_alert_qname_length(),
+            txt_per_minute: options.dns_alert_txt_per_minute(),
+            nxdomain_per_minute: options.dns_alert_nxdomain_per_minute(),
+        }
+    }
+}
+
+/// 一条 DNS 告警
+#[derive(Debug, Clone)]
+pub struct DnsAlert {
+    pub id: u64,
+    pub ts_ms: u64,
+    pub kind: DnsAlertKind,
+    pub device_mac: String,
+    pub device_ip: String,
+    pub device_name: String,
+    pub domain: String,
+    pub detail: String,
+    pub value: f64,     // 触发时的测量值
+    pub threshold: f64, // 对应阈值
+}
+
+#[derive(Default)]
+struct DeviceWindow {
+    txt: VecDeque<u64>,
+    nxdomain: VecDeque<u64>,
+}
+
+/// 基于 DnsQueryRecord 流的可疑行为检测
+/// - 查询：高熵标签、超长查询名、TXT 查询速率
+/// - 响应：NXDOMAIN 速率
+pub struct DnsAlertDetector {
+    thresholds: DnsAlertThresholds,
+    alerts: Mutex<VecDeque<DnsAlert>>,
+    windows: Mutex<HashMap<String, DeviceWindow>>,
+    // (设备, 类型, 域名) -> 上次告警时间，用于冷却
+    last_alert: Mutex<HashMap<(String, DnsAlertKind, String), u64>>,
+    pending_events: Mutex<Vec<DnsAlert>>,
+    next_id: AtomicU64,
+}
+
+impl DnsAlertDetector {
+    pub fn new(thresholds: DnsAlertThresholds) -> Self {
+        Self {
+            thresholds,
+            alerts: Mutex::new(VecDeque::new()),
+            windows: Mutex::new(HashMap::new()),
+            last_alert: Mutex::new(HashMap::new()),
+            pending_events: Mutex::new(Vec::new()),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    pub fn thresholds(&self) -> DnsAlertThresholds {
+        self.thresholds
+    }
+
+    /// 检查一条新记录
+    pub fn observe(&self, record: &DnsQueryRecord, now_ms: u64) {
+        let device_ip = if record.is_query {
+            &record.source_ip
+        } else {
+            &record.destination_ip
+        };
+        // 未知 MAC 的设备按 IP 统计
+        let device_key = if record.device_mac.is_empty() {
+            device_ip.clone()
+        } else {
+            record.device_mac.clone()
+        };
+        let domain = record.domain.trim_end_matches('.').to_lowercase();
+
+        if record.is_query {
+            if !domain.ends_with(".arpa") {
+                if let Some((label, entropy)) = max_label_entropy(&domain) {
+                    if entropy >= self.thresholds.entropy {
+                        self.raise(
+                            record,
+                            &device_key,
+                            device_ip,
+                            DnsAlertKind::HighEntropy,
+                            base_domain(&domain),
+                            format!("label '{}' entropy {:.2}", label, entropy),
+                            entropy,
+                            self.thresholds.entropy,
+                            now_ms,
+                        );
+                    }
+                }
+            }
+
+            if domain.len() >= self.thresholds.qname_length {
+                self.raise(
+                    record,
+                    &device_key,
+                    device_ip,
+                    DnsAlertKind::LongName,
+                    base_domain(&domain),
+                    format!("query name length {}", domain.len()),
+                    domain.len() as f64,
+                    self.thresholds.qname_length as f64,
+                    now_ms,
+                );
+            }
+
+            if record.query_type == "TXT" {
+                let count = self.count_in_window(&device_key, now_ms, |w| &mut w.txt);
+                if count >= self.thresholds.txt_per_minute {
+                    self.raise(
+                        record,
+                        &device_key,
+                        device_ip,
+                        DnsAlertKind::TxtBurst,
+                        String::new(),
+                        format!("{} TXT queries in the last minute", count),
+                        count as f64,
+                        self.thresholds.txt_per_minute as f64,
+                        now_ms,
+                    );
+                }
+            }
+        } else if record.response_code == "Domain not found" {
+            let count = self.count_in_window(&device_key, now_ms, |w| &mut w.nxdomain);
+            if count >= self.thresholds.nxdomain_per_minute {
+                self.raise(
+                    record,
+                    &device_key,
+                    device_ip,
+                    DnsAlertKind::NxdomainBurst,
+                    String::new(),
+                    format!("{} NXDOMAIN responses in the last minute", count),
+                    count as f64,
+                    self.thresholds.nxdomain_per_minute as f64,
+                    now_ms,
+                );
+            }
+        }
+    }
+
+    /// 记录一次事件并返回窗口内的次数
+    fn count_in_window<F>(&self, device_key: &str, now_ms: u64, select: F) -> usize
+    where
+        F: Fn(&mut DeviceWindow) -> &mut VecDeque<u64>,
+    {
+        let mut windows = self.windows.lock().unwrap();
+        let window = select(windows.entry(device_key.to_string()).or_default());
+        window.push_back(now_ms);
+        while let Some(&front) = window.front() {
+            if now_ms.saturating_sub(front) >= RATE_WINDOW_MS {
+                window.pop_front();
+            } else {
+                break;
+            }
+        }
+        window.len()
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn raise(
+        &self,
+        record: &DnsQueryRecord,
+        device_key: &str,
+        device_ip: &str,
+        kind: DnsAlertKind,
+        domain: String,
+        detail: String,
+        value: f64,
+        threshold: f64,
+        now_ms: u64,
+    ) {
+        {
+            let mut last_alert = self.last_alert.lock().unwrap();
+            let key = (device_key.to_string(), kind, domain.clone());
+            if let Some(last) = last_alert.get(&key) {
+                if now_ms.saturating_sub(*last) < ALERT_COOLDOWN_MS {
+                    return;
+                }
+            }
+            last_alert.insert(key, now_ms);
+            last_alert.retain(|_, ts| now_ms.saturating_sub(*ts) < ALERT_COOLDOWN_MS);
+        }
+
+        let alert = DnsAlert {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            ts_ms: now_ms,
+            kind,
+            device_mac: record.device_mac.clone(),
+            device_ip: device_ip.to_string(),
+            device_name: record.device_name.clone(),
+            domain: if domain.is_empty() { record.domain.clone() } else { domain },
+            detail,
+            value,
+            threshold,
+        };
+        log::info!(
+            "DNS alert [{}] device={} domain={} {}",
+            alert.kind.as_str(),
+            device_key,
+            alert.domain,
+            alert.detail
+        );
+
+        {
+            let mut alerts = self.alerts.lock().unwrap();
+            alerts.push_back(alert.clone());
+            while alerts.len() > MAX_ALERTS {
+                alerts.pop_front();
+            }
+        }
+        self.pending_events.lock().unwrap().push(alert);
+    }
+
+    /// 全部告警（最新的在前）
+    pub fn alerts(&self) -> Vec<DnsAlert> {
+        self.alerts.lock().unwrap().iter().rev().cloned().collect()
+    }
+
+    /// 取出待发送的事件
+    pub fn take_events(&self) -> Vec<DnsAlert> {
+        std::mem::take(&mut *self.pending_events.lock().unwrap())
+    }
+}
+
+/// 除顶级域外熵值最高的标签
+fn max_label_entropy(domain: &str) -> Option<(String, f64)> {
+    let labels: Vec<&str> = domain.split('.').collect();
+    if labels.len() < 2 {
+        return None;
+    }
+    labels[..labels.len() - 1]
+        .iter()
+        .filter(|l| l.len() >= MIN_ENTROPY_LABEL_LEN)
+        .map(|l| (l.to_string(), shannon_entropy(l)))
+        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+}
+
+/// 香农熵（bit/字符）
+fn shannon_entropy(s: &str) -> f64 {
+    let mut counts: HashMap<u8, usize> = HashMap::new();
+    for b in s.bytes() {
+        *counts.entry(b).or_default() += 1;
+    }
+    let len = s.len() as f64;
+    counts
+        .values()
+        .map(|&c| {
+            let p = c as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// 最后两级域名，例如 a.b.example.com -> example.com
+fn base_domain(domain: &str) -> String {
+    let labels: Vec<&str> = domain.split('.').collect();
+    if labels.len() <= 2 {
+        return domain.to_string();
+    }
+    labels[labels.len() - 2..].join(".")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn thresholds() -> DnsAlertThresholds {
+        DnsAlertThresholds {
+            entropy: 3.8,
+            qname_length: 100,
+            txt_per_minute: 30,
+            nxdomain_per_minute: 3,
+        }
+    }
+
+    fn record(domain: &str, query_type: &str, is_query: bool, response_code: &str) -> DnsQueryRecord {
+        DnsQueryRecord {
+            timestamp: 0,
+            domain: domain.to_string(),
+            query_type: query_type.to_string(),
+            response_code: response_code.to_string(),
+            source_ip: if is_query { "192.168.1.10" } else { "192.168.1.1" }.to_string(),
+            destination_ip: if is_query { "192.168.1.1" } else { "192.168.1.10" }.to_string(),
+            source_port: 0,
+            destination_port: 0,
+            transaction_id: 0,
+            is_query,
+            response_ips: vec![],
+            response_records: vec![],
+            response_time_ms: None,
+            device_mac: "aa:bb:cc:dd:ee:ff".to_string(),
+            device_name: String::new(),
+            categories: vec![],
+            blocked: false,
+        }
+    }
+
+    #[test]
+    fn test_high_entropy_label() {
+        let detector = DnsAlertDetector::new(thresholds());
+        detector.observe(&record("www.googleusercontent.com", "A", true, ""), 1000);
+        assert!(detector.alerts().is_empty());
+
+        detector.observe(&record("x7k2qz9vwm4p8rtj3b.example.net", "A", true, ""), 1000);
+        let alerts = detector.alerts();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].kind, DnsAlertKind::HighEntropy);
+        assert_eq!(alerts[0].domain, "example.net");
+
+        // 冷却期内不重复告警
+        detector.observe(&record("p9w2k7zq4m8vx3tj.example.net", "A", true, ""), 2000);
+        assert_eq!(detector.alerts().len(), 1);
+    }
+
+    #[test]
+    fn test_nxdomain_burst() {
+        let detector = DnsAlertDetector::new(thresholds());
+        for i in 0..2 {
+            detector.observe(&record("a.example.com", "A", false, "Domain not found"), 1000 + i);
+        }
+        assert!(detector.alerts().is_empty());
+
+        // 窗口外的响应不计入
+        detector.observe(&record("a.example.com", "A", false, "Domain not found"), 1000 + RATE_WINDOW_MS);
+        assert!(detector.alerts().is_empty());
+
+        detector.observe(&record("b.example.com", "A", false, "Domain not found"), 1001 + RATE_WINDOW_MS);
+        detector.observe(&record("c.example.com", "A", false, "Domain not found"), 1002 + RATE_WINDOW_MS);
+        let alerts = detector.take_events();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].kind, DnsAlertKind::NxdomainBurst);
+        assert_eq!(alerts[0].device_ip, "192.168.1.10");
+    }
+}
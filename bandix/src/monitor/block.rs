@@ -0,0 +1,305 @@
+use crate::monitor::category::CategoryEngine;
+use crate::storage::dns::DnsBlockPolicy;
+use aya::maps::{Array, HashMap as BpfHashMap};
+use bandix_common::{dns_block_key, dns_name_hash};
+use chrono::{DateTime, Local};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+// 与 eBPF DNS_BLOCK_RULES 映射容量一致
+const MAX_BLOCK_RULES: usize = 131072;
+// eBPF 只对 QNAME 最后 8 个后缀计算哈希，用户态判断时保持一致
+const MAX_SUFFIXES: usize = 8;
+
+/// DNS 拦截引擎
+/// - 保存拦截策略（按设备、按时间段，目标为域名或分类）
+/// - 根据当前时间编译出生效规则，并同步到 eBPF DNS_BLOCK_RULES 映射
+/// - 为用户态记录提供与内核一致的拦截判断
+pub struct DnsBlockEngine {
+    policies: Mutex<Vec<DnsBlockPolicy>>,
+    // 当前生效的规则：[mac, 域名哈希] -> 动作（与 eBPF 映射内容一致）
+    active_rules: Mutex<HashMap<[u8; 14], u8>>,
+    // 上次同步时生效的策略 id，用于检测时间段切换
+    active_policy_ids: Mutex<Vec<String>>,
+    dirty: AtomicBool,
+}
+
+impl DnsBlockEngine {
+    pub fn new() -> Self {
+        Self {
+            policies: Mutex::new(Vec::new()),
+            active_rules: Mutex::new(HashMap::new()),
+            active_policy_ids: Mutex::new(Vec::new()),
+            dirty: AtomicBool::new(true),
+        }
+    }
+
+    pub fn policies(&self) -> Vec<DnsBlockPolicy> {
+        self.policies.lock().unwrap().clone()
+    }
+
+    pub fn set_policies(&self, policies: Vec<DnsBlockPolicy>) {
+        *self.policies.lock().unwrap() = policies;
+        self.mark_dirty();
+    }
+
+    /// 新增或替换（按 id）一条策略
+    pub fn upsert_policy(&self, policy: DnsBlockPolicy) {
+        let mut policies = self.policies.lock().unwrap();
+        policies.retain(|p| p.id != policy.id);
+        policies.push(policy);
+        drop(policies);
+        self.mark_dirty();
+    }
+
+    /// 删除策略，返回是否存在
+    pub fn remove_policy(&self, id: &str) -> bool {
+        let mut policies = self.policies.lock().unwrap();
+        let before = policies.len();
+        policies.retain(|p| p.id != id);
+        let removed = policies.len() != before;
+        drop(policies);
+        if removed {
+            self.mark_dirty();
+        }
+        removed
+    }
+
+    /// 策略或分类列表变化后，下次同步时重新编译规则
+    pub fn mark_dirty(&self) {
+        self.dirty.store(true, Ordering::Relaxed);
+    }
+
+    /// 当前生效的规则数
+    pub fn active_rule_count(&self) -> usize {
+        self.active_rules.lock().unwrap().len()
+    }
+
+    /// 判断设备对该域名的查询是否会被内核拦截（使用最近一次同步的规则）
+    pub fn is_blocked(&self, mac: &[u8; 6], domain: &str) -> bool {
+        let rules = self.active_rules.lock().unwrap();
+        if rules.is_empty() {
+            return false;
+        }
+
+        let domain = domain.trim_end_matches('.').to_lowercase();
+        let any_device = [0u8; 6];
+        suffixes(&domain).iter().any(|suffix| {
+            let hash = dns_name_hash(suffix);
+            rules.contains_key(&dns_block_key(mac, hash)) || rules.contains_key(&dns_block_key(&any_device, hash))
+        })
+    }
+
+    /// 根据当前时间同步规则到 eBPF，只有策略变化或生效策略切换时才会写映射
+    /// 返回是否执行了同步
+    pub fn sync(&self, ebpf: &mut aya::Ebpf, categories: &CategoryEngine) -> Result<bool, anyhow::Error> {
+        let now = Local::now();
+        let policies = self.policies();
+        let active = active_policies(&policies, &now);
+        let active_ids: Vec<String> = active.iter().map(|p| p.id.clone()).collect();
+
+        let dirty = self.dirty.swap(false, Ordering::Relaxed);
+        if !dirty && *self.active_policy_ids.lock().unwrap() == active_ids {
+            return Ok(false);
+        }
+
+        let desired = compile_rules(&active, categories);
+
+        // 写映射失败时恢复 dirty 标记，下次同步时重试
+        if let Err(e) = self.write_rules(ebpf, &desired) {
+            self.mark_dirty();
+            return Err(e);
+        }
+
+        log::info!(
+            "DNS block rules synced: {} active policies, {} rules",
+            active_ids.len(),
+            desired.len()
+        );
+
+        *self.active_rules.lock().unwrap() = desired;
+        *self.active_policy_ids.lock().unwrap() = active_ids;
+        Ok(true)
+    }
+
+    /// 将规则差量写入 eBPF 映射，并更新规则数
+    fn write_rules(&self, ebpf: &mut aya::Ebpf, desired: &HashMap<[u8; 14], u8>) -> Result<(), anyhow::Error> {
+        {
+            let mut rules_map: BpfHashMap<_, [u8; 14], u8> = BpfHashMap::try_from(
+                ebpf.map_mut("DNS_BLOCK_RULES")
+                    .ok_or_else(|| anyhow::anyhow!("Cannot find DNS_BLOCK_RULES map"))?,
+            )?;
+
+            let current = self.active_rules.lock().unwrap().clone();
+            for key in current.keys() {
+                if !desired.contains_key(key) {
+                    let _ = rules_map.remove(key);
+                }
+            }
+            for (key, action) in desired.iter() {
+                if current.get(key) != Some(action) {
+                    rules_map.insert(key, action, 0)?;
+                }
+            }
+        }
+
+        let mut config: Array<_, u32> = Array::try_from(
+            ebpf.map_mut("DNS_BLOCK_CONFIG")
+                .ok_or_else(|| anyhow::anyhow!("Cannot find DNS_BLOCK_CONFIG map"))?,
+        )?;
+        config.set(0, desired.len() as u32, 0)?;
+        Ok(())
+    }
+}
+
+/// 在 now 所处时间段内生效的策略
+fn active_policies<'a>(policies: &'a [DnsBlockPolicy], now: &DateTime<Local>) -> Vec<&'a DnsBlockPolicy> {
+    policies.iter().filter(|p| p.time_slot.matches(now)).collect()
+}
+
+/// 将生效策略编译为规则：[mac, 域名哈希] -> 动作
+/// 同一 key 以先出现的策略为准；超出映射容量的域名被忽略
+fn compile_rules(active: &[&DnsBlockPolicy], categories: &CategoryEngine) -> HashMap<[u8; 14], u8> {
+    let mut desired: HashMap<[u8; 14], u8> = HashMap::new();
+    let mut category_cache: HashMap<String, Vec<String>> = HashMap::new();
+    let mut truncated = false;
+    for policy in active.iter() {
+        let mac = policy.mac.unwrap_or([0u8; 6]);
+        let action = policy.action.to_ebpf();
+
+        let mut targets: Vec<String> = policy.domains.clone();
+        for category in policy.categories.iter() {
+            let domains = category_cache
+                .entry(category.clone())
+                .or_insert_with(|| categories.domains_in_category(category));
+            targets.extend(domains.iter().cloned());
+        }
+
+        for domain in targets.iter() {
+            if desired.len() >= MAX_BLOCK_RULES {
+                truncated = true;
+                break;
+            }
+            desired.entry(dns_block_key(&mac, dns_name_hash(domain))).or_insert(action);
+        }
+    }
+    if truncated {
+        log::warn!("DNS block rules exceed {} entries, extra domains are ignored", MAX_BLOCK_RULES);
+    }
+    desired
+}
+
+/// 域名的最后 MAX_SUFFIXES 个后缀，例如 a.b.example.com -> [a.b.example.com, b.example.com, example.com, com]
+fn suffixes(domain: &str) -> Vec<&str> {
+    let mut out = vec![domain];
+    let mut rest = domain;
+    while let Some(pos) = rest.find('.') {
+        rest = &rest[pos + 1..];
+        out.push(rest);
+    }
+    let skip = out.len().saturating_sub(MAX_SUFFIXES);
+    out.split_off(skip)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::dns::DnsBlockAction;
+    use crate::storage::traffic::TimeSlot;
+    use chrono::TimeZone;
+
+    const MAC_KID: [u8; 6] = [0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0x01];
+    const MAC_OTHER: [u8; 6] = [0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0x02];
+
+    fn policy(id: &str, mac: Option<[u8; 6]>, time_slot: TimeSlot, domains: &[&str], categories: &[&str]) -> DnsBlockPolicy {
+        DnsBlockPolicy {
+            id: id.to_string(),
+            mac,
+            time_slot,
+            action: DnsBlockAction::NxDomain,
+            domains: domains.iter().map(|d| d.to_string()).collect(),
+            categories: categories.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+
+    fn categories_with(dir_name: &str, category: &str, content: &str) -> (CategoryEngine, std::path::PathBuf) {
+        let dir = std::env::temp_dir().join(format!("bandix-block-test-{}-{}", dir_name, std::process::id()));
+        let engine = CategoryEngine::new(dir.to_str().unwrap());
+        std::fs::create_dir_all(engine.dir()).unwrap();
+        std::fs::write(engine.dir().join(format!("{}.txt", category)), content).unwrap();
+        engine.reload().unwrap();
+        (engine, dir)
+    }
+
+    #[test]
+    fn test_compile_rules_and_suffix_match() {
+        let (categories, dir) = categories_with("compile", "games", "game.example.net\n");
+        let policies = [
+            policy("kid", Some(MAC_KID), TimeSlot::all_time(), &["video.example.com"], &["games"]),
+            policy("all", None, TimeSlot::all_time(), &["ads.example.org"], &[]),
+        ];
+        let active: Vec<&DnsBlockPolicy> = policies.iter().collect();
+        let rules = compile_rules(&active, &categories);
+        assert_eq!(rules.len(), 3);
+        assert_eq!(
+            rules.get(&dns_block_key(&MAC_KID, dns_name_hash("game.example.net"))),
+            Some(&bandix_common::DNS_BLOCK_ACTION_NXDOMAIN)
+        );
+
+        let engine = DnsBlockEngine::new();
+        *engine.active_rules.lock().unwrap() = rules;
+
+        // 规则匹配域名本身及其子域名，大小写与结尾的点不影响
+        assert!(engine.is_blocked(&MAC_KID, "video.example.com"));
+        assert!(engine.is_blocked(&MAC_KID, "CDN.Video.Example.com."));
+        assert!(engine.is_blocked(&MAC_KID, "eu.game.example.net"));
+        assert!(!engine.is_blocked(&MAC_KID, "example.com"));
+        assert!(!engine.is_blocked(&MAC_KID, "notvideo.example.com"));
+        // 设备规则只对该设备生效，全局规则对所有设备生效
+        assert!(!engine.is_blocked(&MAC_OTHER, "video.example.com"));
+        assert!(engine.is_blocked(&MAC_OTHER, "x.ads.example.org"));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_suffixes_keep_last_labels() {
+        assert_eq!(
+            suffixes("a.b.example.com"),
+            vec!["a.b.example.com", "b.example.com", "example.com", "com"]
+        );
+        let long = "1.2.3.4.5.6.7.8.9.example.com";
+        let out = suffixes(long);
+        assert_eq!(out.len(), MAX_SUFFIXES);
+        assert_eq!(out.last(), Some(&"com"));
+        assert_eq!(out[0], "4.5.6.7.8.9.example.com");
+    }
+
+    #[test]
+    fn test_active_policies_follow_schedule() {
+        // 周一至周五 21:00 - 次日 07:00
+        let night = TimeSlot {
+            start_hour: 21,
+            start_minute: 0,
+            end_hour: 7,
+            end_minute: 0,
+            days_of_week: 0b0011111,
+        };
+        let policies = vec![
+            policy("night", Some(MAC_KID), night, &["video.example.com"], &[]),
+            policy("always", None, TimeSlot::all_time(), &["ads.example.org"], &[]),
+        ];
+        let ids = |now: DateTime<Local>| -> Vec<String> { active_policies(&policies, &now).iter().map(|p| p.id.clone()).collect() };
+
+        // 2024-01-01 为周一
+        let monday_night = Local.with_ymd_and_hms(2024, 1, 1, 22, 30, 0).unwrap();
+        let monday_noon = Local.with_ymd_and_hms(2024, 1, 1, 12, 0, 0).unwrap();
+        let tuesday_morning = Local.with_ymd_and_hms(2024, 1, 2, 6, 59, 0).unwrap();
+        let saturday_night = Local.with_ymd_and_hms(2024, 1, 6, 22, 30, 0).unwrap();
+        assert_eq!(ids(monday_night), vec!["night", "always"]);
+        assert_eq!(ids(monday_noon), vec!["always"]);
+        assert_eq!(ids(tuesday_morning), vec!["night", "always"]);
+        assert_eq!(ids(saturday_night), vec!["always"]);
+    }
+}
@@ -0,0 +1,277 @@
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+// 分类列表所在目录（相对于数据目录）
+pub const CATEGORY_DIR_NAME: &str = "dns_categories";
+// 支持的列表文件扩展名；文件名（不含扩展名）即分类名，例如 ads.txt -> ads
+const LIST_EXTENSIONS: [&str; 4] = ["txt", "list", "hosts", "conf"];
+// hosts 格式中需要忽略的本地主机名
+const IGNORED_HOSTS: [&str; 6] = [
+    "localhost",
+    "localhost.localdomain",
+    "local",
+    "broadcasthost",
+    "ip6-localhost",
+    "ip6-loopback",
+];
+
+/// 已加载的分类列表摘要，用于 API 响应
+#[derive(Debug, Clone)]
+pub struct CategoryListInfo {
+    pub category: String,
+    pub file: String,
+    pub domain_count: usize,
+}
+
+/// 域名分类引擎
+/// - 从 <data_dir>/dns_categories/ 加载 hosts 格式或纯域名列表
+/// - 按域名后缀匹配：列表中的 example.com 同时匹配 www.example.com
+/// - 支持运行时重新加载（文件变更检测或 API 手动触发）
+pub struct CategoryEngine {
+    dir: PathBuf,
+    // 域名 -> 分类列表（已排序、去重）
+    domains: Mutex<HashMap<String, Vec<String>>>,
+    lists: Mutex<Vec<CategoryListInfo>>,
+    // 上次加载时的文件指纹（文件名、大小、修改时间）
+    fingerprint: Mutex<Vec<(String, u64, Option<SystemTime>)>>,
+}
+
+impl CategoryEngine {
+    pub fn new(data_dir: &str) -> Self {
+        Self {
+            dir: Path::new(data_dir).join(CATEGORY_DIR_NAME),
+            domains: Mutex::new(HashMap::new()),
+            lists: Mutex::new(Vec::new()),
+            fingerprint: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// 列表目录路径
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// 重新加载全部列表，返回 (分类数, 域名数)
+    pub fn reload(&self) -> Result<(usize, usize), anyhow::Error> {
+        let files = self.list_files()?;
+        let fingerprint = Self::fingerprint_of(&files);
+
+        let mut merged: HashMap<String, BTreeSet<String>> = HashMap::new();
+        let mut lists: Vec<CategoryListInfo> = Vec::new();
+
+        for path in files.iter() {
+            let category = match path.file_stem().and_then(|s| s.to_str()) {
+                Some(stem) if !stem.is_empty() => stem.to_lowercase(),
+                _ => continue,
+            };
+            let content = match std::fs::read_to_string(path) {
+                Ok(c) => c,
+                Err(e) => {
+                    log::warn!("Failed to read category list {:?}: {}", path, e);
+                    continue;
+                }
+            };
+
+            let entries = parse_list(&content);
+            lists.push(CategoryListInfo {
+                category: category.clone(),
+                file: path.file_name().and_then(|s| s.to_str()).unwrap_or_default().to_string(),
+                domain_count: entries.len(),
+            });
+            for domain in entries {
+                merged.entry(domain).or_default().insert(category.clone());
+            }
+        }
+
+        let category_count = lists.iter().map(|l| l.category.as_str()).collect::<BTreeSet<_>>().len();
+        let domain_count = merged.len();
+
+        {
+            let mut domains = self.domains.lock().unwrap();
+            *domains = merged.into_iter().map(|(d, c)| (d, c.into_iter().collect())).collect();
+        }
+        *self.lists.lock().unwrap() = lists;
+        *self.fingerprint.lock().unwrap() = fingerprint;
+
+        log::info!(
+            "Loaded {} DNS categories ({} domains) from {:?}",
+            category_count,
+            domain_count,
+            self.dir
+        );
+
+        Ok((category_count, domain_count))
+    }
+
+    /// 列表文件发生变化（新增、删除、修改）时重新加载，返回是否执行了加载
+    pub fn reload_if_changed(&self) -> Result<bool, anyhow::Error> {
+        let files = self.list_files()?;
+        let fingerprint = Self::fingerprint_of(&files);
+        if *self.fingerprint.lock().unwrap() == fingerprint {
+            return Ok(false);
+        }
+        self.reload()?;
+        Ok(true)
+    }
+
+    /// 查询域名所属的分类（按后缀匹配，结果已排序、去重）
+    pub fn classify(&self, domain: &str) -> Vec<String> {
+        let domain = domain.trim_end_matches('.').to_lowercase();
+        if domain.is_empty() {
+            return Vec::new();
+        }
+
+        let domains = self.domains.lock().unwrap();
+        if domains.is_empty() {
+            return Vec::new();
+        }
+
+        let mut result: BTreeSet<String> = BTreeSet::new();
+        let mut candidate = domain.as_str();
+        loop {
+            if let Some(categories) = domains.get(candidate) {
+                result.extend(categories.iter().cloned());
+            }
+            match candidate.find('.') {
+                Some(pos) => candidate = &candidate[pos + 1..],
+                None => break,
+            }
+        }
+
+        result.into_iter().collect()
+    }
+
+    /// 某个分类下的全部域名
+    pub fn domains_in_category(&self, category: &str) -> Vec<String> {
+        let category = category.to_lowercase();
+        let domains = self.domains.lock().unwrap();
+        domains
+            .iter()
+            .filter(|(_, categories)| categories.contains(&category))
+            .map(|(domain, _)| domain.clone())
+            .collect()
+    }
+
+    /// 已加载的列表摘要
+    pub fn lists(&self) -> Vec<CategoryListInfo> {
+        self.lists.lock().unwrap().clone()
+    }
+
+    fn list_files(&self) -> Result<Vec<PathBuf>, anyhow::Error> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut files: Vec<PathBuf> = std::fs::read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file())
+            .filter(|path| {
+                path.extension()
+                    .and_then(|e| e.to_str())
+                    .map(|e| LIST_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+                    .unwrap_or(false)
+            })
+            .collect();
+        files.sort();
+        Ok(files)
+    }
+
+    fn fingerprint_of(files: &[PathBuf]) -> Vec<(String, u64, Option<SystemTime>)> {
+        files
+            .iter()
+            .map(|path| {
+                let meta = std::fs::metadata(path).ok();
+                (
+                    path.to_string_lossy().to_string(),
+                    meta.as_ref().map(|m| m.len()).unwrap_or(0),
+                    meta.and_then(|m| m.modified().ok()),
+                )
+            })
+            .collect()
+    }
+}
+
+/// 解析列表内容，同时支持 hosts 格式（"0.0.0.0 example.com"）和纯域名格式（"example.com"）
+fn parse_list(content: &str) -> Vec<String> {
+    let mut result = Vec::new();
+
+    for line in content.lines() {
+        let line = match line.find('#') {
+            Some(pos) => &line[..pos],
+            None => line,
+        };
+        let mut tokens = line.split_whitespace().peekable();
+        let first = match tokens.peek() {
+            Some(t) => *t,
+            None => continue,
+        };
+
+        // hosts 格式：第一列是 IP，其后的每一列都是主机名
+        if first.parse::<std::net::IpAddr>().is_ok() {
+            tokens.next();
+            for host in tokens {
+                if let Some(domain) = normalize_domain(host) {
+                    result.push(domain);
+                }
+            }
+        } else if let Some(domain) = normalize_domain(first) {
+            result.push(domain);
+        }
+    }
+
+    result
+}
+
+fn normalize_domain(raw: &str) -> Option<String> {
+    // 兼容 "*.example.com" 与 "||example.com^" 等常见写法
+    let domain = raw
+        .trim_start_matches("||")
+        .trim_end_matches('^')
+        .trim_start_matches("*.")
+        .trim_end_matches('.')
+        .to_lowercase();
+
+    if domain.is_empty() || IGNORED_HOSTS.contains(&domain.as_str()) {
+        return None;
+    }
+    if !domain
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.' || c == '_')
+    {
+        return None;
+    }
+
+    Some(domain)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_list_formats() {
+        let content = "# comment\n0.0.0.0 ads.example.com tracker.example.com\n127.0.0.1 localhost\n\nsocial.example.org # inline\n||game.example.net^\n";
+        let domains = parse_list(content);
+        assert_eq!(
+            domains,
+            vec!["ads.example.com", "tracker.example.com", "social.example.org", "game.example.net"]
+        );
+    }
+
+    #[test]
+    fn test_classify_suffix_match() {
+        let engine = CategoryEngine::new("/nonexistent");
+        {
+            let mut domains = engine.domains.lock().unwrap();
+            domains.insert("example.com".to_string(), vec!["ads".to_string()]);
+            domains.insert("cdn.example.com".to_string(), vec!["trackers".to_string()]);
+        }
+
+        assert_eq!(engine.classify("a.cdn.example.com."), vec!["ads", "trackers"]);
+        assert_eq!(engine.classify("WWW.Example.com"), vec!["ads"]);
+        assert!(engine.classify("example.org").is_empty());
+    }
+}
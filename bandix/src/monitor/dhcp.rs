@@ -0,0 +1,410 @@
+// DHCP 监听：从 DHCPv4 报文中学习设备的 IP、主机名、厂商类别与租约信息
+//
+// eBPF 将 UDP 67/68 报文通过 DHCP_DATA RingBuf 转发到用户态，这里解析客户端的
+// DISCOVER / REQUEST / INFORM / RELEASE 与服务器的 ACK，按客户端 MAC 维护最新的租约状态。
+
+use crate::device::DeviceManager;
+use aya::maps::{MapData, RingBuf};
+use bandix_common::PacketHeader;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+const DHCP_SERVER_PORT: u16 = 67;
+const DHCP_CLIENT_PORT: u16 = 68;
+
+// BOOTP 固定部分
+const BOOTP_OP_REQUEST: u8 = 1;
+const BOOTP_OP_REPLY: u8 = 2;
+const BOOTP_FIXED_LEN: usize = 236;
+const DHCP_MAGIC_COOKIE: [u8; 4] = [99, 130, 83, 99];
+
+// DHCP 选项
+const OPT_PAD: u8 = 0;
+const OPT_HOSTNAME: u8 = 12;
+const OPT_REQUESTED_IP: u8 = 50;
+const OPT_LEASE_TIME: u8 = 51;
+const OPT_MESSAGE_TYPE: u8 = 53;
+const OPT_SERVER_ID: u8 = 54;
+const OPT_PARAMETER_REQUEST_LIST: u8 = 55;
+const OPT_VENDOR_CLASS: u8 = 60;
+const OPT_CLIENT_ID: u8 = 61;
+const OPT_END: u8 = 255;
+
+// DHCP 消息类型
+pub const DHCP_DISCOVER: u8 = 1;
+pub const DHCP_OFFER: u8 = 2;
+pub const DHCP_REQUEST: u8 = 3;
+pub const DHCP_DECLINE: u8 = 4;
+pub const DHCP_ACK: u8 = 5;
+pub const DHCP_NAK: u8 = 6;
+pub const DHCP_RELEASE: u8 = 7;
+pub const DHCP_INFORM: u8 = 8;
+
+const MAX_CLIENTS: usize = 4096;
+const MAX_STRING_LEN: usize = 255;
+
+/// 解析后的 DHCP 报文
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DhcpMessage {
+    pub op: u8,
+    pub message_type: u8,
+    pub xid: u32,
+    pub client_mac: [u8; 6],
+    pub ciaddr: [u8; 4],
+    pub yiaddr: [u8; 4],
+    pub hostname: Option<String>,
+    pub vendor_class: Option<String>,
+    pub parameter_request_list: Vec<u8>,
+    pub client_id: Vec<u8>, // 客户端标识（选项 61），未携带时为空
+    pub requested_ip: Option<[u8; 4]>,
+    pub server_id: Option<[u8; 4]>,
+    pub lease_time_secs: Option<u32>,
+    pub ttl: Option<u8>, // IPv4 报文的 TTL，客户端报文可据此推断操作系统
+}
+
+/// 某个客户端的 DHCP 状态
+#[derive(Debug, Clone, Default)]
+pub struct DhcpLease {
+    pub ip: Option<[u8; 4]>,
+    pub requested_ip: Option<[u8; 4]>,
+    pub hostname: Option<String>,
+    pub vendor_class: Option<String>,
+    pub parameter_request_list: Vec<u8>,
+    pub server_ip: Option<[u8; 4]>,
+    pub lease_time_secs: Option<u32>,
+    pub lease_start_ms: Option<u64>, // 收到 ACK 的时间
+    pub released: bool,              // 客户端已发送 RELEASE
+    pub last_message_type: u8,
+    pub first_seen_ms: u64,
+    pub last_seen_ms: u64,
+}
+
+impl DhcpLease {
+    /// 租约到期时间；释放后为释放时间
+    pub fn lease_expires_ms(&self) -> Option<u64> {
+        let start = self.lease_start_ms?;
+        let secs = self.lease_time_secs?;
+        // 0xFFFFFFFF 表示永久租约
+        if secs == u32::MAX {
+            return None;
+        }
+        Some(start + secs as u64 * 1000)
+    }
+}
+
+pub fn message_type_name(message_type: u8) -> &'static str {
+    match message_type {
+        DHCP_DISCOVER => "DISCOVER",
+        DHCP_OFFER => "OFFER",
+        DHCP_REQUEST => "REQUEST",
+        DHCP_DECLINE => "DECLINE",
+        DHCP_ACK => "ACK",
+        DHCP_NAK => "NAK",
+        DHCP_RELEASE => "RELEASE",
+        DHCP_INFORM => "INFORM",
+        _ => "UNKNOWN",
+    }
+}
+
+/// DHCP 监听状态表
+pub struct DhcpSnooper {
+    leases: Mutex<HashMap<[u8; 6], DhcpLease>>,
+}
+
+impl DhcpSnooper {
+    pub fn new() -> Self {
+        Self {
+            leases: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 记录一条 DHCP 报文，返回更新后的客户端状态；OFFER 等不影响状态的报文返回 None
+    pub fn observe(&self, msg: &DhcpMessage, now_ms: u64) -> Option<DhcpLease> {
+        let from_client = msg.op == BOOTP_OP_REQUEST;
+        let relevant = match msg.message_type {
+            DHCP_DISCOVER | DHCP_REQUEST | DHCP_INFORM | DHCP_RELEASE | DHCP_DECLINE => from_client,
+            DHCP_ACK | DHCP_NAK => !from_client,
+            _ => false,
+        };
+        if !relevant {
+            return None;
+        }
+
+        let mut leases = self.leases.lock().unwrap();
+        if !leases.contains_key(&msg.client_mac) && leases.len() >= MAX_CLIENTS {
+            return None;
+        }
+        let lease = leases.entry(msg.client_mac).or_insert_with(|| DhcpLease {
+            first_seen_ms: now_ms,
+            ..Default::default()
+        });
+        lease.last_seen_ms = now_ms;
+        lease.last_message_type = msg.message_type;
+
+        if from_client {
+            // 客户端报文携带的身份信息（主机名、厂商类别、参数请求列表）
+            if msg.hostname.is_some() {
+                lease.hostname = msg.hostname.clone();
+            }
+            if msg.vendor_class.is_some() {
+                lease.vendor_class = msg.vendor_class.clone();
+            }
+            if !msg.parameter_request_list.is_empty() {
+                lease.parameter_request_list = msg.parameter_request_list.clone();
+            }
+            if msg.requested_ip.is_some() {
+                lease.requested_ip = msg.requested_ip;
+            }
+            if msg.message_type == DHCP_RELEASE {
+                lease.released = true;
+                lease.lease_start_ms = Some(now_ms);
+                lease.lease_time_secs = Some(0);
+            }
+        } else if msg.message_type == DHCP_ACK {
+            // INFORM 的 ACK 不分配地址（yiaddr 为 0），使用客户端已有的 ciaddr
+            let ip = if msg.yiaddr != [0u8; 4] { msg.yiaddr } else { msg.ciaddr };
+            if ip != [0u8; 4] {
+                lease.ip = Some(ip);
+            }
+            if msg.lease_time_secs.is_some() {
+                lease.lease_time_secs = msg.lease_time_secs;
+                lease.lease_start_ms = Some(now_ms);
+                lease.released = false;
+            }
+            if msg.server_id.is_some() {
+                lease.server_ip = msg.server_id;
+            }
+            if lease.hostname.is_none() && msg.hostname.is_some() {
+                lease.hostname = msg.hostname.clone();
+            }
+        }
+
+        Some(lease.clone())
+    }
+
+    pub fn get(&self, mac: &[u8; 6]) -> Option<DhcpLease> {
+        let leases = self.leases.lock().unwrap();
+        leases.get(mac).cloned()
+    }
+}
+
+/// 从以太网帧中解析 DHCP 报文
+pub fn parse_dhcp_frame(frame: &[u8]) -> Option<DhcpMessage> {
+    let (_, src_port, dst_port, payload) = super::localname::locate_udp_payload(frame)?;
+    let ports_ok =
+        (src_port == DHCP_CLIENT_PORT && dst_port == DHCP_SERVER_PORT) || (src_port == DHCP_SERVER_PORT && dst_port == DHCP_CLIENT_PORT);
+    if !ports_ok {
+        return None;
+    }
+    let mut msg = parse_dhcp_message(payload)?;
+    if u16::from_be_bytes([frame[12], frame[13]]) == 0x0800 {
+        msg.ttl = frame.get(14 + 8).copied();
+    }
+    Some(msg)
+}
+
+/// 解析 DHCP 消息（BOOTP 固定部分 + 选项）
+pub fn parse_dhcp_message(data: &[u8]) -> Option<DhcpMessage> {
+    if data.len() < BOOTP_FIXED_LEN + 4 || data[BOOTP_FIXED_LEN..BOOTP_FIXED_LEN + 4] != DHCP_MAGIC_COOKIE {
+        return None;
+    }
+    let op = data[0];
+    if op != BOOTP_OP_REQUEST && op != BOOTP_OP_REPLY {
+        return None;
+    }
+    // 只处理以太网硬件地址
+    if data[1] != 1 || data[2] != 6 {
+        return None;
+    }
+
+    let mut msg = DhcpMessage {
+        op,
+        xid: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+        ..Default::default()
+    };
+    msg.ciaddr.copy_from_slice(&data[12..16]);
+    msg.yiaddr.copy_from_slice(&data[16..20]);
+    msg.client_mac.copy_from_slice(&data[28..34]);
+
+    let mut offset = BOOTP_FIXED_LEN + 4;
+    while offset < data.len() {
+        let code = data[offset];
+        if code == OPT_END {
+            break;
+        }
+        if code == OPT_PAD {
+            offset += 1;
+            continue;
+        }
+        let len = *data.get(offset + 1)? as usize;
+        let value = data.get(offset + 2..offset + 2 + len)?;
+        offset += 2 + len;
+
+        match code {
+            OPT_MESSAGE_TYPE if len == 1 => msg.message_type = value[0],
+            OPT_HOSTNAME => msg.hostname = option_string(value),
+            OPT_VENDOR_CLASS => msg.vendor_class = option_string(value),
+            OPT_CLIENT_ID => msg.client_id = value.to_vec(),
+            OPT_PARAMETER_REQUEST_LIST => msg.parameter_request_list = value.to_vec(),
+            OPT_REQUESTED_IP if len == 4 => msg.requested_ip = Some([value[0], value[1], value[2], value[3]]),
+            OPT_SERVER_ID if len == 4 => msg.server_id = Some([value[0], value[1], value[2], value[3]]),
+            OPT_LEASE_TIME if len == 4 => msg.lease_time_secs = Some(u32::from_be_bytes([value[0], value[1], value[2], value[3]])),
+            // sname / file 字段被选项复用（选项 52）的情况很少见，不解析其中的选项
+            _ => {}
+        }
+    }
+
+    if msg.message_type == 0 {
+        // 没有消息类型的是纯 BOOTP 报文
+        return None;
+    }
+    Some(msg)
+}
+
+/// 选项中的字符串值：去掉结尾的 NUL，拒绝控制字符
+fn option_string(value: &[u8]) -> Option<String> {
+    let value = match value.iter().position(|b| *b == 0) {
+        Some(end) => &value[..end],
+        None => value,
+    };
+    let s = String::from_utf8_lossy(value).trim().to_string();
+    if s.is_empty() || s.len() > MAX_STRING_LEN || s.chars().any(|c| c.is_control()) {
+        return None;
+    }
+    Some(s)
+}
+
+/// 处理 DHCP RingBuf 中的报文：更新租约表，并将 IP / 主机名同步到设备管理器
+pub fn process_dhcp_events(ringbuf: &mut RingBuf<MapData>, snooper: &DhcpSnooper, device_manager: &DeviceManager) {
+    let header_size = std::mem::size_of::<PacketHeader>();
+    while let Some(item) = ringbuf.next() {
+        let bytes: &[u8] = item.as_ref();
+        if bytes.len() < header_size {
+            continue;
+        }
+        let header: PacketHeader = unsafe { std::ptr::read_unaligned(bytes.as_ptr() as *const PacketHeader) };
+        let payload_all = &bytes[header_size..];
+        let cap_len = std::cmp::min(payload_all.len(), header.captured_len as usize);
+
+        let msg = match parse_dhcp_frame(&payload_all[..cap_len]) {
+            Some(msg) => msg,
+            None => continue,
+        };
+        let now_ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+
+        log::debug!(
+            "DHCP {} [{}] client={} xid={:08x} hostname={:?} vendor_class={:?}",
+            message_type_name(msg.message_type),
+            if header.direction == 0 { "Ingress" } else { "Egress" },
+            crate::utils::format_utils::format_mac(&msg.client_mac),
+            msg.xid,
+            msg.hostname,
+            msg.vendor_class
+        );
+
+        // 客户端报文的参数请求列表、厂商类别与 TTL 用于识别设备类型
+        if msg.op == BOOTP_OP_REQUEST {
+            device_manager.device_classifier().observe_dhcp(
+                msg.client_mac,
+                &msg.parameter_request_list,
+                msg.vendor_class.as_deref(),
+                msg.ttl,
+            );
+            // 客户端标识与主机名用于关联同一设备轮换使用的随机化 MAC
+            let registry = device_manager.identity_registry();
+            if let Some(primary) = registry.observe_dhcp(msg.client_mac, &msg.client_id, msg.hostname.as_deref(), now_ms) {
+                log::info!(
+                    "Linked {} to device identity {}",
+                    crate::utils::format_utils::format_mac(&msg.client_mac),
+                    crate::utils::format_utils::format_mac(&primary)
+                );
+            }
+        }
+
+        if let Some(lease) = snooper.observe(&msg, now_ms) {
+            // 只有服务器确认的地址才用于更新设备
+            let ip = if msg.message_type == DHCP_ACK { lease.ip } else { None };
+            device_manager.apply_dhcp_lease(msg.client_mac, ip, lease.hostname.as_deref());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_message(op: u8, message_type: u8, options: &[(u8, &[u8])]) -> Vec<u8> {
+        let mut data = vec![0u8; BOOTP_FIXED_LEN];
+        data[0] = op;
+        data[1] = 1;
+        data[2] = 6;
+        data[4..8].copy_from_slice(&0x1234_5678u32.to_be_bytes());
+        data[16..20].copy_from_slice(&[192, 168, 1, 50]);
+        data[28..34].copy_from_slice(&[0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]);
+        data.extend_from_slice(&DHCP_MAGIC_COOKIE);
+        data.extend_from_slice(&[OPT_MESSAGE_TYPE, 1, message_type]);
+        for (code, value) in options {
+            data.push(*code);
+            data.push(value.len() as u8);
+            data.extend_from_slice(value);
+        }
+        data.push(OPT_END);
+        data
+    }
+
+    #[test]
+    fn test_parse_request_options() {
+        let data = build_message(
+            BOOTP_OP_REQUEST,
+            DHCP_REQUEST,
+            &[
+                (OPT_HOSTNAME, b"Johns-iPhone\0"),
+                (OPT_VENDOR_CLASS, b"android-dhcp-13"),
+                (OPT_PARAMETER_REQUEST_LIST, &[1, 3, 6, 15, 119]),
+                (OPT_CLIENT_ID, &[1, 0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]),
+                (OPT_REQUESTED_IP, &[192, 168, 1, 50]),
+            ],
+        );
+        let msg = parse_dhcp_message(&data).unwrap();
+        assert_eq!(msg.message_type, DHCP_REQUEST);
+        assert_eq!(msg.client_mac, [0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]);
+        assert_eq!(msg.hostname.as_deref(), Some("Johns-iPhone"));
+        assert_eq!(msg.vendor_class.as_deref(), Some("android-dhcp-13"));
+        assert_eq!(msg.parameter_request_list, vec![1, 3, 6, 15, 119]);
+        assert_eq!(msg.client_id, vec![1, 0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]);
+        assert_eq!(msg.requested_ip, Some([192, 168, 1, 50]));
+
+        // 截断的选项
+        assert!(parse_dhcp_message(&data[..data.len() - 10]).is_none());
+    }
+
+    #[test]
+    fn test_snooper_tracks_request_and_ack() {
+        let snooper = DhcpSnooper::new();
+        let mac = [0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff];
+
+        let request = parse_dhcp_message(&build_message(BOOTP_OP_REQUEST, DHCP_REQUEST, &[(OPT_HOSTNAME, b"laptop")])).unwrap();
+        let lease = snooper.observe(&request, 1_000).unwrap();
+        assert_eq!(lease.ip, None);
+        assert_eq!(lease.hostname.as_deref(), Some("laptop"));
+
+        let ack = parse_dhcp_message(&build_message(
+            BOOTP_OP_REPLY,
+            DHCP_ACK,
+            &[(OPT_LEASE_TIME, &3600u32.to_be_bytes()), (OPT_SERVER_ID, &[192, 168, 1, 1])],
+        ))
+        .unwrap();
+        let lease = snooper.observe(&ack, 2_000).unwrap();
+        assert_eq!(lease.ip, Some([192, 168, 1, 50]));
+        assert_eq!(lease.server_ip, Some([192, 168, 1, 1]));
+        assert_eq!(lease.lease_expires_ms(), Some(2_000 + 3600 * 1000));
+        assert_eq!(snooper.get(&mac).unwrap().hostname.as_deref(), Some("laptop"));
+
+        // 服务器发出的 OFFER 不改变状态
+        let offer = parse_dhcp_message(&build_message(BOOTP_OP_REPLY, DHCP_OFFER, &[])).unwrap();
+        assert!(snooper.observe(&offer, 3_000).is_none());
+    }
+}
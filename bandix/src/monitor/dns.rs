@@ -1,4 +1,6 @@
-use crate::monitor::{DnsModuleContext, DnsQueryRecord};
+use crate::monitor::dnstap::DnstapMessage;
+use crate::monitor::localname;
+use crate::monitor::{DnsIpIndexEntry, DnsModuleContext, DnsQueryRecord};
 use anyhow::Result;
 use aya::maps::{MapData, RingBuf};
 use bandix_common::PacketHeader;
@@ -90,23 +92,242 @@ impl DnsMonitor {
         shutdown_notify: std::sync::Arc<tokio::sync::Notify>,
     ) -> Result<()> {
         let mut interval = tokio::time::interval(tokio::time::Duration::from_millis(10));
+        // 定期检查分类列表文件是否变化，变化时重新加载
+        let mut category_interval = tokio::time::interval(tokio::time::Duration::from_secs(60));
+        // 拦截策略按时间段生效，每秒检查一次是否需要同步到 eBPF
+        let mut block_interval = tokio::time::interval(tokio::time::Duration::from_secs(1));
+        // 定期扫描 conntrack，检测 DoT / DoH 连接
+        let mut compliance_interval = tokio::time::interval(tokio::time::Duration::from_secs(30));
+
+        // 可疑 DNS 告警的发送间隔
+        let mut alert_interval = tokio::time::interval(tokio::time::Duration::from_secs(5));
+        // 整点后将上一小时的 DNS 指标写入环形文件
+        let mut metrics_interval = tokio::time::interval(tokio::time::Duration::from_secs(60));
+
+        // 解析器绕过事件与 DNS 告警复用 --traffic-event-url
+        let event_url = ctx.options.traffic_event_url().trim().to_string();
+        let event_url = if event_url.is_empty() { None } else { Some(event_url) };
+        let bypass_event_url = event_url.clone().filter(|_| ctx.options.dns_bypass_events());
+        let alert_event_url = event_url.filter(|_| ctx.options.dns_alert_events());
+        let http = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_millis(800))
+            .build()?;
 
         loop {
             tokio::select! {
                 _ = shutdown_notify.notified() => {
                     log::debug!("DNS monitoring module received shutdown signal, stopping...");
+                    if let Err(e) = ctx.dns_metrics.flush() {
+                        log::error!("Failed to flush DNS metrics during shutdown: {}", e);
+                    }
                     break;
                 }
                 _ = interval.tick() => {
                     // Process RingBuf events
                     self.process_ringbuf_events(ringbuf, ctx).await;
                 }
+                _ = category_interval.tick() => {
+                    match ctx.category_engine.reload_if_changed() {
+                        // 分类内容变化会影响按分类拦截的规则
+                        Ok(true) => ctx.block_engine.mark_dirty(),
+                        Ok(false) => {}
+                        Err(e) => log::warn!("Failed to reload DNS category lists: {}", e),
+                    }
+                }
+                _ = block_interval.tick() => {
+                    if let Err(e) = self.sync_block_rules(ctx) {
+                        log::warn!("Failed to sync DNS block rules: {}", e);
+                    }
+                }
+                _ = compliance_interval.tick() => {
+                    if let Err(e) = self.scan_resolver_bypass(ctx) {
+                        log::debug!("Failed to scan connections for resolver bypass: {}", e);
+                    }
+                    self.emit_bypass_events(ctx, &http, bypass_event_url.as_deref());
+                }
+                _ = alert_interval.tick() => {
+                    self.emit_alert_events(ctx, &http, alert_event_url.as_deref());
+                }
+                _ = metrics_interval.tick() => {
+                    let now_ms = std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_millis() as u64;
+                    ctx.dns_metrics.roll(now_ms);
+                }
             }
         }
 
         Ok(())
     }
 
+    /// 将拦截策略同步到 eBPF 映射
+    fn sync_block_rules(&self, ctx: &DnsModuleContext) -> Result<()> {
+        let ingress_ebpf = ctx
+            .ingress_ebpf
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Ingress eBPF program not initialized"))?;
+
+        // 使用 unsafe 获取对 eBPF 对象的可变访问（与流量模块写限速映射的方式相同）
+        let ebpf_mut = unsafe {
+            let ptr = std::sync::Arc::as_ptr(ingress_ebpf) as *mut aya::Ebpf;
+            &mut *ptr
+        };
+
+        ctx.block_engine.sync(ebpf_mut, &ctx.category_engine)?;
+        Ok(())
+    }
+
+    /// 扫描 conntrack 表，检测设备的 DoT / DoH 连接
+    fn scan_resolver_bypass(&self, ctx: &DnsModuleContext) -> Result<()> {
+        use crate::utils::network_utils;
+        use std::net::IpAddr;
+
+        let content = std::fs::read_to_string("/proc/net/nf_conntrack")?;
+
+        // 局域网设备 IP -> MAC
+        let mut ip_to_mac: std::collections::HashMap<IpAddr, [u8; 6]> = std::collections::HashMap::new();
+        for (ip, mac) in network_utils::get_ip_mac_mapping()?.into_iter() {
+            ip_to_mac.insert(IpAddr::from(ip), mac);
+        }
+        if let Ok(neighbors) = network_utils::get_ipv6_neighbors() {
+            for (mac, ips) in neighbors.into_iter() {
+                for ip in ips {
+                    ip_to_mac.insert(IpAddr::from(ip), mac);
+                }
+            }
+        }
+
+        let now_ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+
+        // 通过 DNS 应答索引判断远端 IP 是否属于已知 DoH 域名
+        let resolve_domains = |ip: &IpAddr| -> Vec<String> {
+            match ctx.dns_ip_index.lock() {
+                Ok(index) => index
+                    .get(&ip.to_string())
+                    .map(|entries| entries.iter().map(|e| e.domain.clone()).collect())
+                    .unwrap_or_default(),
+                Err(_) => Vec::new(),
+            }
+        };
+
+        ctx.resolver_compliance.scan_conntrack(&content, &ip_to_mac, resolve_domains, now_ms);
+        Ok(())
+    }
+
+    /// 发送解析器绕过事件（未配置事件地址时只清空队列）
+    fn emit_bypass_events(&self, ctx: &DnsModuleContext, http: &reqwest::Client, event_url: Option<&str>) {
+        let events = ctx.resolver_compliance.take_events();
+        let url = match event_url {
+            Some(url) => url,
+            None => return,
+        };
+        if !events.is_empty() {
+            log::debug!("Emitting {} resolver bypass event(s) to {}", events.len(), url);
+        }
+
+        for event in events {
+            let hostname = ctx
+                .hostname_bindings
+                .lock()
+                .ok()
+                .and_then(|b| b.get(&event.mac).cloned())
+                .unwrap_or_default();
+            let payload = ResolverBypassEventPayload {
+                ts_ms: event.ts_ms,
+                event: "resolver_bypass".to_string(),
+                mac: crate::utils::format_utils::format_mac(&event.mac),
+                ip: event.ip,
+                hostname,
+                kind: event.kind.as_str().to_string(),
+                server: event.server,
+                detail: event.detail,
+            };
+            post_event(http, url, payload);
+        }
+    }
+
+    /// 发送可疑 DNS 告警事件（未配置事件地址时只清空队列）
+    fn emit_alert_events(&self, ctx: &DnsModuleContext, http: &reqwest::Client, event_url: Option<&str>) {
+        let alerts = ctx.alert_detector.take_events();
+        let url = match event_url {
+            Some(url) => url,
+            None => return,
+        };
+        if !alerts.is_empty() {
+            log::debug!("Emitting {} DNS alert event(s) to {}", alerts.len(), url);
+        }
+
+        for alert in alerts {
+            let payload = DnsAlertEventPayload {
+                ts_ms: alert.ts_ms,
+                event: "dns_alert".to_string(),
+                mac: alert.device_mac,
+                ip: alert.device_ip,
+                hostname: alert.device_name,
+                kind: alert.kind.as_str().to_string(),
+                domain: alert.domain,
+                detail: alert.detail,
+                value: alert.value,
+                threshold: alert.threshold,
+            };
+            post_event(http, url, payload);
+        }
+    }
+
+    /// 处理名称发现报文：只从 Ingress（局域网设备发出）的通告中学习设备名称与服务类型
+    /// 不是名称发现端口的报文返回 None
+    fn process_local_name_packet(&self, frame: &[u8], direction: u32, ctx: &DnsModuleContext) -> Option<String> {
+        let (src_mac, src_port, dst_port, payload) = localname::locate_udp_payload(frame)?;
+        if !localname::is_local_name_port(src_port) && !localname::is_local_name_port(dst_port) {
+            return None;
+        }
+        if direction != 0 {
+            return Some("egress, ignored".to_string());
+        }
+        // 多播 / 广播源地址不是真实设备
+        if src_mac[0] & 0x01 != 0 || src_mac == [0u8; 6] {
+            return Some("invalid source MAC, ignored".to_string());
+        }
+        if ctx.privacy_excluded.contains(&src_mac) {
+            return Some("privacy excluded, ignored".to_string());
+        }
+        let announcement = match localname::parse_announcement(payload, src_port, dst_port) {
+            Some(a) => a,
+            None => return Some(format!("{} bytes to port {}, no announcement", payload.len(), dst_port)),
+        };
+
+        let now_ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+        let best = ctx.local_names.observe(src_mac, &announcement, now_ms);
+        ctx.device_classifier.observe_services(src_mac, &announcement.services);
+        if let Some(name) = &best {
+            if let Ok(mut bindings) = ctx.hostname_bindings.lock() {
+                if ctx.local_names.apply_to_bindings(src_mac, name, &mut bindings) {
+                    log::info!(
+                        "Learned hostname {} for {} via {}",
+                        name,
+                        crate::utils::format_utils::format_mac(&src_mac),
+                        announcement.source.as_str()
+                    );
+                }
+            }
+        }
+
+        Some(format!(
+            "{} from {}: names={:?} services={:?}",
+            announcement.source.as_str(),
+            crate::utils::format_utils::format_mac(&src_mac),
+            announcement.names,
+            announcement.services
+        ))
+    }
+
     /// 处理events from RingBuf
     async fn process_ringbuf_events(&self, ringbuf: &mut RingBuf<MapData>, ctx: &DnsModuleContext) {
         let mut packet_count = 0;
@@ -135,8 +356,14 @@ impl DnsMonitor {
             let cap_len = std::cmp::min(payload_all.len(), header.captured_len as usize);
             let payload = &payload_all[..cap_len];
 
+            // 名称发现报文（mDNS / LLMNR / NetBIOS）不进入 DNS 记录
+            if let Some(summary) = self.process_local_name_packet(payload, header.direction, ctx) {
+                log::debug!("Local name [Interface:{}] => {}", header.ifindex, summary);
+                continue;
+            }
+
             // Parse DNS packet
-            match self.parse_dns_packet_from_ethernet(payload, header.timestamp, ctx) {
+            match self.parse_dns_packet_from_ethernet(payload, header.packet_len as usize, header.timestamp, ctx) {
                 Some(dns_info) => {
                     let direction_str = if header.direction == 0 { "Ingress" } else { "Egress" };
                     log::debug!(
@@ -309,7 +536,8 @@ impl DnsMonitor {
     /// Parse DNS packet from Ethernet frame
     /// Returns Some(String) if DNS packet, None otherwise
     /// Supports both IPv4 and IPv6
-    fn parse_dns_packet_from_ethernet(&self, data: &[u8], timestamp: u64, ctx: &DnsModuleContext) -> Option<String> {
+    /// packet_len 为原始报文长度，大于 data 的长度时说明 eBPF 截断了报文
+    fn parse_dns_packet_from_ethernet(&self, data: &[u8], packet_len: usize, timestamp: u64, ctx: &DnsModuleContext) -> Option<String> {
         // At least need Ethernet header (14 bytes)
         if data.len() < 14 {
             return None;
@@ -319,15 +547,16 @@ impl DnsMonitor {
         let eth_type = u16::from_be_bytes([data[12], data[13]]);
 
         // Handle IPv4 and IPv6
+        let truncated = data.len() < packet_len;
         match eth_type {
-            0x0800 => self.parse_dns_ipv4(data, timestamp, ctx),
-            0x86DD => self.parse_dns_ipv6(data, timestamp, ctx),
+            0x0800 => self.parse_dns_ipv4(data, truncated, timestamp, ctx),
+            0x86DD => self.parse_dns_ipv6(data, truncated, timestamp, ctx),
             _ => None, // Not IP packet
         }
     }
 
     /// Parse IPv4 DNS packet (supports both UDP and TCP)
-    fn parse_dns_ipv4(&self, data: &[u8], timestamp: u64, ctx: &DnsModuleContext) -> Option<String> {
+    fn parse_dns_ipv4(&self, data: &[u8], truncated: bool, timestamp: u64, ctx: &DnsModuleContext) -> Option<String> {
         // IPv4 header start position (after Ethernet header)
         let ip_header_start = 14;
 
@@ -420,20 +649,28 @@ impl DnsMonitor {
                     // TCP DNS data start position (after TCP header)
                     let tcp_data_start = tcp_header_start + tcp_header_len;
 
-                    // TCP DNS messages have a 2-byte length prefix
-                    if data.len() < tcp_data_start + 2 {
-                        return None;
-                    }
-
-                    // Skip the 2-byte length prefix to get to DNS data
-                    let dns_offset = tcp_data_start + 2;
-
-                    // Check if there's actual DNS data
-                    if data.len() <= dns_offset {
+                    // 按 IP 总长度截取 TCP 负载，排除以太网填充
+                    let ip_total_len = u16::from_be_bytes([data[ip_header_start + 2], data[ip_header_start + 3]]) as usize;
+                    let payload_end = std::cmp::min(data.len(), ip_header_start + ip_total_len);
+                    if payload_end <= tcp_data_start {
                         return None;
                     }
 
-                    Some(self.parse_dns_packet(data, dns_offset, &src_ip, &dst_ip, src_port, dst_port, timestamp, ctx, "TCP"))
+                    let seq = u32::from_be_bytes([
+                        data[tcp_header_start + 4],
+                        data[tcp_header_start + 5],
+                        data[tcp_header_start + 6],
+                        data[tcp_header_start + 7],
+                    ]);
+                    let flags = data[tcp_header_start + 13];
+
+                    let segment = TcpSegment {
+                        seq,
+                        flags,
+                        payload: &data[tcp_data_start..payload_end],
+                        truncated,
+                    };
+                    Some(self.parse_tcp_segment((src_ip, src_port, dst_ip, dst_port), segment, timestamp, ctx))
                 } else {
                     None
                 }
@@ -446,7 +683,7 @@ impl DnsMonitor {
     }
 
     /// Parse IPv6 DNS packet (supports both UDP and TCP)
-    fn parse_dns_ipv6(&self, data: &[u8], timestamp: u64, ctx: &DnsModuleContext) -> Option<String> {
+    fn parse_dns_ipv6(&self, data: &[u8], truncated: bool, timestamp: u64, ctx: &DnsModuleContext) -> Option<String> {
         const IPV6_HEADER_START: usize = 14;
         const IPV6_HEADER_LEN: usize = 40;
 
@@ -536,20 +773,24 @@ impl DnsMonitor {
                     // TCP DNS data start position (after TCP header)
                     let tcp_data_start = offset + tcp_header_len;
 
-                    // TCP DNS messages have a 2-byte length prefix
-                    if data.len() < tcp_data_start + 2 {
+                    // 按 IPv6 负载长度截取 TCP 负载，排除以太网填充
+                    let ipv6_payload_len =
+                        u16::from_be_bytes([data[IPV6_HEADER_START + 4], data[IPV6_HEADER_START + 5]]) as usize;
+                    let payload_end = std::cmp::min(data.len(), IPV6_HEADER_START + IPV6_HEADER_LEN + ipv6_payload_len);
+                    if payload_end <= tcp_data_start {
                         return None;
                     }
 
-                    // Skip the 2-byte length prefix to get to DNS data
-                    let dns_offset = tcp_data_start + 2;
-
-                    // Check if there's actual DNS data
-                    if data.len() <= dns_offset {
-                        return None;
-                    }
+                    let seq = u32::from_be_bytes([data[offset + 4], data[offset + 5], data[offset + 6], data[offset + 7]]);
+                    let flags = data[offset + 13];
 
-                    Some(self.parse_dns_packet(data, dns_offset, &src_ip, &dst_ip, src_port, dst_port, timestamp, ctx, "TCP"))
+                    let segment = TcpSegment {
+                        seq,
+                        flags,
+                        payload: &data[tcp_data_start..payload_end],
+                        truncated,
+                    };
+                    Some(self.parse_tcp_segment((src_ip, src_port, dst_ip, dst_port), segment, timestamp, ctx))
                 } else {
                     None
                 }
@@ -573,6 +814,55 @@ impl DnsMonitor {
         ipv6.to_string()
     }
 
+    /// TCP DNS：按连接重组分段，对每条完整消息调用 parse_dns_packet
+    /// 被截断的分段（例如超过 eBPF 复制上限的 GRO/TSO 大包）无法重组，直接丢弃该方向的缓存
+    fn parse_tcp_segment(&self, key: TcpFlowKey, segment: TcpSegment, timestamp: u64, ctx: &DnsModuleContext) -> String {
+        let TcpSegment {
+            seq,
+            flags,
+            payload,
+            truncated,
+        } = segment;
+        let (src_ip, src_port, dst_ip, dst_port) = key.clone();
+        if truncated {
+            if let Ok(mut reassembler) = ctx.tcp_reassembler.lock() {
+                reassembler.drop_stream(&key);
+            }
+            return format!(
+                "DNS/TCP {}:{} -> {}:{} [Segment truncated: {} bytes captured, stream dropped]",
+                src_ip,
+                src_port,
+                dst_ip,
+                dst_port,
+                payload.len()
+            );
+        }
+
+        let closing = flags & (TCP_FLAG_FIN | TCP_FLAG_RST) != 0;
+        let messages = match ctx.tcp_reassembler.lock() {
+            Ok(mut reassembler) => reassembler.push(key, seq, payload, closing, timestamp),
+            Err(_) => Vec::new(),
+        };
+
+        if messages.is_empty() {
+            return format!(
+                "DNS/TCP {}:{} -> {}:{} [Segment buffered: {} bytes, seq {}]",
+                src_ip,
+                src_port,
+                dst_ip,
+                dst_port,
+                payload.len(),
+                seq
+            );
+        }
+
+        messages
+            .iter()
+            .map(|message| self.parse_dns_packet(message, 0, &src_ip, &dst_ip, src_port, dst_port, timestamp, ctx, "TCP"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
     /// Get device information (MAC and hostname) from IP address
     /// Supports both IPv4 and IPv6 addresses
     fn get_device_info(&self, ip: &str, ctx: &DnsModuleContext) -> (String, String) {
@@ -715,6 +1005,8 @@ impl DnsMonitor {
         // Parse response records (A, AAAA, CNAME, etc.)
         let mut response_ips = Vec::new();
         let mut response_records = Vec::new();
+        // 应答中的 IP 及其 TTL，用于流量的域名归属
+        let mut answer_ips: Vec<(std::net::IpAddr, u32)> = Vec::new();
         if !is_query {
             // This is a response packet
             let answer_count = message.answer_count();
@@ -736,10 +1028,12 @@ impl DnsMonitor {
                 if let Some(rdata) = answer.data() {
                     match rdata {
                         RData::A(ipv4) => {
+                            answer_ips.push((std::net::IpAddr::V4(ipv4.0), answer.ttl()));
                             response_ips.push(ipv4.to_string());
                             response_records.push(format!("A:{}", ipv4));
                         }
                         RData::AAAA(ipv6) => {
+                            answer_ips.push((std::net::IpAddr::V6(ipv6.0), answer.ttl()));
                             response_ips.push(ipv6.to_string());
                             response_records.push(format!("AAAA:{}", ipv6));
                         }
@@ -848,92 +1142,200 @@ impl DnsMonitor {
             let device_ip = if is_query { src_ip } else { dst_ip };
             let (device_mac, device_name) = self.get_device_info(device_ip, ctx);
 
-            let mut record = DnsQueryRecord {
-                timestamp,
-                domain: domain_name.clone(),
-                query_type: query_type.clone(),
-                response_code: response_code.to_string(),
-                source_ip: src_ip.to_string(),
-                destination_ip: dst_ip.to_string(),
-                source_port: src_port,
-                destination_port: dst_port,
-                transaction_id,
-                is_query,
-                response_ips: response_ips.clone(),
-                response_records: response_records.clone(),
-                response_time_ms: None,
-                device_mac,
-                device_name,
+            // 隐私排除的设备（--dns-privacy-exclude）：不留下任何 DNS 活动记录，包括域名流量归属
+            let device_mac_bytes = crate::utils::network_utils::parse_mac_address(&device_mac).ok();
+            let excluded = device_mac_bytes.map(|m| ctx.privacy_excluded.contains(&m)).unwrap_or(false);
+
+            // 运行时配置中忽略的设备 / 域名：不输出、不保存，也不参与统计与告警
+            let (ignored, store_responses, max_records) = match ctx.dns_config.lock() {
+                Ok(config) => (
+                    excluded || config.is_ignored(device_mac_bytes.as_ref(), &domain_name),
+                    config.store_responses,
+                    config.max_records,
+                ),
+                Err(_) => (excluded, true, ctx.options.dns_max_records()),
             };
 
-            // Try to match with existing records and calculate response time
-            if let Ok(mut queries) = ctx.dns_queries.lock() {
-                if is_query {
-                    // 这is a query, try to find matching response (response might come after)
-                    // We'll match it when the response arrives
-                    record.response_time_ms = None;
+            // dnstap 输出：查询/响应映射为 CLIENT_QUERY/CLIENT_RESPONSE，携带原始报文与设备身份
+            if let Some(dnstap) = ctx.dnstap.as_ref().filter(|_| !ignored) {
+                let (client_ip, client_port, server_ip, server_port) = if is_query {
+                    (src_ip, src_port, dst_ip, dst_port)
                 } else {
-                    // 这is a response, try to find matching query
-                    if let Some(matching_query_idx) = queries.iter().position(|q| {
-                        // Match criteria:
-                        // 1. Transaction ID matches
-                        q.transaction_id == transaction_id &&
-                        // 2. IP addresses are swapped
-                        q.source_ip == dst_ip &&
-                        q.destination_ip == src_ip &&
-                        // 3. Ports are swapped
-                        q.source_port == dst_port &&
-                        q.destination_port == src_port &&
-                        // 4. Domain and query type match
-                        q.domain == domain_name &&
-                        q.query_type == query_type &&
-                        // 5. It's a query (not a response)
-                        q.is_query &&
-                        // 6. Response time hasn't been set yet (not needed anymore, but keep for consistency)
-                        q.response_time_ms.is_none() &&
-                        // 7. Response timestamp is after query timestamp
-                        timestamp > q.timestamp
-                    }) {
-                        // 计算response time in milliseconds
-                        let query_timestamp = queries[matching_query_idx].timestamp;
-                        // 计算response time: convert nanoseconds to milliseconds
-                        // Use floating point division then round to avoid precision loss
-                        let diff_ns = timestamp - query_timestamp;
-                        let response_time_ms = if diff_ns < 1_000_000 {
-                            // 如果difference is less than 1ms, round up to 1ms for visibility
-                            // (0ms would indicate no response matched)
-                            1
-                        } else {
-                            diff_ns / 1_000_000
-                        };
+                    (dst_ip, dst_port, src_ip, src_port)
+                };
+                if let (Ok(query_address), Ok(response_address)) =
+                    (client_ip.parse::<std::net::IpAddr>(), server_ip.parse::<std::net::IpAddr>())
+                {
+                    dnstap.log_message(&DnstapMessage {
+                        is_response: !is_query,
+                        is_tcp: protocol == "TCP",
+                        query_address,
+                        query_port: client_port,
+                        response_address,
+                        response_port: server_port,
+                        timestamp_ns: timestamp,
+                        wire: dns_data,
+                        extra: format!("mac={};hostname={}", device_mac, device_name),
+                    });
+                }
+            }
 
-                        // Don't update the query record's response_time_ms - queries should never have response time
-                        // 仅set response time for this response record
-                        record.response_time_ms = Some(response_time_ms);
+            // 记录 设备 + 应答 IP -> 域名，供流量模块归属 WAN 流量
+            if !answer_ips.is_empty() && !excluded {
+                if let Ok(mac) = crate::utils::network_utils::parse_mac_address(&device_mac) {
+                    let now_ms = std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .unwrap_or_default()
+                        .as_millis() as u64;
+                    for (ip, ttl) in answer_ips.iter() {
+                        ctx.domain_tracker.record_answer(mac, *ip, &domain_name, *ttl, now_ms);
+                    }
+                }
+            }
 
-                        log::debug!(
-                            "Matched DNS query/response: domain={}, transaction_id={}, query_ts={}ns, response_ts={}ns, diff={}ns, response_time={}ms",
-                            domain_name, transaction_id, query_timestamp, timestamp, diff_ns, response_time_ms
-                        );
-                    } else {
-                        log::debug!(
-                            "DNS response not matched: domain={}, transaction_id={}, src={}:{}, dst={}:{}",
-                            domain_name,
-                            transaction_id,
-                            src_ip,
-                            src_port,
-                            dst_ip,
-                            dst_port
-                        );
+            if !ignored {
+                let mut record = DnsQueryRecord {
+                    timestamp,
+                    domain: domain_name.clone(),
+                    query_type: query_type.clone(),
+                    response_code: response_code.to_string(),
+                    source_ip: src_ip.to_string(),
+                    destination_ip: dst_ip.to_string(),
+                    source_port: src_port,
+                    destination_port: dst_port,
+                    transaction_id,
+                    is_query,
+                    response_ips: response_ips.clone(),
+                    response_records: response_records.clone(),
+                    response_time_ms: None,
+                    device_mac,
+                    device_name,
+                    categories: ctx.category_engine.classify(&domain_name),
+                    blocked: false,
+                };
+                if is_query {
+                    if let Ok(mac) = crate::utils::network_utils::parse_mac_address(&record.device_mac) {
+                        record.blocked = ctx.block_engine.is_blocked(&mac, &domain_name);
+                        ctx.device_classifier.observe_domain(mac, &domain_name);
+                        // 记录查询发往的解析器，用于解析器合规检测
+                        if let Ok(server) = dst_ip.parse::<std::net::IpAddr>() {
+                            let now_ms = std::time::SystemTime::now()
+                                .duration_since(std::time::UNIX_EPOCH)
+                                .unwrap_or_default()
+                                .as_millis() as u64;
+                            ctx.resolver_compliance.record_dns_query(mac, src_ip, server, now_ms);
+                        }
                     }
                 }
 
-                // Store the new record (both query and response are stored)
-                queries.push(record);
-                // Keep only last N records (configurable via --dns-max-records)
-                let max_records = ctx.options.dns_max_records();
-                if queries.len() > max_records {
-                    queries.remove(0);
+                // 可疑行为检测（高熵标签、超长查询名、TXT / NXDOMAIN 突发）
+                let now_ms = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis() as u64;
+                ctx.alert_detector.observe(&record, now_ms);
+
+                // Try to match with existing records and calculate response time
+                if let Ok(mut queries) = ctx.dns_queries.lock() {
+                    if is_query {
+                        // 这is a query, try to find matching response (response might come after)
+                        // We'll match it when the response arrives
+                        record.response_time_ms = None;
+                    } else {
+                        // 这is a response, try to find matching query
+                        if let Some(matching_query_idx) = queries.iter().position(|q| {
+                            // Match criteria:
+                            // 1. Transaction ID matches
+                            q.transaction_id == transaction_id &&
+                            // 2. IP addresses are swapped
+                            q.source_ip == dst_ip &&
+                            q.destination_ip == src_ip &&
+                            // 3. Ports are swapped
+                            q.source_port == dst_port &&
+                            q.destination_port == src_port &&
+                            // 4. Domain and query type match
+                            q.domain == domain_name &&
+                            q.query_type == query_type &&
+                            // 5. It's a query (not a response)
+                            q.is_query &&
+                            // 6. Response time hasn't been set yet (not needed anymore, but keep for consistency)
+                            q.response_time_ms.is_none() &&
+                            // 7. Response timestamp is after query timestamp
+                            timestamp > q.timestamp
+                        }) {
+                            // 计算response time in milliseconds
+                            let query_timestamp = queries[matching_query_idx].timestamp;
+                            // 计算response time: convert nanoseconds to milliseconds
+                            // Use floating point division then round to avoid precision loss
+                            let diff_ns = timestamp - query_timestamp;
+                            let response_time_ms = if diff_ns < 1_000_000 {
+                                // 如果difference is less than 1ms, round up to 1ms for visibility
+                                // (0ms would indicate no response matched)
+                                1
+                            } else {
+                                diff_ns / 1_000_000
+                            };
+
+                            // Don't update the query record's response_time_ms - queries should never have response time
+                            // 仅set response time for this response record
+                            record.response_time_ms = Some(response_time_ms);
+
+                            log::debug!(
+                                "Matched DNS query/response: domain={}, transaction_id={}, query_ts={}ns, response_ts={}ns, diff={}ns, response_time={}ms",
+                                domain_name, transaction_id, query_timestamp, timestamp, diff_ns, response_time_ms
+                            );
+                        } else {
+                            log::debug!(
+                                "DNS response not matched: domain={}, transaction_id={}, src={}:{}, dst={}:{}",
+                                domain_name,
+                                transaction_id,
+                                src_ip,
+                                src_port,
+                                dst_ip,
+                                dst_port
+                            );
+                        }
+                    }
+
+                    // 每设备小时指标（响应延迟在上面匹配查询后才可知）
+                    if let Ok(mac) = crate::utils::network_utils::parse_mac_address(&record.device_mac) {
+                        if is_query {
+                            ctx.dns_metrics.record_query(mac, &record.domain, now_ms);
+                        } else {
+                            let nxdomain = matches!(message.response_code(), ResponseCode::NXDomain);
+                            ctx.dns_metrics.record_response(mac, nxdomain, record.response_time_ms, now_ms);
+                        }
+                    }
+
+                    // 关闭 store_responses 时只保存查询；响应仍用于上面的延迟计算
+                    if is_query || store_responses {
+                        // 响应记录的应答 IP 加入反向索引，与 dns_queries 保持同步
+                        if let Ok(mut index) = ctx.dns_ip_index.lock() {
+                            index_answer_ips(&mut index, &record);
+                        }
+
+                        // Store the new record (both query and response are stored)
+                        queries.push(record);
+                    } else if !record.response_ips.is_empty() {
+                        // 不保存的响应仍加入反向索引（/api/dns/lookup 依赖它），单独按 max_records 淘汰
+                        if let Ok(mut index) = ctx.dns_ip_index.lock() {
+                            index_answer_ips(&mut index, &record);
+                            if let Ok(mut unstored) = ctx.unstored_responses.lock() {
+                                unstored.push_back(record);
+                                while unstored.len() > max_records {
+                                    if let Some(evicted) = unstored.pop_front() {
+                                        unindex_answer_ips(&mut index, &evicted);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    // Keep only last N records (--dns-max-records, adjustable via /api/dns/config)
+                    if queries.len() > max_records {
+                        if let Ok(mut index) = ctx.dns_ip_index.lock() {
+                            trim_records(&mut queries, max_records, &mut index);
+                        }
+                    }
                 }
             }
         }
@@ -1001,9 +1403,476 @@ impl DnsMonitor {
     }
 }
 
+// TCP 标志位
+const TCP_FLAG_FIN: u8 = 0x01;
+const TCP_FLAG_RST: u8 = 0x04;
+// TCP DNS 重组缓冲区限制
+const TCP_MAX_STREAMS: usize = 256;
+const TCP_MAX_BUFFERED_BYTES: usize = 4 * 1024 * 1024;
+const TCP_STREAM_TIMEOUT_NS: u64 = 30_000_000_000;
+
+/// TCP 连接标识：(源 IP, 源端口, 目的 IP, 目的端口)
+pub type TcpFlowKey = (String, u16, String, u16);
+
+/// 从数据包中取出的一个 TCP 分段
+struct TcpSegment<'a> {
+    seq: u32,
+    flags: u8,
+    payload: &'a [u8],
+    truncated: bool, // eBPF 只复制了报文的前一部分
+}
+
+/// 单个方向的 TCP 字节流缓冲
+struct TcpStreamBuffer {
+    next_seq: u32,  // 期望的下一个序列号
+    buf: Vec<u8>,   // 尚未组成完整消息的数据（含 2 字节长度前缀）
+    synced: bool,   // 已对齐到消息边界；新连接或丢段后为 false
+    last_seen: u64, // 最后一个分段的时间戳（单调时间，纳秒）
+}
+
+/// TCP DNS 重组器
+/// - 按连接方向缓存分段，依据 2 字节长度前缀切分出完整 DNS 消息
+/// - 处理重传（重叠部分丢弃）；出现丢段时丢弃缓存，等待下一条消息的开头重新同步
+/// - 连接数、缓存总字节数与空闲时间都有上限
+pub struct TcpDnsReassembler {
+    streams: std::collections::HashMap<TcpFlowKey, TcpStreamBuffer>,
+    buffered: usize,
+}
+
+impl TcpDnsReassembler {
+    pub fn new() -> Self {
+        Self {
+            streams: std::collections::HashMap::new(),
+            buffered: 0,
+        }
+    }
+
+    /// 加入一个分段，返回已完整的 DNS 消息（不含长度前缀）
+    pub fn push(&mut self, key: TcpFlowKey, seq: u32, payload: &[u8], closing: bool, timestamp: u64) -> Vec<Vec<u8>> {
+        self.expire(timestamp);
+
+        let mut stream = match self.streams.remove(&key) {
+            Some(stream) => {
+                self.buffered -= stream.buf.len();
+                stream
+            }
+            None => TcpStreamBuffer {
+                next_seq: seq,
+                buf: Vec::new(),
+                synced: false,
+                last_seen: timestamp,
+            },
+        };
+        stream.last_seen = timestamp;
+
+        // 与期望序列号的距离（考虑回绕）：> 0 为丢段，< 0 为重传
+        let distance = seq.wrapping_sub(stream.next_seq) as i32;
+        let data: &[u8] = if distance > 0 {
+            stream.buf.clear();
+            stream.synced = false;
+            stream.next_seq = seq;
+            payload
+        } else if distance < 0 {
+            let overlap = distance.unsigned_abs() as usize;
+            if overlap >= payload.len() {
+                &[]
+            } else {
+                &payload[overlap..]
+            }
+        } else {
+            payload
+        };
+
+        // 未对齐时只接受看起来像消息开头的数据（新连接、丢段后重新同步）
+        if !stream.synced && !data.is_empty() {
+            stream.synced = looks_like_message_start(data);
+        }
+        if stream.synced {
+            stream.buf.extend_from_slice(data);
+        }
+        stream.next_seq = stream.next_seq.wrapping_add(data.len() as u32);
+
+        let messages = split_messages(&mut stream.buf);
+
+        if !closing {
+            self.buffered += stream.buf.len();
+            self.streams.insert(key, stream);
+            self.enforce_limits();
+        }
+
+        messages
+    }
+
+    /// 丢弃一个方向的缓存，之后从下一条消息的开头重新同步
+    pub fn drop_stream(&mut self, key: &TcpFlowKey) {
+        if let Some(stream) = self.streams.remove(key) {
+            self.buffered -= stream.buf.len();
+        }
+    }
+
+    fn expire(&mut self, now: u64) {
+        let before = self.streams.len();
+        self.streams
+            .retain(|_, stream| now.saturating_sub(stream.last_seen) < TCP_STREAM_TIMEOUT_NS);
+        if self.streams.len() != before {
+            self.buffered = self.streams.values().map(|s| s.buf.len()).sum();
+        }
+    }
+
+    /// 超出连接数或内存上限时，淘汰最久未活动的连接
+    fn enforce_limits(&mut self) {
+        while self.streams.len() > TCP_MAX_STREAMS || self.buffered > TCP_MAX_BUFFERED_BYTES {
+            let oldest = match self.streams.iter().min_by_key(|(_, s)| s.last_seen) {
+                Some((key, _)) => key.clone(),
+                None => break,
+            };
+            if let Some(stream) = self.streams.remove(&oldest) {
+                self.buffered -= stream.buf.len();
+            }
+        }
+    }
+}
+
+/// 从缓冲区切分出所有完整的消息
+fn split_messages(buf: &mut Vec<u8>) -> Vec<Vec<u8>> {
+    let mut messages = Vec::new();
+    let mut consumed = 0;
+    while buf.len() - consumed >= 2 {
+        let len = u16::from_be_bytes([buf[consumed], buf[consumed + 1]]) as usize;
+        if buf.len() - consumed - 2 < len {
+            break;
+        }
+        if len > 0 {
+            messages.push(buf[consumed + 2..consumed + 2 + len].to_vec());
+        }
+        consumed += 2 + len;
+    }
+    buf.drain(..consumed);
+    messages
+}
+
+/// 判断数据是否以 "长度前缀 + DNS 头" 开始（与 eBPF 的头部校验一致）
+fn looks_like_message_start(data: &[u8]) -> bool {
+    if data.len() < 2 + 12 {
+        return false;
+    }
+    let len = u16::from_be_bytes([data[0], data[1]]) as usize;
+    let flags = u16::from_be_bytes([data[4], data[5]]);
+    let opcode = (flags >> 11) & 0x0F;
+    let rcode = flags & 0x0F;
+    let qdcount = u16::from_be_bytes([data[6], data[7]]);
+    len >= 12 && opcode <= 5 && (rcode <= 5 || rcode == 9 || rcode == 10) && qdcount <= 1
+}
+
+/// 异步 POST 一个事件到 --traffic-event-url，失败只记录日志
+fn post_event<T: serde::Serialize + Send + 'static>(http: &reqwest::Client, url: &str, payload: T) {
+    let url = url.to_string();
+    let client = http.clone();
+    tokio::spawn(async move {
+        match client.post(&url).json(&crate::privacy::export_value(&payload)).send().await {
+            Ok(resp) => {
+                if !resp.status().is_success() {
+                    log::warn!("DNS event export got HTTP {} from {}", resp.status(), url);
+                }
+            }
+            Err(e) => {
+                log::warn!("DNS event export failed: {}", e);
+            }
+        }
+    });
+}
+
+/// 可疑 DNS 告警事件
+#[derive(serde::Serialize)]
+struct DnsAlertEventPayload {
+    ts_ms: u64,
+    event: String,
+    mac: String,
+    ip: String,
+    hostname: String,
+    kind: String,
+    domain: String,
+    detail: String,
+    value: f64,
+    threshold: f64,
+}
+
+/// 解析器绕过事件，格式与设备上下线事件一致
+#[derive(serde::Serialize)]
+struct ResolverBypassEventPayload {
+    ts_ms: u64,
+    event: String,
+    mac: String,
+    ip: String,
+    hostname: String,
+    kind: String,
+    server: String,
+    detail: String,
+}
+
+/// 将响应记录中的应答 IP 加入反向索引
+pub fn index_answer_ips(index: &mut std::collections::HashMap<String, Vec<DnsIpIndexEntry>>, record: &DnsQueryRecord) {
+    if record.is_query {
+        return;
+    }
+    for ip in record.response_ips.iter() {
+        index.entry(ip.clone()).or_default().push(DnsIpIndexEntry {
+            timestamp: record.timestamp,
+            domain: record.domain.clone(),
+            device_mac: record.device_mac.clone(),
+            device_name: record.device_name.clone(),
+        });
+    }
+}
+
+/// 记录从 dns_queries 中淘汰时，同步移除其反向索引条目
+pub fn unindex_answer_ips(index: &mut std::collections::HashMap<String, Vec<DnsIpIndexEntry>>, record: &DnsQueryRecord) {
+    if record.is_query {
+        return;
+    }
+    for ip in record.response_ips.iter() {
+        if let Some(entries) = index.get_mut(ip) {
+            if let Some(pos) = entries
+                .iter()
+                .position(|e| e.timestamp == record.timestamp && e.domain == record.domain && e.device_mac == record.device_mac)
+            {
+                entries.remove(pos);
+            }
+            if entries.is_empty() {
+                index.remove(ip);
+            }
+        }
+    }
+}
+
+/// 只保留最近的 max_records 条记录，淘汰的记录同步移除其反向索引条目
+pub fn trim_records(
+    queries: &mut Vec<DnsQueryRecord>,
+    max_records: usize,
+    index: &mut std::collections::HashMap<String, Vec<DnsIpIndexEntry>>,
+) {
+    let overflow = queries.len().saturating_sub(max_records);
+    for evicted in queries.drain(..overflow) {
+        unindex_answer_ips(index, &evicted);
+    }
+}
+
 /// Flush DNS cache to disk
 pub async fn flush() -> Result<(), anyhow::Error> {
     log::info!("DNS cache flushed");
     // TODO: Implement actual flush logic
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 抓包得到的 example.com A 查询（事务 ID 0xabcd）
+    const QUERY_EXAMPLE_COM: [u8; 29] = [
+        0xab, 0xcd, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x07, b'e', b'x', b'a', b'm', b'p', b'l',
+        b'e', 0x03, b'c', b'o', b'm', 0x00, 0x00, 0x01, 0x00, 0x01,
+    ];
+
+    /// example.com TXT 响应，TXT 数据总长 txt_len 字节（大于单个分段）
+    fn txt_response(txt_len: usize) -> Vec<u8> {
+        let mut rdata = Vec::new();
+        let mut remaining = txt_len;
+        while remaining > 0 {
+            let chunk = remaining.min(255);
+            rdata.push(chunk as u8);
+            rdata.extend(std::iter::repeat_n(b'a', chunk));
+            remaining -= chunk;
+        }
+
+        let mut msg = vec![0xab, 0xcd, 0x81, 0x80, 0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00];
+        msg.extend_from_slice(&QUERY_EXAMPLE_COM[12..]);
+        let len = msg.len();
+        msg[len - 3] = 0x10; // QTYPE = TXT
+        msg.extend_from_slice(&[0xc0, 0x0c, 0x00, 0x10, 0x00, 0x01, 0x00, 0x00, 0x0e, 0x10]);
+        msg.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        msg.extend_from_slice(&rdata);
+        msg
+    }
+
+    fn with_length_prefix(msg: &[u8]) -> Vec<u8> {
+        let mut out = (msg.len() as u16).to_be_bytes().to_vec();
+        out.extend_from_slice(msg);
+        out
+    }
+
+    fn flow(port: u16) -> TcpFlowKey {
+        ("192.168.1.1".to_string(), 53, "192.168.1.10".to_string(), port)
+    }
+
+    #[test]
+    fn test_tcp_reassembly_multi_segment_response() {
+        let response = txt_response(3000);
+        let stream = with_length_prefix(&response);
+        let mut reassembler = TcpDnsReassembler::new();
+
+        let mut seq: u32 = 1000;
+        let mut messages = Vec::new();
+        for segment in stream.chunks(1448) {
+            messages.extend(reassembler.push(flow(40000), seq, segment, false, 1));
+            seq = seq.wrapping_add(segment.len() as u32);
+        }
+
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0], response);
+        assert_eq!(reassembler.buffered, 0);
+
+        let message = Message::from_bytes(&messages[0]).unwrap();
+        assert_eq!(message.answer_count(), 1);
+    }
+
+    #[test]
+    fn test_tcp_reassembly_retransmit_and_pipelined() {
+        let mut reassembler = TcpDnsReassembler::new();
+        let mut stream = with_length_prefix(&QUERY_EXAMPLE_COM);
+        stream.extend(with_length_prefix(&QUERY_EXAMPLE_COM));
+        // 长度前缀被拆在两个分段之间
+        let third = with_length_prefix(&QUERY_EXAMPLE_COM);
+
+        let key = flow(40001);
+        let seq = u32::MAX - 10; // 覆盖序列号回绕
+        assert_eq!(reassembler.push(key.clone(), seq, &stream, false, 1).len(), 2);
+        // 重传的分段不会重复输出
+        assert!(reassembler.push(key.clone(), seq, &stream, false, 2).is_empty());
+
+        let next = seq.wrapping_add(stream.len() as u32);
+        assert!(reassembler.push(key.clone(), next, &third[..1], false, 3).is_empty());
+        let messages = reassembler.push(key, next.wrapping_add(1), &third[1..], false, 4);
+        assert_eq!(messages, vec![QUERY_EXAMPLE_COM.to_vec()]);
+    }
+
+    #[test]
+    fn test_tcp_reassembly_resyncs_after_gap() {
+        let response = with_length_prefix(&txt_response(3000));
+        let mut reassembler = TcpDnsReassembler::new();
+        let key = flow(40002);
+
+        assert!(reassembler.push(key.clone(), 0, &response[..1000], false, 1).is_empty());
+        // 丢失 1000..2000，收到的中间分段无法组成消息
+        assert!(reassembler.push(key.clone(), 2000, &response[2000..], false, 2).is_empty());
+        assert_eq!(reassembler.buffered, 0);
+
+        // 下一条消息从头开始，重新同步
+        let next = 2000 + (response.len() - 2000) as u32;
+        let query = with_length_prefix(&QUERY_EXAMPLE_COM);
+        let messages = reassembler.push(key, next, &query, false, 3);
+        assert_eq!(messages, vec![QUERY_EXAMPLE_COM.to_vec()]);
+    }
+
+    #[test]
+    fn test_tcp_reassembly_drops_truncated_stream() {
+        let response = with_length_prefix(&txt_response(3000));
+        let mut reassembler = TcpDnsReassembler::new();
+        let key = flow(40003);
+
+        assert!(reassembler.push(key.clone(), 0, &response[..1000], false, 1).is_empty());
+        // 下一个分段被 eBPF 截断：丢弃缓存，剩余分段不会拼出错误的消息
+        reassembler.drop_stream(&key);
+        assert_eq!(reassembler.buffered, 0);
+        assert!(reassembler.push(key.clone(), 2500, &response[2500..], false, 2).is_empty());
+
+        let next = response.len() as u32;
+        let query = with_length_prefix(&QUERY_EXAMPLE_COM);
+        let messages = reassembler.push(key, next, &query, false, 3);
+        assert_eq!(messages, vec![QUERY_EXAMPLE_COM.to_vec()]);
+    }
+
+    #[test]
+    fn test_tcp_reassembly_bounded() {
+        let response = with_length_prefix(&txt_response(3000));
+        let mut reassembler = TcpDnsReassembler::new();
+
+        for port in 0..(TCP_MAX_STREAMS as u16 + 50) {
+            reassembler.push(flow(port), 0, &response[..1000], false, port as u64);
+        }
+        assert_eq!(reassembler.streams.len(), TCP_MAX_STREAMS);
+        assert_eq!(reassembler.buffered, TCP_MAX_STREAMS * 1000);
+
+        // FIN/RST 释放连接，超时的连接被清理
+        reassembler.push(flow(300), 1000, &response[1000..2000], true, 400);
+        assert_eq!(reassembler.streams.len(), TCP_MAX_STREAMS - 1);
+        reassembler.push(flow(1), 0, &[], false, 400 + TCP_STREAM_TIMEOUT_NS);
+        assert_eq!(reassembler.streams.len(), 1);
+        assert_eq!(reassembler.buffered, 0);
+    }
+
+    fn answer(timestamp: u64, domain: &str, mac: &str, ips: &[&str]) -> DnsQueryRecord {
+        DnsQueryRecord {
+            timestamp,
+            domain: domain.to_string(),
+            query_type: "A".to_string(),
+            response_code: "Success".to_string(),
+            source_ip: "192.168.1.1".to_string(),
+            destination_ip: "192.168.1.10".to_string(),
+            source_port: 53,
+            destination_port: 40000,
+            transaction_id: 0,
+            is_query: false,
+            response_ips: ips.iter().map(|ip| ip.to_string()).collect(),
+            response_records: Vec::new(),
+            response_time_ms: None,
+            device_mac: mac.to_string(),
+            device_name: String::new(),
+            categories: Vec::new(),
+            blocked: false,
+        }
+    }
+
+    #[test]
+    fn test_answer_ip_index() {
+        let mut index = std::collections::HashMap::new();
+
+        // 查询记录没有应答 IP，不加入索引
+        let mut query = answer(1, "example.com", "00:11:22:33:44:55", &["93.184.216.34"]);
+        query.is_query = true;
+        index_answer_ips(&mut index, &query);
+        assert!(index.is_empty());
+
+        let first = answer(2, "example.com", "00:11:22:33:44:55", &["93.184.216.34", "2606:2800::1"]);
+        let second = answer(3, "example.com", "00:11:22:33:44:66", &["93.184.216.34"]);
+        index_answer_ips(&mut index, &first);
+        index_answer_ips(&mut index, &second);
+        assert_eq!(index["93.184.216.34"].len(), 2);
+        assert_eq!(index["2606:2800::1"].len(), 1);
+
+        // 移除一条记录只删除它自己的条目，IP 没有条目后从索引中删除
+        unindex_answer_ips(&mut index, &first);
+        assert_eq!(index["93.184.216.34"].len(), 1);
+        assert_eq!(index["93.184.216.34"][0].device_mac, "00:11:22:33:44:66");
+        assert!(!index.contains_key("2606:2800::1"));
+        unindex_answer_ips(&mut index, &second);
+        assert!(index.is_empty());
+    }
+
+    #[test]
+    fn test_trim_records_unindexes_evicted() {
+        let mut queries: Vec<DnsQueryRecord> = Vec::new();
+        let mut index = std::collections::HashMap::new();
+        for (ts, domain, ip) in [(1, "a.com", "10.0.0.1"), (2, "b.com", "10.0.0.2"), (3, "c.com", "10.0.0.1")] {
+            let record = answer(ts, domain, "00:11:22:33:44:55", &[ip]);
+            index_answer_ips(&mut index, &record);
+            queries.push(record);
+        }
+
+        // 未超过上限时不淘汰
+        trim_records(&mut queries, 3, &mut index);
+        assert_eq!(queries.len(), 3);
+        assert_eq!(index["10.0.0.1"].len(), 2);
+
+        // 淘汰最早的记录，只移除它自己的索引条目
+        trim_records(&mut queries, 2, &mut index);
+        assert_eq!(queries.iter().map(|q| q.timestamp).collect::<Vec<_>>(), vec![2, 3]);
+        assert_eq!(index["10.0.0.1"].len(), 1);
+        assert_eq!(index["10.0.0.1"][0].domain, "c.com");
+        assert_eq!(index["10.0.0.2"][0].domain, "b.com");
+
+        trim_records(&mut queries, 0, &mut index);
+        assert!(queries.is_empty());
+        assert!(index.is_empty());
+    }
+}
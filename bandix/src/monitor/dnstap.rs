@@ -0,0 +1,441 @@
+use crate::command::Options;
+use std::io::{BufWriter, Read, Write};
+use std::net::IpAddr;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, RecvTimeoutError, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// Frame Streams 内容类型
+const CONTENT_TYPE: &[u8] = b"protobuf:dnstap.Dnstap";
+// Frame Streams 控制帧类型
+const CONTROL_ACCEPT: u32 = 0x01;
+const CONTROL_START: u32 = 0x02;
+const CONTROL_STOP: u32 = 0x03;
+const CONTROL_READY: u32 = 0x04;
+const CONTROL_FIELD_CONTENT_TYPE: u32 = 0x01;
+// 控制帧最大长度（规范要求接收方至少支持 512 字节）
+const MAX_CONTROL_FRAME_LEN: usize = 512;
+
+// dnstap.proto 中的枚举值
+const DNSTAP_TYPE_MESSAGE: u64 = 1;
+const MESSAGE_TYPE_CLIENT_QUERY: u64 = 5;
+const MESSAGE_TYPE_CLIENT_RESPONSE: u64 = 6;
+const SOCKET_FAMILY_INET: u64 = 1;
+const SOCKET_FAMILY_INET6: u64 = 2;
+const SOCKET_PROTOCOL_UDP: u64 = 1;
+const SOCKET_PROTOCOL_TCP: u64 = 2;
+
+// 写线程队列长度，队列满时丢弃新消息而不阻塞 DNS 处理
+const QUEUE_CAPACITY: usize = 4096;
+// 保留的轮转文件数（<file>.1 ... <file>.N）
+const ROTATED_FILES: usize = 5;
+// Unix socket 断开后的重连间隔
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// dnstap 输出目标
+#[derive(Debug, Clone)]
+pub enum DnstapSink {
+    Socket(PathBuf),                        // Frame Streams 双向模式（READY/ACCEPT/START）
+    File { path: PathBuf, max_bytes: u64 }, // Frame Streams 单向模式，超过大小后轮转
+}
+
+/// 一条待输出的 DNS 消息
+pub struct DnstapMessage<'a> {
+    pub is_response: bool,
+    pub is_tcp: bool,
+    pub query_address: IpAddr, // 客户端（设备）地址
+    pub query_port: u16,
+    pub response_address: IpAddr, // 解析器地址
+    pub response_port: u16,
+    pub timestamp_ns: u64, // PacketHeader.timestamp（单调时间，纳秒）
+    pub wire: &'a [u8],    // DNS 原始报文
+    pub extra: String,     // 设备身份信息，写入 Dnstap.extra
+}
+
+/// dnstap 编码器与写线程
+/// - DNS 监控循环调用 log_message 编码并入队，不做任何 IO
+/// - 后台线程负责 Frame Streams 握手、写入、文件轮转与 socket 重连
+pub struct DnstapWriter {
+    tx: SyncSender<Vec<u8>>,
+    identity: Vec<u8>,
+    version: Vec<u8>,
+    boot_time_offset_ns: u64, // 单调时间 -> Unix 时间的偏移量
+    dropped: AtomicU64,
+}
+
+impl DnstapWriter {
+    /// 根据命令行参数创建写入器，未配置或启动失败时返回 None
+    pub fn from_options(options: &Options) -> Option<Arc<Self>> {
+        let sink = if !options.dns_dnstap_socket().trim().is_empty() {
+            DnstapSink::Socket(PathBuf::from(options.dns_dnstap_socket().trim()))
+        } else if !options.dns_dnstap_file().trim().is_empty() {
+            DnstapSink::File {
+                path: PathBuf::from(options.dns_dnstap_file().trim()),
+                max_bytes: options.dns_dnstap_file_max_mb().max(1) * 1024 * 1024,
+            }
+        } else {
+            return None;
+        };
+
+        match Self::start(sink.clone()) {
+            Ok(writer) => {
+                log::info!("dnstap output enabled: {:?}", sink);
+                Some(writer)
+            }
+            Err(e) => {
+                log::warn!("Failed to start dnstap output {:?}: {}", sink, e);
+                None
+            }
+        }
+    }
+
+    pub fn start(sink: DnstapSink) -> Result<Arc<Self>, anyhow::Error> {
+        let (tx, rx) = sync_channel::<Vec<u8>>(QUEUE_CAPACITY);
+
+        std::thread::Builder::new()
+            .name("bandix-dnstap".to_string())
+            .spawn(move || match sink {
+                DnstapSink::Socket(path) => run_socket_writer(&path, rx),
+                DnstapSink::File { path, max_bytes } => run_file_writer(&path, max_bytes, rx),
+            })?;
+
+        let identity = std::fs::read_to_string("/proc/sys/kernel/hostname")
+            .map(|s| s.trim().to_string())
+            .unwrap_or_else(|_| "bandix".to_string());
+
+        Ok(Arc::new(Self {
+            tx,
+            identity: identity.into_bytes(),
+            version: format!("bandix {}", env!("CARGO_PKG_VERSION")).into_bytes(),
+            boot_time_offset_ns: boot_time_offset_ns(),
+            dropped: AtomicU64::new(0),
+        }))
+    }
+
+    /// 编码并入队一条消息，队列满时丢弃
+    pub fn log_message(&self, msg: &DnstapMessage) {
+        let unix_ns = msg.timestamp_ns.saturating_add(self.boot_time_offset_ns);
+        let frame = encode_dnstap(&self.identity, &self.version, msg, unix_ns);
+
+        match self.tx.try_send(frame) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => {
+                let dropped = self.dropped.fetch_add(1, Ordering::Relaxed) + 1;
+                if dropped % 1000 == 1 {
+                    log::warn!("dnstap queue full, {} messages dropped so far", dropped);
+                }
+            }
+            Err(TrySendError::Disconnected(_)) => {}
+        }
+    }
+}
+
+/// 与 DNS API 相同：通过 /proc/uptime 计算启动时刻的 Unix 时间
+fn boot_time_offset_ns() -> u64 {
+    let now_unix_ns = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64;
+    if let Ok(content) = std::fs::read_to_string("/proc/uptime") {
+        if let Some(uptime) = content.split_whitespace().next().and_then(|s| s.parse::<f64>().ok()) {
+            return now_unix_ns.saturating_sub((uptime * 1_000_000_000.0) as u64);
+        }
+    }
+    now_unix_ns
+}
+
+/// 单向模式：文件以 START 开头，轮转前写入 STOP
+fn run_file_writer(path: &Path, max_bytes: u64, rx: Receiver<Vec<u8>>) {
+    let open = |path: &Path| -> std::io::Result<BufWriter<std::fs::File>> {
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+        // 已存在的文件无法继续追加（需要以 START 开头），先轮转
+        if path.exists() {
+            rotate_files(path);
+        }
+        let mut writer = BufWriter::new(std::fs::File::create(path)?);
+        writer.write_all(&control_frame(CONTROL_START, true))?;
+        Ok(writer)
+    };
+
+    let mut writer = match open(path) {
+        Ok(w) => w,
+        Err(e) => {
+            log::warn!("Failed to open dnstap file {:?}: {}", path, e);
+            return;
+        }
+    };
+    let mut written: u64 = 0;
+
+    while let Some(frame) = next_frame(&rx, &mut writer) {
+        if let Err(e) = writer.write_all(&data_frame(&frame)) {
+            log::warn!("Failed to write dnstap file {:?}: {}", path, e);
+            continue;
+        }
+        written += frame.len() as u64 + 4;
+
+        if written >= max_bytes {
+            let _ = writer.write_all(&control_frame(CONTROL_STOP, false));
+            let _ = writer.flush();
+            writer = match open(path) {
+                Ok(w) => w,
+                Err(e) => {
+                    log::warn!("Failed to rotate dnstap file {:?}: {}", path, e);
+                    return;
+                }
+            };
+            written = 0;
+        }
+    }
+
+    let _ = writer.write_all(&control_frame(CONTROL_STOP, false));
+    let _ = writer.flush();
+}
+
+/// 双向模式：READY -> ACCEPT -> START，断开后定期重连，断开期间的消息被丢弃
+fn run_socket_writer(path: &Path, rx: Receiver<Vec<u8>>) {
+    loop {
+        let mut writer = match connect_socket(path) {
+            Ok(w) => {
+                log::info!("dnstap connected to {:?}", path);
+                w
+            }
+            Err(e) => {
+                log::debug!("dnstap connect to {:?} failed: {}", path, e);
+                // 丢弃重连等待期间的消息，避免阻塞 DNS 处理
+                let deadline = std::time::Instant::now() + RECONNECT_INTERVAL;
+                loop {
+                    let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+                    if remaining.is_zero() {
+                        break;
+                    }
+                    match rx.recv_timeout(remaining) {
+                        Ok(_) => {}
+                        Err(RecvTimeoutError::Timeout) => break,
+                        Err(RecvTimeoutError::Disconnected) => return,
+                    }
+                }
+                continue;
+            }
+        };
+
+        loop {
+            let frame = match next_frame(&rx, &mut writer) {
+                Some(frame) => frame,
+                None => {
+                    let _ = writer.write_all(&control_frame(CONTROL_STOP, false));
+                    let _ = writer.flush();
+                    return;
+                }
+            };
+            if let Err(e) = writer.write_all(&data_frame(&frame)) {
+                log::warn!("dnstap write to {:?} failed: {}, reconnecting", path, e);
+                break;
+            }
+        }
+    }
+}
+
+fn connect_socket(path: &Path) -> std::io::Result<BufWriter<UnixStream>> {
+    let mut stream = UnixStream::connect(path)?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    stream.write_all(&control_frame(CONTROL_READY, true))?;
+
+    // 读取 ACCEPT 控制帧
+    let mut header = [0u8; 8];
+    stream.read_exact(&mut header)?;
+    let escape = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+    let len = u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize;
+    if escape != 0 || !(4..=MAX_CONTROL_FRAME_LEN).contains(&len) {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "invalid control frame"));
+    }
+    let mut body = vec![0u8; len];
+    stream.read_exact(&mut body)?;
+    if u32::from_be_bytes([body[0], body[1], body[2], body[3]]) != CONTROL_ACCEPT {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "expected ACCEPT control frame"));
+    }
+
+    let mut writer = BufWriter::new(stream);
+    writer.write_all(&control_frame(CONTROL_START, true))?;
+    writer.flush()?;
+    Ok(writer)
+}
+
+/// 取下一条消息；队列暂时为空时先 flush 再阻塞等待，发送端全部关闭时返回 None
+fn next_frame<W: Write>(rx: &Receiver<Vec<u8>>, writer: &mut W) -> Option<Vec<u8>> {
+    match rx.try_recv() {
+        Ok(frame) => Some(frame),
+        Err(_) => {
+            let _ = writer.flush();
+            rx.recv().ok()
+        }
+    }
+}
+
+fn rotate_files(path: &Path) {
+    let rotated = |n: usize| PathBuf::from(format!("{}.{}", path.display(), n));
+    let _ = std::fs::remove_file(rotated(ROTATED_FILES));
+    for n in (1..ROTATED_FILES).rev() {
+        let _ = std::fs::rename(rotated(n), rotated(n + 1));
+    }
+    let _ = std::fs::rename(path, rotated(1));
+}
+
+/// Frame Streams 数据帧：4 字节大端长度 + 内容
+fn data_frame(payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(payload.len() + 4);
+    out.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    out.extend_from_slice(payload);
+    out
+}
+
+/// Frame Streams 控制帧：4 字节 0（转义）+ 4 字节长度 + 控制类型 [+ CONTENT_TYPE 字段]
+fn control_frame(control_type: u32, with_content_type: bool) -> Vec<u8> {
+    let mut body = Vec::new();
+    body.extend_from_slice(&control_type.to_be_bytes());
+    if with_content_type {
+        body.extend_from_slice(&CONTROL_FIELD_CONTENT_TYPE.to_be_bytes());
+        body.extend_from_slice(&(CONTENT_TYPE.len() as u32).to_be_bytes());
+        body.extend_from_slice(CONTENT_TYPE);
+    }
+
+    let mut out = Vec::with_capacity(body.len() + 8);
+    out.extend_from_slice(&0u32.to_be_bytes());
+    out.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    out.extend_from_slice(&body);
+    out
+}
+
+/// 按 dnstap.proto 编码一条 Dnstap 消息（protobuf）
+fn encode_dnstap(identity: &[u8], version: &[u8], msg: &DnstapMessage, unix_ns: u64) -> Vec<u8> {
+    let secs = unix_ns / 1_000_000_000;
+    let nsecs = (unix_ns % 1_000_000_000) as u32;
+
+    let mut message = Vec::with_capacity(msg.wire.len() + 64);
+    let message_type = if msg.is_response {
+        MESSAGE_TYPE_CLIENT_RESPONSE
+    } else {
+        MESSAGE_TYPE_CLIENT_QUERY
+    };
+    put_varint_field(&mut message, 1, message_type);
+    put_varint_field(
+        &mut message,
+        2,
+        if msg.query_address.is_ipv6() { SOCKET_FAMILY_INET6 } else { SOCKET_FAMILY_INET },
+    );
+    put_varint_field(
+        &mut message,
+        3,
+        if msg.is_tcp { SOCKET_PROTOCOL_TCP } else { SOCKET_PROTOCOL_UDP },
+    );
+    put_bytes_field(&mut message, 4, &ip_bytes(&msg.query_address));
+    put_bytes_field(&mut message, 5, &ip_bytes(&msg.response_address));
+    put_varint_field(&mut message, 6, msg.query_port as u64);
+    put_varint_field(&mut message, 7, msg.response_port as u64);
+    if msg.is_response {
+        put_varint_field(&mut message, 12, secs);
+        put_fixed32_field(&mut message, 13, nsecs);
+        put_bytes_field(&mut message, 14, msg.wire);
+    } else {
+        put_varint_field(&mut message, 8, secs);
+        put_fixed32_field(&mut message, 9, nsecs);
+        put_bytes_field(&mut message, 10, msg.wire);
+    }
+
+    let mut out = Vec::with_capacity(message.len() + identity.len() + version.len() + msg.extra.len() + 16);
+    put_bytes_field(&mut out, 1, identity);
+    put_bytes_field(&mut out, 2, version);
+    if !msg.extra.is_empty() {
+        put_bytes_field(&mut out, 3, msg.extra.as_bytes());
+    }
+    put_bytes_field(&mut out, 14, &message);
+    put_varint_field(&mut out, 15, DNSTAP_TYPE_MESSAGE);
+    out
+}
+
+fn ip_bytes(ip: &IpAddr) -> Vec<u8> {
+    match ip {
+        IpAddr::V4(v4) => v4.octets().to_vec(),
+        IpAddr::V6(v6) => v6.octets().to_vec(),
+    }
+}
+
+fn put_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+fn put_varint_field(buf: &mut Vec<u8>, field: u32, value: u64) {
+    put_varint(buf, (field as u64) << 3);
+    put_varint(buf, value);
+}
+
+fn put_fixed32_field(buf: &mut Vec<u8>, field: u32, value: u32) {
+    put_varint(buf, ((field as u64) << 3) | 5);
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_bytes_field(buf: &mut Vec<u8>, field: u32, value: &[u8]) {
+    put_varint(buf, ((field as u64) << 3) | 2);
+    put_varint(buf, value.len() as u64);
+    buf.extend_from_slice(value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_control_frame_layout() {
+        let frame = control_frame(CONTROL_START, true);
+        assert_eq!(&frame[0..4], &[0, 0, 0, 0]);
+        let len = u32::from_be_bytes([frame[4], frame[5], frame[6], frame[7]]) as usize;
+        assert_eq!(len, frame.len() - 8);
+        assert_eq!(&frame[8..12], &CONTROL_START.to_be_bytes());
+        assert_eq!(&frame[12..16], &CONTROL_FIELD_CONTENT_TYPE.to_be_bytes());
+        assert_eq!(&frame[20..], CONTENT_TYPE);
+
+        assert_eq!(control_frame(CONTROL_STOP, false), vec![0, 0, 0, 0, 0, 0, 0, 4, 0, 0, 0, 3]);
+    }
+
+    #[test]
+    fn test_encode_client_query() {
+        let wire = [0x12u8, 0x34, 0x01, 0x00];
+        let msg = DnstapMessage {
+            is_response: false,
+            is_tcp: false,
+            query_address: "192.168.1.10".parse().unwrap(),
+            query_port: 53000,
+            response_address: "192.168.1.1".parse().unwrap(),
+            response_port: 53,
+            timestamp_ns: 0,
+            wire: &wire,
+            extra: "mac=aa:bb:cc:dd:ee:ff".to_string(),
+        };
+        let encoded = encode_dnstap(b"r", b"v", &msg, 1_500_000_000_000_000_123);
+
+        // identity / version / extra
+        assert_eq!(&encoded[0..6], &[0x0a, 1, b'r', 0x12, 1, b'v']);
+        assert_eq!(encoded[6], 0x1a);
+        assert_eq!(encoded[7] as usize, msg.extra.len());
+        // message 字段（14，长度分隔）
+        let msg_start = 8 + msg.extra.len();
+        assert_eq!(encoded[msg_start], 0x72);
+        let inner = &encoded[msg_start + 2..encoded.len() - 2];
+        assert_eq!(encoded[msg_start + 1] as usize, inner.len());
+        // type = CLIENT_QUERY, family = INET, protocol = UDP
+        assert_eq!(&inner[0..6], &[0x08, 5, 0x10, 1, 0x18, 1]);
+        // query_address
+        assert_eq!(&inner[6..12], &[0x22, 4, 192, 168, 1, 10]);
+        // query_message 位于末尾
+        assert!(inner.ends_with(&[0x52, 4, 0x12, 0x34, 0x01, 0x00]));
+        // Dnstap.type = MESSAGE
+        assert_eq!(&encoded[encoded.len() - 2..], &[0x78, 1]);
+    }
+}
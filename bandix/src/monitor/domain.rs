@@ -0,0 +1,279 @@
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+use std::sync::Mutex;
+
+// 每个设备最多缓存的 IP -> 域名条目数
+const MAX_CACHE_ENTRIES_PER_DEVICE: usize = 4096;
+// DNS 记录 TTL 的下限（秒），避免 TTL 为 0 的应答立即失效
+const MIN_TTL_SECONDS: u64 = 60;
+// 域名流量小时桶保留时长（7 天）
+const USAGE_RETENTION_HOURS: u64 = 7 * 24;
+const HOUR_MS: u64 = 3600 * 1000;
+
+// 小时桶：hour_start_ms -> (mac, domain) -> 流量
+type DomainUsageBuckets = BTreeMap<u64, HashMap<([u8; 6], String), DomainUsage>>;
+// 一个远端 IP 的流量增量：(mac, 远端 IP, rx, tx)
+type RemoteDelta = ([u8; 6], IpAddr, u64, u64);
+
+/// IP -> 域名缓存条目
+#[derive(Debug, Clone)]
+struct DomainIpEntry {
+    domain: String,
+    expires_at_ms: u64,
+}
+
+/// 域名流量统计（某个小时桶内的累计值）
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DomainUsage {
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+}
+
+/// 基于 DNS 应答的流量域名归属
+/// - DNS 模块写入 设备 + 应答 IP -> 查询域名 的映射（遵循 TTL）
+/// - 流量模块读取 eBPF REMOTE_TRAFFIC 映射，将 WAN 字节增量按远端 IP 归属到域名
+pub struct DomainTracker {
+    // 每个设备的 IP -> 域名缓存
+    cache: Mutex<HashMap<[u8; 6], HashMap<IpAddr, DomainIpEntry>>>,
+    // 小时桶：hour_start_ms -> (mac, domain) -> 流量
+    usage: Mutex<DomainUsageBuckets>,
+    // 无法归属到域名的流量：hour_start_ms -> mac -> 流量
+    unattributed: Mutex<BTreeMap<u64, HashMap<[u8; 6], DomainUsage>>>,
+    // 上次从 eBPF 读取的累积值 [tx, rx]
+    last_remote_traffic: Mutex<HashMap<[u8; 22], [u64; 2]>>,
+}
+
+impl DomainTracker {
+    pub fn new() -> Self {
+        Self {
+            cache: Mutex::new(HashMap::new()),
+            usage: Mutex::new(BTreeMap::new()),
+            unattributed: Mutex::new(BTreeMap::new()),
+            last_remote_traffic: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 记录一条 DNS 应答：设备 mac 查询 domain 得到 ip，有效期 ttl_seconds
+    pub fn record_answer(&self, mac: [u8; 6], ip: IpAddr, domain: &str, ttl_seconds: u32, now_ms: u64) {
+        let domain = domain.trim_end_matches('.').to_lowercase();
+        if domain.is_empty() {
+            return;
+        }
+
+        let ttl_ms = std::cmp::max(ttl_seconds as u64, MIN_TTL_SECONDS) * 1000;
+        let mut cache = self.cache.lock().unwrap();
+        let entries = cache.entry(mac).or_default();
+
+        if entries.len() >= MAX_CACHE_ENTRIES_PER_DEVICE && !entries.contains_key(&ip) {
+            entries.retain(|_, e| e.expires_at_ms > now_ms);
+            if entries.len() >= MAX_CACHE_ENTRIES_PER_DEVICE {
+                // 仍然已满：淘汰最早过期的条目
+                if let Some(oldest) = entries.iter().min_by_key(|(_, e)| e.expires_at_ms).map(|(ip, _)| *ip) {
+                    entries.remove(&oldest);
+                }
+            }
+        }
+
+        entries.insert(
+            ip,
+            DomainIpEntry {
+                domain,
+                expires_at_ms: now_ms.saturating_add(ttl_ms),
+            },
+        );
+    }
+
+    /// 查询设备访问某个远端 IP 时对应的域名（仅返回未过期的条目）
+    pub fn lookup(&self, mac: &[u8; 6], ip: &IpAddr, now_ms: u64) -> Option<String> {
+        let cache = self.cache.lock().unwrap();
+        cache
+            .get(mac)
+            .and_then(|entries| entries.get(ip))
+            .filter(|e| e.expires_at_ms > now_ms)
+            .map(|e| e.domain.clone())
+    }
+
+    /// 处理一次 eBPF REMOTE_TRAFFIC 快照，按域名累加增量
+    /// key 格式：[mac (6 字节), 远端 IP (16 字节，IPv4 为 IPv4-mapped 格式)]
+    /// value 格式：[wan 发送字节, wan 接收字节]
+    pub fn process_remote_traffic(&self, snapshot: HashMap<[u8; 22], [u64; 2]>, now_ms: u64) {
+        let mut deltas: Vec<RemoteDelta> = Vec::new();
+        {
+            let mut last = self.last_remote_traffic.lock().unwrap();
+            for (key, value) in snapshot.iter() {
+                let prev = last.get(key).copied().unwrap_or([0, 0]);
+                // LRU 淘汰后重新插入的条目计数从 0 开始，此时整个值都是增量
+                let tx_delta = if value[0] >= prev[0] { value[0] - prev[0] } else { value[0] };
+                let rx_delta = if value[1] >= prev[1] { value[1] - prev[1] } else { value[1] };
+                if tx_delta == 0 && rx_delta == 0 {
+                    continue;
+                }
+
+                let mut mac = [0u8; 6];
+                mac.copy_from_slice(&key[0..6]);
+                let mut ip_bytes = [0u8; 16];
+                ip_bytes.copy_from_slice(&key[6..22]);
+                let ipv6 = std::net::Ipv6Addr::from(ip_bytes);
+                let ip = match ipv6.to_ipv4_mapped() {
+                    Some(v4) => IpAddr::V4(v4),
+                    None => IpAddr::V6(ipv6),
+                };
+
+                deltas.push((mac, ip, rx_delta, tx_delta));
+            }
+            *last = snapshot;
+        }
+
+        if deltas.is_empty() {
+            return;
+        }
+
+        let hour_start = (now_ms / HOUR_MS) * HOUR_MS;
+        let resolved: Vec<(RemoteDelta, Option<String>)> = deltas
+            .into_iter()
+            .map(|d| {
+                let domain = self.lookup(&d.0, &d.1, now_ms);
+                (d, domain)
+            })
+            .collect();
+
+        {
+            let mut usage = self.usage.lock().unwrap();
+            let mut unattributed = self.unattributed.lock().unwrap();
+
+            for ((mac, _ip, rx, tx), domain) in resolved {
+                let entry = match domain {
+                    Some(domain) => usage.entry(hour_start).or_default().entry((mac, domain)).or_default(),
+                    None => unattributed.entry(hour_start).or_default().entry(mac).or_default(),
+                };
+                entry.rx_bytes = entry.rx_bytes.saturating_add(rx);
+                entry.tx_bytes = entry.tx_bytes.saturating_add(tx);
+            }
+
+            // 清理过期的小时桶
+            let cutoff = hour_start.saturating_sub(USAGE_RETENTION_HOURS * HOUR_MS);
+            usage.retain(|ts, _| *ts >= cutoff);
+            unattributed.retain(|ts, _| *ts >= cutoff);
+        }
+    }
+
+    /// 清理过期的 IP -> 域名缓存
+    pub fn prune_expired(&self, now_ms: u64) {
+        let mut cache = self.cache.lock().unwrap();
+        for entries in cache.values_mut() {
+            entries.retain(|_, e| e.expires_at_ms > now_ms);
+        }
+        cache.retain(|_, entries| !entries.is_empty());
+    }
+
+    /// 查询时间范围内按域名汇总的流量
+    /// 返回 (域名 -> 流量, 未归属流量)
+    pub fn query_usage(&self, mac: Option<[u8; 6]>, start_ms: u64, end_ms: u64) -> (HashMap<String, DomainUsage>, DomainUsage) {
+        let mut by_domain: HashMap<String, DomainUsage> = HashMap::new();
+        let mut unknown = DomainUsage::default();
+
+        // 小时桶与查询范围有重叠即计入
+        let range_start = (start_ms / HOUR_MS) * HOUR_MS;
+
+        {
+            let usage = self.usage.lock().unwrap();
+            for (_, bucket) in usage.range(range_start..end_ms) {
+                for ((bucket_mac, domain), u) in bucket.iter() {
+                    if mac.is_some_and(|m| m != *bucket_mac) {
+                        continue;
+                    }
+                    let entry = by_domain.entry(domain.clone()).or_default();
+                    entry.rx_bytes = entry.rx_bytes.saturating_add(u.rx_bytes);
+                    entry.tx_bytes = entry.tx_bytes.saturating_add(u.tx_bytes);
+                }
+            }
+        }
+
+        {
+            let unattributed = self.unattributed.lock().unwrap();
+            for (_, bucket) in unattributed.range(range_start..end_ms) {
+                for (bucket_mac, u) in bucket.iter() {
+                    if mac.is_some_and(|m| m != *bucket_mac) {
+                        continue;
+                    }
+                    unknown.rx_bytes = unknown.rx_bytes.saturating_add(u.rx_bytes);
+                    unknown.tx_bytes = unknown.tx_bytes.saturating_add(u.tx_bytes);
+                }
+            }
+        }
+
+        (by_domain, unknown)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAC_A: [u8; 6] = [0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0x01];
+    const MAC_B: [u8; 6] = [0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0x02];
+
+    /// REMOTE_TRAFFIC 映射的 key：mac + IPv4-mapped 远端地址
+    fn remote_key(mac: [u8; 6], ip: std::net::Ipv4Addr) -> [u8; 22] {
+        let mut key = [0u8; 22];
+        key[..6].copy_from_slice(&mac);
+        key[6..].copy_from_slice(&ip.to_ipv6_mapped().octets());
+        key
+    }
+
+    #[test]
+    fn test_lookup_per_device_and_ttl_expiry() {
+        let tracker = DomainTracker::new();
+        let ip: IpAddr = "93.184.216.34".parse().unwrap();
+        tracker.record_answer(MAC_A, ip, "WWW.Example.com.", 300, 0);
+
+        assert_eq!(tracker.lookup(&MAC_A, &ip, 1000), Some("www.example.com".to_string()));
+        // 映射按设备区分
+        assert_eq!(tracker.lookup(&MAC_B, &ip, 1000), None);
+        // TTL 到期后失效
+        assert_eq!(tracker.lookup(&MAC_A, &ip, 300_000), None);
+
+        // TTL 过小时按下限处理
+        tracker.record_answer(MAC_A, ip, "cdn.example.net", 0, 0);
+        assert_eq!(
+            tracker.lookup(&MAC_A, &ip, MIN_TTL_SECONDS * 1000 - 1),
+            Some("cdn.example.net".to_string())
+        );
+
+        tracker.prune_expired(MIN_TTL_SECONDS * 1000);
+        assert!(tracker.cache.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_remote_traffic_attribution_and_accounting() {
+        let tracker = DomainTracker::new();
+        let known = std::net::Ipv4Addr::new(93, 184, 216, 34);
+        let unknown = std::net::Ipv4Addr::new(203, 0, 113, 9);
+        let now = 10 * HOUR_MS + 1000;
+        tracker.record_answer(MAC_A, IpAddr::V4(known), "example.com", 3600, now - 2 * HOUR_MS);
+
+        let mut snapshot = HashMap::new();
+        snapshot.insert(remote_key(MAC_A, known), [100, 1000]);
+        snapshot.insert(remote_key(MAC_A, unknown), [10, 20]);
+        tracker.process_remote_traffic(snapshot, now - 2 * HOUR_MS);
+
+        // 累积值只计算增量；计数回退（LRU 淘汰后重建）时整个值为增量
+        let mut snapshot = HashMap::new();
+        snapshot.insert(remote_key(MAC_A, known), [150, 1500]);
+        snapshot.insert(remote_key(MAC_A, unknown), [5, 5]);
+        tracker.process_remote_traffic(snapshot, now);
+
+        let (by_domain, unattributed) = tracker.query_usage(Some(MAC_A), now - 3 * HOUR_MS, now + 1);
+        // 第一次快照时域名缓存仍有效，第二次时已过期，增量计入未归属
+        let usage = by_domain.get("example.com").unwrap();
+        assert_eq!((usage.rx_bytes, usage.tx_bytes), (1000, 100));
+        assert_eq!((unattributed.rx_bytes, unattributed.tx_bytes), (500 + 20 + 5, 50 + 10 + 5));
+
+        // 其他设备没有流量；时间范围之外的小时桶不计入
+        let (by_domain, unattributed) = tracker.query_usage(Some(MAC_B), 0, now + 1);
+        assert!(by_domain.is_empty());
+        assert_eq!(unattributed.rx_bytes, 0);
+        let (by_domain, _) = tracker.query_usage(None, now - HOUR_MS / 2, now + 1);
+        assert!(by_domain.is_empty());
+    }
+}
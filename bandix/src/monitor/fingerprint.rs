@@ -0,0 +1,449 @@
+// 设备类型识别：综合多种被动信号为设备打上类型标签（手机、电脑、电视、游戏机、IoT 等）
+//
+// 信号来源：
+// - DHCP 参数请求列表（选项 55）与厂商类别（选项 60），以及 DHCP 报文的 IP TTL（初始 TTL 反映操作系统）
+// - mDNS 通告的服务类型（_googlecast、_ipp、_hap 等）
+// - DNS 查询的特征域名（xboxlive.com、playstation.net 等）
+// - OUI 厂商与主机名
+// 每个信号为若干类型投票，得分最高者为结果，置信度为其得分占比。手动设置的类型始终优先。
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Mutex;
+
+// 每台设备最多记录的 mDNS 服务数
+const MAX_SERVICES_PER_DEVICE: usize = 32;
+// 单个特征域名最多计入的查询次数，避免高频查询主导结果
+const MAX_DOMAIN_HITS: u32 = 3;
+// 最多跟踪的设备数
+const MAX_DEVICES: usize = 4096;
+
+/// 设备类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeviceType {
+    Phone,
+    Tablet,
+    Computer,
+    Tv,
+    Console,
+    Speaker,
+    Printer,
+    Camera,
+    Iot,
+    Network,
+}
+
+impl DeviceType {
+    pub const ALL: [DeviceType; 10] = [
+        DeviceType::Phone,
+        DeviceType::Tablet,
+        DeviceType::Computer,
+        DeviceType::Tv,
+        DeviceType::Console,
+        DeviceType::Speaker,
+        DeviceType::Printer,
+        DeviceType::Camera,
+        DeviceType::Iot,
+        DeviceType::Network,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeviceType::Phone => "phone",
+            DeviceType::Tablet => "tablet",
+            DeviceType::Computer => "computer",
+            DeviceType::Tv => "tv",
+            DeviceType::Console => "console",
+            DeviceType::Speaker => "speaker",
+            DeviceType::Printer => "printer",
+            DeviceType::Camera => "camera",
+            DeviceType::Iot => "iot",
+            DeviceType::Network => "network",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        let s = s.trim().to_ascii_lowercase();
+        DeviceType::ALL.iter().copied().find(|t| t.as_str() == s)
+    }
+}
+
+/// 识别结果
+#[derive(Debug, Clone, PartialEq)]
+pub struct Classification {
+    pub device_type: DeviceType,
+    pub confidence: f32, // 0.0 ~ 1.0，手动设置时为 1.0
+    pub manual: bool,
+    pub signals: Vec<String>, // 支持该结果的信号，例如 "dhcp:android"、"mdns:_googlecast._tcp"
+}
+
+/// 某台设备收集到的原始信号
+#[derive(Debug, Clone, Default)]
+struct DeviceSignals {
+    dhcp_parameter_request_list: Vec<u8>,
+    dhcp_vendor_class: Option<String>,
+    ttl: Option<u8>,
+    mdns_services: BTreeSet<String>,
+    domain_hits: HashMap<&'static str, u32>, // 特征域名后缀 -> 查询次数
+}
+
+// mDNS 服务类型 -> (类型, 权重)
+const SERVICE_HINTS: &[(&str, DeviceType, f32)] = &[
+    ("_googlecast._tcp", DeviceType::Tv, 2.0),
+    ("_amzn-wplay._tcp", DeviceType::Tv, 2.0),
+    ("_roku-rcp._tcp", DeviceType::Tv, 2.5),
+    ("_airplay._tcp", DeviceType::Tv, 1.0),
+    ("_raop._tcp", DeviceType::Speaker, 1.0),
+    ("_spotify-connect._tcp", DeviceType::Speaker, 1.5),
+    ("_sonos._tcp", DeviceType::Speaker, 2.5),
+    ("_ipp._tcp", DeviceType::Printer, 3.0),
+    ("_ipps._tcp", DeviceType::Printer, 3.0),
+    ("_printer._tcp", DeviceType::Printer, 3.0),
+    ("_pdl-datastream._tcp", DeviceType::Printer, 3.0),
+    ("_scanner._tcp", DeviceType::Printer, 2.0),
+    ("_hap._tcp", DeviceType::Iot, 2.0),
+    ("_hap._udp", DeviceType::Iot, 2.0),
+    ("_matter._tcp", DeviceType::Iot, 2.0),
+    ("_meshcop._udp", DeviceType::Iot, 1.5),
+    ("_axis-video._tcp", DeviceType::Camera, 3.0),
+    ("_rtsp._tcp", DeviceType::Camera, 1.5),
+    ("_apple-mobdev2._tcp", DeviceType::Phone, 2.0),
+    ("_companion-link._tcp", DeviceType::Phone, 0.5),
+    ("_smb._tcp", DeviceType::Computer, 1.5),
+    ("_sftp-ssh._tcp", DeviceType::Computer, 1.0),
+    ("_rfb._tcp", DeviceType::Computer, 1.5),
+    ("_workstation._tcp", DeviceType::Computer, 1.5),
+    ("_device-info._tcp", DeviceType::Computer, 0.5),
+];
+
+// 特征域名后缀 -> (类型, 每次查询的权重)
+const DOMAIN_HINTS: &[(&str, DeviceType, f32)] = &[
+    ("xboxlive.com", DeviceType::Console, 1.0),
+    ("playstation.net", DeviceType::Console, 1.0),
+    ("playstation.com", DeviceType::Console, 0.5),
+    ("nintendo.net", DeviceType::Console, 1.0),
+    ("roku.com", DeviceType::Tv, 1.0),
+    ("samsungcloudsolution.com", DeviceType::Tv, 1.0),
+    ("samsungotn.net", DeviceType::Tv, 1.0),
+    ("lgtvsdp.com", DeviceType::Tv, 1.0),
+    ("lgsmartad.com", DeviceType::Tv, 1.0),
+    ("mesu.apple.com", DeviceType::Phone, 0.5),
+    ("connectivitycheck.gstatic.com", DeviceType::Phone, 0.5),
+    ("android.clients.google.com", DeviceType::Phone, 0.5),
+    ("msftconnecttest.com", DeviceType::Computer, 1.0),
+    ("windowsupdate.com", DeviceType::Computer, 1.0),
+    ("tuyaus.com", DeviceType::Iot, 1.0),
+    ("tuyaeu.com", DeviceType::Iot, 1.0),
+    ("tuyacn.com", DeviceType::Iot, 1.0),
+    ("meethue.com", DeviceType::Iot, 1.0),
+    ("iot.us-east-1.amazonaws.com", DeviceType::Iot, 1.0),
+    ("sonos.com", DeviceType::Speaker, 1.0),
+    ("ring.com", DeviceType::Camera, 1.0),
+];
+
+// OUI 厂商名称片段（小写）-> (类型, 权重)
+const VENDOR_HINTS: &[(&str, DeviceType, f32)] = &[
+    ("nintendo", DeviceType::Console, 2.0),
+    ("sony interactive", DeviceType::Console, 2.0),
+    ("roku", DeviceType::Tv, 2.0),
+    ("sonos", DeviceType::Speaker, 2.5),
+    ("espressif", DeviceType::Iot, 2.0),
+    ("philips lighting", DeviceType::Iot, 2.0),
+    ("nest labs", DeviceType::Iot, 1.5),
+    ("tuya", DeviceType::Iot, 2.0),
+    ("raspberry pi", DeviceType::Computer, 1.0),
+    ("intel", DeviceType::Computer, 1.0),
+    ("dell", DeviceType::Computer, 1.5),
+    ("hewlett packard", DeviceType::Computer, 1.0),
+    ("asustek", DeviceType::Computer, 1.0),
+    ("lg electronics", DeviceType::Tv, 1.0),
+    ("ubiquiti", DeviceType::Network, 1.5),
+    ("tp-link", DeviceType::Network, 1.0),
+    ("netgear", DeviceType::Network, 1.0),
+    ("cisco", DeviceType::Network, 1.0),
+    ("apple", DeviceType::Phone, 0.5),
+    ("samsung", DeviceType::Phone, 0.5),
+    ("xiaomi", DeviceType::Phone, 0.5),
+    ("huawei", DeviceType::Phone, 0.5),
+    ("oneplus", DeviceType::Phone, 1.0),
+];
+
+// 主机名片段（小写）-> (类型, 权重)
+const HOSTNAME_HINTS: &[(&str, DeviceType, f32)] = &[
+    ("iphone", DeviceType::Phone, 2.5),
+    ("android", DeviceType::Phone, 2.0),
+    ("galaxy", DeviceType::Phone, 1.5),
+    ("pixel", DeviceType::Phone, 1.5),
+    ("ipad", DeviceType::Tablet, 2.5),
+    ("macbook", DeviceType::Computer, 2.5),
+    ("imac", DeviceType::Computer, 2.5),
+    ("laptop", DeviceType::Computer, 2.0),
+    ("desktop", DeviceType::Computer, 2.0),
+    ("-pc", DeviceType::Computer, 1.5),
+    ("xbox", DeviceType::Console, 2.5),
+    ("playstation", DeviceType::Console, 2.5),
+    ("ps4", DeviceType::Console, 2.0),
+    ("ps5", DeviceType::Console, 2.0),
+    ("nintendo", DeviceType::Console, 2.0),
+    ("chromecast", DeviceType::Tv, 2.5),
+    ("appletv", DeviceType::Tv, 2.5),
+    ("apple-tv", DeviceType::Tv, 2.5),
+    ("firetv", DeviceType::Tv, 2.5),
+    ("roku", DeviceType::Tv, 2.0),
+    ("bravia", DeviceType::Tv, 2.0),
+    ("homepod", DeviceType::Speaker, 2.5),
+    ("echo", DeviceType::Speaker, 1.5),
+    ("sonos", DeviceType::Speaker, 2.0),
+    ("printer", DeviceType::Printer, 2.5),
+    ("camera", DeviceType::Camera, 2.0),
+    ("esp-", DeviceType::Iot, 2.0),
+    ("esp32", DeviceType::Iot, 2.0),
+    ("shelly", DeviceType::Iot, 2.0),
+    ("tasmota", DeviceType::Iot, 2.0),
+];
+
+/// 设备类型识别器，在 DNS、流量（DHCP）模块与设备管理器之间共享
+pub struct DeviceClassifier {
+    signals: Mutex<HashMap<[u8; 6], DeviceSignals>>,
+    overrides: Mutex<HashMap<[u8; 6], DeviceType>>,
+}
+
+impl DeviceClassifier {
+    pub fn new(overrides: HashMap<[u8; 6], DeviceType>) -> Self {
+        Self {
+            signals: Mutex::new(HashMap::new()),
+            overrides: Mutex::new(overrides),
+        }
+    }
+
+    fn with_signals(&self, mac: [u8; 6], f: impl FnOnce(&mut DeviceSignals)) {
+        let mut signals = self.signals.lock().unwrap();
+        if !signals.contains_key(&mac) && signals.len() >= MAX_DEVICES {
+            return;
+        }
+        f(signals.entry(mac).or_default());
+    }
+
+    /// 记录客户端发出的 DHCP 报文特征
+    pub fn observe_dhcp(&self, mac: [u8; 6], parameter_request_list: &[u8], vendor_class: Option<&str>, ttl: Option<u8>) {
+        self.with_signals(mac, |s| {
+            if !parameter_request_list.is_empty() {
+                s.dhcp_parameter_request_list = parameter_request_list.to_vec();
+            }
+            if let Some(vendor_class) = vendor_class {
+                s.dhcp_vendor_class = Some(vendor_class.to_string());
+            }
+            if ttl.is_some() {
+                s.ttl = ttl;
+            }
+        });
+    }
+
+    /// 记录设备通过 mDNS 通告的服务类型
+    pub fn observe_services(&self, mac: [u8; 6], services: &[String]) {
+        if services.is_empty() {
+            return;
+        }
+        self.with_signals(mac, |s| {
+            for service in services {
+                if s.mdns_services.len() >= MAX_SERVICES_PER_DEVICE {
+                    break;
+                }
+                s.mdns_services.insert(service.clone());
+            }
+        });
+    }
+
+    /// 记录设备发出的 DNS 查询，只统计特征域名
+    pub fn observe_domain(&self, mac: [u8; 6], domain: &str) {
+        let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+        let suffix = match DOMAIN_HINTS.iter().find(|(suffix, _, _)| domain_matches(&domain, suffix)) {
+            Some((suffix, _, _)) => *suffix,
+            None => return,
+        };
+        self.with_signals(mac, |s| {
+            let hits = s.domain_hits.entry(suffix).or_insert(0);
+            *hits = (*hits + 1).min(MAX_DOMAIN_HITS);
+        });
+    }
+
+    /// 设置或清除手动指定的类型
+    pub fn set_override(&self, mac: [u8; 6], device_type: Option<DeviceType>) {
+        let mut overrides = self.overrides.lock().unwrap();
+        match device_type {
+            Some(t) => {
+                overrides.insert(mac, t);
+            }
+            None => {
+                overrides.remove(&mac);
+            }
+        }
+    }
+
+    pub fn get_override(&self, mac: &[u8; 6]) -> Option<DeviceType> {
+        self.overrides.lock().unwrap().get(mac).copied()
+    }
+
+    /// 所有手动设置的类型
+    pub fn overrides(&self) -> HashMap<[u8; 6], DeviceType> {
+        self.overrides.lock().unwrap().clone()
+    }
+
+    /// 综合所有信号识别设备类型；没有任何可用信号时返回 None
+    pub fn classify(&self, mac: &[u8; 6], vendor: Option<&str>, hostname: &str) -> Option<Classification> {
+        if let Some(device_type) = self.get_override(mac) {
+            return Some(Classification {
+                device_type,
+                confidence: 1.0,
+                manual: true,
+                signals: vec!["manual".to_string()],
+            });
+        }
+
+        let signals = self.signals.lock().unwrap().get(mac).cloned().unwrap_or_default();
+        score(&signals, vendor, hostname)
+    }
+}
+
+fn domain_matches(domain: &str, suffix: &str) -> bool {
+    domain == suffix || domain.ends_with(&format!(".{}", suffix))
+}
+
+/// 根据 DHCP 参数请求列表粗略判断操作系统
+fn dhcp_fingerprint(prl: &[u8]) -> Option<(&'static str, DeviceType, f32)> {
+    if prl.is_empty() {
+        return None;
+    }
+    let has = |code: u8| prl.contains(&code);
+    // Windows：请求 WPAD(252) 与 Classless Static Route(249)
+    if has(249) && has(252) && has(43) {
+        return Some(("windows", DeviceType::Computer, 2.5));
+    }
+    // Apple 设备以 1,121,3,6,15 开头；macOS 额外请求 NetBIOS(44/46) 或 LDAP(95)
+    if prl.starts_with(&[1, 121, 3, 6, 15]) {
+        return if has(95) || has(44) || has(46) {
+            Some(("macos", DeviceType::Computer, 2.0))
+        } else {
+            Some(("ios", DeviceType::Phone, 2.0))
+        };
+    }
+    // Android：请求 MTU(26)、广播地址(28)、租期(51)、续约时间(58/59) 与厂商信息(43)
+    if prl.starts_with(&[1, 3, 6, 15, 26, 28, 51, 58, 59]) {
+        return Some(("android", DeviceType::Phone, 2.0));
+    }
+    None
+}
+
+fn score(signals: &DeviceSignals, vendor: Option<&str>, hostname: &str) -> Option<Classification> {
+    let mut scores: HashMap<DeviceType, f32> = HashMap::new();
+    let mut reasons: HashMap<DeviceType, Vec<String>> = HashMap::new();
+    let mut vote = |device_type: DeviceType, weight: f32, reason: String| {
+        *scores.entry(device_type).or_insert(0.0) += weight;
+        reasons.entry(device_type).or_default().push(reason);
+    };
+
+    if let Some((os, device_type, weight)) = dhcp_fingerprint(&signals.dhcp_parameter_request_list) {
+        vote(device_type, weight, format!("dhcp:{}", os));
+    }
+    if let Some(vendor_class) = signals.dhcp_vendor_class.as_deref() {
+        let vc = vendor_class.to_ascii_lowercase();
+        if vc.starts_with("android-dhcp") {
+            vote(DeviceType::Phone, 3.0, format!("dhcp_vendor:{}", vendor_class));
+        } else if vc.starts_with("msft") {
+            vote(DeviceType::Computer, 2.0, format!("dhcp_vendor:{}", vendor_class));
+        } else if vc.starts_with("udhcp") {
+            vote(DeviceType::Iot, 1.0, format!("dhcp_vendor:{}", vendor_class));
+        }
+    }
+    // 初始 TTL 128 基本只有 Windows；64 过于常见，不作为信号
+    if signals.ttl == Some(128) {
+        vote(DeviceType::Computer, 1.5, "ttl:128".to_string());
+    }
+    for service in &signals.mdns_services {
+        if let Some((_, device_type, weight)) = SERVICE_HINTS.iter().find(|(s, _, _)| s == service) {
+            vote(*device_type, *weight, format!("mdns:{}", service));
+        }
+    }
+    for (suffix, hits) in &signals.domain_hits {
+        if let Some((_, device_type, weight)) = DOMAIN_HINTS.iter().find(|(s, _, _)| s == suffix) {
+            vote(*device_type, weight * *hits as f32, format!("dns:{}", suffix));
+        }
+    }
+    if let Some(vendor) = vendor {
+        let v = vendor.to_ascii_lowercase();
+        if let Some((_, device_type, weight)) = VENDOR_HINTS.iter().find(|(pattern, _, _)| v.contains(pattern)) {
+            vote(*device_type, *weight, format!("vendor:{}", vendor));
+        }
+    }
+    let h = hostname.to_ascii_lowercase();
+    if !h.is_empty() {
+        if let Some((pattern, device_type, weight)) = HOSTNAME_HINTS.iter().find(|(pattern, _, _)| h.contains(pattern)) {
+            vote(*device_type, *weight, format!("hostname:{}", pattern));
+        }
+    }
+
+    let total: f32 = scores.values().sum();
+    // 得分相同时按类型顺序取第一个，保证结果稳定
+    let (device_type, best) = scores
+        .iter()
+        .map(|(t, s)| (*t, *s))
+        .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal).then(b.0.cmp(&a.0)))?;
+
+    // 置信度：最佳类型的得分占比，再按总证据量折减（证据越少越不确定）
+    let confidence = (best / total) * (total / (total + 1.0));
+    Some(Classification {
+        device_type,
+        confidence: (confidence * 100.0).round() / 100.0,
+        manual: false,
+        signals: reasons.remove(&device_type).unwrap_or_default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAC: [u8; 6] = [0x00, 0x11, 0x22, 0x33, 0x44, 0x55];
+
+    #[test]
+    fn test_classify_combines_signals() {
+        let classifier = DeviceClassifier::new(HashMap::new());
+        assert!(classifier.classify(&MAC, None, "").is_none());
+
+        classifier.observe_dhcp(MAC, &[1, 3, 6, 15, 26, 28, 51, 58, 59, 43], Some("android-dhcp-13"), Some(64));
+        classifier.observe_domain(MAC, "connectivitycheck.gstatic.com.");
+        let result = classifier.classify(&MAC, Some("Samsung Electronics Co.,Ltd"), "").unwrap();
+        assert_eq!(result.device_type, DeviceType::Phone);
+        assert!(!result.manual);
+        assert!(result.confidence > 0.8 && result.confidence < 1.0);
+        assert!(result.signals.contains(&"dhcp:android".to_string()));
+
+        // 游戏机：OUI + 特征域名，多次查询只计入上限次数
+        let console = [0x00, 0x09, 0xBF, 0x00, 0x00, 0x01];
+        for _ in 0..10 {
+            classifier.observe_domain(console, "conntest.nintendo.net");
+        }
+        let result = classifier.classify(&console, Some("Nintendo Co.,Ltd"), "").unwrap();
+        assert_eq!(result.device_type, DeviceType::Console);
+        assert_eq!(result.signals.len(), 2);
+    }
+
+    #[test]
+    fn test_manual_override_wins() {
+        let classifier = DeviceClassifier::new(HashMap::new());
+        classifier.observe_services(MAC, &["_googlecast._tcp".to_string()]);
+        assert_eq!(classifier.classify(&MAC, None, "").unwrap().device_type, DeviceType::Tv);
+
+        classifier.set_override(MAC, Some(DeviceType::Speaker));
+        let result = classifier.classify(&MAC, None, "").unwrap();
+        assert_eq!(result.device_type, DeviceType::Speaker);
+        assert!(result.manual);
+        assert_eq!(result.confidence, 1.0);
+
+        classifier.set_override(MAC, None);
+        assert_eq!(classifier.classify(&MAC, None, "").unwrap().device_type, DeviceType::Tv);
+        assert_eq!(DeviceType::parse(" TV "), Some(DeviceType::Tv));
+        assert_eq!(DeviceType::parse("fridge"), None);
+    }
+}
@@ -0,0 +1,515 @@
+// 局域网名称发现：从 mDNS / LLMNR / NetBIOS 名称服务通告中学习设备名称与服务类型
+//
+// 学到的名称作为主机名的补充来源，优先级低于手动绑定（以及 ubus 主机提示）：
+// 仅当设备没有主机名绑定，或现有绑定本身就是此前学到的名称时才写入。
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Mutex;
+
+pub const MDNS_PORT: u16 = 5353;
+pub const LLMNR_PORT: u16 = 5355;
+pub const NETBIOS_NS_PORT: u16 = 137;
+
+const MAX_NAME_LEN: usize = 63;
+const MAX_SERVICES_PER_DEVICE: usize = 32;
+const MAX_DEVICES: usize = 1024;
+
+// DNS 资源记录类型
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_AAAA: u16 = 28;
+const TYPE_SRV: u16 = 33;
+const TYPE_NB: u16 = 32;
+
+const DNS_SD_SERVICES: &str = "_services._dns-sd._udp";
+
+/// 名称来源协议
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NameSource {
+    Mdns,
+    Llmnr,
+    Netbios,
+}
+
+impl NameSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NameSource::Mdns => "mdns",
+            NameSource::Llmnr => "llmnr",
+            NameSource::Netbios => "netbios",
+        }
+    }
+
+    /// 多个来源都有名称时的优先级（数值越小越优先）：mDNS 名称通常保留大小写与完整长度
+    fn rank(&self) -> u8 {
+        match self {
+            NameSource::Mdns => 0,
+            NameSource::Llmnr => 1,
+            NameSource::Netbios => 2,
+        }
+    }
+}
+
+/// 从单个数据包中解析出的通告内容
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Announcement {
+    pub source: NameSource,
+    pub names: Vec<String>,
+    pub services: Vec<String>, // 服务类型，例如 _airplay._tcp
+}
+
+/// 某个设备已学到的名称
+#[derive(Debug, Clone)]
+pub struct DiscoveredName {
+    pub name: String,
+    pub source: NameSource,
+    pub last_seen_ms: u64,
+}
+
+/// 某个设备的名称发现结果
+#[derive(Debug, Clone, Default)]
+pub struct LocalNameEntry {
+    pub names: Vec<DiscoveredName>,
+    pub services: BTreeSet<String>,
+    pub last_seen_ms: u64,
+}
+
+impl LocalNameEntry {
+    /// 按来源优先级选出的设备名称
+    pub fn best_name(&self) -> Option<&DiscoveredName> {
+        self.names
+            .iter()
+            .min_by(|a, b| a.source.rank().cmp(&b.source.rank()).then(b.last_seen_ms.cmp(&a.last_seen_ms)))
+    }
+}
+
+/// 局域网名称发现注册表
+pub struct LocalNameRegistry {
+    entries: Mutex<HashMap<[u8; 6], LocalNameEntry>>,
+    applied: Mutex<HashMap<[u8; 6], String>>, // 已写入主机名绑定的名称，用于识别绑定是否来自本模块
+}
+
+impl LocalNameRegistry {
+    pub fn new() -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            applied: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 记录一次通告，返回设备当前的最佳名称
+    pub fn observe(&self, mac: [u8; 6], announcement: &Announcement, now_ms: u64) -> Option<String> {
+        let mut entries = self.entries.lock().unwrap();
+        if !entries.contains_key(&mac) && entries.len() >= MAX_DEVICES {
+            return None;
+        }
+        let entry = entries.entry(mac).or_default();
+        entry.last_seen_ms = now_ms;
+
+        for name in &announcement.names {
+            match entry.names.iter_mut().find(|n| n.source == announcement.source) {
+                Some(existing) => {
+                    existing.name = name.clone();
+                    existing.last_seen_ms = now_ms;
+                }
+                None => entry.names.push(DiscoveredName {
+                    name: name.clone(),
+                    source: announcement.source,
+                    last_seen_ms: now_ms,
+                }),
+            }
+        }
+        for service in &announcement.services {
+            if entry.services.len() >= MAX_SERVICES_PER_DEVICE {
+                break;
+            }
+            entry.services.insert(service.clone());
+        }
+
+        entry.best_name().map(|n| n.name.clone())
+    }
+
+    /// 将学到的名称写入主机名绑定；其他来源（手动绑定、ubus）写入的绑定保持不变
+    /// 返回是否修改了绑定
+    pub fn apply_to_bindings(&self, mac: [u8; 6], name: &str, bindings: &mut HashMap<[u8; 6], String>) -> bool {
+        let mut applied = self.applied.lock().unwrap();
+        let owned_by_us = match (bindings.get(&mac), applied.get(&mac)) {
+            (None, _) => true,
+            (Some(current), Some(previous)) => current == previous,
+            (Some(_), None) => false,
+        };
+        if !owned_by_us || bindings.get(&mac).map(|s| s.as_str()) == Some(name) {
+            return false;
+        }
+        bindings.insert(mac, name.to_string());
+        applied.insert(mac, name.to_string());
+        true
+    }
+
+    pub fn entries(&self) -> Vec<([u8; 6], LocalNameEntry)> {
+        let entries = self.entries.lock().unwrap();
+        entries.iter().map(|(mac, entry)| (*mac, entry.clone())).collect()
+    }
+}
+
+pub fn is_local_name_port(port: u16) -> bool {
+    port == MDNS_PORT || port == LLMNR_PORT || port == NETBIOS_NS_PORT
+}
+
+/// 从以太网帧中定位 UDP 负载，返回 (源 MAC, 源端口, 目的端口, 负载)
+pub fn locate_udp_payload(frame: &[u8]) -> Option<([u8; 6], u16, u16, &[u8])> {
+    if frame.len() < 14 {
+        return None;
+    }
+    let mut src_mac = [0u8; 6];
+    src_mac.copy_from_slice(&frame[6..12]);
+
+    let udp_start = match u16::from_be_bytes([frame[12], frame[13]]) {
+        0x0800 => {
+            let ihl = (*frame.get(14)? & 0x0F) as usize * 4;
+            if ihl < 20 || *frame.get(14 + 9)? != 17 {
+                return None;
+            }
+            14 + ihl
+        }
+        0x86DD => {
+            // 名称发现报文不带扩展头
+            if *frame.get(14 + 6)? != 17 {
+                return None;
+            }
+            14 + 40
+        }
+        _ => return None,
+    };
+    if frame.len() < udp_start + 8 {
+        return None;
+    }
+    let src_port = u16::from_be_bytes([frame[udp_start], frame[udp_start + 1]]);
+    let dst_port = u16::from_be_bytes([frame[udp_start + 2], frame[udp_start + 3]]);
+    let udp_len = u16::from_be_bytes([frame[udp_start + 4], frame[udp_start + 5]]) as usize;
+    let end = if udp_len >= 8 {
+        std::cmp::min(frame.len(), udp_start + udp_len)
+    } else {
+        frame.len()
+    };
+    Some((src_mac, src_port, dst_port, &frame[udp_start + 8..end]))
+}
+
+/// 解析名称发现报文；不是通告（例如普通查询）或没有可用信息时返回 None
+pub fn parse_announcement(payload: &[u8], src_port: u16, dst_port: u16) -> Option<Announcement> {
+    if src_port == NETBIOS_NS_PORT || dst_port == NETBIOS_NS_PORT {
+        parse_netbios(payload)
+    } else if src_port == MDNS_PORT || dst_port == MDNS_PORT {
+        parse_dns_style(payload, NameSource::Mdns)
+    } else if src_port == LLMNR_PORT {
+        // LLMNR 只有应答方从 5355 端口发出，应答中的名称即应答方自己的名称
+        parse_dns_style(payload, NameSource::Llmnr)
+    } else {
+        None
+    }
+}
+
+/// mDNS / LLMNR：只处理响应，从 A / AAAA / SRV 中取主机名，从 PTR 中取服务类型
+fn parse_dns_style(msg: &[u8], source: NameSource) -> Option<Announcement> {
+    if msg.len() < 12 || msg[2] & 0x80 == 0 {
+        return None;
+    }
+    let qdcount = u16::from_be_bytes([msg[4], msg[5]]) as usize;
+    let rrcount = u16::from_be_bytes([msg[6], msg[7]]) as usize
+        + u16::from_be_bytes([msg[8], msg[9]]) as usize
+        + u16::from_be_bytes([msg[10], msg[11]]) as usize;
+
+    let mut offset = 12;
+    for _ in 0..qdcount {
+        let (_, next) = read_name(msg, offset)?;
+        offset = next + 4;
+    }
+
+    let mut names = Vec::new();
+    let mut services = Vec::new();
+    for _ in 0..rrcount {
+        let (owner, next) = read_name(msg, offset)?;
+        if msg.len() < next + 10 {
+            break;
+        }
+        let rtype = u16::from_be_bytes([msg[next], msg[next + 1]]);
+        let rdlen = u16::from_be_bytes([msg[next + 8], msg[next + 9]]) as usize;
+        let rdata = next + 10;
+        if msg.len() < rdata + rdlen {
+            break;
+        }
+        offset = rdata + rdlen;
+
+        match rtype {
+            TYPE_A | TYPE_AAAA => {
+                if let Some(name) = host_label(&owner, source) {
+                    names.push(name);
+                }
+            }
+            TYPE_SRV if rdlen > 6 => {
+                if let Some((target, _)) = read_name(msg, rdata + 6) {
+                    if let Some(name) = host_label(&target, source) {
+                        names.push(name);
+                    }
+                }
+            }
+            TYPE_PTR => {
+                // _services._dns-sd._udp.local PTR _airplay._tcp.local / _airplay._tcp.local PTR 实例名
+                let service = if trim_local(&owner) == DNS_SD_SERVICES {
+                    read_name(msg, rdata).map(|(target, _)| trim_local(&target).to_string())
+                } else {
+                    Some(trim_local(&owner).to_string())
+                };
+                if let Some(service) = service.filter(|s| is_service_type(s)) {
+                    services.push(service);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    names.dedup();
+    services.sort();
+    services.dedup();
+    if names.is_empty() && services.is_empty() {
+        return None;
+    }
+    Some(Announcement { source, names, services })
+}
+
+/// NetBIOS 名称服务：名称注册 / 刷新请求与肯定的名称查询响应
+fn parse_netbios(msg: &[u8]) -> Option<Announcement> {
+    if msg.len() < 12 {
+        return None;
+    }
+    let is_response = msg[2] & 0x80 != 0;
+    let opcode = (msg[2] >> 3) & 0x0F;
+    let rcode = msg[3] & 0x0F;
+    let announces = match (is_response, opcode) {
+        (false, 5) | (false, 8) | (false, 9) => true, // 注册 / 刷新
+        (true, 0) => rcode == 0,                      // 名称查询的肯定响应
+        _ => false,
+    };
+    if !announces {
+        return None;
+    }
+
+    // 第一个名称：请求在问题段，响应在应答段，都是紧跟在头部之后
+    let (encoded, next) = read_name(msg, 12)?;
+    let (name, suffix) = decode_netbios_name(&encoded)?;
+    // 0x00 工作站、0x20 文件服务；其他后缀（域控制器、浏览器等）不代表设备名称
+    if suffix != 0x00 && suffix != 0x20 {
+        return None;
+    }
+
+    // 跳过问题的 type/class（请求）或定位应答 RR，检查 NB_FLAGS 的组名位
+    let rr_start = if is_response { Some(next) } else { read_name(msg, next + 4).map(|(_, n)| n) };
+    if let Some(rr) = rr_start {
+        if msg.len() >= rr + 12 && u16::from_be_bytes([msg[rr], msg[rr + 1]]) == TYPE_NB {
+            let nb_flags = u16::from_be_bytes([msg[rr + 10], msg[rr + 11]]);
+            if nb_flags & 0x8000 != 0 {
+                return None;
+            }
+        }
+    }
+
+    let name = sanitize_name(&name)?;
+    Some(Announcement {
+        source: NameSource::Netbios,
+        names: vec![name],
+        services: Vec::new(),
+    })
+}
+
+/// 解码 NetBIOS 一级编码的名称（32 个 'A'-'P' 字符），返回 (名称, 后缀字节)
+fn decode_netbios_name(encoded: &str) -> Option<(String, u8)> {
+    let first = encoded.split('.').next()?.as_bytes();
+    if first.len() != 32 {
+        return None;
+    }
+    let mut raw = [0u8; 16];
+    for (i, pair) in first.chunks(2).enumerate() {
+        let hi = pair[0].to_ascii_uppercase().checked_sub(b'A')?;
+        let lo = pair[1].to_ascii_uppercase().checked_sub(b'A')?;
+        if hi > 15 || lo > 15 {
+            return None;
+        }
+        raw[i] = (hi << 4) | lo;
+    }
+    let name = String::from_utf8_lossy(&raw[..15]).trim_end().to_string();
+    Some((name, raw[15]))
+}
+
+/// 读取（可能压缩的）域名，返回 (名称, 名称之后的偏移)
+fn read_name(msg: &[u8], start: usize) -> Option<(String, usize)> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut offset = start;
+    let mut end = None;
+    let mut jumps = 0;
+
+    loop {
+        let len = *msg.get(offset)? as usize;
+        if len == 0 {
+            if end.is_none() {
+                end = Some(offset + 1);
+            }
+            break;
+        }
+        if len & 0xC0 == 0xC0 {
+            let pointer = ((len & 0x3F) << 8) | *msg.get(offset + 1)? as usize;
+            if end.is_none() {
+                end = Some(offset + 2);
+            }
+            jumps += 1;
+            if jumps > 16 || pointer >= msg.len() {
+                return None;
+            }
+            offset = pointer;
+            continue;
+        }
+        if len & 0xC0 != 0 {
+            return None;
+        }
+        let label = msg.get(offset + 1..offset + 1 + len)?;
+        labels.push(String::from_utf8_lossy(label).to_string());
+        offset += 1 + len;
+    }
+
+    Some((labels.join("."), end?))
+}
+
+fn trim_local(name: &str) -> &str {
+    let name = name.trim_end_matches('.');
+    name.strip_suffix(".local").unwrap_or(name)
+}
+
+/// 从 A / AAAA / SRV 的名称中取设备主机名
+fn host_label(name: &str, source: NameSource) -> Option<String> {
+    let name = name.trim_end_matches('.');
+    let lower = name.to_ascii_lowercase();
+    if lower.ends_with(".arpa") {
+        return None;
+    }
+    let host = match source {
+        // mDNS 主机名形如 Johns-iPhone.local
+        NameSource::Mdns => name.strip_suffix(".local").or_else(|| name.strip_suffix(".LOCAL"))?,
+        _ => name,
+    };
+    if host.contains('.') {
+        return None;
+    }
+    sanitize_name(host)
+}
+
+fn is_service_type(name: &str) -> bool {
+    let mut labels = name.split('.');
+    matches!(
+        (labels.next(), labels.next(), labels.next()),
+        (Some(service), Some("_tcp") | Some("_udp"), None) if service.starts_with('_') && service.len() > 1
+    )
+}
+
+fn sanitize_name(name: &str) -> Option<String> {
+    let name = name.trim();
+    if name.is_empty()
+        || name.len() > MAX_NAME_LEN
+        || name.eq_ignore_ascii_case("localhost")
+        || name.chars().any(|c| c.is_control() || c.is_whitespace())
+    {
+        return None;
+    }
+    Some(name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn push_name(buf: &mut Vec<u8>, name: &str) {
+        for label in name.split('.') {
+            buf.push(label.len() as u8);
+            buf.extend_from_slice(label.as_bytes());
+        }
+        buf.push(0);
+    }
+
+    fn push_rr(buf: &mut Vec<u8>, name: &str, rtype: u16, rdata: &[u8]) {
+        push_name(buf, name);
+        buf.extend_from_slice(&rtype.to_be_bytes());
+        buf.extend_from_slice(&0x8001u16.to_be_bytes()); // IN + cache-flush
+        buf.extend_from_slice(&120u32.to_be_bytes());
+        buf.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        buf.extend_from_slice(rdata);
+    }
+
+    #[test]
+    fn test_mdns_announcement() {
+        let mut msg = vec![0, 0, 0x84, 0, 0, 0, 0, 3, 0, 0, 0, 0];
+        let mut ptr = Vec::new();
+        push_name(&mut ptr, "Living Room._airplay._tcp.local");
+        push_rr(&mut msg, "_airplay._tcp.local", TYPE_PTR, &ptr);
+        let mut services = Vec::new();
+        push_name(&mut services, "_raop._tcp.local");
+        push_rr(&mut msg, "_services._dns-sd._udp.local", TYPE_PTR, &services);
+        push_rr(&mut msg, "Living-Room-TV.local", TYPE_A, &[192, 168, 1, 20]);
+
+        let ann = parse_announcement(&msg, MDNS_PORT, MDNS_PORT).unwrap();
+        assert_eq!(ann.source, NameSource::Mdns);
+        assert_eq!(ann.names, vec!["Living-Room-TV".to_string()]);
+        assert_eq!(ann.services, vec!["_airplay._tcp".to_string(), "_raop._tcp".to_string()]);
+
+        // 查询（QR=0）不是通告
+        msg[2] = 0;
+        assert!(parse_announcement(&msg, MDNS_PORT, MDNS_PORT).is_none());
+    }
+
+    #[test]
+    fn test_netbios_registration() {
+        let encode = |name: &str, suffix: u8| -> String {
+            let mut raw = [b' '; 16];
+            raw[..name.len()].copy_from_slice(name.as_bytes());
+            raw[15] = suffix;
+            raw.iter()
+                .flat_map(|b| [b'A' + (b >> 4), b'A' + (b & 0x0F)])
+                .map(|c| c as char)
+                .collect()
+        };
+        let build = |name: &str, suffix: u8, nb_flags: u16| -> Vec<u8> {
+            // 注册请求：opcode 5，1 个问题 + 1 个附加记录
+            let mut msg = vec![0x12, 0x34, 0x29, 0x10, 0, 1, 0, 0, 0, 0, 0, 1];
+            push_name(&mut msg, &encode(name, suffix));
+            msg.extend_from_slice(&TYPE_NB.to_be_bytes());
+            msg.extend_from_slice(&1u16.to_be_bytes());
+            msg.extend_from_slice(&[0xC0, 0x0C]);
+            msg.extend_from_slice(&TYPE_NB.to_be_bytes());
+            msg.extend_from_slice(&1u16.to_be_bytes());
+            msg.extend_from_slice(&300000u32.to_be_bytes());
+            msg.extend_from_slice(&6u16.to_be_bytes());
+            msg.extend_from_slice(&nb_flags.to_be_bytes());
+            msg.extend_from_slice(&[192, 168, 1, 30]);
+            msg
+        };
+
+        let ann = parse_announcement(&build("DESKTOP-7Q2K", 0x00, 0x0000), 137, 137).unwrap();
+        assert_eq!(ann.names, vec!["DESKTOP-7Q2K".to_string()]);
+        // 组名（工作组）与非工作站后缀不是设备名称
+        assert!(parse_announcement(&build("WORKGROUP", 0x00, 0x8000), 137, 137).is_none());
+        assert!(parse_announcement(&build("DESKTOP-7Q2K", 0x1D, 0x0000), 137, 137).is_none());
+    }
+
+    #[test]
+    fn test_discovered_names_yield_to_existing_bindings() {
+        let registry = LocalNameRegistry::new();
+        let mac = [0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff];
+        let mut bindings = HashMap::new();
+
+        assert!(registry.apply_to_bindings(mac, "Living-Room-TV", &mut bindings));
+        assert!(registry.apply_to_bindings(mac, "Living-Room-TV-2", &mut bindings));
+        assert_eq!(bindings.get(&mac).unwrap(), "Living-Room-TV-2");
+
+        // 其他来源（ubus、手动）写入后不再覆盖
+        bindings.insert(mac, "tv".to_string());
+        assert!(!registry.apply_to_bindings(mac, "Living-Room-TV-3", &mut bindings));
+        assert_eq!(bindings.get(&mac).unwrap(), "tv");
+    }
+}
@@ -1,13 +1,36 @@
+pub mod alert;
+pub mod block;
+pub mod category;
 pub mod connection;
+pub mod dhcp;
 pub mod dns;
+pub mod dnstap;
+pub mod domain;
+pub mod fingerprint;
+pub mod localname;
+pub mod resolver;
+pub mod spoof;
 pub mod traffic;
 
 use crate::api::ApiRouter;
 use crate::command::Options;
 use crate::device::DeviceManager;
+use crate::monitor::alert::{DnsAlertDetector, DnsAlertThresholds};
+use crate::monitor::block::DnsBlockEngine;
+use crate::monitor::category::CategoryEngine;
+use crate::monitor::dhcp::DhcpSnooper;
+use crate::monitor::dns::TcpDnsReassembler;
+use crate::monitor::dnstap::DnstapWriter;
+use crate::monitor::domain::DomainTracker;
+use crate::monitor::fingerprint::DeviceClassifier;
+use crate::monitor::localname::LocalNameRegistry;
+use crate::monitor::resolver::ResolverCompliance;
+use crate::monitor::spoof::SpoofDetector;
+use crate::storage::dns::{DnsMetricsManager, DnsRuntimeConfig};
 use crate::storage::traffic::{LongTermRingManager, RealtimeRingManager, ScheduledRateLimit};
 use std::collections::HashMap as StdHashMap;
 use std::collections::HashSet;
+use std::collections::VecDeque;
 use std::sync::{Arc, Mutex};
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
@@ -29,6 +52,12 @@ pub struct TrafficModuleContext {
     pub ingress_ebpf: Option<Arc<aya::Ebpf>>,
     pub egress_ebpf: Option<Arc<aya::Ebpf>>,
     pub last_ebpf_traffic: Arc<Mutex<StdHashMap<[u8; 6], [u64; 4]>>>, // 上次从 eBPF 读取的累积值
+    pub domain_tracker: Arc<DomainTracker>,                          // 基于 DNS 应答的域名流量归属
+    pub dhcp_map: Option<aya::maps::Map>,                            // DHCP_DATA RingBuf，未开启 DHCP 监听时为 None
+    pub dhcp_snooper: Arc<DhcpSnooper>,                              // DHCP 监听到的客户端租约信息
+    pub arp_map: Option<aya::maps::Map>,                             // ARP_DATA RingBuf，未开启欺骗检测时为 None
+    pub spoof_detector: Arc<SpoofDetector>,                          // ARP/NDP 欺骗检测状态
+    pub active_scheduled_limits: StdHashMap<[u8; 6], [u64; 2]>,      // 上次应用时生效的预定限速，用于记录规则生效/失效事件
 }
 
 impl TrafficModuleContext {
@@ -50,6 +79,7 @@ impl TrafficModuleContext {
         let rate_limit_whitelist = Arc::new(Mutex::new(HashSet::new()));
         let rate_limit_whitelist_enabled = Arc::new(AtomicBool::new(false));
         let default_wan_rate_limits = Arc::new(Mutex::new([0u64; 2]));
+        let spoof_detector = Arc::new(SpoofDetector::new(device_manager.subnet_info()));
 
         Self {
             options,
@@ -64,6 +94,12 @@ impl TrafficModuleContext {
             ingress_ebpf: Some(ingress_ebpf),
             egress_ebpf: Some(egress_ebpf),
             last_ebpf_traffic: Arc::new(Mutex::new(StdHashMap::new())),
+            domain_tracker: Arc::new(DomainTracker::new()),
+            dhcp_map: None,
+            dhcp_snooper: Arc::new(DhcpSnooper::new()),
+            arp_map: None,
+            spoof_detector,
+            active_scheduled_limits: StdHashMap::new(),
         }
     }
 }
@@ -86,6 +122,17 @@ pub struct DnsQueryRecord {
     pub response_time_ms: Option<u64>, // 响应时间（毫秒），如果没有匹配的响应则为 None
     pub device_mac: String,            // 设备 MAC 地址（来自源 IP）
     pub device_name: String,           // 设备主机名（来自主机名绑定）
+    pub categories: Vec<String>,       // 域名所属分类（来自分类列表，例如 ads、trackers）
+    pub blocked: bool,                 // 查询是否被拦截策略拦截
+}
+
+/// DNS 应答 IP 反向索引条目，对应 dns_queries 中的一条响应记录
+#[derive(Debug, Clone)]
+pub struct DnsIpIndexEntry {
+    pub timestamp: u64,      // 响应记录的时间戳（单调时间，纳秒）
+    pub domain: String,      // 查询的域名
+    pub device_mac: String,  // 发起查询的设备 MAC 地址
+    pub device_name: String, // 设备主机名
 }
 
 /// DNS 模块上下文
@@ -95,7 +142,21 @@ pub struct DnsModuleContext {
     pub egress_ebpf: Option<std::sync::Arc<aya::Ebpf>>,
     pub dns_map: Option<aya::maps::Map>,
     pub dns_queries: Arc<Mutex<Vec<DnsQueryRecord>>>,
+    pub dns_ip_index: Arc<Mutex<StdHashMap<String, Vec<DnsIpIndexEntry>>>>, // 应答 IP -> 解析出该 IP 的记录
+    pub unstored_responses: Arc<Mutex<VecDeque<DnsQueryRecord>>>,           // 未保存（store_responses 关闭）但已加入索引的响应
     pub hostname_bindings: Arc<Mutex<std::collections::HashMap<[u8; 6], String>>>,
+    pub domain_tracker: Arc<DomainTracker>,
+    pub category_engine: Arc<CategoryEngine>,
+    pub block_engine: Arc<DnsBlockEngine>,
+    pub resolver_compliance: Arc<ResolverCompliance>,
+    pub dnstap: Option<Arc<DnstapWriter>>, // dnstap 输出，未配置时为 None
+    pub tcp_reassembler: Arc<Mutex<TcpDnsReassembler>>, // TCP DNS 分段重组缓冲
+    pub alert_detector: Arc<DnsAlertDetector>,
+    pub dns_metrics: Arc<DnsMetricsManager>, // 每设备小时级 DNS 指标
+    pub dns_config: Arc<Mutex<DnsRuntimeConfig>>, // 运行时可修改的 DNS 配置
+    pub privacy_excluded: Arc<HashSet<[u8; 6]>>,   // 不记录 DNS 活动的设备（--dns-privacy-exclude）
+    pub local_names: Arc<LocalNameRegistry>,       // mDNS / LLMNR / NetBIOS 学到的设备名称
+    pub device_classifier: Arc<DeviceClassifier>,  // 设备类型识别，与设备管理器共享
 }
 
 impl DnsModuleContext {
@@ -107,18 +168,69 @@ impl DnsModuleContext {
         egress_ebpf: std::sync::Arc<aya::Ebpf>,
         dns_map: aya::maps::Map,
         hostname_bindings: Arc<Mutex<std::collections::HashMap<[u8; 6], String>>>,
+        domain_tracker: Arc<DomainTracker>,
+        device_classifier: Arc<DeviceClassifier>,
     ) -> Self {
+        let category_engine = Arc::new(CategoryEngine::new(options.data_dir()));
+        let resolver_compliance = Arc::new(ResolverCompliance::new(
+            options.data_dir(),
+            approved_resolvers(&options),
+        ));
+        let dnstap = DnstapWriter::from_options(&options);
+        let alert_detector = Arc::new(DnsAlertDetector::new(DnsAlertThresholds::from_options(&options)));
+        let dns_metrics = Arc::new(DnsMetricsManager::new(options.data_dir()));
+        let dns_config = Arc::new(Mutex::new(DnsRuntimeConfig::from_options(&options)));
+        let privacy_excluded: HashSet<[u8; 6]> = options
+            .dns_privacy_exclude()
+            .split(',')
+            .filter_map(|s| crate::utils::network_utils::parse_mac_address(s.trim()).ok())
+            .collect();
         Self {
             options,
             ingress_ebpf: Some(ingress_ebpf),
             egress_ebpf: Some(egress_ebpf),
             dns_map: Some(dns_map),
             dns_queries: Arc::new(Mutex::new(Vec::new())),
+            dns_ip_index: Arc::new(Mutex::new(StdHashMap::new())),
+            unstored_responses: Arc::new(Mutex::new(VecDeque::new())),
             hostname_bindings,
+            domain_tracker,
+            category_engine,
+            block_engine: Arc::new(DnsBlockEngine::new()),
+            resolver_compliance,
+            dnstap,
+            tcp_reassembler: Arc::new(Mutex::new(TcpDnsReassembler::new())),
+            alert_detector,
+            dns_metrics,
+            dns_config,
+            privacy_excluded: Arc::new(privacy_excluded),
+            local_names: Arc::new(LocalNameRegistry::new()),
+            device_classifier,
         }
     }
 }
 
+/// 批准的 DNS 解析器：--dns-approved-resolvers 为空时使用监听接口自身的地址
+fn approved_resolvers(options: &Options) -> HashSet<std::net::IpAddr> {
+    let configured: HashSet<std::net::IpAddr> = options
+        .dns_approved_resolvers()
+        .split(',')
+        .filter_map(|s| s.trim().parse().ok())
+        .collect();
+    if !configured.is_empty() {
+        return configured;
+    }
+
+    let mut out = HashSet::new();
+    if let Some((ip, _)) = crate::utils::network_utils::get_interface_info(options.iface()) {
+        out.insert(std::net::IpAddr::from(ip));
+    }
+    for (ip, _) in crate::utils::network_utils::get_interface_ipv6_info(options.iface()) {
+        out.insert(std::net::IpAddr::from(ip));
+    }
+    out
+}
+
 /// 通用模块上下文（用于模块管理器）
 pub enum ModuleContext {
     Traffic(TrafficModuleContext),
@@ -142,6 +254,12 @@ impl Clone for ModuleContext {
                 ingress_ebpf: ctx.ingress_ebpf.as_ref().map(|e| Arc::clone(e)),
                 egress_ebpf: ctx.egress_ebpf.as_ref().map(|e| Arc::clone(e)),
                 last_ebpf_traffic: Arc::clone(&ctx.last_ebpf_traffic),
+                domain_tracker: Arc::clone(&ctx.domain_tracker),
+                dhcp_map: None, // Don't clone the map, it should be taken only once
+                dhcp_snooper: Arc::clone(&ctx.dhcp_snooper),
+                arp_map: None, // Don't clone the map, it should be taken only once
+                spoof_detector: Arc::clone(&ctx.spoof_detector),
+                active_scheduled_limits: ctx.active_scheduled_limits.clone(),
             }),
             ModuleContext::Dns(ctx) => ModuleContext::Dns(DnsModuleContext {
                 options: ctx.options.clone(),
@@ -149,7 +267,21 @@ impl Clone for ModuleContext {
                 egress_ebpf: ctx.egress_ebpf.as_ref().map(|e| std::sync::Arc::clone(e)),
                 dns_map: None, // Don't clone the map, it should be taken only once
                 dns_queries: Arc::clone(&ctx.dns_queries),
+                dns_ip_index: Arc::clone(&ctx.dns_ip_index),
+                unstored_responses: Arc::clone(&ctx.unstored_responses),
                 hostname_bindings: Arc::clone(&ctx.hostname_bindings),
+                domain_tracker: Arc::clone(&ctx.domain_tracker),
+                category_engine: Arc::clone(&ctx.category_engine),
+                block_engine: Arc::clone(&ctx.block_engine),
+                resolver_compliance: Arc::clone(&ctx.resolver_compliance),
+                dnstap: ctx.dnstap.as_ref().map(Arc::clone),
+                tcp_reassembler: Arc::clone(&ctx.tcp_reassembler),
+                alert_detector: Arc::clone(&ctx.alert_detector),
+                dns_metrics: Arc::clone(&ctx.dns_metrics),
+                dns_config: Arc::clone(&ctx.dns_config),
+                privacy_excluded: Arc::clone(&ctx.privacy_excluded),
+                local_names: Arc::clone(&ctx.local_names),
+                device_classifier: Arc::clone(&ctx.device_classifier),
             }),
             ModuleContext::Connection(ctx) => ModuleContext::Connection(ctx.clone()),
         }
@@ -257,8 +389,35 @@ impl ModuleType {
 
                 Ok(())
             }
-            (ModuleType::Dns, ModuleContext::Dns(_dns_ctx)) => {
-                // DNS 模块数据初始化逻辑
+            (ModuleType::Dns, ModuleContext::Dns(dns_ctx)) => {
+                // DNS 模块数据初始化逻辑：加载域名分类列表
+                if let Err(e) = dns_ctx.category_engine.reload() {
+                    log::warn!("Failed to load DNS category lists: {}", e);
+                }
+
+                // 加载 DNS 拦截策略，由 DNS 监控循环同步到 eBPF
+                let policies = crate::storage::dns::load_block_policies(dns_ctx.options.data_dir())?;
+                if !policies.is_empty() {
+                    log::info!("Loaded {} DNS block policies", policies.len());
+                }
+                dns_ctx.block_engine.set_policies(policies);
+
+                // 加载 DoH 服务器列表，用于解析器绕过检测
+                if let Err(e) = dns_ctx.resolver_compliance.load_doh_servers() {
+                    log::warn!("Failed to load DoH server list: {}", e);
+                }
+
+                // 加载运行时 DNS 配置（覆盖命令行参数中的初始值）
+                let defaults = DnsRuntimeConfig::from_options(&dns_ctx.options);
+                let config = crate::storage::dns::load_runtime_config(dns_ctx.options.data_dir(), defaults)?;
+                *dns_ctx.dns_config.lock().unwrap() = config;
+
+                // 加载每设备 DNS 小时指标
+                match dns_ctx.dns_metrics.load_from_files() {
+                    Ok(count) if count > 0 => log::info!("Loaded DNS metrics for {} devices", count),
+                    Ok(_) => {}
+                    Err(e) => log::warn!("Failed to load DNS metrics ring files: {}", e),
+                }
                 Ok(())
             }
             (ModuleType::Connection, ModuleContext::Connection(_connection_ctx)) => {
@@ -284,6 +443,8 @@ impl ModuleType {
                     Arc::clone(&traffic_ctx.realtime_manager),
                     Arc::clone(&traffic_ctx.long_term_manager),
                     Arc::clone(&traffic_ctx.device_manager),
+                    Arc::clone(&traffic_ctx.domain_tracker),
+                    Arc::clone(&traffic_ctx.dhcp_snooper),
                     traffic_ctx.options.clone(),
                 ));
 
@@ -299,7 +460,16 @@ impl ModuleType {
                 let handler = ApiHandler::Dns(DnsApiHandler::new(
                     dns_ctx.options.clone(),
                     Arc::clone(&dns_ctx.dns_queries),
+                    Arc::clone(&dns_ctx.dns_ip_index),
+                    Arc::clone(&dns_ctx.unstored_responses),
                     Arc::clone(&dns_ctx.hostname_bindings),
+                    Arc::clone(&dns_ctx.category_engine),
+                    Arc::clone(&dns_ctx.block_engine),
+                    Arc::clone(&dns_ctx.resolver_compliance),
+                    Arc::clone(&dns_ctx.alert_detector),
+                    Arc::clone(&dns_ctx.dns_metrics),
+                    Arc::clone(&dns_ctx.dns_config),
+                    Arc::clone(&dns_ctx.local_names),
                 ));
 
                 // 注册到 API 路由器
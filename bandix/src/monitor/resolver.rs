@@ -0,0 +1,347 @@
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+// DoH 服务器列表文件（相对于数据目录），每行一个主机名或 IP 地址
+pub const DOH_SERVERS_FILE: &str = "doh_servers.txt";
+// 列表文件不存在时使用的常见公共 DoH 服务
+const DEFAULT_DOH_SERVERS: [&str; 16] = [
+    "dns.google",
+    "cloudflare-dns.com",
+    "mozilla.cloudflare-dns.com",
+    "dns.quad9.net",
+    "doh.opendns.com",
+    "dns.adguard-dns.com",
+    "dns.nextdns.io",
+    "doh.pub",
+    "dns.alidns.com",
+    "1.1.1.1",
+    "1.0.0.1",
+    "8.8.8.8",
+    "8.8.4.4",
+    "9.9.9.9",
+    "223.5.5.5",
+    "223.6.6.6",
+];
+const DOT_PORT: u16 = 853;
+const HTTPS_PORT: u16 = 443;
+
+/// 绕过本地解析器的方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BypassKind {
+    ExternalDns, // 明文 DNS 发往未批准的解析器
+    DoT,         // DNS over TLS（TCP/853）
+    DoH,         // DNS over HTTPS（443 端口连接到已知 DoH 服务）
+}
+
+impl BypassKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BypassKind::ExternalDns => "external_dns",
+            BypassKind::DoT => "dot",
+            BypassKind::DoH => "doh",
+        }
+    }
+}
+
+/// 单个设备对某个服务器的违规记录
+#[derive(Debug, Clone)]
+pub struct BypassRecord {
+    pub kind: BypassKind,
+    pub server: String,
+    pub detail: String, // DoH 时为匹配到的服务域名
+    pub count: u64,     // 查询数（external_dns）或新连接数（dot/doh）
+    pub first_seen_ms: u64,
+    pub last_seen_ms: u64,
+}
+
+/// 设备的解析器合规情况
+#[derive(Debug, Clone, Default)]
+pub struct DeviceCompliance {
+    pub compliant_queries: u64, // 发往批准解析器的查询数
+    pub violations: Vec<BypassRecord>,
+}
+
+/// 首次发现某种绕过行为时产生的事件
+#[derive(Debug, Clone)]
+pub struct BypassEvent {
+    pub ts_ms: u64,
+    pub mac: [u8; 6],
+    pub ip: String,
+    pub kind: BypassKind,
+    pub server: String,
+    pub detail: String,
+}
+
+/// 检测设备绕过本地解析器的行为
+/// - 明文 DNS：由 DNS 模块在记录查询时调用 record_dns_query
+/// - DoT / DoH：定期扫描 /proc/net/nf_conntrack 中来自局域网设备的连接
+pub struct ResolverCompliance {
+    approved: Mutex<HashSet<IpAddr>>,
+    doh_path: PathBuf,
+    doh_ips: Mutex<HashSet<IpAddr>>,
+    doh_domains: Mutex<Vec<String>>,
+    devices: Mutex<HashMap<[u8; 6], DeviceCompliance>>,
+    // 上次扫描时看到的 DoT/DoH 连接（src, sport, dst, dport），用于只统计新连接
+    seen_flows: Mutex<HashSet<(IpAddr, u16, IpAddr, u16)>>,
+    pending_events: Mutex<Vec<BypassEvent>>,
+}
+
+impl ResolverCompliance {
+    pub fn new(data_dir: &str, approved: HashSet<IpAddr>) -> Self {
+        Self {
+            approved: Mutex::new(approved),
+            doh_path: Path::new(data_dir).join(DOH_SERVERS_FILE),
+            doh_ips: Mutex::new(HashSet::new()),
+            doh_domains: Mutex::new(Vec::new()),
+            devices: Mutex::new(HashMap::new()),
+            seen_flows: Mutex::new(HashSet::new()),
+            pending_events: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn approved_resolvers(&self) -> Vec<IpAddr> {
+        let mut out: Vec<IpAddr> = self.approved.lock().unwrap().iter().copied().collect();
+        out.sort();
+        out
+    }
+
+    /// DoH 服务器列表（IP 与域名）条目数
+    pub fn doh_server_count(&self) -> usize {
+        self.doh_ips.lock().unwrap().len() + self.doh_domains.lock().unwrap().len()
+    }
+
+    /// 加载 DoH 服务器列表，文件不存在时使用内置列表
+    pub fn load_doh_servers(&self) -> Result<(), anyhow::Error> {
+        let entries: Vec<String> = if self.doh_path.exists() {
+            std::fs::read_to_string(&self.doh_path)?
+                .lines()
+                .map(|l| l.split('#').next().unwrap_or("").trim().to_lowercase())
+                .filter(|l| !l.is_empty())
+                .collect()
+        } else {
+            DEFAULT_DOH_SERVERS.iter().map(|s| s.to_string()).collect()
+        };
+
+        let mut ips = HashSet::new();
+        let mut domains = Vec::new();
+        for entry in entries {
+            match entry.parse::<IpAddr>() {
+                Ok(ip) => {
+                    ips.insert(ip);
+                }
+                Err(_) => domains.push(entry.trim_end_matches('.').to_string()),
+            }
+        }
+
+        log::debug!("Loaded {} DoH server IPs and {} DoH domains", ips.len(), domains.len());
+        *self.doh_ips.lock().unwrap() = ips;
+        *self.doh_domains.lock().unwrap() = domains;
+        Ok(())
+    }
+
+    /// 记录一次设备发出的明文 DNS 查询
+    pub fn record_dns_query(&self, mac: [u8; 6], device_ip: &str, server: IpAddr, now_ms: u64) {
+        if self.approved.lock().unwrap().contains(&server) {
+            let mut devices = self.devices.lock().unwrap();
+            devices.entry(mac).or_default().compliant_queries += 1;
+            return;
+        }
+        self.record_violation(mac, device_ip, BypassKind::ExternalDns, server.to_string(), String::new(), now_ms);
+    }
+
+    /// 扫描 conntrack 内容，检测 DoT 与 DoH 连接
+    /// - ip_to_mac：局域网设备 IP -> MAC
+    /// - resolve_domains：根据远端 IP 返回曾解析到该 IP 的域名（来自 DNS 应答索引）
+    pub fn scan_conntrack<F>(&self, content: &str, ip_to_mac: &HashMap<IpAddr, [u8; 6]>, resolve_domains: F, now_ms: u64)
+    where
+        F: Fn(&IpAddr) -> Vec<String>,
+    {
+        let doh_ips = self.doh_ips.lock().unwrap().clone();
+        let doh_domains = self.doh_domains.lock().unwrap().clone();
+        let previous = self.seen_flows.lock().unwrap().clone();
+        let mut current: HashSet<(IpAddr, u16, IpAddr, u16)> = HashSet::new();
+
+        for line in content.lines() {
+            let (src, sport, dst, dport) = match parse_tcp_flow(line) {
+                Some(flow) => flow,
+                None => continue,
+            };
+            let mac = match ip_to_mac.get(&src) {
+                Some(mac) => *mac,
+                None => continue,
+            };
+
+            let (kind, detail) = if dport == DOT_PORT {
+                (BypassKind::DoT, String::new())
+            } else if dport == HTTPS_PORT {
+                if doh_ips.contains(&dst) {
+                    (BypassKind::DoH, String::new())
+                } else {
+                    match resolve_domains(&dst).into_iter().find(|d| matches_any_suffix(d, &doh_domains)) {
+                        Some(domain) => (BypassKind::DoH, domain),
+                        None => continue,
+                    }
+                }
+            } else {
+                continue;
+            };
+
+            let flow = (src, sport, dst, dport);
+            current.insert(flow);
+            if previous.contains(&flow) {
+                continue;
+            }
+            self.record_violation(mac, &src.to_string(), kind, dst.to_string(), detail, now_ms);
+        }
+
+        *self.seen_flows.lock().unwrap() = current;
+    }
+
+    fn record_violation(&self, mac: [u8; 6], device_ip: &str, kind: BypassKind, server: String, detail: String, now_ms: u64) {
+        let mut devices = self.devices.lock().unwrap();
+        let device = devices.entry(mac).or_default();
+
+        if let Some(record) = device.violations.iter_mut().find(|v| v.kind == kind && v.server == server) {
+            record.count += 1;
+            record.last_seen_ms = now_ms;
+            if record.detail.is_empty() && !detail.is_empty() {
+                record.detail = detail;
+            }
+            return;
+        }
+
+        device.violations.push(BypassRecord {
+            kind,
+            server: server.clone(),
+            detail: detail.clone(),
+            count: 1,
+            first_seen_ms: now_ms,
+            last_seen_ms: now_ms,
+        });
+        drop(devices);
+
+        self.pending_events.lock().unwrap().push(BypassEvent {
+            ts_ms: now_ms,
+            mac,
+            ip: device_ip.to_string(),
+            kind,
+            server,
+            detail,
+        });
+    }
+
+    /// 所有设备的合规情况
+    pub fn report(&self) -> HashMap<[u8; 6], DeviceCompliance> {
+        self.devices.lock().unwrap().clone()
+    }
+
+    /// 取出待发送的事件
+    pub fn take_events(&self) -> Vec<BypassEvent> {
+        std::mem::take(&mut *self.pending_events.lock().unwrap())
+    }
+}
+
+/// 从 conntrack 行中解析 TCP 连接的原始方向 (src, sport, dst, dport)
+fn parse_tcp_flow(line: &str) -> Option<(IpAddr, u16, IpAddr, u16)> {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    if parts.get(2) != Some(&"tcp") {
+        return None;
+    }
+
+    let mut src = None;
+    let mut dst = None;
+    let mut sport = None;
+    let mut dport = None;
+    // 只取第一次出现的字段（原始方向）
+    for part in parts.iter() {
+        if let Some(v) = part.strip_prefix("src=") {
+            if src.is_none() {
+                src = v.parse::<IpAddr>().ok();
+            }
+        } else if let Some(v) = part.strip_prefix("dst=") {
+            if dst.is_none() {
+                dst = v.parse::<IpAddr>().ok();
+            }
+        } else if let Some(v) = part.strip_prefix("sport=") {
+            if sport.is_none() {
+                sport = v.parse::<u16>().ok();
+            }
+        } else if let Some(v) = part.strip_prefix("dport=") {
+            if dport.is_none() {
+                dport = v.parse::<u16>().ok();
+            }
+        }
+    }
+
+    Some((src?, sport?, dst?, dport?))
+}
+
+fn matches_any_suffix(domain: &str, suffixes: &[String]) -> bool {
+    let domain = domain.trim_end_matches('.').to_lowercase();
+    suffixes
+        .iter()
+        .any(|s| domain == *s || domain.ends_with(&format!(".{}", s)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scan_conntrack_detects_dot_and_doh() {
+        let tracker = ResolverCompliance::new("/nonexistent", HashSet::new());
+        tracker.load_doh_servers().unwrap();
+
+        let device_ip: IpAddr = "192.168.1.10".parse().unwrap();
+        let mac = [0x00, 0x11, 0x22, 0x33, 0x44, 0x55];
+        let mut ip_to_mac = HashMap::new();
+        ip_to_mac.insert(device_ip, mac);
+
+        let content = "\
+ipv4     2 tcp      6 431999 ESTABLISHED src=192.168.1.10 dst=9.9.9.9 sport=50000 dport=853 src=9.9.9.9 dst=1.2.3.4 sport=853 dport=50000 [ASSURED] mark=0 zone=0 use=2
+ipv4     2 tcp      6 431999 ESTABLISHED src=192.168.1.10 dst=1.1.1.1 sport=50001 dport=443 src=1.1.1.1 dst=1.2.3.4 sport=443 dport=50001 [ASSURED] mark=0 zone=0 use=2
+ipv4     2 tcp      6 431999 ESTABLISHED src=192.168.1.10 dst=142.250.1.1 sport=50002 dport=443 src=142.250.1.1 dst=1.2.3.4 sport=443 dport=50002 [ASSURED] mark=0 zone=0 use=2
+ipv4     2 tcp      6 431999 ESTABLISHED src=192.168.1.10 dst=93.184.216.34 sport=50003 dport=443 src=93.184.216.34 dst=1.2.3.4 sport=443 dport=50003 [ASSURED] mark=0 zone=0 use=2
+";
+        let resolve = |ip: &IpAddr| -> Vec<String> {
+            if *ip == "142.250.1.1".parse::<IpAddr>().unwrap() {
+                vec!["dns.google.".to_string()]
+            } else {
+                vec![]
+            }
+        };
+
+        tracker.scan_conntrack(content, &ip_to_mac, resolve, 1000);
+        // 同一连接再次出现不重复计数
+        tracker.scan_conntrack(content, &ip_to_mac, resolve, 2000);
+
+        let report = tracker.report();
+        let device = report.get(&mac).unwrap();
+        assert_eq!(device.violations.len(), 3);
+        assert!(device.violations.iter().all(|v| v.count == 1));
+        assert!(device
+            .violations
+            .iter()
+            .any(|v| v.kind == BypassKind::DoH && v.server == "142.250.1.1" && v.detail == "dns.google."));
+        assert_eq!(tracker.take_events().len(), 3);
+    }
+
+    #[test]
+    fn test_record_dns_query_approved() {
+        let server: IpAddr = "192.168.1.1".parse().unwrap();
+        let tracker = ResolverCompliance::new("/nonexistent", [server].into_iter().collect());
+        let mac = [0x00, 0x11, 0x22, 0x33, 0x44, 0x55];
+
+        tracker.record_dns_query(mac, "192.168.1.10", server, 1000);
+        tracker.record_dns_query(mac, "192.168.1.10", "8.8.8.8".parse().unwrap(), 1000);
+        tracker.record_dns_query(mac, "192.168.1.10", "8.8.8.8".parse().unwrap(), 2000);
+
+        let device = tracker.report().remove(&mac).unwrap();
+        assert_eq!(device.compliant_queries, 1);
+        assert_eq!(device.violations.len(), 1);
+        assert_eq!(device.violations[0].count, 2);
+        assert_eq!(tracker.take_events().len(), 1);
+    }
+}
@@ -0,0 +1,397 @@
+// ARP/NDP 监听：检测网关仿冒、IP 冲突与 MAC 频繁变化
+//
+// eBPF 将 ARP 报文与 ICMPv6 邻居请求/通告通过 ARP_DATA RingBuf 转发到用户态，这里从中取出
+// “IP 属于某个 MAC”的声明，按 IP 记录最近的声明者：
+//   - 网关（本接口）的 IP 被其他 MAC 声明时产生 gateway_impersonation
+//   - 两个 MAC 在短时间内交替声明同一 IP（双方都仍在使用）时产生 duplicate_ip；
+//     IP 重新分配、设备换用新的随机 MAC 重连或主备切换只是单向换手，不算冲突
+//   - 一个 IP 的声明者在时间窗口内反复变化时产生 mac_flip
+// 告警写入设备事件日志，经 webhook 推送，并可通过 /api/security/alerts 查询。
+
+use crate::command::SubnetInfo;
+use crate::device::DeviceManager;
+use crate::events::EventKind;
+use aya::maps::{MapData, RingBuf};
+use bandix_common::PacketHeader;
+use std::collections::{HashMap, VecDeque};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::Mutex;
+
+const ETH_HEADER_LEN: usize = 14;
+const ETH_TYPE_ARP: u16 = 0x0806;
+const ETH_TYPE_IPV6: u16 = 0x86DD;
+const ARP_HTYPE_ETHERNET: u16 = 1;
+const ARP_PTYPE_IPV4: u16 = 0x0800;
+const ARP_LEN: usize = 28;
+const IPV6_HEADER_LEN: usize = 40;
+const PROTO_ICMPV6: u8 = 58;
+const NDP_FIXED_LEN: usize = 24;
+const ICMPV6_NEIGHBOR_SOLICIT: u8 = 135;
+const ICMPV6_NEIGHBOR_ADVERT: u8 = 136;
+const NDP_OPT_SOURCE_LINK_ADDR: u8 = 1;
+const NDP_OPT_TARGET_LINK_ADDR: u8 = 2;
+
+// 其他 MAC 在此时间内仍在声明同一 IP 时视为冲突
+const CONFLICT_WINDOW_MS: u64 = 60 * 1000;
+// 统计 MAC 变化次数的时间窗口
+const FLIP_WINDOW_MS: u64 = 5 * 60 * 1000;
+// 时间窗口内 MAC 变化达到此次数时产生 mac_flip
+const FLIP_THRESHOLD: usize = 4;
+// 同一 IP 的同类告警的最小间隔
+const ALERT_COOLDOWN_MS: u64 = 10 * 60 * 1000;
+// 最多跟踪的 IP 数
+const MAX_TRACKED_IPS: usize = 4096;
+
+/// 从 ARP/NDP 报文中取出的一条声明：ip 属于 mac
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NeighborClaim {
+    pub ip: IpAddr,
+    pub mac: [u8; 6],
+}
+
+/// 检测到的一条安全告警
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpoofAlert {
+    pub kind: EventKind,
+    pub ip: IpAddr,
+    pub mac: [u8; 6],             // 发出声明的 MAC
+    pub conflicting_mac: [u8; 6], // 网关的 MAC 或此前声明该 IP 的 MAC
+    pub flip_count: Option<u32>,  // mac_flip：时间窗口内的变化次数
+}
+
+#[derive(Debug, Default)]
+struct IpClaims {
+    owners: Vec<([u8; 6], u64)>, // 最近声明该 IP 的 MAC 与最后一次见到的时间
+    current: Option<[u8; 6]>,    // 最近一次声明的 MAC
+    changes: VecDeque<u64>,      // 声明者变化的时间
+}
+
+#[derive(Debug, Default)]
+struct DetectorState {
+    claims: HashMap<IpAddr, IpClaims>,
+    last_alert: HashMap<(EventKind, IpAddr), u64>, // 各类告警最后一次报告的时间
+}
+
+/// ARP/NDP 欺骗检测器
+pub struct SpoofDetector {
+    gateway_mac: [u8; 6],
+    gateway_ips: Vec<IpAddr>,
+    state: Mutex<DetectorState>,
+}
+
+impl SpoofDetector {
+    /// 以本接口的 MAC 与地址作为网关
+    pub fn new(subnet_info: &SubnetInfo) -> Self {
+        let mut gateway_ips = vec![IpAddr::V4(Ipv4Addr::from(subnet_info.interface_ip))];
+        gateway_ips.extend(
+            subnet_info
+                .ipv6_addresses
+                .iter()
+                .map(|(addr, _)| IpAddr::V6(Ipv6Addr::from(*addr))),
+        );
+        Self {
+            gateway_mac: subnet_info.interface_mac,
+            gateway_ips,
+            state: Mutex::new(DetectorState::default()),
+        }
+    }
+
+    /// 处理一条声明，返回新产生的告警
+    pub fn observe(&self, claim: NeighborClaim, now_ms: u64) -> Vec<SpoofAlert> {
+        let NeighborClaim { ip, mac } = claim;
+        if ip.is_unspecified() || ip.is_multicast() || mac == [0; 6] || mac[0] & 0x01 != 0 {
+            return Vec::new();
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let mut alerts = Vec::new();
+
+        if self.gateway_ips.contains(&ip) {
+            if self.gateway_mac != [0; 6] && mac != self.gateway_mac {
+                alerts.push(SpoofAlert {
+                    kind: EventKind::GatewayImpersonation,
+                    ip,
+                    mac,
+                    conflicting_mac: self.gateway_mac,
+                    flip_count: None,
+                });
+            }
+        } else {
+            if !state.claims.contains_key(&ip) && state.claims.len() >= MAX_TRACKED_IPS {
+                state
+                    .claims
+                    .retain(|_, c| c.owners.iter().any(|(_, seen)| now_ms.saturating_sub(*seen) < FLIP_WINDOW_MS));
+                if state.claims.len() >= MAX_TRACKED_IPS {
+                    return Vec::new();
+                }
+            }
+
+            let claims = state.claims.entry(ip).or_default();
+            claims
+                .owners
+                .retain(|(_, seen)| now_ms.saturating_sub(*seen) < CONFLICT_WINDOW_MS);
+            claims.changes.retain(|t| now_ms.saturating_sub(*t) < FLIP_WINDOW_MS);
+
+            // 本 MAC 上次声明之后其他 MAC 也声明过，说明双方都在使用该 IP
+            let previous_seen = claims.owners.iter().find(|(m, _)| *m == mac).map(|(_, seen)| *seen);
+            let conflicting = previous_seen.and_then(|since| {
+                claims
+                    .owners
+                    .iter()
+                    .filter(|(m, seen)| *m != mac && *seen >= since)
+                    .max_by_key(|(_, seen)| *seen)
+                    .map(|(m, _)| *m)
+            });
+            if let Some(other) = conflicting {
+                alerts.push(SpoofAlert {
+                    kind: EventKind::DuplicateIp,
+                    ip,
+                    mac,
+                    conflicting_mac: other,
+                    flip_count: None,
+                });
+            }
+            if let Some(previous) = claims.current.filter(|m| *m != mac) {
+                claims.changes.push_back(now_ms);
+                if claims.changes.len() >= FLIP_THRESHOLD {
+                    alerts.push(SpoofAlert {
+                        kind: EventKind::MacFlip,
+                        ip,
+                        mac,
+                        conflicting_mac: previous,
+                        flip_count: Some(claims.changes.len() as u32),
+                    });
+                }
+            }
+            claims.current = Some(mac);
+            claims.owners.retain(|(m, _)| *m != mac);
+            claims.owners.push((mac, now_ms));
+        }
+
+        // 同一 IP 的同类告警在冷却时间内只报告一次
+        state.last_alert.retain(|_, at| now_ms.saturating_sub(*at) < ALERT_COOLDOWN_MS);
+        alerts.retain(|alert| {
+            let key = (alert.kind, alert.ip);
+            if state.last_alert.contains_key(&key) {
+                return false;
+            }
+            state.last_alert.insert(key, now_ms);
+            true
+        });
+        alerts
+    }
+}
+
+/// 从以太网帧中解析 ARP 或 NDP 声明
+pub fn parse_neighbor_frame(frame: &[u8]) -> Option<NeighborClaim> {
+    if frame.len() < ETH_HEADER_LEN {
+        return None;
+    }
+    let eth_src: [u8; 6] = frame[6..12].try_into().ok()?;
+    let eth_type = u16::from_be_bytes([frame[12], frame[13]]);
+    let payload = &frame[ETH_HEADER_LEN..];
+    match eth_type {
+        ETH_TYPE_ARP => parse_arp(payload),
+        ETH_TYPE_IPV6 => parse_ndp(payload, eth_src),
+        _ => None,
+    }
+}
+
+/// ARP 请求与应答中的发送方地址；ARP 探测（发送方 IP 为 0.0.0.0）不算声明
+fn parse_arp(arp: &[u8]) -> Option<NeighborClaim> {
+    if arp.len() < ARP_LEN {
+        return None;
+    }
+    let htype = u16::from_be_bytes([arp[0], arp[1]]);
+    let ptype = u16::from_be_bytes([arp[2], arp[3]]);
+    if htype != ARP_HTYPE_ETHERNET || ptype != ARP_PTYPE_IPV4 || arp[4] != 6 || arp[5] != 4 {
+        return None;
+    }
+    let mac: [u8; 6] = arp[8..14].try_into().ok()?;
+    let ip: [u8; 4] = arp[14..18].try_into().ok()?;
+    Some(NeighborClaim {
+        ip: IpAddr::V4(Ipv4Addr::from(ip)),
+        mac,
+    })
+}
+
+/// 邻居通告中的目标地址，或邻居请求中的源地址；链路层地址优先取选项，缺省时取以太网源地址
+fn parse_ndp(ipv6: &[u8], eth_src: [u8; 6]) -> Option<NeighborClaim> {
+    if ipv6.len() < IPV6_HEADER_LEN + NDP_FIXED_LEN || ipv6[6] != PROTO_ICMPV6 {
+        return None;
+    }
+    let icmp = &ipv6[IPV6_HEADER_LEN..];
+    let (ip, option_type): ([u8; 16], u8) = match icmp[0] {
+        ICMPV6_NEIGHBOR_ADVERT => (icmp[8..24].try_into().ok()?, NDP_OPT_TARGET_LINK_ADDR),
+        ICMPV6_NEIGHBOR_SOLICIT => (ipv6[8..24].try_into().ok()?, NDP_OPT_SOURCE_LINK_ADDR),
+        _ => return None,
+    };
+
+    let mut mac = eth_src;
+    let mut options = &icmp[NDP_FIXED_LEN..];
+    while options.len() >= 8 {
+        let len = options[1] as usize * 8;
+        if len == 0 || len > options.len() {
+            break;
+        }
+        if options[0] == option_type {
+            mac = options[2..8].try_into().ok()?;
+            break;
+        }
+        options = &options[len..];
+    }
+
+    Some(NeighborClaim {
+        ip: IpAddr::V6(Ipv6Addr::from(ip)),
+        mac,
+    })
+}
+
+/// 从 ARP_DATA RingBuf 读取报文并检测欺骗
+pub fn process_arp_events(ringbuf: &mut RingBuf<MapData>, detector: &SpoofDetector, device_manager: &DeviceManager) {
+    let header_size = std::mem::size_of::<PacketHeader>();
+    while let Some(item) = ringbuf.next() {
+        let bytes: &[u8] = item.as_ref();
+        if bytes.len() < header_size {
+            continue;
+        }
+        let header: PacketHeader = unsafe { std::ptr::read_unaligned(bytes.as_ptr() as *const PacketHeader) };
+        let payload_all = &bytes[header_size..];
+        let cap_len = std::cmp::min(payload_all.len(), header.captured_len as usize);
+
+        let claim = match parse_neighbor_frame(&payload_all[..cap_len]) {
+            Some(claim) => claim,
+            None => continue,
+        };
+        let now_ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as u64;
+
+        for alert in detector.observe(claim, now_ms) {
+            // 合并到同一身份的 MAC（例如轮换的随机化 MAC）先后使用同一 IP 不算冲突
+            if alert.kind != EventKind::GatewayImpersonation
+                && device_manager
+                    .identity_registry()
+                    .group_macs(&alert.mac)
+                    .contains(&alert.conflicting_mac)
+            {
+                continue;
+            }
+            log::warn!(
+                "Security alert {}: {} claimed by {} (conflicting with {})",
+                alert.kind.as_str(),
+                alert.ip,
+                crate::utils::format_utils::format_mac(&alert.mac),
+                crate::utils::format_utils::format_mac(&alert.conflicting_mac)
+            );
+            device_manager.record_security_alert(&alert);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GATEWAY: [u8; 6] = [0x00, 0x11, 0x22, 0x33, 0x44, 0x01];
+    const PHONE: [u8; 6] = [0x00, 0x11, 0x22, 0x33, 0x44, 0x55];
+    const LAPTOP: [u8; 6] = [0x00, 0x11, 0x22, 0x33, 0x44, 0x66];
+
+    fn detector() -> SpoofDetector {
+        SpoofDetector::new(&SubnetInfo {
+            interface_ip: [192, 168, 1, 1],
+            subnet_mask: [255, 255, 255, 0],
+            interface_mac: GATEWAY,
+            ipv6_addresses: Vec::new(),
+        })
+    }
+
+    fn claim(ip: [u8; 4], mac: [u8; 6]) -> NeighborClaim {
+        NeighborClaim {
+            ip: IpAddr::V4(Ipv4Addr::from(ip)),
+            mac,
+        }
+    }
+
+    fn kinds(alerts: &[SpoofAlert]) -> Vec<EventKind> {
+        alerts.iter().map(|a| a.kind).collect()
+    }
+
+    #[test]
+    fn test_parse_arp_and_ndp() {
+        // ARP 应答：192.168.1.20 is-at PHONE
+        let mut frame = vec![0xff; 6];
+        frame.extend_from_slice(&PHONE);
+        frame.extend_from_slice(&[0x08, 0x06, 0x00, 0x01, 0x08, 0x00, 6, 4, 0x00, 0x02]);
+        frame.extend_from_slice(&PHONE);
+        frame.extend_from_slice(&[192, 168, 1, 20]);
+        frame.extend_from_slice(&[0; 6]);
+        frame.extend_from_slice(&[192, 168, 1, 1]);
+        assert_eq!(parse_neighbor_frame(&frame), Some(claim([192, 168, 1, 20], PHONE)));
+
+        // 邻居通告：目标地址 fe80::1，目标链路层地址选项为 LAPTOP
+        let target: [u8; 16] = "fe80::1".parse::<Ipv6Addr>().unwrap().octets();
+        let mut frame = vec![0x33, 0x33, 0, 0, 0, 1];
+        frame.extend_from_slice(&PHONE);
+        frame.extend_from_slice(&[0x86, 0xdd]);
+        let mut ipv6 = vec![0x60, 0, 0, 0, 0, 32, PROTO_ICMPV6, 255];
+        ipv6.extend_from_slice(&target);
+        ipv6.extend_from_slice(&[0xff; 16]);
+        frame.extend_from_slice(&ipv6);
+        frame.extend_from_slice(&[ICMPV6_NEIGHBOR_ADVERT, 0, 0, 0, 0x20, 0, 0, 0]);
+        frame.extend_from_slice(&target);
+        frame.extend_from_slice(&[NDP_OPT_TARGET_LINK_ADDR, 1]);
+        frame.extend_from_slice(&LAPTOP);
+        let parsed = parse_neighbor_frame(&frame).unwrap();
+        assert_eq!(parsed.ip, IpAddr::V6(Ipv6Addr::from(target)));
+        assert_eq!(parsed.mac, LAPTOP);
+    }
+
+    #[test]
+    fn test_detection() {
+        let detector = detector();
+
+        // 网关 IP 只能由网关 MAC 声明，告警在冷却时间内只报告一次
+        assert!(detector.observe(claim([192, 168, 1, 1], GATEWAY), 0).is_empty());
+        let alerts = detector.observe(claim([192, 168, 1, 1], PHONE), 1_000);
+        assert_eq!(kinds(&alerts), vec![EventKind::GatewayImpersonation]);
+        assert_eq!(alerts[0].conflicting_mac, GATEWAY);
+        assert!(detector.observe(claim([192, 168, 1, 1], PHONE), 2_000).is_empty());
+
+        // IP 重新分配给其他设备时，原设备已不再出现，不算冲突
+        assert!(detector.observe(claim([192, 168, 1, 20], PHONE), 0).is_empty());
+        assert!(detector
+            .observe(claim([192, 168, 1, 20], LAPTOP), CONFLICT_WINDOW_MS + 1)
+            .is_empty());
+
+        // 两个 MAC 交替声明同一 IP，并在时间窗口内反复变化
+        let ip = [192, 168, 1, 30];
+        assert!(detector.observe(claim(ip, PHONE), 0).is_empty());
+        assert!(detector.observe(claim(ip, LAPTOP), 1_000).is_empty());
+        let alerts = detector.observe(claim(ip, PHONE), 2_000);
+        assert_eq!(kinds(&alerts), vec![EventKind::DuplicateIp]);
+        assert_eq!(alerts[0].conflicting_mac, LAPTOP);
+        assert!(detector.observe(claim(ip, LAPTOP), 3_000).is_empty());
+        let alerts = detector.observe(claim(ip, PHONE), 4_000);
+        assert_eq!(kinds(&alerts), vec![EventKind::MacFlip]);
+        assert_eq!(alerts[0].flip_count, Some(FLIP_THRESHOLD as u32));
+        assert_eq!(alerts[0].conflicting_mac, LAPTOP);
+    }
+
+    #[test]
+    fn test_handover_is_not_a_conflict() {
+        let detector = detector();
+        let ip = [192, 168, 1, 40];
+
+        // 设备换用新的随机 MAC 重连或主备切换：IP 在窗口内换手，此后只有新 MAC 声明
+        assert!(detector.observe(claim(ip, PHONE), 0).is_empty());
+        assert!(detector.observe(claim(ip, PHONE), 500).is_empty());
+        assert!(detector.observe(claim(ip, LAPTOP), 1_000).is_empty());
+        assert!(detector.observe(claim(ip, LAPTOP), 2_000).is_empty());
+        assert!(detector.observe(claim(ip, LAPTOP), 30_000).is_empty());
+
+        // 原设备在窗口外才回来并接管，同样是换手
+        assert!(detector.observe(claim(ip, PHONE), 30_000 + CONFLICT_WINDOW_MS).is_empty());
+    }
+}
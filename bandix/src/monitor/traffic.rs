@@ -2,6 +2,7 @@ use crate::monitor::TrafficModuleContext;
 use anyhow::Result;
 use aya::maps::HashMap;
 use aya::maps::MapData;
+use aya::maps::RingBuf;
 use serde::Serialize;
 use std::collections::HashMap as StdHashMap;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -66,11 +67,35 @@ impl TrafficMonitor {
             self.sync_last_ebpf_traffic(ctx, ebpf);
         }
 
+        // DHCP 监听：RingBuf 仅在开启 --traffic-dhcp-snooping 时存在
+        let mut dhcp_ringbuf = match ctx.dhcp_map.take() {
+            Some(map) => Some(RingBuf::<MapData>::try_from(map)?),
+            None => None,
+        };
+        let mut dhcp_interval = tokio::time::interval(tokio::time::Duration::from_millis(100));
+
+        // ARP/NDP 欺骗检测：RingBuf 仅在开启 --traffic-spoof-detection 时存在
+        let mut arp_ringbuf = match ctx.arp_map.take() {
+            Some(map) => Some(RingBuf::<MapData>::try_from(map)?),
+            None => None,
+        };
+        let mut arp_interval = tokio::time::interval(tokio::time::Duration::from_millis(100));
+
         loop {
             tokio::select! {
                 _ = interval.tick() => {
                     self.process_monitoring_cycle(ctx).await;
                 }
+                _ = dhcp_interval.tick(), if dhcp_ringbuf.is_some() => {
+                    if let Some(ringbuf) = dhcp_ringbuf.as_mut() {
+                        crate::monitor::dhcp::process_dhcp_events(ringbuf, &ctx.dhcp_snooper, &ctx.device_manager);
+                    }
+                }
+                _ = arp_interval.tick(), if arp_ringbuf.is_some() => {
+                    if let Some(ringbuf) = arp_ringbuf.as_mut() {
+                        crate::monitor::spoof::process_arp_events(ringbuf, &ctx.spoof_detector, &ctx.device_manager);
+                    }
+                }
                 _ = shutdown_notify.notified() => {
                     log::debug!("Traffic monitoring module received shutdown signal, stopping...");
                     if persist_enabled {
@@ -110,6 +135,11 @@ impl TrafficMonitor {
             .unwrap_or(std::time::Duration::from_secs(0))
             .as_millis() as u64;
 
+        // 将 WAN 流量按远端 IP 归属到域名
+        if let Err(e) = self.process_domain_traffic(ctx, &ingress_ebpf, ts_ms) {
+            log::warn!("Failed to attribute traffic to domains: {}", e);
+        }
+
         let traffic_snapshot = ctx.device_manager.get_all_devices_with_mac();
 
         if let Err(e) = ctx.realtime_manager.insert_metrics_batch(ts_ms, &traffic_snapshot) {
@@ -195,6 +225,20 @@ impl TrafficMonitor {
         Ok(traffic_data)
     }
 
+    /// 读取 REMOTE_TRAFFIC 映射（mac + 远端 IP -> WAN 字节），交给域名归属模块计算增量
+    fn process_domain_traffic(&self, ctx: &TrafficModuleContext, ebpf: &Arc<aya::Ebpf>, now_ms: u64) -> Result<(), anyhow::Error> {
+        let remote_map = HashMap::<&MapData, [u8; 22], [u64; 2]>::try_from(
+            ebpf.map("REMOTE_TRAFFIC").ok_or(anyhow::anyhow!("Cannot find REMOTE_TRAFFIC map"))?,
+        )?;
+
+        let snapshot: StdHashMap<[u8; 22], [u64; 2]> = remote_map.iter().flatten().collect();
+
+        ctx.domain_tracker.process_remote_traffic(snapshot, now_ms);
+        ctx.domain_tracker.prune_expired(now_ms);
+
+        Ok(())
+    }
+
     fn build_raw_device_traffic(
         &self,
         traffic_data: &StdHashMap<[u8; 6], [u64; 4]>,
@@ -426,10 +470,19 @@ impl TrafficMonitor {
             .collect();
 
         let mut desired_limits: std::collections::HashMap<[u8; 6], [u64; 2]> = std::collections::HashMap::new();
+        let mut active_scheduled_limits: std::collections::HashMap<[u8; 6], [u64; 2]> = std::collections::HashMap::new();
+
+        // 设备自身的预定规则优先，其次是所在组的规则
+        let groups = ctx.device_manager.device_groups();
+        let identities = ctx.device_manager.identity_registry();
+        let now = chrono::Local::now();
 
         for mac in device_macs {
-            if let Some(limits) = crate::storage::traffic::calculate_current_rate_limit(&scheduled_limits, &mac) {
+            let limits = crate::storage::traffic::calculate_current_rate_limit(&scheduled_limits, &mac)
+                .or_else(|| groups.current_rate_limit(&identities.group_macs(&mac), &now));
+            if let Some(limits) = limits {
                 desired_limits.insert(mac, limits);
+                active_scheduled_limits.insert(mac, limits);
                 continue;
             }
 
@@ -447,6 +500,19 @@ impl TrafficMonitor {
 
         drop(scheduled_limits);
 
+        // 预定规则开始生效、限速值变化或失效时写入事件日志
+        for (mac, limits) in active_scheduled_limits.iter() {
+            if ctx.active_scheduled_limits.get(mac) != Some(limits) {
+                ctx.device_manager.record_rate_limit_change(mac, Some(*limits));
+            }
+        }
+        for mac in ctx.active_scheduled_limits.keys() {
+            if !active_scheduled_limits.contains_key(mac) {
+                ctx.device_manager.record_rate_limit_change(mac, None);
+            }
+        }
+        ctx.active_scheduled_limits = active_scheduled_limits;
+
         // 获取入口 eBPF 引用（入口和出口共享同一个 eBPF 对象和映射）
         let ingress_ebpf = ctx
             .ingress_ebpf
@@ -547,7 +613,7 @@ impl TrafficMonitor {
         tokio::spawn(async move {
             let payload = { export_latest.lock().await.clone() };
             if let Some(payload) = payload {
-                let _ = client.post(url).json(&payload).send().await;
+                let _ = client.post(url).json(&crate::privacy::export_value(&payload)).send().await;
             }
             export_in_flight.store(false, Ordering::Relaxed);
         });
@@ -0,0 +1,224 @@
+// 基于 rtnetlink 的邻居表访问
+//
+// 通过 RTM_GETNEIGH 获取完整邻居表，并订阅 RTM_NEWNEIGH / RTM_DELNEIGH 组播，
+// 邻居状态变化由内核即时推送，无需周期性执行 `ip neigh` 并解析文本输出。
+
+use crate::netlink::{self, NLM_F_DUMP, RECV_BUFFER_SIZE};
+use anyhow::{anyhow, Result};
+use std::ffi::CString;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::os::fd::OwnedFd;
+use tokio::io::unix::AsyncFd;
+
+// rtnetlink 消息类型与组播组
+const RTM_NEWNEIGH: u16 = 28;
+const RTM_DELNEIGH: u16 = 29;
+const RTM_GETNEIGH: u16 = 30;
+const RTMGRP_NEIGH: u32 = 0x04;
+
+// 邻居属性类型
+const NDA_DST: u16 = 1;
+const NDA_LLADDR: u16 = 2;
+
+const NDMSG_LEN: usize = 12;
+
+// 视为在线的邻居状态（NUD_*），INCOMPLETE / FAILED / NOARP / PERMANENT 均不计入
+const NUD_REACHABLE: u16 = 0x02;
+const NUD_STALE: u16 = 0x04;
+const NUD_DELAY: u16 = 0x08;
+const NUD_PROBE: u16 = 0x10;
+
+/// 一条邻居表记录
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NeighborEntry {
+    pub ifindex: u32,
+    pub ip: IpAddr,
+    pub mac: Option<[u8; 6]>,
+    pub state: u16,
+}
+
+impl NeighborEntry {
+    /// 邻居是否在线（REACHABLE / STALE / DELAY / PROBE）
+    pub fn is_online(&self) -> bool {
+        self.state & (NUD_REACHABLE | NUD_STALE | NUD_DELAY | NUD_PROBE) != 0
+    }
+}
+
+/// 内核推送的邻居变化
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NeighborMessage {
+    New(NeighborEntry),
+    Del(NeighborEntry),
+}
+
+impl NeighborMessage {
+    pub fn entry(&self) -> &NeighborEntry {
+        match self {
+            NeighborMessage::New(entry) | NeighborMessage::Del(entry) => entry,
+        }
+    }
+}
+
+/// 获取接口索引，接口不存在时返回 None
+pub fn interface_index(iface: &str) -> Option<u32> {
+    let name = CString::new(iface).ok()?;
+    let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+    if index == 0 {
+        None
+    } else {
+        Some(index)
+    }
+}
+
+/// 获取完整邻居表（IPv4 与 IPv6）
+pub fn dump_neighbors() -> Result<Vec<NeighborEntry>> {
+    // ndmsg 的 family 为 AF_UNSPEC，同时获取 IPv4 与 IPv6
+    let replies = netlink::request(libc::NETLINK_ROUTE, RTM_GETNEIGH, NLM_F_DUMP, &[0u8; NDMSG_LEN])?;
+    Ok(replies
+        .iter()
+        .filter(|(msg_type, _)| *msg_type == RTM_NEWNEIGH)
+        .filter_map(|(_, payload)| parse_ndmsg(payload))
+        .collect())
+}
+
+/// 邻居变化订阅（RTMGRP_NEIGH）
+pub struct NeighborSubscription {
+    fd: AsyncFd<OwnedFd>,
+    buf: Vec<u8>,
+}
+
+impl NeighborSubscription {
+    pub fn new() -> Result<Self> {
+        let fd = netlink::open_socket(libc::NETLINK_ROUTE, RTMGRP_NEIGH, true)?;
+        Ok(Self {
+            fd: AsyncFd::new(fd)?,
+            buf: vec![0u8; RECV_BUFFER_SIZE],
+        })
+    }
+
+    /// 等待下一批邻居变化
+    ///
+    /// 接收缓冲区溢出（ENOBUFS）时返回错误，调用方应重新获取完整邻居表以保持一致。
+    pub async fn recv(&mut self) -> Result<Vec<NeighborMessage>> {
+        let buf = &mut self.buf;
+        loop {
+            let mut guard = self.fd.readable().await?;
+            match guard.try_io(|inner| netlink::recv_into(inner.get_ref(), buf)) {
+                Ok(Ok(n)) => return Ok(parse_neighbor_messages(&buf[..n])?.0),
+                Ok(Err(e)) => return Err(anyhow!("Failed to read neighbor events: {}", e)),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+}
+
+/// 解析一段 netlink 数据，返回邻居消息以及是否已收到 NLMSG_DONE
+pub fn parse_neighbor_messages(buf: &[u8]) -> Result<(Vec<NeighborMessage>, bool)> {
+    let (messages, done) = netlink::parse_messages(buf)?;
+    let neighbors = messages
+        .iter()
+        .filter_map(|message| match message.msg_type {
+            RTM_NEWNEIGH => parse_ndmsg(message.payload).map(NeighborMessage::New),
+            RTM_DELNEIGH => parse_ndmsg(message.payload).map(NeighborMessage::Del),
+            _ => None,
+        })
+        .collect();
+    Ok((neighbors, done))
+}
+
+fn parse_ndmsg(payload: &[u8]) -> Option<NeighborEntry> {
+    if payload.len() < NDMSG_LEN {
+        return None;
+    }
+    let family = payload[0] as i32;
+    let ifindex = i32::from_ne_bytes(payload[4..8].try_into().unwrap());
+    let state = u16::from_ne_bytes(payload[8..10].try_into().unwrap());
+
+    let mut ip = None;
+    let mut mac = None;
+    for (attr_type, data) in netlink::parse_attributes(&payload[NDMSG_LEN..]) {
+        match attr_type {
+            NDA_DST => {
+                ip = match (family, data.len()) {
+                    (libc::AF_INET, 4) => Some(IpAddr::V4(Ipv4Addr::new(data[0], data[1], data[2], data[3]))),
+                    (libc::AF_INET6, 16) => {
+                        let octets: [u8; 16] = data.try_into().unwrap();
+                        Some(IpAddr::V6(Ipv6Addr::from(octets)))
+                    }
+                    _ => None,
+                };
+            }
+            NDA_LLADDR if data.len() == 6 => {
+                mac = Some(data.try_into().unwrap());
+            }
+            _ => {}
+        }
+    }
+
+    Some(NeighborEntry {
+        ifindex: u32::try_from(ifindex).ok()?,
+        ip: ip?,
+        mac,
+        state,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build_neigh_message(msg_type: u16, family: u8, ifindex: i32, state: u16, dst: &[u8], lladdr: Option<&[u8; 6]>) -> Vec<u8> {
+        let mut body = vec![family, 0, 0, 0];
+        body.extend_from_slice(&ifindex.to_ne_bytes());
+        body.extend_from_slice(&state.to_ne_bytes());
+        body.extend_from_slice(&[0, 0]);
+
+        netlink::push_attribute(&mut body, NDA_DST, dst);
+        if let Some(mac) = lladdr {
+            netlink::push_attribute(&mut body, NDA_LLADDR, mac);
+        }
+
+        netlink::build_message(msg_type, 0, 0, &body)
+    }
+
+    const NUD_FAILED: u16 = 0x20;
+
+    #[test]
+    fn test_parse_neighbor_messages() {
+        let mac = [0x00, 0x11, 0x22, 0x33, 0x44, 0x55];
+        let mut buf = build_neigh_message(
+            RTM_NEWNEIGH,
+            libc::AF_INET as u8,
+            3,
+            NUD_REACHABLE,
+            &[192, 168, 1, 10],
+            Some(&mac),
+        );
+        let v6: [u8; 16] = "fe80::1".parse::<Ipv6Addr>().unwrap().octets();
+        buf.extend(build_neigh_message(
+            RTM_DELNEIGH,
+            libc::AF_INET6 as u8,
+            3,
+            NUD_FAILED,
+            &v6,
+            None,
+        ));
+
+        let (messages, done) = parse_neighbor_messages(&buf).unwrap();
+        assert!(!done);
+        assert_eq!(messages.len(), 2);
+
+        let first = messages[0].entry();
+        assert!(matches!(messages[0], NeighborMessage::New(_)));
+        assert_eq!(first.ifindex, 3);
+        assert_eq!(first.ip, IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10)));
+        assert_eq!(first.mac, Some(mac));
+        assert!(first.is_online());
+
+        let second = messages[1].entry();
+        assert!(matches!(messages[1], NeighborMessage::Del(_)));
+        assert_eq!(second.ip, IpAddr::V6(Ipv6Addr::from(v6)));
+        assert_eq!(second.mac, None);
+        assert!(!second.is_online());
+    }
+}
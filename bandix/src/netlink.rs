@@ -0,0 +1,218 @@
+// netlink 通用工具：套接字、消息与属性编解码
+//
+// rtnetlink（邻居表）与 generic netlink（nl80211）共用，均为主机字节序。
+
+use anyhow::{anyhow, Result};
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::time::Duration;
+
+pub const NLMSG_ERROR: u16 = 2;
+pub const NLMSG_DONE: u16 = 3;
+pub const NLM_F_REQUEST: u16 = 0x01;
+pub const NLM_F_DUMP: u16 = 0x300;
+const NLA_TYPE_MASK: u16 = 0x3fff;
+
+pub const NLMSG_HDR_LEN: usize = 16;
+pub const NLA_HDR_LEN: usize = 4;
+pub const RECV_BUFFER_SIZE: usize = 64 * 1024;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// 一条 netlink 消息（不含 nlmsghdr）
+#[derive(Debug, Clone, Copy)]
+pub struct Message<'a> {
+    pub msg_type: u16,
+    pub payload: &'a [u8],
+}
+
+/// 打开并绑定 netlink 套接字，groups 为要订阅的组播组
+pub fn open_socket(protocol: libc::c_int, groups: u32, nonblocking: bool) -> Result<OwnedFd> {
+    let mut flags = libc::SOCK_RAW | libc::SOCK_CLOEXEC;
+    if nonblocking {
+        flags |= libc::SOCK_NONBLOCK;
+    }
+
+    let fd = unsafe { libc::socket(libc::AF_NETLINK, flags, protocol) };
+    if fd < 0 {
+        return Err(anyhow!("Failed to open netlink socket: {}", io::Error::last_os_error()));
+    }
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    let mut addr: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+    addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+    addr.nl_groups = groups;
+    let ret = unsafe {
+        libc::bind(
+            fd.as_raw_fd(),
+            &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+            std::mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+        )
+    };
+    if ret < 0 {
+        return Err(anyhow!("Failed to bind netlink socket: {}", io::Error::last_os_error()));
+    }
+
+    Ok(fd)
+}
+
+pub fn recv_into(fd: &OwnedFd, buf: &mut [u8]) -> io::Result<usize> {
+    let n = unsafe { libc::recv(fd.as_raw_fd(), buf.as_mut_ptr() as *mut libc::c_void, buf.len(), 0) };
+    if n < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(n as usize)
+    }
+}
+
+/// 构造一条 netlink 消息（nlmsghdr + payload）
+pub fn build_message(msg_type: u16, flags: u16, seq: u32, payload: &[u8]) -> Vec<u8> {
+    let mut msg = Vec::with_capacity(NLMSG_HDR_LEN + payload.len());
+    msg.extend_from_slice(&((NLMSG_HDR_LEN + payload.len()) as u32).to_ne_bytes());
+    msg.extend_from_slice(&msg_type.to_ne_bytes());
+    msg.extend_from_slice(&flags.to_ne_bytes());
+    msg.extend_from_slice(&seq.to_ne_bytes());
+    msg.extend_from_slice(&0u32.to_ne_bytes());
+    msg.extend_from_slice(payload);
+    msg
+}
+
+/// 追加一个属性（自动补齐到 4 字节）
+pub fn push_attribute(buf: &mut Vec<u8>, attr_type: u16, data: &[u8]) {
+    buf.extend_from_slice(&((NLA_HDR_LEN + data.len()) as u16).to_ne_bytes());
+    buf.extend_from_slice(&attr_type.to_ne_bytes());
+    buf.extend_from_slice(data);
+    buf.resize(align4(buf.len()), 0);
+}
+
+/// 解析一段 netlink 数据，返回消息列表以及是否已收到 NLMSG_DONE
+///
+/// 携带错误码的 NLMSG_ERROR 转换为错误，错误码为 0 的确认消息直接忽略。
+pub fn parse_messages(buf: &[u8]) -> Result<(Vec<Message<'_>>, bool)> {
+    let mut messages = Vec::new();
+    let mut offset = 0usize;
+
+    while offset + NLMSG_HDR_LEN <= buf.len() {
+        let len = u32::from_ne_bytes(buf[offset..offset + 4].try_into().unwrap()) as usize;
+        let msg_type = u16::from_ne_bytes(buf[offset + 4..offset + 6].try_into().unwrap());
+        if len < NLMSG_HDR_LEN || offset + len > buf.len() {
+            break;
+        }
+        let payload = &buf[offset + NLMSG_HDR_LEN..offset + len];
+
+        match msg_type {
+            NLMSG_DONE => return Ok((messages, true)),
+            NLMSG_ERROR => {
+                let errno = payload
+                    .get(0..4)
+                    .map(|b| i32::from_ne_bytes(b.try_into().unwrap()))
+                    .unwrap_or(0);
+                if errno != 0 {
+                    return Err(anyhow!("Netlink error: {}", io::Error::from_raw_os_error(-errno)));
+                }
+            }
+            _ => messages.push(Message { msg_type, payload }),
+        }
+
+        offset += align4(len);
+    }
+
+    Ok((messages, false))
+}
+
+/// 解析属性列表，返回 (类型, 数据)，类型已去除 NESTED / BYTEORDER 标志
+pub fn parse_attributes(buf: &[u8]) -> Vec<(u16, &[u8])> {
+    let mut attrs = Vec::new();
+    let mut offset = 0usize;
+
+    while offset + NLA_HDR_LEN <= buf.len() {
+        let len = u16::from_ne_bytes(buf[offset..offset + 2].try_into().unwrap()) as usize;
+        let attr_type = u16::from_ne_bytes(buf[offset + 2..offset + 4].try_into().unwrap()) & NLA_TYPE_MASK;
+        if len < NLA_HDR_LEN || offset + len > buf.len() {
+            break;
+        }
+        attrs.push((attr_type, &buf[offset + NLA_HDR_LEN..offset + len]));
+        offset += align4(len);
+    }
+
+    attrs
+}
+
+/// 发送一次请求并收集全部应答消息（类型, 负载）
+///
+/// dump 请求读取到 NLMSG_DONE 为止，普通请求读取到第一条应答为止。
+pub fn request(protocol: libc::c_int, msg_type: u16, flags: u16, payload: &[u8]) -> Result<Vec<(u16, Vec<u8>)>> {
+    let fd = open_socket(protocol, 0, false)?;
+
+    // 设置接收超时，避免内核无响应时阻塞调用方
+    let timeout = libc::timeval {
+        tv_sec: REQUEST_TIMEOUT.as_secs() as libc::time_t,
+        tv_usec: 0,
+    };
+    unsafe {
+        libc::setsockopt(
+            fd.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_RCVTIMEO,
+            &timeout as *const libc::timeval as *const libc::c_void,
+            std::mem::size_of::<libc::timeval>() as libc::socklen_t,
+        );
+    }
+
+    let message = build_message(msg_type, NLM_F_REQUEST | flags, 1, payload);
+    let sent = unsafe { libc::send(fd.as_raw_fd(), message.as_ptr() as *const libc::c_void, message.len(), 0) };
+    if sent < 0 {
+        return Err(anyhow!("Failed to send netlink request: {}", io::Error::last_os_error()));
+    }
+
+    let is_dump = flags & NLM_F_DUMP == NLM_F_DUMP;
+    let mut buf = vec![0u8; RECV_BUFFER_SIZE];
+    let mut out = Vec::new();
+    loop {
+        let n = recv_into(&fd, &mut buf).map_err(|e| anyhow!("Failed to read netlink reply: {}", e))?;
+        if n == 0 {
+            break;
+        }
+        let (messages, done) = parse_messages(&buf[..n])?;
+        out.extend(messages.iter().map(|m| (m.msg_type, m.payload.to_vec())));
+        if done || (!is_dump && !out.is_empty()) {
+            break;
+        }
+    }
+
+    Ok(out)
+}
+
+pub fn align4(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_messages_done_and_error() {
+        let done = build_message(NLMSG_DONE, 0, 1, &0i32.to_ne_bytes());
+        assert!(parse_messages(&done).unwrap().1);
+
+        let error = build_message(NLMSG_ERROR, 0, 1, &(-libc::EPERM).to_ne_bytes());
+        assert!(parse_messages(&error).is_err());
+
+        let ack = build_message(NLMSG_ERROR, 0, 1, &0i32.to_ne_bytes());
+        let (messages, done) = parse_messages(&ack).unwrap();
+        assert!(messages.is_empty() && !done);
+    }
+
+    #[test]
+    fn test_parse_attributes() {
+        let mut buf = Vec::new();
+        push_attribute(&mut buf, 1, &[0xaa, 0xbb, 0xcc]);
+        // 0x8000 为 NLA_F_NESTED 标志
+        push_attribute(&mut buf, 2 | 0x8000, &7u32.to_ne_bytes());
+
+        let attrs = parse_attributes(&buf);
+        assert_eq!(attrs.len(), 2);
+        assert_eq!(attrs[0], (1, &[0xaa, 0xbb, 0xcc][..]));
+        assert_eq!(attrs[1], (2, &7u32.to_ne_bytes()[..]));
+    }
+}
@@ -0,0 +1,253 @@
+// 设备厂商识别：根据 MAC 地址前缀（OUI）查询 IEEE 分配的厂商名称
+//
+// 内置常见厂商的 OUI 表；数据目录中存在 IEEE 注册表文件时一并加载以获得完整覆盖：
+// - oui.txt（IEEE MA-L 文本格式）
+// - oui.csv / mam.csv / oui36.csv（IEEE MA-L / MA-M / MA-S CSV 格式）
+// 同时识别本地管理地址（通常是系统随机化生成的 MAC），此类地址没有厂商信息。
+
+use once_cell::sync::OnceCell;
+use std::collections::HashMap;
+use std::path::Path;
+
+static DATABASE: OnceCell<OuiDatabase> = OnceCell::new();
+
+// 数据目录中的 IEEE 注册表文件
+const REGISTRY_FILES: &[&str] = &["oui.txt", "oui.csv", "mam.csv", "oui36.csv"];
+
+// 前缀长度（十六进制位数）：MA-S 36 位、MA-M 28 位、MA-L 24 位，按最长匹配查询
+const PREFIX_DIGITS: &[u8] = &[9, 7, 6];
+
+// 内置 OUI 表（MA-L），仅覆盖家庭网络中常见的厂商
+const BUILTIN_VENDORS: &[(&str, &[u32])] = &[
+    (
+        "Apple, Inc.",
+        &[
+            0x000393, 0x000A95, 0x0017F2, 0x001B63, 0x001EC2, 0x001FF3, 0x002500, 0x28CFE9, 0x3C0754, 0x7CD1C3, 0x8C8590, 0xA45E60,
+            0xACBC32, 0xF01898,
+        ],
+    ),
+    (
+        "Samsung Electronics Co.,Ltd",
+        &[0x001632, 0x001D25, 0x002119, 0x0012FB, 0x5C0A5B, 0x8C7712],
+    ),
+    ("Google, Inc.", &[0x001A11, 0x3C5AB4, 0x546009, 0xF4F5D8, 0xF4F5E8]),
+    (
+        "Amazon Technologies Inc.",
+        &[0x0C47C9, 0x44650D, 0x6837E9, 0x74C246, 0x84D6D0, 0xF0272D, 0xFC65DE],
+    ),
+    (
+        "Xiaomi Communications Co Ltd",
+        &[0x009EC8, 0x286C07, 0x34CE00, 0x50642B, 0x640980, 0x7811DC, 0x7C1DD9, 0xF8A45F],
+    ),
+    (
+        "Huawei Technologies Co.,Ltd",
+        &[0x001882, 0x001E10, 0x00259E, 0x00E0FC, 0x286ED4, 0x4846FB, 0x80FB06],
+    ),
+    ("OnePlus Technology (Shenzhen) Co., Ltd", &[0x94652D, 0xC0EEFB]),
+    ("Intel Corporate", &[0x0013E8, 0x001517, 0x001B21, 0x001E67]),
+    ("Dell Inc.", &[0x001422, 0x180373, 0xB8AC6F, 0xD4BED9, 0xF8BC12]),
+    ("Hewlett Packard", &[0x0017A4, 0x001E0B, 0x3CD92B]),
+    (
+        "ASUSTek COMPUTER INC.",
+        &[0x000C6E, 0x001A92, 0x049226, 0x2C56DC, 0xAC9E17, 0xF832E4],
+    ),
+    ("Microsoft Corporation", &[0x000D3A, 0x00155D, 0x0050F2, 0x281878, 0x7C1E52]),
+    ("Sony Interactive Entertainment Inc.", &[0x00041F, 0x001315, 0x00D9D1, 0x280DFC]),
+    (
+        "Nintendo Co.,Ltd",
+        &[
+            0x0009BF, 0x0017AB, 0x00191D, 0x001F32, 0x0022AA, 0x002444, 0x34AF2C, 0x7CBB8A, 0x98B6E9, 0xE0E751,
+        ],
+    ),
+    ("Roku, Inc.", &[0x080581, 0xB0A737, 0xCC6DA0, 0xD83134, 0xDC3A5E]),
+    ("LG Electronics", &[0x001C62, 0x001E75, 0x10683F, 0xA816B2, 0xCC2D8C]),
+    ("Sonos, Inc.", &[0x000E58, 0x5CAAFD, 0x7828CA, 0x949F3E, 0xB8E937]),
+    ("Nest Labs Inc.", &[0x18B430, 0x641666]),
+    ("Philips Lighting BV", &[0x001788, 0xECB5FA]),
+    (
+        "Espressif Inc.",
+        &[
+            0x18FE34, 0x240AC4, 0x246F28, 0x30AEA4, 0x3C71BF, 0x5CCF7F, 0x600194, 0x84F3EB, 0x8CAAB5, 0xA4CF12, 0xBCDDC2, 0xECFABC,
+        ],
+    ),
+    ("Raspberry Pi Trading Ltd", &[0xB827EB, 0xDCA632, 0xE45F01, 0xD83ADD, 0x2CCF67]),
+    (
+        "TP-LINK TECHNOLOGIES CO.,LTD.",
+        &[
+            0x002719, 0x14CC20, 0x50C7BF, 0x60E327, 0x647002, 0xA0F3C1, 0xC46E1F, 0xEC086B, 0xF4F26D,
+        ],
+    ),
+    ("NETGEAR", &[0x00095B, 0x00146C, 0x001B2F, 0xA040A0, 0xC03F0E]),
+    (
+        "Ubiquiti Inc",
+        &[
+            0x00156D, 0x002722, 0x0418D6, 0x24A43C, 0x687251, 0x788A20, 0x802AA8, 0xDC9FDB, 0xF09FC2, 0xFCECDA,
+        ],
+    ),
+    ("Synology Incorporated", &[0x001132]),
+    ("Realtek Semiconductor Corp.", &[0x00E04C]),
+    ("Cisco Systems, Inc", &[0x00000C]),
+    ("VMware, Inc.", &[0x000569, 0x000C29, 0x005056]),
+    ("PCS Computer Systems GmbH", &[0x080027]),
+];
+
+/// OUI 数据库，键为（前缀十六进制位数, 前缀值）
+pub struct OuiDatabase {
+    entries: HashMap<(u8, u64), String>,
+}
+
+impl OuiDatabase {
+    /// 仅包含内置表的数据库
+    pub fn builtin() -> Self {
+        let mut entries = HashMap::new();
+        for (vendor, prefixes) in BUILTIN_VENDORS {
+            for prefix in prefixes.iter() {
+                entries.insert((6, *prefix as u64), vendor.to_string());
+            }
+        }
+        Self { entries }
+    }
+
+    /// 内置表加上数据目录中的 IEEE 注册表文件，文件中的条目覆盖内置条目
+    pub fn load(data_dir: &str) -> Self {
+        let mut db = Self::builtin();
+        for name in REGISTRY_FILES {
+            let path = Path::new(data_dir).join(name);
+            let content = match std::fs::read(&path) {
+                Ok(content) => String::from_utf8_lossy(&content).into_owned(),
+                Err(_) => continue,
+            };
+            let count = db.merge_registry(&content);
+            log::info!("Loaded {} OUI entries from {}", count, path.display());
+        }
+        db
+    }
+
+    /// 合并 IEEE 注册表内容（自动识别文本或 CSV 格式），返回新增条目数
+    pub fn merge_registry(&mut self, content: &str) -> usize {
+        let mut count = 0;
+        for line in content.lines() {
+            if let Some((digits, prefix, vendor)) = parse_registry_line(line) {
+                self.entries.insert((digits, prefix), vendor);
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// 按最长前缀匹配查询厂商
+    pub fn lookup(&self, mac: &[u8; 6]) -> Option<&str> {
+        let value = mac.iter().fold(0u64, |acc, &b| (acc << 8) | b as u64);
+        PREFIX_DIGITS.iter().find_map(|&digits| {
+            let prefix = value >> (48 - digits as u32 * 4);
+            self.entries.get(&(digits, prefix)).map(|vendor| vendor.as_str())
+        })
+    }
+}
+
+/// 加载 OUI 数据库，需在创建设备之前调用
+pub fn init(data_dir: &str) {
+    let db = OuiDatabase::load(data_dir);
+    log::debug!("OUI database ready with {} entries", db.entries.len());
+    let _ = DATABASE.set(db);
+}
+
+fn database() -> &'static OuiDatabase {
+    DATABASE.get_or_init(OuiDatabase::builtin)
+}
+
+/// 查询 MAC 地址对应的厂商；随机化地址返回 None
+pub fn lookup_vendor(mac: &[u8; 6]) -> Option<String> {
+    if is_randomized_mac(mac) {
+        return None;
+    }
+    database().lookup(mac).map(|vendor| vendor.to_string())
+}
+
+/// 是否为本地管理的单播地址（手机等设备的随机化 MAC 均属于此类）
+pub fn is_randomized_mac(mac: &[u8; 6]) -> bool {
+    mac[0] & 0x02 != 0 && mac[0] & 0x01 == 0
+}
+
+/// 解析注册表中的一行，返回（前缀十六进制位数, 前缀值, 厂商名）
+///
+/// 文本格式：`00-22-72   (hex)\t\tAmerican Micro-Fuel Device Corp.`
+/// CSV 格式：`MA-L,002272,American Micro-Fuel Device Corp.,<地址>`
+fn parse_registry_line(line: &str) -> Option<(u8, u64, String)> {
+    let (assignment, vendor) = if let Some((prefix, vendor)) = line.split_once("(hex)") {
+        (prefix.trim().replace('-', ""), vendor.trim().to_string())
+    } else if line.starts_with("MA-") {
+        let fields = split_csv_line(line);
+        (fields.get(1)?.trim().to_string(), fields.get(2)?.trim().to_string())
+    } else {
+        return None;
+    };
+
+    if vendor.is_empty() || !matches!(assignment.len(), 6 | 7 | 9) {
+        return None;
+    }
+    let prefix = u64::from_str_radix(&assignment, 16).ok()?;
+    Some((assignment.len() as u8, prefix, vendor))
+}
+
+/// 拆分一行 CSV，支持双引号包裹的字段
+fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                current.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => fields.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    fields.push(current);
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookup_builtin_and_randomized() {
+        let db = OuiDatabase::builtin();
+        assert_eq!(
+            db.lookup(&[0xB8, 0x27, 0xEB, 0x01, 0x02, 0x03]),
+            Some("Raspberry Pi Trading Ltd")
+        );
+        assert_eq!(db.lookup(&[0x00, 0x00, 0x5E, 0x00, 0x01, 0x02]), None);
+
+        assert!(is_randomized_mac(&[0xDA, 0xA1, 0x19, 0x00, 0x00, 0x01]));
+        assert!(!is_randomized_mac(&[0xB8, 0x27, 0xEB, 0x01, 0x02, 0x03]));
+        // 组播地址不属于随机化地址
+        assert!(!is_randomized_mac(&[0x33, 0x33, 0x00, 0x00, 0x00, 0x01]));
+        assert_eq!(lookup_vendor(&[0xDA, 0xA1, 0x19, 0x00, 0x00, 0x01]), None);
+    }
+
+    #[test]
+    fn test_merge_registry_longest_prefix() {
+        let mut db = OuiDatabase::builtin();
+        let content = "\
+00-22-72   (hex)\t\tAmerican Micro-Fuel Device Corp.
+002272     (base 16)\t\tAmerican Micro-Fuel Device Corp.
+MA-M,70B3D51,\"Example, Inc.\",Somewhere
+MA-S,70B3D5123,Tiny Vendor,\"1 Road, City\"
+Registry,Assignment,Organization Name,Organization Address
+";
+        assert_eq!(db.merge_registry(content), 3);
+        assert_eq!(
+            db.lookup(&[0x00, 0x22, 0x72, 0xAA, 0xBB, 0xCC]),
+            Some("American Micro-Fuel Device Corp.")
+        );
+        assert_eq!(db.lookup(&[0x70, 0xB3, 0xD5, 0x12, 0x34, 0x56]), Some("Tiny Vendor"));
+        assert_eq!(db.lookup(&[0x70, 0xB3, 0xD5, 0x1F, 0x00, 0x00]), Some("Example, Inc."));
+    }
+}
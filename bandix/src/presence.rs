@@ -0,0 +1,287 @@
+// 设备在线会话：记录每台设备每次上线到离线的时间段
+//
+// last_online_ts 会被不断覆盖，无法回答“这周电视什么时候开着”。这里根据邻居表的 IPv4 在线状态
+// 为每个 MAC 维护上线/离线会话，并持久化到数据目录，用于查询单设备的会话与全网按天统计的在线时长。
+
+use crate::storage::presence::{PresenceSession, PresenceSnapshot};
+use chrono::{Local, NaiveDate, TimeZone};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Mutex;
+
+// 会话保留时长：90 天
+const RETENTION_MS: u64 = 90 * 24 * 3600 * 1000;
+// 每台设备最多保留的会话数
+const MAX_SESSIONS_PER_DEVICE: usize = 2000;
+// 最短写盘间隔，减少对闪存的写入
+const SAVE_INTERVAL_MS: u64 = 5 * 60 * 1000;
+
+/// 某一天（本地时间）全网的在线时长
+#[derive(Debug, Clone, PartialEq)]
+pub struct DailyOnline {
+    pub date: NaiveDate,
+    pub online_ms: u64,      // 所有设备在线时长之和
+    pub device_count: usize, // 当天有在线记录的设备数
+}
+
+#[derive(Debug, Default)]
+struct PresenceState {
+    sessions: HashMap<[u8; 6], Vec<PresenceSession>>, // 按开始时间升序，只有最后一个可能未结束
+    dirty: bool,
+    last_save_ms: u64,
+}
+
+impl PresenceState {
+    fn open(&mut self, mac: [u8; 6], ts_ms: u64) {
+        let sessions = self.sessions.entry(mac).or_default();
+        if !has_open_session(sessions) {
+            sessions.push(PresenceSession {
+                start_ms: ts_ms,
+                end_ms: None,
+            });
+            self.dirty = true;
+        }
+    }
+
+    fn close(&mut self, mac: &[u8; 6], ts_ms: u64) {
+        if let Some(session) = self.sessions.get_mut(mac).and_then(|s| s.last_mut()) {
+            if session.end_ms.is_none() {
+                session.end_ms = Some(ts_ms.max(session.start_ms));
+                self.dirty = true;
+            }
+        }
+    }
+
+    fn prune(&mut self, now_ms: u64) {
+        let cutoff = now_ms.saturating_sub(RETENTION_MS);
+        for sessions in self.sessions.values_mut() {
+            let before = sessions.len();
+            sessions.retain(|s| s.end_ms.unwrap_or(u64::MAX) >= cutoff);
+            if sessions.len() > MAX_SESSIONS_PER_DEVICE {
+                sessions.drain(..sessions.len() - MAX_SESSIONS_PER_DEVICE);
+            }
+            self.dirty |= sessions.len() != before;
+        }
+        self.sessions.retain(|_, sessions| !sessions.is_empty());
+    }
+
+    fn snapshot(&self, checkpoint_ms: u64) -> PresenceSnapshot {
+        let sorted: BTreeMap<_, _> = self.sessions.iter().collect();
+        PresenceSnapshot {
+            checkpoint_ms,
+            sessions: sorted
+                .into_iter()
+                .flat_map(|(mac, sessions)| sessions.iter().map(move |s| (*mac, *s)))
+                .collect(),
+        }
+    }
+}
+
+/// 设备在线会话记录，由设备管理器在邻居状态变化时更新
+pub struct PresenceTracker {
+    data_dir: Option<String>, // 为 None 时不持久化
+    state: Mutex<PresenceState>,
+}
+
+impl PresenceTracker {
+    /// 从数据目录加载已保存的会话
+    pub fn load(data_dir: &str) -> Self {
+        let snapshot = crate::storage::presence::load_sessions(data_dir).unwrap_or_else(|e| {
+            log::warn!("Failed to load device sessions: {}", e);
+            PresenceSnapshot::default()
+        });
+        Self::from_snapshot(Some(data_dir.to_string()), snapshot)
+    }
+
+    fn from_snapshot(data_dir: Option<String>, snapshot: PresenceSnapshot) -> Self {
+        let mut state = PresenceState::default();
+        for (mac, mut session) in snapshot.sessions {
+            // 上次运行结束时仍在线的会话，按最后一次写盘时间结束
+            if session.end_ms.is_none() {
+                session.end_ms = Some(snapshot.checkpoint_ms.max(session.start_ms));
+            }
+            state.sessions.entry(mac).or_default().push(session);
+        }
+        for sessions in state.sessions.values_mut() {
+            sessions.sort_by_key(|s| s.start_ms);
+        }
+        Self {
+            data_dir,
+            state: Mutex::new(state),
+        }
+    }
+
+    fn persist(&self, state: &mut PresenceState, now_ms: u64) {
+        if let Some(data_dir) = &self.data_dir {
+            if let Err(e) = crate::storage::presence::save_sessions(data_dir, &state.snapshot(now_ms)) {
+                log::warn!("Failed to save device sessions: {}", e);
+                return;
+            }
+        }
+        state.dirty = false;
+        state.last_save_ms = now_ms;
+    }
+
+    /// 记录单个设备的上线/离线
+    pub fn record(&self, mac: [u8; 6], online: bool, now_ms: u64) {
+        let mut state = self.state.lock().unwrap();
+        if online {
+            state.open(mac, now_ms);
+        } else {
+            state.close(&mac, now_ms);
+        }
+    }
+
+    /// 按全量刷新得到的在线状态校准会话，并按间隔写盘
+    pub fn reconcile(&self, online: &HashMap<[u8; 6], bool>, now_ms: u64) {
+        let mut state = self.state.lock().unwrap();
+        for (mac, is_online) in online.iter() {
+            if *is_online {
+                state.open(*mac, now_ms);
+            }
+        }
+        let macs: Vec<[u8; 6]> = state.sessions.keys().copied().collect();
+        for mac in macs {
+            if !online.get(&mac).copied().unwrap_or(false) {
+                state.close(&mac, now_ms);
+            }
+        }
+        state.prune(now_ms);
+
+        // 有设备在线时也定期写盘，使重启后能按较新的时间结束未完成的会话
+        let has_open = state.sessions.values().any(|s| has_open_session(s));
+        if (state.dirty || has_open) && now_ms.saturating_sub(state.last_save_ms) >= SAVE_INTERVAL_MS {
+            self.persist(&mut state, now_ms);
+        }
+    }
+
+    /// 立即写盘，用于退出前
+    pub fn flush(&self, now_ms: u64) {
+        let mut state = self.state.lock().unwrap();
+        self.persist(&mut state, now_ms);
+    }
+
+    /// 某设备与 [start_ms, end_ms) 有重叠的会话，按开始时间升序
+    pub fn sessions(&self, mac: &[u8; 6], start_ms: u64, end_ms: u64) -> Vec<PresenceSession> {
+        let state = self.state.lock().unwrap();
+        state
+            .sessions
+            .get(mac)
+            .map(|sessions| {
+                sessions
+                    .iter()
+                    .filter(|s| s.start_ms < end_ms && s.end_ms.unwrap_or(u64::MAX) > start_ms)
+                    .copied()
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// 全网按本地日期统计 [start_ms, end_ms) 内的在线时长，未结束的会话计算到 now_ms
+    pub fn daily_online(&self, start_ms: u64, end_ms: u64, now_ms: u64) -> Vec<DailyOnline> {
+        let mut days: BTreeMap<NaiveDate, (u64, HashSet<[u8; 6]>)> = BTreeMap::new();
+        for_each_local_day(start_ms, end_ms, |date, _, _| {
+            days.entry(date).or_default();
+        });
+
+        let state = self.state.lock().unwrap();
+        for (mac, sessions) in state.sessions.iter() {
+            for session in sessions {
+                let from = session.start_ms.max(start_ms);
+                let to = session.end_ms.unwrap_or(now_ms).min(end_ms);
+                for_each_local_day(from, to, |date, day_from, day_to| {
+                    let entry = days.entry(date).or_default();
+                    entry.0 += day_to - day_from;
+                    entry.1.insert(*mac);
+                });
+            }
+        }
+
+        days.into_iter()
+            .map(|(date, (online_ms, macs))| DailyOnline {
+                date,
+                online_ms,
+                device_count: macs.len(),
+            })
+            .collect()
+    }
+}
+
+fn has_open_session(sessions: &[PresenceSession]) -> bool {
+    matches!(sessions.last(), Some(session) if session.end_ms.is_none())
+}
+
+/// 下一个本地零点（毫秒时间戳）
+fn next_local_midnight(ts_ms: u64) -> Option<(NaiveDate, u64)> {
+    let date = Local.timestamp_millis_opt(ts_ms as i64).earliest()?.date_naive();
+    let midnight = date.succ_opt()?.and_hms_opt(0, 0, 0)?;
+    let next = Local.from_local_datetime(&midnight).earliest()?.timestamp_millis();
+    Some((date, next as u64))
+}
+
+/// 将 [start_ms, end_ms) 按本地日期切分，对每一段调用 f(date, from, to)
+fn for_each_local_day(start_ms: u64, end_ms: u64, mut f: impl FnMut(NaiveDate, u64, u64)) {
+    let mut from = start_ms;
+    while from < end_ms {
+        let (date, next) = match next_local_midnight(from) {
+            Some((date, next)) if next > from => (date, next),
+            _ => return,
+        };
+        let to = next.min(end_ms);
+        f(date, from, to);
+        from = to;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TV: [u8; 6] = [0x00, 0x11, 0x22, 0x33, 0x44, 0x55];
+    const PHONE: [u8; 6] = [0x00, 0x11, 0x22, 0x33, 0x44, 0x66];
+
+    #[test]
+    fn test_sessions_and_restart() {
+        let tracker = PresenceTracker::from_snapshot(None, PresenceSnapshot::default());
+        tracker.record(TV, true, 1_000);
+        tracker.record(TV, true, 2_000);
+        tracker.record(TV, false, 5_000);
+        tracker.reconcile(&HashMap::from([(TV, true), (PHONE, false)]), 8_000);
+        assert_eq!(
+            tracker.sessions(&TV, 0, u64::MAX),
+            vec![
+                PresenceSession {
+                    start_ms: 1_000,
+                    end_ms: Some(5_000)
+                },
+                PresenceSession {
+                    start_ms: 8_000,
+                    end_ms: None
+                },
+            ]
+        );
+        assert!(tracker.sessions(&PHONE, 0, u64::MAX).is_empty());
+        assert_eq!(tracker.sessions(&TV, 5_000, 8_000).len(), 0);
+
+        // 重启后未结束的会话按写盘时间结束
+        let snapshot = tracker.state.lock().unwrap().snapshot(9_000);
+        let restored = PresenceTracker::from_snapshot(None, snapshot);
+        assert_eq!(restored.sessions(&TV, 0, u64::MAX)[1].end_ms, Some(9_000));
+    }
+
+    #[test]
+    fn test_daily_online_splits_at_midnight() {
+        let (_, start) = next_local_midnight(1_700_000_000_000).unwrap();
+        let (_, midnight) = next_local_midnight(start).unwrap();
+        let tracker = PresenceTracker::from_snapshot(None, PresenceSnapshot::default());
+        tracker.record(TV, true, midnight - 3_600_000);
+        tracker.record(TV, false, midnight + 7_200_000);
+        tracker.record(PHONE, true, midnight + 1_000);
+
+        let days = tracker.daily_online(start, midnight + 3_600_000 * 4, midnight + 3_600_000 * 4);
+        assert_eq!(days.len(), 2);
+        assert_eq!(days[0].online_ms, 3_600_000);
+        assert_eq!(days[0].device_count, 1);
+        assert_eq!(days[1].online_ms, 7_200_000 + 3_600_000 * 4 - 1_000);
+        assert_eq!(days[1].device_count, 2);
+    }
+}
@@ -0,0 +1,308 @@
+// 隐私控制：API 响应与 webhook 导出中的 MAC / IP / 域名匿名化
+//
+// 开启 --privacy-anonymize 后：
+// - MAC 替换为由哈希生成的本地管理地址（02:xx:xx:xx:xx:xx）
+// - IPv4 替换为 240.0.0.0/4 保留段中的地址，IPv6 替换为 fd00::/8 中的地址
+// - 域名截断为可注册域名（例如 a.b.example.co.uk -> example.co.uk）
+// 哈希使用持久化在数据目录中的随机盐，同一设备在重启前后映射结果一致，但无法从结果反推原值。
+
+use serde::Serialize;
+use serde_json::Value;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+static ANONYMIZE: AtomicBool = AtomicBool::new(false);
+static SALT: AtomicU64 = AtomicU64::new(0);
+
+const SALT_FILE: &str = "privacy_salt";
+
+// 值为 MAC 地址的 JSON 字段
+const MAC_KEYS: &[&str] = &[
+    "mac",
+    "device_mac",
+    "mac_address",
+    "macs",
+    "identity",
+    "conflicting_mac",
+    "devices",
+];
+// 值为设备 IP 地址（或地址列表）的 JSON 字段
+const IP_KEYS: &[&str] = &[
+    "ip",
+    "device_ip",
+    "source_ip",
+    "destination_ip",
+    "ip_address",
+    "ipv6_addresses",
+    "requested_ip",
+    "previous_ip",
+];
+// 值为域名（或域名列表）的 JSON 字段
+const DOMAIN_KEYS: &[&str] = &["domain", "domains"];
+// 元素为 {name, count} 的域名排行字段
+const DOMAIN_RANKING_KEYS: &[&str] = &["top_domains", "top_blocked_domains"];
+// 元素为 {name, count} 的设备排行字段，name 为主机名，没有主机名时为 MAC
+const DEVICE_RANKING_KEYS: &[&str] = &["top_devices", "blocked_devices"];
+// 元素为 {name, count} 的 DNS 服务器排行字段，name 为服务器 IP
+const SERVER_RANKING_KEYS: &[&str] = &["top_dns_servers"];
+
+// 常见的二级公共后缀（co.uk、com.cn 等），用于近似计算可注册域名
+const SECOND_LEVEL_SUFFIXES: &[&str] = &["co", "com", "net", "org", "gov", "edu", "ac", "or", "ne", "go"];
+
+/// 根据命令行参数初始化隐私设置；开启匿名化时加载（或生成）哈希盐
+pub fn init(anonymize: bool, data_dir: &str) -> Result<(), anyhow::Error> {
+    if anonymize {
+        SALT.store(load_or_create_salt(data_dir)?, Ordering::Relaxed);
+    }
+    ANONYMIZE.store(anonymize, Ordering::Relaxed);
+    Ok(())
+}
+
+/// 是否开启了匿名化
+pub fn anonymize_enabled() -> bool {
+    ANONYMIZE.load(Ordering::Relaxed)
+}
+
+fn load_or_create_salt(data_dir: &str) -> Result<u64, anyhow::Error> {
+    let path = Path::new(data_dir).join(SALT_FILE);
+    if let Ok(content) = std::fs::read_to_string(&path) {
+        if let Ok(salt) = u64::from_str_radix(content.trim(), 16) {
+            return Ok(salt);
+        }
+        log::warn!("Invalid privacy salt file {}, regenerating", path.display());
+    }
+
+    // 随机盐：RandomState 的随机密钥 + 当前时间 + 进程号
+    use std::hash::{BuildHasher, Hasher};
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u128(
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos(),
+    );
+    hasher.write_u32(std::process::id());
+    let salt = hasher.finish();
+
+    std::fs::create_dir_all(data_dir)?;
+    std::fs::write(&path, format!("{:016x}\n", salt))?;
+    Ok(salt)
+}
+
+/// 带盐的 FNV-1a 64 位哈希
+fn salted_hash(salt: u64, input: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in salt.to_le_bytes().iter().chain(input.iter()) {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// 将 MAC 地址映射为本地管理地址；无法解析的值（例如 "all"）原样返回
+fn anonymize_mac_with(salt: u64, mac: &str) -> String {
+    match crate::utils::network_utils::parse_mac_address(mac) {
+        Ok(parsed) => {
+            let h = salted_hash(salt, &parsed).to_be_bytes();
+            let anon = [0x02, h[0], h[1], h[2], h[3], h[4]];
+            crate::utils::format_utils::format_mac(&anon)
+        }
+        Err(_) => mac.to_string(),
+    }
+}
+
+/// 将 IP 地址映射到保留地址段；无法解析的值原样返回
+fn anonymize_ip_with(salt: u64, ip: &str) -> String {
+    match ip.parse::<IpAddr>() {
+        Ok(IpAddr::V4(v4)) => {
+            let h = salted_hash(salt, &v4.octets()).to_be_bytes();
+            Ipv4Addr::new(240 | (h[0] & 0x0f), h[1], h[2], h[3]).to_string()
+        }
+        Ok(IpAddr::V6(v6)) => {
+            let h = salted_hash(salt, &v6.octets()).to_be_bytes();
+            let mut octets = [0u8; 16];
+            octets[0] = 0xfd;
+            octets[8..16].copy_from_slice(&h);
+            Ipv6Addr::from(octets).to_string()
+        }
+        Err(_) => ip.to_string(),
+    }
+}
+
+/// 截断为可注册域名（近似：最后两级，若倒数第二级是常见的二级后缀则保留三级）
+pub fn registrable_domain(domain: &str) -> String {
+    let domain = domain.trim_end_matches('.');
+    let labels: Vec<&str> = domain.split('.').collect();
+    if labels.len() <= 2 {
+        return domain.to_string();
+    }
+    let tld = labels[labels.len() - 1];
+    let second = labels[labels.len() - 2];
+    let keep = if tld.len() == 2 && SECOND_LEVEL_SUFFIXES.contains(&second) { 3 } else { 2 };
+    labels[labels.len() - keep..].join(".")
+}
+
+/// 按字段名递归改写 JSON 中的 MAC / IP / 域名
+fn anonymize_value_with(salt: u64, value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, v) in map.iter_mut() {
+                let key = key.as_str();
+                if MAC_KEYS.contains(&key) {
+                    map_strings(v, |s| anonymize_mac_with(salt, s));
+                    anonymize_value_with(salt, v);
+                } else if IP_KEYS.contains(&key) {
+                    map_strings(v, |s| anonymize_ip_with(salt, s));
+                } else if DOMAIN_KEYS.contains(&key) {
+                    map_strings(v, registrable_domain);
+                    anonymize_value_with(salt, v);
+                } else if DOMAIN_RANKING_KEYS.contains(&key) {
+                    map_ranking_names(v, registrable_domain);
+                } else if DEVICE_RANKING_KEYS.contains(&key) {
+                    map_ranking_names(v, |s| anonymize_mac_with(salt, s));
+                } else if SERVER_RANKING_KEYS.contains(&key) {
+                    map_ranking_names(v, |s| anonymize_ip_with(salt, s));
+                } else {
+                    anonymize_value_with(salt, v);
+                }
+            }
+        }
+        Value::Array(items) => {
+            for item in items.iter_mut() {
+                anonymize_value_with(salt, item);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// 对排行数组中每个元素的 name 应用 f
+fn map_ranking_names<F: Fn(&str) -> String>(value: &mut Value, f: F) {
+    if let Value::Array(items) = value {
+        for item in items.iter_mut() {
+            if let Some(name) = item.get_mut("name") {
+                map_strings(name, &f);
+            }
+        }
+    }
+}
+
+/// 对字符串或字符串数组中的每个字符串应用 f
+fn map_strings<F: Fn(&str) -> String>(value: &mut Value, f: F) {
+    match value {
+        Value::String(s) => *s = f(s),
+        Value::Array(items) => {
+            for item in items.iter_mut() {
+                if let Value::String(s) = item {
+                    *s = f(s);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// 匿名化 JSON 响应体；未开启匿名化或不是 JSON 时原样返回
+pub fn anonymize_json_body(body: String) -> String {
+    if !anonymize_enabled() {
+        return body;
+    }
+    match serde_json::from_str::<Value>(&body) {
+        Ok(mut value) => {
+            anonymize_value_with(SALT.load(Ordering::Relaxed), &mut value);
+            serde_json::to_string(&value).unwrap_or(body)
+        }
+        Err(_) => body,
+    }
+}
+
+/// webhook 导出前的负载转换：开启匿名化时改写其中的 MAC / IP / 域名
+pub fn export_value<T: Serialize>(payload: &T) -> Value {
+    let mut value = serde_json::to_value(payload).unwrap_or(Value::Null);
+    if anonymize_enabled() {
+        anonymize_value_with(SALT.load(Ordering::Relaxed), &mut value);
+    }
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registrable_domain() {
+        assert_eq!(registrable_domain("a.b.example.com"), "example.com");
+        assert_eq!(registrable_domain("www.bbc.co.uk."), "bbc.co.uk");
+        assert_eq!(registrable_domain("example.org"), "example.org");
+        assert_eq!(registrable_domain("localhost"), "localhost");
+    }
+
+    #[test]
+    fn test_anonymize_value_rewrites_identifiers() {
+        let mut value = serde_json::json!({
+            "status": "success",
+            "data": {
+                "mac": "all",
+                "queries": [{
+                    "device_mac": "aa:bb:cc:dd:ee:ff",
+                    "source_ip": "192.168.1.10",
+                    "domain": "api.tracker.example.com",
+                    "response_ips": ["93.184.216.34"]
+                }],
+                "top_domains": [{ "name": "cdn.example.net", "count": 3 }]
+            }
+        });
+        anonymize_value_with(42, &mut value);
+
+        let query = &value["data"]["queries"][0];
+        let mac = query["device_mac"].as_str().unwrap();
+        assert_ne!(mac, "aa:bb:cc:dd:ee:ff");
+        assert!(mac.starts_with("02:"));
+        assert_eq!(mac, anonymize_mac_with(42, "AA:BB:CC:DD:EE:FF"));
+        assert!(query["source_ip"].as_str().unwrap().parse::<Ipv4Addr>().unwrap().octets()[0] >= 240);
+        assert_eq!(query["domain"], "example.com");
+        assert_eq!(query["response_ips"][0], "93.184.216.34");
+        assert_eq!(value["data"]["mac"], "all");
+        assert_eq!(value["data"]["top_domains"][0]["name"], "example.net");
+    }
+
+    /// 与 anonymize_json_body 相同的改写，但使用给定的盐，不修改全局开关
+    fn anonymize_body(salt: u64, body: &str) -> Value {
+        let mut value: Value = serde_json::from_str(body).unwrap();
+        anonymize_value_with(salt, &mut value);
+        value
+    }
+
+    #[test]
+    fn test_anonymize_dns_stats_and_lookup_bodies() {
+        let stats = r#"{"status":"success","data":{
+            "top_devices":[{"name":"aa:bb:cc:dd:ee:01","count":5},{"name":"laptop","count":2}],
+            "blocked_devices":[{"name":"aa:bb:cc:dd:ee:02","count":1}],
+            "top_dns_servers":[{"name":"192.168.1.1","count":7}],
+            "top_query_types":[{"name":"A","count":7}]
+        }}"#;
+        let value = anonymize_body(7, stats);
+        let data = &value["data"];
+        assert_eq!(data["top_devices"][0]["name"], anonymize_mac_with(7, "aa:bb:cc:dd:ee:01"));
+        assert_eq!(data["top_devices"][1]["name"], "laptop");
+        assert_eq!(data["blocked_devices"][0]["name"], anonymize_mac_with(7, "aa:bb:cc:dd:ee:02"));
+        assert_eq!(data["top_dns_servers"][0]["name"], anonymize_ip_with(7, "192.168.1.1"));
+        assert_eq!(data["top_query_types"][0]["name"], "A");
+
+        let lookup = r#"{"status":"success","data":{
+            "ip":"93.184.216.34",
+            "domains":["www.example.com"],
+            "devices":["aa:bb:cc:dd:ee:01"],
+            "total":1,
+            "records":[{"domain":"www.example.com","device_mac":"aa:bb:cc:dd:ee:01","device_name":"phone"}]
+        }}"#;
+        let value = anonymize_body(7, lookup);
+        let data = &value["data"];
+        assert_eq!(data["ip"], anonymize_ip_with(7, "93.184.216.34"));
+        assert_eq!(data["domains"][0], "example.com");
+        assert_eq!(data["devices"][0], anonymize_mac_with(7, "aa:bb:cc:dd:ee:01"));
+        assert_eq!(data["records"][0]["device_mac"], anonymize_mac_with(7, "aa:bb:cc:dd:ee:01"));
+        assert_eq!(data["total"], 1);
+    }
+}
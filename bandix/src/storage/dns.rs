@@ -1 +1,945 @@
+use crate::storage::traffic::TimeSlot;
+use crate::utils::format_utils::format_mac;
+use crate::utils::network_utils::parse_mac_address;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 
+/// DNS 拦截动作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DnsBlockAction {
+    Drop,     // 直接丢弃查询，客户端等待超时
+    NxDomain, // 回送 NXDOMAIN 响应（仅 IPv4/UDP，其余情况退化为丢弃）
+}
+
+impl DnsBlockAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DnsBlockAction::Drop => "drop",
+            DnsBlockAction::NxDomain => "nxdomain",
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Self, anyhow::Error> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "drop" => Ok(DnsBlockAction::Drop),
+            "nxdomain" => Ok(DnsBlockAction::NxDomain),
+            other => Err(anyhow::anyhow!("Invalid block action: {} (expected drop or nxdomain)", other)),
+        }
+    }
+
+    /// 写入 eBPF 拦截规则映射的动作值
+    pub fn to_ebpf(self) -> u8 {
+        match self {
+            DnsBlockAction::Drop => bandix_common::DNS_BLOCK_ACTION_DROP,
+            DnsBlockAction::NxDomain => bandix_common::DNS_BLOCK_ACTION_NXDOMAIN,
+        }
+    }
+}
+
+/// DNS 拦截策略：在 time_slot 内，对 mac（None 表示所有设备）拦截 domains 及 categories 中的域名
+#[derive(Debug, Clone)]
+pub struct DnsBlockPolicy {
+    pub id: String,
+    pub mac: Option<[u8; 6]>,
+    pub time_slot: TimeSlot,
+    pub action: DnsBlockAction,
+    pub domains: Vec<String>,
+    pub categories: Vec<String>,
+}
+
+fn block_policies_path(base_dir: &str) -> PathBuf {
+    Path::new(base_dir).join("dns_block_policies.txt")
+}
+
+/// 从文件加载 DNS 拦截策略
+/// 文件格式：每行一条 - "id mac|* action start end days domains|- categories|-"
+/// domains 与 categories 为逗号分隔列表，"-" 表示空
+pub fn load_block_policies(base_dir: &str) -> Result<Vec<DnsBlockPolicy>, anyhow::Error> {
+    let path = block_policies_path(base_dir);
+    let mut out = Vec::new();
+    if !path.exists() {
+        return Ok(out);
+    }
+
+    let content = fs::read_to_string(&path)?;
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() != 8 {
+            log::warn!("Skipping malformed DNS block policy line: {}", line);
+            continue;
+        }
+
+        let mac = if parts[1] == "*" {
+            None
+        } else {
+            match parse_mac_address(parts[1]) {
+                Ok(mac) => Some(mac),
+                Err(_) => continue,
+            }
+        };
+        let action = match DnsBlockAction::parse(parts[2]) {
+            Ok(action) => action,
+            Err(_) => continue,
+        };
+        let (start_hour, start_minute) = match TimeSlot::parse_time(parts[3]) {
+            Ok(t) => t,
+            Err(_) => continue,
+        };
+        let (end_hour, end_minute) = match TimeSlot::parse_time(parts[4]) {
+            Ok(t) => t,
+            Err(_) => continue,
+        };
+        let days_of_week = match TimeSlot::parse_days(parts[5]) {
+            Ok(d) => d,
+            Err(_) => continue,
+        };
+
+        out.push(DnsBlockPolicy {
+            id: parts[0].to_string(),
+            mac,
+            time_slot: TimeSlot {
+                start_hour,
+                start_minute,
+                end_hour,
+                end_minute,
+                days_of_week,
+            },
+            action,
+            domains: parse_list_field(parts[6]),
+            categories: parse_list_field(parts[7]),
+        });
+    }
+
+    Ok(out)
+}
+
+/// 将全部 DNS 拦截策略写入文件
+pub fn save_block_policies(base_dir: &str, policies: &[DnsBlockPolicy]) -> Result<(), anyhow::Error> {
+    let path = block_policies_path(base_dir);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut buf = String::new();
+    buf.push_str("# id mac|* action start end days domains categories\n");
+    for p in policies {
+        buf.push_str(&format!(
+            "{} {} {} {} {} {} {} {}\n",
+            p.id,
+            p.mac.map(|m| format_mac(&m)).unwrap_or_else(|| "*".to_string()),
+            p.action.as_str(),
+            TimeSlot::format_time(p.time_slot.start_hour, p.time_slot.start_minute),
+            TimeSlot::format_time(p.time_slot.end_hour, p.time_slot.end_minute),
+            TimeSlot::format_days(p.time_slot.days_of_week),
+            format_list_field(&p.domains),
+            format_list_field(&p.categories),
+        ));
+    }
+    fs::write(&path, buf)?;
+    Ok(())
+}
+
+/// 运行时可修改的 DNS 监控配置（通过 /api/dns/config 修改，持久化到 dns_config.txt）
+#[derive(Debug, Clone)]
+pub struct DnsRuntimeConfig {
+    pub max_records: usize,               // 内存中保留的最大记录数
+    pub store_responses: bool,            // 是否保存响应记录（关闭后仅保存查询）
+    pub ignored_domains: Vec<String>,     // 忽略的域名（包含其子域名）
+    pub ignored_devices: HashSet<[u8; 6]>, // 忽略的设备 MAC
+}
+
+impl DnsRuntimeConfig {
+    /// 以命令行参数作为初始值
+    pub fn from_options(options: &crate::command::Options) -> Self {
+        Self {
+            max_records: options.dns_max_records(),
+            store_responses: true,
+            ignored_domains: Vec::new(),
+            ignored_devices: HashSet::new(),
+        }
+    }
+
+    /// 设备或域名是否被忽略；被忽略的记录不保存，也不参与统计与告警
+    pub fn is_ignored(&self, mac: Option<&[u8; 6]>, domain: &str) -> bool {
+        if let Some(mac) = mac {
+            if self.ignored_devices.contains(mac) {
+                return true;
+            }
+        }
+        let domain = domain.trim_end_matches('.').as_bytes();
+        self.ignored_domains.iter().any(|d| {
+            let d = d.as_bytes();
+            domain.eq_ignore_ascii_case(d)
+                || (domain.len() > d.len()
+                    && domain[domain.len() - d.len() - 1] == b'.'
+                    && domain[domain.len() - d.len()..].eq_ignore_ascii_case(d))
+        })
+    }
+}
+
+fn runtime_config_path(base_dir: &str) -> PathBuf {
+    Path::new(base_dir).join("dns_config.txt")
+}
+
+/// 从文件加载运行时 DNS 配置，文件中缺失的项保留 defaults 中的值
+/// 文件格式：每行一项 "key=value"，列表为逗号分隔，"-" 表示空
+pub fn load_runtime_config(base_dir: &str, defaults: DnsRuntimeConfig) -> Result<DnsRuntimeConfig, anyhow::Error> {
+    let path = runtime_config_path(base_dir);
+    let mut config = defaults;
+    if !path.exists() {
+        return Ok(config);
+    }
+
+    let content = fs::read_to_string(&path)?;
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = match line.split_once('=') {
+            Some((k, v)) => (k.trim(), v.trim()),
+            None => {
+                log::warn!("Skipping malformed DNS config line: {}", line);
+                continue;
+            }
+        };
+        match key {
+            "max_records" => match value.parse::<usize>() {
+                Ok(v) if v > 0 => config.max_records = v,
+                _ => log::warn!("Ignoring invalid DNS config max_records: {}", value),
+            },
+            "store_responses" => config.store_responses = matches!(value, "1" | "true" | "yes" | "on"),
+            "ignored_domains" => config.ignored_domains = parse_list_field(value),
+            "ignored_devices" => {
+                config.ignored_devices = parse_list_field(value)
+                    .iter()
+                    .filter_map(|m| parse_mac_address(m).ok())
+                    .collect();
+            }
+            other => log::warn!("Unknown DNS config key: {}", other),
+        }
+    }
+
+    Ok(config)
+}
+
+/// 将运行时 DNS 配置写入文件
+pub fn save_runtime_config(base_dir: &str, config: &DnsRuntimeConfig) -> Result<(), anyhow::Error> {
+    let path = runtime_config_path(base_dir);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut devices: Vec<String> = config.ignored_devices.iter().map(format_mac).collect();
+    devices.sort();
+
+    let mut buf = String::new();
+    buf.push_str(&format!("max_records={}\n", config.max_records));
+    buf.push_str(&format!("store_responses={}\n", config.store_responses));
+    buf.push_str(&format!("ignored_domains={}\n", format_list_field(&config.ignored_domains)));
+    buf.push_str(&format!("ignored_devices={}\n", format_list_field(&devices)));
+    fs::write(&path, buf)?;
+    Ok(())
+}
+
+fn parse_list_field(field: &str) -> Vec<String> {
+    if field == "-" {
+        return Vec::new();
+    }
+    field
+        .split(',')
+        .map(|s| s.trim().trim_end_matches('.').to_lowercase())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+fn format_list_field(items: &[String]) -> String {
+    if items.is_empty() {
+        "-".to_string()
+    } else {
+        items.join(",")
+    }
+}
+
+// ============================================================================
+// DNS 小时级指标环形存储（1小时采样，365天保留）
+// ============================================================================
+
+const DNS_RING_MAGIC: [u8; 4] = *b"BXD1"; // bandix DNS 指标环形文件魔数
+const DNS_RING_VERSION: u32 = 1;
+const DNS_RING_HEADER_SIZE: usize = 4 /*magic*/ + 4 /*version*/ + 4 /*capacity*/;
+const DNS_METRICS_INTERVAL_SECONDS: u64 = 3600;
+const DNS_METRICS_RETENTION_SECONDS: u64 = 365 * 24 * 3600;
+const SLOT_U64S_DNS: usize = 8;
+const SLOT_SIZE_DNS: usize = SLOT_U64S_DNS * 8;
+const MAX_LATENCY_SAMPLES: usize = 4096; // 每设备每小时保留的延迟样本上限
+
+// DNS 指标环形文件槽位结构（小端字节序，8个u64字段，总共64字节）：
+// 索引 | 字段名              | 类型 | 说明
+// -----|---------------------|------|-------------------------------
+//   0   | start_ts_ms         | u64  | 时间段开始时间戳（毫秒）
+//   1   | end_ts_ms           | u64  | 时间段结束时间戳（毫秒）
+//   2   | queries             | u64  | 查询数
+//   3   | responses           | u64  | 响应数
+//   4   | nxdomain            | u64  | NXDOMAIN 响应数
+//   5   | unique_domains      | u64  | 查询过的不同域名数
+//   6   | median_latency_ms   | u64  | 响应延迟中位数（毫秒）
+//   7   | latency_samples     | u64  | 参与计算中位数的响应数
+
+/// 单个小时的 DNS 指标
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DnsMetricsRow {
+    pub start_ts_ms: u64,
+    pub end_ts_ms: u64,
+    pub queries: u64,
+    pub responses: u64,
+    pub nxdomain: u64,
+    pub unique_domains: u64,
+    pub median_latency_ms: u64,
+    pub latency_samples: u64,
+}
+
+impl DnsMetricsRow {
+    fn from_slot(slot: &[u64; SLOT_U64S_DNS]) -> Self {
+        Self {
+            start_ts_ms: slot[0],
+            end_ts_ms: slot[1],
+            queries: slot[2],
+            responses: slot[3],
+            nxdomain: slot[4],
+            unique_domains: slot[5],
+            median_latency_ms: slot[6],
+            latency_samples: slot[7],
+        }
+    }
+
+    fn to_slot(self) -> [u64; SLOT_U64S_DNS] {
+        [
+            self.start_ts_ms,
+            self.end_ts_ms,
+            self.queries,
+            self.responses,
+            self.nxdomain,
+            self.unique_domains,
+            self.median_latency_ms,
+            self.latency_samples,
+        ]
+    }
+}
+
+/// 当前小时内某设备的 DNS 指标累加器
+#[derive(Debug, Clone)]
+struct DnsHourAccumulator {
+    start_ts_ms: u64,
+    last_ts_ms: u64,
+    queries: u64,
+    responses: u64,
+    nxdomain: u64,
+    domains: HashSet<String>,
+    latencies: Vec<u64>, // 延迟样本（蓄水池抽样，最多 MAX_LATENCY_SAMPLES 个）
+    latency_count: u64,
+    rng: u64, // 蓄水池抽样使用的 xorshift 状态
+}
+
+impl DnsHourAccumulator {
+    fn new(start_ts_ms: u64) -> Self {
+        Self {
+            start_ts_ms,
+            last_ts_ms: start_ts_ms,
+            queries: 0,
+            responses: 0,
+            nxdomain: 0,
+            domains: HashSet::new(),
+            latencies: Vec::new(),
+            latency_count: 0,
+            rng: random_seed(),
+        }
+    }
+
+    /// 蓄水池抽样：样本数超过上限后，第 n 个延迟以 MAX_LATENCY_SAMPLES / n 的概率替换一个已有样本，
+    /// 保证保留的样本是整小时内延迟的均匀抽样
+    fn add_latency(&mut self, latency_ms: u64) {
+        self.latency_count += 1;
+        if self.latencies.len() < MAX_LATENCY_SAMPLES {
+            self.latencies.push(latency_ms);
+            return;
+        }
+
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        let j = self.rng % self.latency_count;
+        if (j as usize) < MAX_LATENCY_SAMPLES {
+            self.latencies[j as usize] = latency_ms;
+        }
+    }
+
+    /// 生成指标行；existing 为同一小时已写入的数据（例如重启前记录的部分）时与之合并
+    /// 计数直接相加；不同域名数无法精确合并，取较大值；
+    /// 中位数重新计算：existing 只保留了中位数，按其样本数加权后与本段样本一起计算
+    fn to_row_merged(&self, existing: Option<DnsMetricsRow>) -> DnsMetricsRow {
+        let mut sorted = self.latencies.clone();
+        sorted.sort_unstable();
+
+        let existing = existing.filter(|e| e.start_ts_ms == self.start_ts_ms);
+        let median = match existing {
+            Some(e) if e.latency_samples > 0 && !sorted.is_empty() => {
+                // 每个保留样本代表 latency_count / latencies.len() 个响应
+                let weight = self.latency_count as f64 / sorted.len() as f64;
+                let half = (self.latency_count + e.latency_samples) as f64 / 2.0;
+                let mut below = 0.0;
+                let mut existing_counted = false;
+                let mut median = e.median_latency_ms;
+                for v in sorted.iter() {
+                    if !existing_counted && e.median_latency_ms <= *v {
+                        existing_counted = true;
+                        below += e.latency_samples as f64;
+                        if below >= half {
+                            break;
+                        }
+                    }
+                    below += weight;
+                    if below >= half {
+                        median = *v;
+                        break;
+                    }
+                }
+                median
+            }
+            Some(e) if sorted.is_empty() => e.median_latency_ms,
+            _ => median_of_sorted(&sorted),
+        };
+
+        let mut row = DnsMetricsRow {
+            start_ts_ms: self.start_ts_ms,
+            end_ts_ms: self.last_ts_ms,
+            queries: self.queries,
+            responses: self.responses,
+            nxdomain: self.nxdomain,
+            unique_domains: self.domains.len() as u64,
+            median_latency_ms: median,
+            latency_samples: self.latency_count,
+        };
+        if let Some(e) = existing {
+            row.end_ts_ms = row.end_ts_ms.max(e.end_ts_ms);
+            row.queries += e.queries;
+            row.responses += e.responses;
+            row.nxdomain += e.nxdomain;
+            row.unique_domains = row.unique_domains.max(e.unique_domains);
+            row.latency_samples += e.latency_samples;
+        }
+        row
+    }
+}
+
+fn median_of_sorted(sorted: &[u64]) -> u64 {
+    if sorted.is_empty() {
+        0
+    } else if sorted.len() % 2 == 1 {
+        sorted[sorted.len() / 2]
+    } else {
+        (sorted[sorted.len() / 2 - 1] + sorted[sorted.len() / 2]) / 2
+    }
+}
+
+/// xorshift 的非零随机种子
+fn random_seed() -> u64 {
+    use std::hash::{BuildHasher, Hasher};
+    let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+    hasher.write_u64(0);
+    hasher.finish() | 1
+}
+
+/// 内存中的 DNS 指标环形结构
+#[derive(Debug, Clone)]
+struct DnsMetricsRing {
+    slots: Vec<[u64; SLOT_U64S_DNS]>,
+}
+
+impl DnsMetricsRing {
+    fn new(capacity: u32) -> Self {
+        Self {
+            slots: vec![[0u64; SLOT_U64S_DNS]; capacity as usize],
+        }
+    }
+
+    fn slot_index(&self, ts_ms: u64) -> usize {
+        ((ts_ms / 1000 / DNS_METRICS_INTERVAL_SECONDS) % self.slots.len() as u64) as usize
+    }
+
+    /// 写入一小时的数据（覆盖槽位中的旧数据），返回槽位索引和写入后的槽位
+    fn insert(&mut self, row: DnsMetricsRow) -> (usize, [u64; SLOT_U64S_DNS]) {
+        let idx = self.slot_index(row.start_ts_ms);
+        self.slots[idx] = row.to_slot();
+        (idx, self.slots[idx])
+    }
+
+    fn get(&self, start_ts_ms: u64) -> Option<DnsMetricsRow> {
+        let row = DnsMetricsRow::from_slot(&self.slots[self.slot_index(start_ts_ms)]);
+        if row.start_ts_ms == start_ts_ms {
+            Some(row)
+        } else {
+            None
+        }
+    }
+}
+
+/// 每设备 DNS 小时级指标管理器
+/// 当前小时在内存中累加，整点之后写入 <data_dir>/metrics/dns/<mac>.ring
+pub struct DnsMetricsManager {
+    base_dir: PathBuf,
+    capacity: u32,
+    rings: Mutex<HashMap<[u8; 6], DnsMetricsRing>>,
+    accumulators: Mutex<HashMap<[u8; 6], DnsHourAccumulator>>,
+}
+
+impl DnsMetricsManager {
+    pub fn new(data_dir: &str) -> Self {
+        Self {
+            base_dir: Path::new(data_dir).join("metrics").join("dns"),
+            capacity: (DNS_METRICS_RETENTION_SECONDS / DNS_METRICS_INTERVAL_SECONDS) as u32,
+            rings: Mutex::new(HashMap::new()),
+            accumulators: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 指标保留天数
+    pub fn retention_days(&self) -> u64 {
+        self.capacity as u64 * DNS_METRICS_INTERVAL_SECONDS / (24 * 3600)
+    }
+
+    fn hour_start(ts_ms: u64) -> u64 {
+        let interval_ms = DNS_METRICS_INTERVAL_SECONDS * 1000;
+        (ts_ms / interval_ms) * interval_ms
+    }
+
+    /// 取出设备当前小时的累加器；若累加器属于更早的小时，先将其写入环形
+    fn with_accumulator<F: FnOnce(&mut DnsHourAccumulator)>(&self, mac: [u8; 6], now_ms: u64, f: F) {
+        let hour = Self::hour_start(now_ms);
+        let finished = {
+            let mut accs = self.accumulators.lock().unwrap();
+            let acc = accs.entry(mac).or_insert_with(|| DnsHourAccumulator::new(hour));
+            let finished = if acc.start_ts_ms != hour {
+                Some(std::mem::replace(acc, DnsHourAccumulator::new(hour)))
+            } else {
+                None
+            };
+            acc.last_ts_ms = now_ms;
+            f(acc);
+            finished
+        };
+
+        if let Some(acc) = finished {
+            if let Err(e) = self.commit(&mac, &acc) {
+                log::warn!("Failed to persist DNS metrics for {}: {}", format_mac(&mac), e);
+            }
+        }
+    }
+
+    /// 记录一次 DNS 查询
+    pub fn record_query(&self, mac: [u8; 6], domain: &str, now_ms: u64) {
+        self.with_accumulator(mac, now_ms, |acc| {
+            acc.queries += 1;
+            if !domain.is_empty() && !acc.domains.contains(domain) {
+                acc.domains.insert(domain.to_string());
+            }
+        });
+    }
+
+    /// 记录一次 DNS 响应；latency_ms 为 None 表示未匹配到对应的查询
+    pub fn record_response(&self, mac: [u8; 6], nxdomain: bool, latency_ms: Option<u64>, now_ms: u64) {
+        self.with_accumulator(mac, now_ms, |acc| {
+            acc.responses += 1;
+            if nxdomain {
+                acc.nxdomain += 1;
+            }
+            if let Some(latency) = latency_ms {
+                acc.add_latency(latency);
+            }
+        });
+    }
+
+    /// 将已结束小时的累加器写入环形（由 DNS 监控循环定期调用）
+    pub fn roll(&self, now_ms: u64) {
+        let hour = Self::hour_start(now_ms);
+        let finished: Vec<([u8; 6], DnsHourAccumulator)> = {
+            let mut accs = self.accumulators.lock().unwrap();
+            let macs: Vec<[u8; 6]> = accs.iter().filter(|(_, acc)| acc.start_ts_ms != hour).map(|(mac, _)| *mac).collect();
+            macs.into_iter()
+                .filter_map(|mac| accs.remove(&mac).map(|acc| (mac, acc)))
+                .collect()
+        };
+
+        for (mac, acc) in finished {
+            if let Err(e) = self.commit(&mac, &acc) {
+                log::warn!("Failed to persist DNS metrics for {}: {}", format_mac(&mac), e);
+            }
+        }
+    }
+
+    /// 退出前写入所有累加器（包括未结束的当前小时）
+    pub fn flush(&self) -> Result<(), anyhow::Error> {
+        let pending: Vec<([u8; 6], DnsHourAccumulator)> = {
+            let mut accs = self.accumulators.lock().unwrap();
+            accs.drain().collect()
+        };
+        for (mac, acc) in pending {
+            self.commit(&mac, &acc)?;
+        }
+        Ok(())
+    }
+
+    /// 将累加器写入环形（与同一小时已写入的数据合并）并同步到文件
+    fn commit(&self, mac: &[u8; 6], acc: &DnsHourAccumulator) -> Result<(), anyhow::Error> {
+        let (idx, slot) = {
+            let mut rings = self.rings.lock().unwrap();
+            let ring = rings.entry(*mac).or_insert_with(|| DnsMetricsRing::new(self.capacity));
+            let row = acc.to_row_merged(ring.get(acc.start_ts_ms));
+            ring.insert(row)
+        };
+
+        let path = self.base_dir.join(format!("{}.ring", mac_to_filename(mac)));
+        let f = init_dns_ring_file(&path, self.capacity)?;
+        write_dns_slot(&f, idx as u64, &slot)?;
+        f.sync_all()?;
+        Ok(())
+    }
+
+    /// 启动时从环形文件加载历史指标，返回加载的设备数
+    pub fn load_from_files(&self) -> Result<usize, anyhow::Error> {
+        if !self.base_dir.exists() {
+            return Ok(0);
+        }
+
+        let mut rings = self.rings.lock().unwrap();
+        for entry in fs::read_dir(&self.base_dir)? {
+            let path = entry?.path();
+            if !path.is_file() || path.extension().and_then(|s| s.to_str()) != Some("ring") {
+                continue;
+            }
+            let mac = match path.file_stem().and_then(|s| s.to_str()).and_then(mac_from_filename) {
+                Some(mac) => mac,
+                None => continue,
+            };
+
+            let mut f = match OpenOptions::new().read(true).open(&path) {
+                Ok(f) => f,
+                Err(_) => continue,
+            };
+            match read_dns_header(&mut f) {
+                Ok((DNS_RING_VERSION, cap)) if cap == self.capacity => {}
+                _ => {
+                    log::warn!("Skipping DNS metrics file with unexpected header: {}", path.display());
+                    continue;
+                }
+            }
+
+            let mut ring = DnsMetricsRing::new(self.capacity);
+            for i in 0..self.capacity as u64 {
+                if let Ok(slot) = read_dns_slot(&f, i) {
+                    if slot[0] != 0 {
+                        ring.slots[i as usize] = slot;
+                    }
+                }
+            }
+            rings.insert(mac, ring);
+        }
+
+        Ok(rings.len())
+    }
+
+    /// 查询设备在 [start_ms, end_ms] 内的小时指标（包含当前未结束的小时），按时间排序
+    pub fn query(&self, mac: &[u8; 6], start_ms: u64, end_ms: u64) -> Vec<DnsMetricsRow> {
+        let mut rows: Vec<DnsMetricsRow> = Vec::new();
+
+        {
+            let rings = self.rings.lock().unwrap();
+            // 当前小时：与重启前已写入的同一小时数据合并
+            let current = self.accumulators.lock().unwrap().get(mac).map(|acc| {
+                let existing = rings.get(mac).and_then(|r| r.get(acc.start_ts_ms));
+                acc.to_row_merged(existing)
+            });
+
+            if let Some(ring) = rings.get(mac) {
+                for slot in &ring.slots {
+                    let row = DnsMetricsRow::from_slot(slot);
+                    if row.end_ts_ms == 0 || row.end_ts_ms < start_ms || row.start_ts_ms > end_ms {
+                        continue;
+                    }
+                    if current.map(|c| c.start_ts_ms) == Some(row.start_ts_ms) {
+                        continue;
+                    }
+                    rows.push(row);
+                }
+            }
+
+            if let Some(cur) = current {
+                if cur.end_ts_ms >= start_ms && cur.start_ts_ms <= end_ms {
+                    rows.push(cur);
+                }
+            }
+        }
+
+        rows.sort_by_key(|r| r.start_ts_ms);
+        rows
+    }
+}
+
+fn mac_to_filename(mac: &[u8; 6]) -> String {
+    mac.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn mac_from_filename(name: &str) -> Option<[u8; 6]> {
+    if name.len() != 12 {
+        return None;
+    }
+    let mut mac = [0u8; 6];
+    for (i, byte) in mac.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(name.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+    Some(mac)
+}
+
+fn read_dns_header(f: &mut File) -> Result<(u32, u32), anyhow::Error> {
+    let mut magic = [0u8; 4];
+    f.seek(SeekFrom::Start(0))?;
+    f.read_exact(&mut magic)?;
+    if magic != DNS_RING_MAGIC {
+        return Err(anyhow::anyhow!("invalid DNS ring file magic"));
+    }
+    let mut buf4 = [0u8; 4];
+    f.read_exact(&mut buf4)?;
+    let ver = u32::from_le_bytes(buf4);
+    f.read_exact(&mut buf4)?;
+    let cap = u32::from_le_bytes(buf4);
+    Ok((ver, cap))
+}
+
+fn init_dns_ring_file(path: &Path, capacity: u32) -> Result<File, anyhow::Error> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // 已有文件保留原内容，头部或大小不符时才在下面重新初始化
+    let mut f = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)?;
+
+    let expected_size = DNS_RING_HEADER_SIZE as u64 + capacity as u64 * SLOT_SIZE_DNS as u64;
+    let valid = f.metadata()?.len() == expected_size
+        && matches!(read_dns_header(&mut f), Ok((DNS_RING_VERSION, cap)) if cap == capacity);
+    if !valid {
+        // 大小或头部不符时重新初始化
+        f.set_len(0)?;
+        f.seek(SeekFrom::Start(0))?;
+        f.write_all(&DNS_RING_MAGIC)?;
+        f.write_all(&DNS_RING_VERSION.to_le_bytes())?;
+        f.write_all(&capacity.to_le_bytes())?;
+        f.set_len(expected_size)?;
+        f.flush()?;
+    }
+    Ok(f)
+}
+
+fn write_dns_slot(mut f: &File, idx: u64, data: &[u64; SLOT_U64S_DNS]) -> Result<(), anyhow::Error> {
+    let offset = DNS_RING_HEADER_SIZE as u64 + idx * SLOT_SIZE_DNS as u64;
+    let mut bytes = [0u8; SLOT_SIZE_DNS];
+    for (i, v) in data.iter().enumerate() {
+        bytes[i * 8..(i + 1) * 8].copy_from_slice(&v.to_le_bytes());
+    }
+    f.seek(SeekFrom::Start(offset))?;
+    f.write_all(&bytes)?;
+    Ok(())
+}
+
+fn read_dns_slot(mut f: &File, idx: u64) -> Result<[u64; SLOT_U64S_DNS], anyhow::Error> {
+    let offset = DNS_RING_HEADER_SIZE as u64 + idx * SLOT_SIZE_DNS as u64;
+    let mut bytes = [0u8; SLOT_SIZE_DNS];
+    f.seek(SeekFrom::Start(offset))?;
+    f.read_exact(&mut bytes)?;
+    let mut out = [0u64; SLOT_U64S_DNS];
+    for (i, v) in out.iter_mut().enumerate() {
+        let mut b = [0u8; 8];
+        b.copy_from_slice(&bytes[i * 8..(i + 1) * 8]);
+        *v = u64::from_le_bytes(b);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("bandix-dns-storage-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_block_policies_round_trip() {
+        let dir = temp_dir("block");
+        let base = dir.to_str().unwrap();
+        let policies = vec![
+            DnsBlockPolicy {
+                id: "kid-night".to_string(),
+                mac: Some([0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0x01]),
+                time_slot: TimeSlot {
+                    start_hour: 21,
+                    start_minute: 30,
+                    end_hour: 7,
+                    end_minute: 0,
+                    days_of_week: 0b0011111,
+                },
+                action: DnsBlockAction::NxDomain,
+                domains: vec!["video.example.com".to_string()],
+                categories: vec!["games".to_string(), "social".to_string()],
+            },
+            DnsBlockPolicy {
+                id: "ads".to_string(),
+                mac: None,
+                time_slot: TimeSlot::all_time(),
+                action: DnsBlockAction::Drop,
+                domains: Vec::new(),
+                categories: vec!["ads".to_string()],
+            },
+        ];
+
+        save_block_policies(base, &policies).unwrap();
+        let loaded = load_block_policies(base).unwrap();
+        assert_eq!(loaded.len(), policies.len());
+        for (a, b) in loaded.iter().zip(policies.iter()) {
+            assert_eq!(a.id, b.id);
+            assert_eq!(a.mac, b.mac);
+            assert_eq!(a.time_slot, b.time_slot);
+            assert_eq!(a.action, b.action);
+            assert_eq!(a.domains, b.domains);
+            assert_eq!(a.categories, b.categories);
+        }
+
+        // 格式错误的行被跳过
+        let path = block_policies_path(base);
+        let mut content = fs::read_to_string(&path).unwrap();
+        content.push_str("broken * drop 25:00 07:00 1111111 - -\nshort line\n");
+        fs::write(&path, content).unwrap();
+        assert_eq!(load_block_policies(base).unwrap().len(), policies.len());
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    const MAC: [u8; 6] = [0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0x01];
+    const HOUR_MS: u64 = DNS_METRICS_INTERVAL_SECONDS * 1000;
+    // 2023-11-14 22:00:00 UTC，整点
+    const HOUR: u64 = 1_699_999_200_000;
+
+    #[test]
+    fn test_metrics_rollover_and_range_query() {
+        let dir = temp_dir("metrics-rollover");
+        let manager = DnsMetricsManager::new(dir.to_str().unwrap());
+
+        manager.record_query(MAC, "a.example.com", HOUR + 1000);
+        manager.record_query(MAC, "a.example.com", HOUR + 2000);
+        manager.record_query(MAC, "b.example.com", HOUR + 3000);
+        manager.record_response(MAC, false, Some(10), HOUR + 4000);
+        manager.record_response(MAC, true, Some(30), HOUR + 5000);
+        manager.record_response(MAC, false, None, HOUR + 6000);
+
+        let rows = manager.query(&MAC, HOUR, HOUR + HOUR_MS);
+        assert_eq!(rows.len(), 1);
+        let row = rows[0];
+        assert_eq!((row.queries, row.responses, row.nxdomain, row.unique_domains), (3, 3, 1, 2));
+        assert_eq!((row.median_latency_ms, row.latency_samples), (20, 2));
+        assert_eq!((row.start_ts_ms, row.end_ts_ms), (HOUR, HOUR + 6000));
+
+        // 下一小时的第一条记录使上一小时写入环形文件
+        manager.record_query(MAC, "c.example.com", HOUR + HOUR_MS + 1000);
+        let path = dir.join("metrics").join("dns").join("aabbccddee01.ring");
+        assert!(path.exists());
+
+        let rows = manager.query(&MAC, HOUR, HOUR + 3 * HOUR_MS);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].queries, 3);
+        assert_eq!(rows[0].median_latency_ms, 20);
+        assert_eq!((rows[1].start_ts_ms, rows[1].queries), (HOUR + HOUR_MS, 1));
+
+        // 时间范围只覆盖第二个小时
+        let rows = manager.query(&MAC, HOUR + HOUR_MS, HOUR + 2 * HOUR_MS);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].start_ts_ms, HOUR + HOUR_MS);
+        assert!(manager.query(&[0u8; 6], HOUR, HOUR + 3 * HOUR_MS).is_empty());
+
+        // roll 写入已结束的小时，数据仍可查询
+        manager.roll(HOUR + 2 * HOUR_MS);
+        assert!(manager.accumulators.lock().unwrap().is_empty());
+        assert_eq!(manager.query(&MAC, HOUR, HOUR + 3 * HOUR_MS).len(), 2);
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_metrics_reload_and_merge_same_hour() {
+        let dir = temp_dir("metrics-reload");
+        let base = dir.to_str().unwrap();
+
+        let before = DnsMetricsManager::new(base);
+        before.record_query(MAC, "a.example.com", HOUR + 1000);
+        for _ in 0..3 {
+            before.record_response(MAC, false, Some(10), HOUR + 2000);
+        }
+        before.flush().unwrap();
+
+        // 重启后从文件加载，并继续记录同一小时
+        let after = DnsMetricsManager::new(base);
+        assert_eq!(after.load_from_files().unwrap(), 1);
+        let rows = after.query(&MAC, HOUR, HOUR + HOUR_MS);
+        assert_eq!((rows[0].queries, rows[0].responses, rows[0].median_latency_ms), (1, 3, 10));
+
+        after.record_query(MAC, "b.example.com", HOUR + 5000);
+        for latency in [5, 5, 100, 100] {
+            after.record_response(MAC, false, Some(latency), HOUR + 6000);
+        }
+        // 合并后的中位数按全部 7 个延迟重新计算：5 5 10 10 10 100 100
+        let rows = after.query(&MAC, HOUR, HOUR + HOUR_MS);
+        assert_eq!(rows.len(), 1);
+        assert_eq!((rows[0].queries, rows[0].responses, rows[0].latency_samples), (2, 7, 7));
+        assert_eq!(rows[0].median_latency_ms, 10);
+        assert_eq!(rows[0].end_ts_ms, HOUR + 6000);
+
+        // 再次写入文件后计数不会重复累加
+        after.flush().unwrap();
+        let reloaded = DnsMetricsManager::new(base);
+        reloaded.load_from_files().unwrap();
+        let rows = reloaded.query(&MAC, HOUR, HOUR + HOUR_MS);
+        assert_eq!((rows[0].queries, rows[0].responses, rows[0].median_latency_ms), (2, 7, 10));
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_latency_reservoir_samples_whole_hour() {
+        let mut acc = DnsHourAccumulator::new(HOUR);
+        for i in 0..4 * MAX_LATENCY_SAMPLES {
+            acc.add_latency(if i < 2 * MAX_LATENCY_SAMPLES { 10 } else { 1000 });
+        }
+
+        assert_eq!(acc.latencies.len(), MAX_LATENCY_SAMPLES);
+        assert_eq!(acc.latency_count, 4 * MAX_LATENCY_SAMPLES as u64);
+        // 前后两半各占约一半样本（固定位置轮换覆盖时后一半会占满）
+        let late = acc.latencies.iter().filter(|v| **v == 1000).count();
+        assert!(
+            late > MAX_LATENCY_SAMPLES * 4 / 10 && late < MAX_LATENCY_SAMPLES * 6 / 10,
+            "late = {}",
+            late
+        );
+    }
+}
@@ -0,0 +1,135 @@
+use crate::utils::format_utils::format_mac;
+use crate::utils::network_utils::parse_mac_address;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// 事件日志中的一条设备事件，同时也是 webhook 推送的内容
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DeviceEvent {
+    pub id: u64, // 单调递增的事件编号
+    pub ts_ms: u64,
+    pub event: String, // 事件类型，见 crate::events::EventKind
+    pub mac: String,
+    pub ip: String,
+    pub hostname: String,
+    pub connection_type: String, // "wifi" 或 "wired"
+    #[serde(default)]
+    pub previous_ip: Option<String>, // ip_change：变化前的 IPv4 地址
+    #[serde(default)]
+    pub previous_hostname: Option<String>, // hostname_change：变化前的主机名
+    #[serde(default)]
+    pub wan_rx_rate_limit: Option<u64>, // rate_limit_on：生效的下载限速（字节/秒，0 表示不限）
+    #[serde(default)]
+    pub wan_tx_rate_limit: Option<u64>, // rate_limit_on：生效的上传限速（字节/秒，0 表示不限）
+    #[serde(default)]
+    pub conflicting_mac: Option<String>, // 安全事件：网关的 MAC 或此前声明该 IP 的 MAC
+    #[serde(default)]
+    pub flip_count: Option<u32>, // mac_flip：时间窗口内该 IP 的 MAC 变化次数
+}
+
+fn events_path(base_dir: &str) -> PathBuf {
+    Path::new(base_dir).join("events.jsonl")
+}
+
+fn known_devices_path(base_dir: &str) -> PathBuf {
+    Path::new(base_dir).join("known_devices.txt")
+}
+
+fn webhook_cursor_path(base_dir: &str) -> PathBuf {
+    Path::new(base_dir).join("events_webhook_cursor")
+}
+
+/// 加载事件日志，文件格式为每行一个 JSON 对象，无法解析的行会被跳过
+pub fn load_events(base_dir: &str) -> Result<Vec<DeviceEvent>, anyhow::Error> {
+    let path = events_path(base_dir);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = fs::read_to_string(&path)?;
+    Ok(content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// 在事件日志末尾追加事件
+pub fn append_events(base_dir: &str, events: &[DeviceEvent]) -> Result<(), anyhow::Error> {
+    let path = events_path(base_dir);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut buf = String::new();
+    for event in events {
+        buf.push_str(&serde_json::to_string(event)?);
+        buf.push('\n');
+    }
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(&path)?;
+    file.write_all(buf.as_bytes())?;
+    Ok(())
+}
+
+/// 用给定事件重写事件日志，用于丢弃超出上限的旧事件
+pub fn save_events<'a>(base_dir: &str, events: impl IntoIterator<Item = &'a DeviceEvent>) -> Result<(), anyhow::Error> {
+    let path = events_path(base_dir);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut buf = String::new();
+    for event in events {
+        buf.push_str(&serde_json::to_string(event)?);
+        buf.push('\n');
+    }
+
+    super::write_atomic(&path, buf)
+}
+
+/// 加载已见过的设备；文件不存在时返回 None
+pub fn load_known_devices(base_dir: &str) -> Result<Option<Vec<[u8; 6]>>, anyhow::Error> {
+    let path = known_devices_path(base_dir);
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let content = fs::read_to_string(&path)?;
+    Ok(Some(
+        content.lines().filter_map(|line| parse_mac_address(line.trim()).ok()).collect(),
+    ))
+}
+
+/// 追加已见过的设备，每行一个 MAC
+pub fn append_known_devices(base_dir: &str, macs: &[[u8; 6]]) -> Result<(), anyhow::Error> {
+    let path = known_devices_path(base_dir);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let buf: String = macs.iter().map(|mac| format!("{}\n", format_mac(mac))).collect();
+    let mut file = fs::OpenOptions::new().create(true).append(true).open(&path)?;
+    file.write_all(buf.as_bytes())?;
+    Ok(())
+}
+
+/// 加载 webhook 已投递到的事件编号；文件不存在时返回 None
+pub fn load_webhook_cursor(base_dir: &str) -> Result<Option<u64>, anyhow::Error> {
+    let path = webhook_cursor_path(base_dir);
+    if !path.exists() {
+        return Ok(None);
+    }
+    Ok(fs::read_to_string(&path)?.trim().parse().ok())
+}
+
+/// 保存 webhook 已投递到的事件编号
+pub fn save_webhook_cursor(base_dir: &str, id: u64) -> Result<(), anyhow::Error> {
+    let path = webhook_cursor_path(base_dir);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, format!("{}\n", id))?;
+    Ok(())
+}
@@ -0,0 +1,76 @@
+use crate::storage::traffic::TimeSlot;
+use crate::utils::format_utils::format_mac;
+use crate::utils::network_utils::parse_mac_address;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 设备组的预定速率限制规则，对组内每台设备分别生效
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GroupRateLimit {
+    pub time_slot: TimeSlot,
+    pub wan_rx_rate_limit: u64,
+    pub wan_tx_rate_limit: u64,
+}
+
+/// 命名的设备组
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceGroup {
+    pub name: String,
+    pub macs: Vec<[u8; 6]>,
+    pub rate_limits: Vec<GroupRateLimit>,
+}
+
+/// 文件中的一个组，MAC 以文本保存
+#[derive(Serialize, Deserialize)]
+struct GroupRecord {
+    name: String,
+    #[serde(default)]
+    macs: Vec<String>,
+    #[serde(default)]
+    rate_limits: Vec<GroupRateLimit>,
+}
+
+fn groups_path(base_dir: &str) -> PathBuf {
+    Path::new(base_dir).join("device_groups.json")
+}
+
+/// 从文件加载设备组
+/// 文件格式：组对象的 JSON 数组，无法识别的 MAC 会被忽略
+pub fn load_groups(base_dir: &str) -> Result<Vec<DeviceGroup>, anyhow::Error> {
+    let path = groups_path(base_dir);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = fs::read_to_string(&path)?;
+    let records: Vec<GroupRecord> = serde_json::from_str(&content)?;
+    Ok(records
+        .into_iter()
+        .map(|record| DeviceGroup {
+            name: record.name,
+            macs: record.macs.iter().filter_map(|mac| parse_mac_address(mac).ok()).collect(),
+            rate_limits: record.rate_limits,
+        })
+        .collect())
+}
+
+/// 将设备组写入文件
+pub fn save_groups(base_dir: &str, groups: &[DeviceGroup]) -> Result<(), anyhow::Error> {
+    let path = groups_path(base_dir);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let records: Vec<GroupRecord> = groups
+        .iter()
+        .map(|group| GroupRecord {
+            name: group.name.clone(),
+            macs: group.macs.iter().map(format_mac).collect(),
+            rate_limits: group.rate_limits.clone(),
+        })
+        .collect();
+    let buf = serde_json::to_string_pretty(&records)?;
+
+    super::write_atomic(&path, buf)
+}
@@ -0,0 +1,105 @@
+use crate::utils::format_utils::format_mac;
+use crate::utils::network_utils::parse_mac_address;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 设备历史记录的类别
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HistoryKind {
+    Ipv4,
+    Ipv6,
+    Hostname,
+}
+
+impl HistoryKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            HistoryKind::Ipv4 => "ipv4",
+            HistoryKind::Ipv6 => "ipv6",
+            HistoryKind::Hostname => "hostname",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "ipv4" => Some(HistoryKind::Ipv4),
+            "ipv6" => Some(HistoryKind::Ipv6),
+            "hostname" => Some(HistoryKind::Hostname),
+            _ => None,
+        }
+    }
+}
+
+/// 设备在一段时间内使用过的一个地址或主机名
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEntry {
+    pub kind: HistoryKind,
+    pub value: String,
+    pub first_seen_ms: u64,
+    pub last_seen_ms: u64,
+}
+
+fn history_path(base_dir: &str) -> PathBuf {
+    Path::new(base_dir).join("device_history.txt")
+}
+
+/// 从文件加载设备历史
+/// 文件格式：每行 "<mac> <kind> <first_seen_ms> <last_seen_ms> <value>"，value 可能包含空格，放在最后
+pub fn load_history(base_dir: &str) -> Result<Vec<([u8; 6], HistoryEntry)>, anyhow::Error> {
+    let path = history_path(base_dir);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = fs::read_to_string(&path)?;
+    let mut out = Vec::new();
+    for line in content.lines() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let parts: Vec<&str> = line.splitn(5, ' ').collect();
+        let [mac, kind, first, last, value] = parts.as_slice() else {
+            continue;
+        };
+        if let (Ok(mac), Some(kind), Ok(first_seen_ms), Ok(last_seen_ms)) = (
+            parse_mac_address(mac),
+            HistoryKind::parse(kind),
+            first.parse::<u64>(),
+            last.parse::<u64>(),
+        ) {
+            out.push((
+                mac,
+                HistoryEntry {
+                    kind,
+                    value: value.to_string(),
+                    first_seen_ms,
+                    last_seen_ms,
+                },
+            ));
+        }
+    }
+
+    Ok(out)
+}
+
+/// 将设备历史写入文件
+pub fn save_history<'a>(base_dir: &str, entries: impl IntoIterator<Item = ([u8; 6], &'a HistoryEntry)>) -> Result<(), anyhow::Error> {
+    let path = history_path(base_dir);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut buf = String::new();
+    for (mac, entry) in entries {
+        buf.push_str(&format!(
+            "{} {} {} {} {}\n",
+            format_mac(&mac),
+            entry.kind.as_str(),
+            entry.first_seen_ms,
+            entry.last_seen_ms,
+            entry.value
+        ));
+    }
+
+    super::write_atomic(&path, buf)
+}
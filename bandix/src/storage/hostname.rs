@@ -1,20 +1,57 @@
+use crate::monitor::fingerprint::DeviceType;
+use std::collections::HashSet;
 use std::fs;
 use std::path::{Path, PathBuf};
 
+// 各来源未指定路径时依次尝试的默认文件（OpenWrt 与常见 Linux 发行版）
+const DNSMASQ_LEASE_PATHS: &[&str] = &[
+    "/tmp/dhcp.leases",
+    "/var/lib/misc/dnsmasq.leases",
+    "/var/lib/dnsmasq/dnsmasq.leases",
+];
+const DHCPD_LEASE_PATHS: &[&str] = &[
+    "/var/lib/dhcp/dhcpd.leases",
+    "/var/lib/dhcpd/dhcpd.leases",
+    "/var/db/dhcpd.leases",
+];
+const ODHCPD_STATE_PATHS: &[&str] = &["/tmp/hosts/odhcpd", "/tmp/odhcpd.leases"];
+const ETHERS_PATHS: &[&str] = &["/etc/ethers"];
+
+// 将来源文件内容解析为 (MAC, 主机名) 列表
+type HostnameParser = fn(&str) -> Vec<([u8; 6], String)>;
+
 /// 获取主机名绑定文件的路径
 pub fn bindings_path(base_dir: &str) -> PathBuf {
     Path::new(base_dir).join("hostname_bindings.txt")
 }
 
+/// 获取设备类型手动设置文件的路径
+pub fn device_types_path(base_dir: &str) -> PathBuf {
+    Path::new(base_dir).join("device_types.txt")
+}
+
 /// 从文件加载主机名绑定
 /// 文件格式：每行一个条目 - "mac12 hostname"
 pub fn load_hostname_bindings(base_dir: &str) -> Result<Vec<([u8; 6], String)>, anyhow::Error> {
-    let path = bindings_path(base_dir);
+    load_mac_value_file(&bindings_path(base_dir))
+}
+
+/// 从文件加载手动设置的设备类型
+/// 文件格式与主机名绑定相同：每行一个条目 - "mac12 type"，无法识别的类型会被忽略
+pub fn load_device_type_overrides(base_dir: &str) -> Result<Vec<([u8; 6], DeviceType)>, anyhow::Error> {
+    Ok(load_mac_value_file(&device_types_path(base_dir))?
+        .into_iter()
+        .filter_map(|(mac, value)| DeviceType::parse(&value).map(|t| (mac, t)))
+        .collect())
+}
+
+/// 读取 "mac12 value" 格式的文件
+fn load_mac_value_file(path: &Path) -> Result<Vec<([u8; 6], String)>, anyhow::Error> {
     let mut out = Vec::new();
     if !path.exists() {
         return Ok(out);
     }
-    let content = fs::read_to_string(&path)?;
+    let content = fs::read_to_string(path)?;
     for line in content.lines() {
         let line = line.trim();
         if line.is_empty() || line.starts_with('#') {
@@ -116,3 +153,338 @@ pub fn load_hostname_from_ubus() -> Result<Vec<([u8; 6], String)>, anyhow::Error
 
     Ok(out)
 }
+
+/// 主机名来源的类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostnameSourceKind {
+    Ubus,    // ubus call luci-rpc getHostHints
+    Dnsmasq, // dnsmasq 租约文件
+    Dhcpd,   // ISC dhcpd 租约文件
+    Odhcpd,  // odhcpd 状态文件
+    Ethers,  // /etc/ethers
+}
+
+/// 主机名来源：类型 + 可选的文件路径（未指定时尝试默认路径）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostnameSource {
+    pub kind: HostnameSourceKind,
+    pub path: Option<PathBuf>,
+}
+
+impl HostnameSource {
+    /// 解析单个来源，格式为 "kind" 或 "kind:/path/to/file"
+    pub fn parse(spec: &str) -> Result<Self, anyhow::Error> {
+        let (kind_str, path) = match spec.split_once(':') {
+            Some((kind, path)) => (kind.trim(), Some(PathBuf::from(path.trim()))),
+            None => (spec.trim(), None),
+        };
+        let kind = match kind_str.to_ascii_lowercase().as_str() {
+            "ubus" => HostnameSourceKind::Ubus,
+            "dnsmasq" => HostnameSourceKind::Dnsmasq,
+            "dhcpd" => HostnameSourceKind::Dhcpd,
+            "odhcpd" => HostnameSourceKind::Odhcpd,
+            "ethers" => HostnameSourceKind::Ethers,
+            other => return Err(anyhow::anyhow!("Unknown hostname source '{}'", other)),
+        };
+        if kind == HostnameSourceKind::Ubus && path.is_some() {
+            return Err(anyhow::anyhow!("Hostname source 'ubus' does not take a path"));
+        }
+        if path.as_ref().map(|p| p.as_os_str().is_empty()).unwrap_or(false) {
+            return Err(anyhow::anyhow!("Empty path for hostname source '{}'", kind_str));
+        }
+        Ok(Self { kind, path })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self.kind {
+            HostnameSourceKind::Ubus => "ubus",
+            HostnameSourceKind::Dnsmasq => "dnsmasq",
+            HostnameSourceKind::Dhcpd => "dhcpd",
+            HostnameSourceKind::Odhcpd => "odhcpd",
+            HostnameSourceKind::Ethers => "ethers",
+        }
+    }
+
+    /// 读取该来源的主机名；命令不存在或文件不存在时返回空列表
+    pub fn load(&self) -> Vec<([u8; 6], String)> {
+        let parser: HostnameParser = match self.kind {
+            HostnameSourceKind::Ubus => return load_hostname_from_ubus().unwrap_or_default(),
+            HostnameSourceKind::Dnsmasq => parse_dnsmasq_leases,
+            HostnameSourceKind::Dhcpd => parse_dhcpd_leases,
+            HostnameSourceKind::Odhcpd => parse_odhcpd_state,
+            HostnameSourceKind::Ethers => parse_ethers,
+        };
+        match self.read_file() {
+            Some(content) => parser(&content),
+            None => Vec::new(),
+        }
+    }
+
+    fn read_file(&self) -> Option<String> {
+        if let Some(path) = &self.path {
+            return fs::read_to_string(path).ok();
+        }
+        let defaults = match self.kind {
+            HostnameSourceKind::Ubus => return None,
+            HostnameSourceKind::Dnsmasq => DNSMASQ_LEASE_PATHS,
+            HostnameSourceKind::Dhcpd => DHCPD_LEASE_PATHS,
+            HostnameSourceKind::Odhcpd => ODHCPD_STATE_PATHS,
+            HostnameSourceKind::Ethers => ETHERS_PATHS,
+        };
+        defaults.iter().find_map(|p| fs::read_to_string(p).ok())
+    }
+}
+
+/// 解析 --hostname-sources：逗号分隔，排在前面的来源优先级更高
+pub fn parse_hostname_sources(spec: &str) -> Result<Vec<HostnameSource>, anyhow::Error> {
+    let mut out: Vec<HostnameSource> = Vec::new();
+    for item in spec.split(',') {
+        let item = item.trim();
+        if item.is_empty() {
+            continue;
+        }
+        let source = HostnameSource::parse(item)?;
+        if !out.contains(&source) {
+            out.push(source);
+        }
+    }
+    Ok(out)
+}
+
+/// 按优先级合并所有来源的主机名：同一 MAC 以优先级最高的来源为准
+pub fn load_hostname_from_sources(sources: &[HostnameSource]) -> Vec<([u8; 6], String)> {
+    let mut seen: HashSet<[u8; 6]> = HashSet::new();
+    let mut out = Vec::new();
+    for source in sources {
+        let entries = source.load();
+        log::debug!("Hostname source {} returned {} entries", source.name(), entries.len());
+        for (mac, hostname) in entries {
+            if seen.insert(mac) {
+                out.push((mac, hostname));
+            }
+        }
+    }
+    out
+}
+
+/// 校验并规范化来源中的主机名："*"、"-" 与空值表示未知
+fn clean_hostname(name: &str) -> Option<String> {
+    let name = name.trim().trim_matches('"').trim();
+    if name.is_empty() || name == "*" || name == "-" || name.chars().any(|c| c.is_control() || c.is_whitespace()) {
+        return None;
+    }
+    Some(name.to_string())
+}
+
+/// 解析不带分隔符的 12 位十六进制 MAC
+fn parse_mac_hex(s: &str) -> Option<[u8; 6]> {
+    if s.len() != 12 || !s.is_ascii() {
+        return None;
+    }
+    let mut mac = [0u8; 6];
+    for i in 0..6 {
+        mac[i] = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(mac)
+}
+
+/// dnsmasq 租约文件，每行："过期时间 MAC IP 主机名 客户端ID"
+/// DHCPv6 租约的第二列是 IAID 而非 MAC，会被跳过
+pub fn parse_dnsmasq_leases(content: &str) -> Vec<([u8; 6], String)> {
+    let mut out = Vec::new();
+    for line in content.lines() {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() < 4 {
+            continue;
+        }
+        let Ok(mac) = crate::utils::network_utils::parse_mac_address(parts[1]) else {
+            continue;
+        };
+        if let Some(hostname) = clean_hostname(parts[3]) {
+            out.push((mac, hostname));
+        }
+    }
+    out
+}
+
+/// ISC dhcpd 租约文件：lease 块中的 hardware ethernet 与 client-hostname
+/// 文件按时间追加，同一 MAC 以最后一个租约为准
+pub fn parse_dhcpd_leases(content: &str) -> Vec<([u8; 6], String)> {
+    let mut latest: Vec<([u8; 6], String)> = Vec::new();
+    let mut mac: Option<[u8; 6]> = None;
+    let mut hostname: Option<String> = None;
+    let mut in_lease = false;
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with("lease ") && line.ends_with('{') {
+            in_lease = true;
+            mac = None;
+            hostname = None;
+        } else if line == "}" {
+            if in_lease {
+                if let (Some(m), Some(h)) = (mac, hostname.take()) {
+                    latest.retain(|(existing, _)| *existing != m);
+                    latest.push((m, h));
+                }
+            }
+            in_lease = false;
+        } else if in_lease {
+            let statement = line.trim_end_matches(';');
+            if let Some(value) = statement.strip_prefix("hardware ethernet ") {
+                mac = crate::utils::network_utils::parse_mac_address(value.trim()).ok();
+            } else if let Some(value) = statement.strip_prefix("client-hostname ") {
+                hostname = clean_hostname(value);
+            }
+        }
+    }
+    latest
+}
+
+/// odhcpd 状态文件，租约行以 "#" 开头：
+/// DHCPv4："# 接口 MAC(十六进制) ipv4 主机名 ..."
+/// DHCPv6："# 接口 DUID IAID 主机名 ..."，仅 DUID-LLT / DUID-LL（以太网）中包含 MAC
+pub fn parse_odhcpd_state(content: &str) -> Vec<([u8; 6], String)> {
+    let mut out: Vec<([u8; 6], String)> = Vec::new();
+    for line in content.lines() {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() < 5 || parts[0] != "#" {
+            continue;
+        }
+        let mac = if parts[3] == "ipv4" {
+            parse_mac_hex(parts[2])
+        } else {
+            mac_from_duid(parts[2])
+        };
+        let (Some(mac), Some(hostname)) = (mac, clean_hostname(parts[4])) else {
+            continue;
+        };
+        if !out.iter().any(|(m, _)| *m == mac) {
+            out.push((mac, hostname));
+        }
+    }
+    out
+}
+
+/// 从十六进制 DUID 中提取链路层地址（DUID-LLT 类型 1 / DUID-LL 类型 3，硬件类型 1 = 以太网）
+fn mac_from_duid(duid: &str) -> Option<[u8; 6]> {
+    match (duid.len(), duid.get(0..8)?) {
+        (28, "00010001") => parse_mac_hex(&duid[16..28]),
+        (20, "00030001") => parse_mac_hex(&duid[8..20]),
+        _ => None,
+    }
+}
+
+/// /etc/ethers，每行："MAC 主机名或IP"；第二列是 IP 地址的条目没有主机名
+pub fn parse_ethers(content: &str) -> Vec<([u8; 6], String)> {
+    let mut out = Vec::new();
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or("");
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() < 2 || parts[1].parse::<std::net::IpAddr>().is_ok() {
+            continue;
+        }
+        let Ok(mac) = crate::utils::network_utils::parse_mac_address(parts[0]) else {
+            continue;
+        };
+        if let Some(hostname) = clean_hostname(parts[1]) {
+            out.push((mac, hostname));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAC_A: [u8; 6] = [0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0x01];
+    const MAC_B: [u8; 6] = [0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0x02];
+
+    #[test]
+    fn test_parse_dnsmasq_leases() {
+        let content = "\
+1762723538 aa:bb:cc:dd:ee:01 192.168.1.10 laptop 01:aa:bb:cc:dd:ee:01
+1762723538 aa:bb:cc:dd:ee:02 192.168.1.11 * *
+duid 00:01:00:01:2c:1f:6b:54:aa:bb:cc:dd:ee:ff
+1762723538 1234567 fd00::10 phone 00:01:00:01:2c:1f:6b:54:aa:bb:cc:dd:ee:03
+";
+        assert_eq!(parse_dnsmasq_leases(content), vec![(MAC_A, "laptop".to_string())]);
+    }
+
+    #[test]
+    fn test_parse_dhcpd_leases() {
+        let content = r#"
+# The format of this file is documented in the dhcpd.leases(5) manual page.
+lease 192.168.1.10 {
+  starts 4 2025/11/06 10:00:00;
+  binding state active;
+  hardware ethernet aa:bb:cc:dd:ee:01;
+  client-hostname "old-name";
+}
+lease 192.168.1.11 {
+  hardware ethernet aa:bb:cc:dd:ee:02;
+}
+lease 192.168.1.10 {
+  starts 4 2025/11/06 12:00:00;
+  hardware ethernet aa:bb:cc:dd:ee:01;
+  uid "ª»ÌÝî";
+  client-hostname "desktop";
+}
+"#;
+        assert_eq!(parse_dhcpd_leases(content), vec![(MAC_A, "desktop".to_string())]);
+    }
+
+    #[test]
+    fn test_parse_odhcpd_state() {
+        let content = "\
+# br-lan aabbccddee01 ipv4 nas 1762723538 a 0 192.168.1.10/32
+# br-lan 00010001290c6f2aaabbccddee02 5d2c1a8f tv 1762723538 12 128 fd00::1e/128
+# br-lan 00040123456789abcdef 0c1b2a39 - 1762723538 13 128 fd00::1f/128
+192.168.1.10 nas
+";
+        assert_eq!(
+            parse_odhcpd_state(content),
+            vec![(MAC_A, "nas".to_string()), (MAC_B, "tv".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_parse_ethers() {
+        let content = "\
+# static hosts
+aa:bb:cc:dd:ee:01 printer
+aa:bb:cc:dd:ee:02 192.168.1.20
+";
+        assert_eq!(parse_ethers(content), vec![(MAC_A, "printer".to_string())]);
+    }
+
+    #[test]
+    fn test_sources_merge_by_priority() {
+        let dir = std::env::temp_dir().join(format!("bandix-hostname-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let ethers = dir.join("ethers");
+        let leases = dir.join("dhcp.leases");
+        fs::write(&ethers, "aa:bb:cc:dd:ee:01 printer\n").unwrap();
+        fs::write(
+            &leases,
+            "1762723538 aa:bb:cc:dd:ee:01 192.168.1.10 NPI0A1B2C *\n1762723538 aa:bb:cc:dd:ee:02 192.168.1.11 phone *\n",
+        )
+        .unwrap();
+
+        let spec = format!(
+            "ethers:{},dnsmasq:{},dhcpd:{}",
+            ethers.display(),
+            leases.display(),
+            dir.join("missing").display()
+        );
+        let sources = parse_hostname_sources(&spec).unwrap();
+        assert_eq!(sources.len(), 3);
+        let merged = load_hostname_from_sources(&sources);
+        assert_eq!(merged, vec![(MAC_A, "printer".to_string()), (MAC_B, "phone".to_string())]);
+
+        assert!(parse_hostname_sources("dnsmasq,unknown").is_err());
+        assert!(parse_hostname_sources("ubus:/tmp/x").is_err());
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,113 @@
+use crate::utils::format_utils::format_mac;
+use crate::utils::network_utils::parse_mac_address;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 两个 MAC 被关联为同一设备的依据
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkSource {
+    ClientId, // DHCP 客户端标识（选项 61）相同
+    Hostname, // DHCP 主机名相同
+    Manual,   // 通过 API 手动合并
+}
+
+impl LinkSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LinkSource::ClientId => "client_id",
+            LinkSource::Hostname => "hostname",
+            LinkSource::Manual => "manual",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "client_id" => Some(LinkSource::ClientId),
+            "hostname" => Some(LinkSource::Hostname),
+            "manual" => Some(LinkSource::Manual),
+            _ => None,
+        }
+    }
+}
+
+/// 设备身份的持久化内容
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IdentitySnapshot {
+    pub links: Vec<([u8; 6], [u8; 6], LinkSource)>, // (成员 MAC, 主 MAC, 依据)
+    pub keys: Vec<(String, [u8; 6])>,               // (标识，例如 "client_id:01aabb...", 最近使用该标识的 MAC)
+    pub detached: Vec<[u8; 6]>,                     // 手动拆分过、不再自动关联的 MAC
+}
+
+fn identities_path(base_dir: &str) -> PathBuf {
+    Path::new(base_dir).join("device_identities.txt")
+}
+
+/// 从文件加载设备身份
+/// 文件格式：每行一条
+/// - "link <member mac> <primary mac> <source>"
+/// - "key <mac> <identifier>"
+/// - "detached <mac>"
+pub fn load_identities(base_dir: &str) -> Result<IdentitySnapshot, anyhow::Error> {
+    let path = identities_path(base_dir);
+    let mut out = IdentitySnapshot::default();
+    if !path.exists() {
+        return Ok(out);
+    }
+
+    let content = fs::read_to_string(&path)?;
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let parts: Vec<&str> = line.splitn(4, ' ').collect();
+        match parts.as_slice() {
+            ["link", member, primary, source] => {
+                if let (Ok(member), Ok(primary), Some(source)) =
+                    (parse_mac_address(member), parse_mac_address(primary), LinkSource::parse(source))
+                {
+                    out.links.push((member, primary, source));
+                }
+            }
+            ["key", mac, identifier] => {
+                if let Ok(mac) = parse_mac_address(mac) {
+                    out.keys.push((identifier.to_string(), mac));
+                }
+            }
+            ["detached", mac] => {
+                if let Ok(mac) = parse_mac_address(mac) {
+                    out.detached.push(mac);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(out)
+}
+
+/// 将设备身份写入文件
+pub fn save_identities(base_dir: &str, snapshot: &IdentitySnapshot) -> Result<(), anyhow::Error> {
+    let path = identities_path(base_dir);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut buf = String::new();
+    buf.push_str("# link member primary source | key mac identifier | detached mac\n");
+    for (member, primary, source) in &snapshot.links {
+        buf.push_str(&format!(
+            "link {} {} {}\n",
+            format_mac(member),
+            format_mac(primary),
+            source.as_str()
+        ));
+    }
+    for (identifier, mac) in &snapshot.keys {
+        buf.push_str(&format!("key {} {}\n", format_mac(mac), identifier));
+    }
+    for mac in &snapshot.detached {
+        buf.push_str(&format!("detached {}\n", format_mac(mac)));
+    }
+    super::write_atomic(&path, buf)
+}
@@ -0,0 +1,55 @@
+use crate::utils::format_utils::format_mac;
+use crate::utils::network_utils::parse_mac_address;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 用户为设备填写的元数据
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceMeta {
+    pub owner: String,
+    pub tags: Vec<String>,
+    pub notes: String,
+    pub icon: String, // 前端使用的图标名称
+}
+
+impl DeviceMeta {
+    pub fn is_empty(&self) -> bool {
+        self.owner.is_empty() && self.tags.is_empty() && self.notes.is_empty() && self.icon.is_empty()
+    }
+}
+
+fn meta_path(base_dir: &str) -> PathBuf {
+    Path::new(base_dir).join("device_meta.json")
+}
+
+/// 从文件加载设备元数据
+/// 文件格式：以 MAC 为键的 JSON 对象，无法识别的 MAC 会被忽略
+pub fn load_device_meta(base_dir: &str) -> Result<Vec<([u8; 6], DeviceMeta)>, anyhow::Error> {
+    let path = meta_path(base_dir);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = fs::read_to_string(&path)?;
+    let entries: BTreeMap<String, DeviceMeta> = serde_json::from_str(&content)?;
+    Ok(entries
+        .into_iter()
+        .filter_map(|(mac, meta)| parse_mac_address(&mac).ok().map(|mac| (mac, meta)))
+        .collect())
+}
+
+/// 将设备元数据写入文件
+pub fn save_device_meta<'a>(base_dir: &str, entries: impl IntoIterator<Item = ([u8; 6], &'a DeviceMeta)>) -> Result<(), anyhow::Error> {
+    let path = meta_path(base_dir);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let entries: BTreeMap<String, &DeviceMeta> = entries.into_iter().map(|(mac, meta)| (format_mac(&mac), meta)).collect();
+    let buf = serde_json::to_string_pretty(&entries)?;
+
+    super::write_atomic(&path, buf)
+}
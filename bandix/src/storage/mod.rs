@@ -1,3 +1,21 @@
 pub mod dns;
+pub mod events;
+pub mod groups;
+pub mod history;
 pub mod hostname;
+pub mod identity;
+pub mod meta;
+pub mod presence;
 pub mod traffic;
+
+use std::fs;
+use std::path::Path;
+
+/// 先写临时文件（原文件名加 .tmp）再重命名，避免写入中断留下残缺文件
+pub fn write_atomic(path: &Path, contents: impl AsRef<[u8]>) -> Result<(), anyhow::Error> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    fs::write(&tmp_path, contents)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
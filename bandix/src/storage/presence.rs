@@ -0,0 +1,79 @@
+use crate::utils::format_utils::format_mac;
+use crate::utils::network_utils::parse_mac_address;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 一次在线会话
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PresenceSession {
+    pub start_ms: u64,
+    pub end_ms: Option<u64>, // None 表示仍在线
+}
+
+/// 在线会话的持久化内容
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PresenceSnapshot {
+    pub checkpoint_ms: u64, // 写入时间；重启后未结束的会话按此时间结束
+    pub sessions: Vec<([u8; 6], PresenceSession)>,
+}
+
+fn sessions_path(base_dir: &str) -> PathBuf {
+    Path::new(base_dir).join("device_sessions.txt")
+}
+
+/// 从文件加载在线会话
+/// 文件格式：首行 "checkpoint <ts_ms>"，其余每行 "<mac> <start_ms> <end_ms>"，未结束的会话 end_ms 为 "-"
+pub fn load_sessions(base_dir: &str) -> Result<PresenceSnapshot, anyhow::Error> {
+    let path = sessions_path(base_dir);
+    let mut out = PresenceSnapshot::default();
+    if !path.exists() {
+        return Ok(out);
+    }
+
+    let content = fs::read_to_string(&path)?;
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        match parts.as_slice() {
+            ["checkpoint", ts] => {
+                out.checkpoint_ms = ts.parse().unwrap_or(0);
+            }
+            [mac, start, end] => {
+                let (Ok(mac), Ok(start_ms)) = (parse_mac_address(mac), start.parse::<u64>()) else {
+                    continue;
+                };
+                let end_ms = match *end {
+                    "-" => None,
+                    end => match end.parse::<u64>() {
+                        Ok(end_ms) => Some(end_ms),
+                        Err(_) => continue,
+                    },
+                };
+                out.sessions.push((mac, PresenceSession { start_ms, end_ms }));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(out)
+}
+
+/// 将在线会话写入文件
+pub fn save_sessions(base_dir: &str, snapshot: &PresenceSnapshot) -> Result<(), anyhow::Error> {
+    let path = sessions_path(base_dir);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut buf = String::new();
+    buf.push_str(&format!("checkpoint {}\n", snapshot.checkpoint_ms));
+    for (mac, session) in &snapshot.sessions {
+        let end = session.end_ms.map(|ts| ts.to_string()).unwrap_or_else(|| "-".to_string());
+        buf.push_str(&format!("{} {} {}\n", format_mac(mac), session.start_ms, end));
+    }
+
+    super::write_atomic(&path, buf)
+}
@@ -1283,6 +1283,27 @@ impl LongTermRingManager {
             all_rows.append(&mut rows);
         }
 
+        Ok(Self::aggregate_rows_by_ts(all_rows))
+    }
+
+    /// 按时间段聚合多个 MAC 的数据（同一设备身份下的全部 MAC）
+    pub fn query_stats_aggregate_macs(
+        &self,
+        macs: &[[u8; 6]],
+        start_ms: u64,
+        end_ms: u64,
+    ) -> Result<Vec<MetricsRowWithStats>, anyhow::Error> {
+        let rings = self.rings.lock().unwrap();
+        let all_rows = macs
+            .iter()
+            .filter_map(|mac| rings.get(mac))
+            .flat_map(|ring| ring.query_stats(start_ms, end_ms))
+            .collect();
+
+        Ok(Self::aggregate_rows_by_ts(all_rows))
+    }
+
+    fn aggregate_rows_by_ts(all_rows: Vec<MetricsRowWithStats>) -> Vec<MetricsRowWithStats> {
         let mut ts_to_stats: BTreeMap<u64, MetricsRowWithStats> = BTreeMap::new();
 
         for row in all_rows {
@@ -1371,7 +1392,7 @@ impl LongTermRingManager {
             }
         }
 
-        Ok(ts_to_stats.into_values().collect())
+        ts_to_stats.into_values().collect()
     }
 }
 
@@ -1637,6 +1658,37 @@ pub fn upsert_hostname_binding(base_dir: &str, mac: &[u8; 6], hostname: &str) ->
     Ok(())
 }
 
+/// 设置或删除设备类型的手动设置，device_type 为 None 时删除
+pub fn upsert_device_type_override(
+    base_dir: &str,
+    mac: &[u8; 6],
+    device_type: Option<crate::monitor::fingerprint::DeviceType>,
+) -> Result<(), anyhow::Error> {
+    let path = crate::storage::hostname::device_types_path(base_dir);
+    ensure_parent_dir(&path)?;
+    let mut map: std::collections::BTreeMap<String, String> = Default::default();
+    for (m, t) in crate::storage::hostname::load_device_type_overrides(base_dir)? {
+        map.insert(mac_to_filename(&m), t.as_str().to_string());
+    }
+    let key = mac_to_filename(mac);
+    match device_type {
+        Some(t) => {
+            map.insert(key, t.as_str().to_string());
+        }
+        None => {
+            map.remove(&key);
+        }
+    }
+
+    let mut buf = String::new();
+    buf.push_str("# mac type\n");
+    for (k, t) in map {
+        buf.push_str(&format!("{} {}\n", k, t));
+    }
+    fs::write(&path, buf)?;
+    Ok(())
+}
+
 // load_hostname_bindings 和 load_hostname_from_ubus 已移动到 storage::hostname 模块
 
 #[derive(Debug, Clone, Copy)]
@@ -2210,29 +2262,37 @@ pub fn calculate_current_rate_limit(scheduled_limits: &[ScheduledRateLimit], mac
     let now = Local::now();
 
     // 查找此 MAC 的所有匹配规则
-    let matching_rules: Vec<&ScheduledRateLimit> = scheduled_limits
-        .iter()
-        .filter(|rule| rule.mac == *mac && rule.time_slot.matches(&now))
-        .collect();
+    strictest_rate_limit(
+        scheduled_limits
+            .iter()
+            .filter(|rule| rule.mac == *mac && rule.time_slot.matches(&now))
+            .map(|rule| [rule.wan_rx_rate_limit, rule.wan_tx_rate_limit]),
+    )
+}
 
-    if matching_rules.is_empty() {
-        return None;
-    }
+/// 合并同时匹配的多条规则，没有规则时返回 None
+pub fn strictest_rate_limit(limits: impl IntoIterator<Item = [u64; 2]>) -> Option<[u64; 2]> {
+    let mut matched = false;
 
     // 如果多个规则匹配，使用最严格的（最低的非零限制）
     // 如果规则为 0，表示无限制，因此取非零值的最小值
     let mut rx_limit: Option<u64> = None;
     let mut tx_limit: Option<u64> = None;
 
-    for rule in matching_rules {
-        if rule.wan_rx_rate_limit > 0 {
-            rx_limit = Some(rx_limit.map_or(rule.wan_rx_rate_limit, |current: u64| current.min(rule.wan_rx_rate_limit)));
+    for [rx, tx] in limits {
+        matched = true;
+        if rx > 0 {
+            rx_limit = Some(rx_limit.map_or(rx, |current: u64| current.min(rx)));
         }
-        if rule.wan_tx_rate_limit > 0 {
-            tx_limit = Some(tx_limit.map_or(rule.wan_tx_rate_limit, |current: u64| current.min(rule.wan_tx_rate_limit)));
+        if tx > 0 {
+            tx_limit = Some(tx_limit.map_or(tx, |current: u64| current.min(tx)));
         }
     }
 
+    if !matched {
+        return None;
+    }
+
     // 如果无限制则返回 [0, 0]，否则返回计算出的限制
     Some([rx_limit.unwrap_or(0), tx_limit.unwrap_or(0)])
 }
@@ -0,0 +1,409 @@
+// Wi-Fi 客户端信息
+//
+// 优先通过 nl80211（generic netlink）获取 AP 接口上的 station 列表及信号、速率等详情，
+// nl80211 不可用时回退到 `ubus call hostapd.* get_clients`。
+
+use crate::netlink::{self, NLM_F_DUMP};
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::collections::HashMap;
+
+// generic netlink 控制器
+const GENL_ID_CTRL: u16 = 0x10;
+const CTRL_CMD_GETFAMILY: u8 = 3;
+const CTRL_ATTR_FAMILY_ID: u16 = 1;
+const CTRL_ATTR_FAMILY_NAME: u16 = 2;
+const GENL_HDR_LEN: usize = 4;
+
+// nl80211 命令与属性
+const NL80211_CMD_GET_INTERFACE: u8 = 5;
+const NL80211_CMD_GET_STATION: u8 = 17;
+const NL80211_ATTR_IFINDEX: u16 = 3;
+const NL80211_ATTR_IFNAME: u16 = 4;
+const NL80211_ATTR_IFTYPE: u16 = 5;
+const NL80211_ATTR_MAC: u16 = 6;
+const NL80211_ATTR_STA_INFO: u16 = 21;
+const NL80211_ATTR_WIPHY_FREQ: u16 = 38;
+const NL80211_ATTR_SSID: u16 = 52;
+const NL80211_IFTYPE_AP: u32 = 3;
+const NL80211_IFTYPE_P2P_GO: u32 = 9;
+
+// NL80211_ATTR_STA_INFO 内的嵌套属性
+const NL80211_STA_INFO_INACTIVE_TIME: u16 = 1;
+const NL80211_STA_INFO_RX_BYTES: u16 = 2;
+const NL80211_STA_INFO_TX_BYTES: u16 = 3;
+const NL80211_STA_INFO_SIGNAL: u16 = 7;
+const NL80211_STA_INFO_TX_BITRATE: u16 = 8;
+const NL80211_STA_INFO_SIGNAL_AVG: u16 = 13;
+const NL80211_STA_INFO_RX_BITRATE: u16 = 14;
+const NL80211_STA_INFO_CONNECTED_TIME: u16 = 16;
+const NL80211_STA_INFO_RX_BYTES64: u16 = 23;
+const NL80211_STA_INFO_TX_BYTES64: u16 = 24;
+
+// 速率属性，单位 100 kbit/s
+const NL80211_RATE_INFO_BITRATE: u16 = 1;
+const NL80211_RATE_INFO_BITRATE32: u16 = 5;
+
+/// 数据来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WirelessSource {
+    Nl80211,
+    Ubus,
+}
+
+impl WirelessSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WirelessSource::Nl80211 => "nl80211",
+            WirelessSource::Ubus => "ubus",
+        }
+    }
+}
+
+/// 一个已关联的 Wi-Fi 客户端，字节数为 AP 视角（rx 为从客户端接收）
+#[derive(Debug, Clone, PartialEq)]
+pub struct WirelessStation {
+    pub interface: String,
+    pub ssid: Option<String>,
+    pub frequency_mhz: Option<u32>,
+    pub signal_dbm: Option<i32>,
+    pub signal_avg_dbm: Option<i32>,
+    pub tx_bitrate_kbps: Option<u32>,
+    pub rx_bitrate_kbps: Option<u32>,
+    pub rx_bytes: Option<u64>,
+    pub tx_bytes: Option<u64>,
+    pub connected_time_secs: Option<u32>,
+    pub inactive_time_ms: Option<u32>,
+    pub source: WirelessSource,
+}
+
+impl WirelessStation {
+    fn new(interface: String, source: WirelessSource) -> Self {
+        Self {
+            interface,
+            ssid: None,
+            frequency_mhz: None,
+            signal_dbm: None,
+            signal_avg_dbm: None,
+            tx_bitrate_kbps: None,
+            rx_bitrate_kbps: None,
+            rx_bytes: None,
+            tx_bytes: None,
+            connected_time_secs: None,
+            inactive_time_ms: None,
+            source,
+        }
+    }
+
+    /// 根据信道频率推断频段
+    pub fn band(&self) -> Option<&'static str> {
+        match self.frequency_mhz? {
+            2400..=2500 => Some("2.4GHz"),
+            5925..=7125 => Some("6GHz"),
+            4900..=5924 => Some("5GHz"),
+            57000..=71000 => Some("60GHz"),
+            _ => None,
+        }
+    }
+}
+
+/// 一个 AP 模式的无线接口
+#[derive(Debug, Clone)]
+struct AccessPoint {
+    ifindex: u32,
+    name: String,
+    ssid: Option<String>,
+    frequency_mhz: Option<u32>,
+}
+
+/// 读取所有 Wi-Fi 客户端，nl80211 失败时回退到 ubus
+pub fn read_stations() -> HashMap<[u8; 6], WirelessStation> {
+    match read_nl80211_stations() {
+        Ok(stations) => stations,
+        Err(e) => {
+            log::debug!("nl80211 station dump unavailable ({}), falling back to ubus", e);
+            read_hostapd_stations()
+        }
+    }
+}
+
+/// 通过 nl80211 读取所有 AP 接口上的 station
+pub fn read_nl80211_stations() -> Result<HashMap<[u8; 6], WirelessStation>> {
+    let family = resolve_family("nl80211")?;
+
+    let replies = netlink::request(
+        libc::NETLINK_GENERIC,
+        family,
+        NLM_F_DUMP,
+        &genl_header(NL80211_CMD_GET_INTERFACE),
+    )?;
+    let access_points: Vec<AccessPoint> = replies.iter().filter_map(|(_, payload)| parse_interface(payload)).collect();
+    if access_points.is_empty() {
+        return Err(anyhow!("No nl80211 interfaces in AP mode"));
+    }
+
+    let mut out = HashMap::new();
+    for ap in access_points {
+        let mut payload = genl_header(NL80211_CMD_GET_STATION);
+        netlink::push_attribute(&mut payload, NL80211_ATTR_IFINDEX, &ap.ifindex.to_ne_bytes());
+
+        let replies = match netlink::request(libc::NETLINK_GENERIC, family, NLM_F_DUMP, &payload) {
+            Ok(replies) => replies,
+            Err(e) => {
+                log::debug!("Failed to dump stations on {}: {}", ap.name, e);
+                continue;
+            }
+        };
+        for (_, payload) in replies {
+            if let Some((mac, mut station)) = parse_station(&payload, &ap.name) {
+                station.ssid = ap.ssid.clone();
+                station.frequency_mhz = ap.frequency_mhz;
+                out.insert(mac, station);
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+fn genl_header(cmd: u8) -> Vec<u8> {
+    vec![cmd, 0, 0, 0]
+}
+
+/// 通过 generic netlink 控制器查询协议族 ID
+fn resolve_family(name: &str) -> Result<u16> {
+    let mut payload = genl_header(CTRL_CMD_GETFAMILY);
+    let mut family_name = name.as_bytes().to_vec();
+    family_name.push(0);
+    netlink::push_attribute(&mut payload, CTRL_ATTR_FAMILY_NAME, &family_name);
+
+    let replies = netlink::request(libc::NETLINK_GENERIC, GENL_ID_CTRL, 0, &payload)?;
+    replies
+        .iter()
+        .filter(|(_, payload)| payload.len() >= GENL_HDR_LEN)
+        .flat_map(|(_, payload)| netlink::parse_attributes(&payload[GENL_HDR_LEN..]))
+        .find(|(attr_type, data)| *attr_type == CTRL_ATTR_FAMILY_ID && data.len() >= 2)
+        .map(|(_, data)| u16::from_ne_bytes([data[0], data[1]]))
+        .ok_or_else(|| anyhow!("Generic netlink family {} not found", name))
+}
+
+fn parse_interface(payload: &[u8]) -> Option<AccessPoint> {
+    let attrs = netlink::parse_attributes(payload.get(GENL_HDR_LEN..)?);
+
+    let mut ifindex = None;
+    let mut iftype = None;
+    let mut name = String::new();
+    let mut ssid = None;
+    let mut frequency_mhz = None;
+    for (attr_type, data) in attrs {
+        match attr_type {
+            NL80211_ATTR_IFINDEX => ifindex = read_u32(data),
+            NL80211_ATTR_IFTYPE => iftype = read_u32(data),
+            NL80211_ATTR_IFNAME => name = read_string(data),
+            NL80211_ATTR_SSID => ssid = Some(String::from_utf8_lossy(data).into_owned()),
+            NL80211_ATTR_WIPHY_FREQ => frequency_mhz = read_u32(data),
+            _ => {}
+        }
+    }
+
+    if !matches!(iftype, Some(NL80211_IFTYPE_AP) | Some(NL80211_IFTYPE_P2P_GO)) {
+        return None;
+    }
+    Some(AccessPoint {
+        ifindex: ifindex?,
+        name,
+        ssid,
+        frequency_mhz,
+    })
+}
+
+fn parse_station(payload: &[u8], interface: &str) -> Option<([u8; 6], WirelessStation)> {
+    let attrs = netlink::parse_attributes(payload.get(GENL_HDR_LEN..)?);
+
+    let mut mac = None;
+    let mut station = WirelessStation::new(interface.to_string(), WirelessSource::Nl80211);
+    for (attr_type, data) in attrs {
+        match attr_type {
+            NL80211_ATTR_MAC if data.len() == 6 => mac = Some(<[u8; 6]>::try_from(data).ok()?),
+            NL80211_ATTR_STA_INFO => {
+                for (info_type, info) in netlink::parse_attributes(data) {
+                    match info_type {
+                        NL80211_STA_INFO_INACTIVE_TIME => station.inactive_time_ms = read_u32(info),
+                        NL80211_STA_INFO_CONNECTED_TIME => station.connected_time_secs = read_u32(info),
+                        NL80211_STA_INFO_SIGNAL => station.signal_dbm = info.first().map(|&v| v as i8 as i32),
+                        NL80211_STA_INFO_SIGNAL_AVG => station.signal_avg_dbm = info.first().map(|&v| v as i8 as i32),
+                        NL80211_STA_INFO_TX_BITRATE => station.tx_bitrate_kbps = parse_bitrate(info),
+                        NL80211_STA_INFO_RX_BITRATE => station.rx_bitrate_kbps = parse_bitrate(info),
+                        // 32 位计数器会回绕，有 64 位计数器时优先使用
+                        NL80211_STA_INFO_RX_BYTES if station.rx_bytes.is_none() => station.rx_bytes = read_u32(info).map(u64::from),
+                        NL80211_STA_INFO_TX_BYTES if station.tx_bytes.is_none() => station.tx_bytes = read_u32(info).map(u64::from),
+                        NL80211_STA_INFO_RX_BYTES64 => station.rx_bytes = read_u64(info),
+                        NL80211_STA_INFO_TX_BYTES64 => station.tx_bytes = read_u64(info),
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Some((mac?, station))
+}
+
+/// 解析 rate_info 嵌套属性，返回 kbit/s
+fn parse_bitrate(data: &[u8]) -> Option<u32> {
+    let mut bitrate = None;
+    for (attr_type, value) in netlink::parse_attributes(data) {
+        match attr_type {
+            NL80211_RATE_INFO_BITRATE32 => bitrate = read_u32(value),
+            NL80211_RATE_INFO_BITRATE if bitrate.is_none() => bitrate = value.get(0..2).map(|b| u16::from_ne_bytes([b[0], b[1]]) as u32),
+            _ => {}
+        }
+    }
+    bitrate.map(|rate| rate * 100)
+}
+
+fn read_u32(data: &[u8]) -> Option<u32> {
+    Some(u32::from_ne_bytes(data.get(0..4)?.try_into().ok()?))
+}
+
+fn read_u64(data: &[u8]) -> Option<u64> {
+    Some(u64::from_ne_bytes(data.get(0..8)?.try_into().ok()?))
+}
+
+fn read_string(data: &[u8]) -> String {
+    let end = data.iter().position(|&b| b == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).into_owned()
+}
+
+/// 通过 hostapd 的 ubus 接口读取 station（回退路径）
+pub fn read_hostapd_stations() -> HashMap<[u8; 6], WirelessStation> {
+    let mut out = HashMap::new();
+    for obj in read_hostapd_interfaces() {
+        let clients = match ubus_call(&obj, "get_clients") {
+            Some(v) => v,
+            None => continue,
+        };
+        let ssid = ubus_call(&obj, "get_status").and_then(|v| v.get("ssid").and_then(|s| s.as_str()).map(|s| s.to_string()));
+        let interface = obj.trim_start_matches("hostapd.").to_string();
+        out.extend(parse_hostapd_clients(&interface, ssid, &clients));
+    }
+    out
+}
+
+fn read_hostapd_interfaces() -> Vec<String> {
+    let output = match std::process::Command::new("ubus").args(["list"]).output() {
+        Ok(o) => o,
+        Err(_) => return Vec::new(),
+    };
+    if !output.status.success() {
+        return Vec::new();
+    }
+
+    let s = String::from_utf8_lossy(&output.stdout);
+    s.lines()
+        .map(|l| l.trim().to_string())
+        .filter(|l| l.starts_with("hostapd."))
+        .collect()
+}
+
+fn ubus_call(obj: &str, method: &str) -> Option<Value> {
+    let output = std::process::Command::new("ubus").args(["call", obj, method]).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    serde_json::from_slice(&output.stdout).ok()
+}
+
+/// 解析 `hostapd.* get_clients` 的输出，rate 字段单位为 kbit/s
+fn parse_hostapd_clients(interface: &str, ssid: Option<String>, v: &Value) -> Vec<([u8; 6], WirelessStation)> {
+    let clients = match v.get("clients").and_then(|x| x.as_object()) {
+        Some(o) => o,
+        None => return Vec::new(),
+    };
+    let frequency_mhz = v.get("freq").and_then(|f| f.as_u64()).map(|f| f as u32);
+
+    let mut out = Vec::new();
+    for (mac_str, info) in clients.iter() {
+        let mac = match crate::utils::network_utils::parse_mac_address(mac_str) {
+            Ok(mac) => mac,
+            Err(_) => continue,
+        };
+
+        let mut station = WirelessStation::new(interface.to_string(), WirelessSource::Ubus);
+        station.ssid = ssid.clone();
+        station.frequency_mhz = frequency_mhz;
+        station.signal_dbm = info.get("signal").and_then(|s| s.as_i64()).map(|s| s as i32);
+        station.rx_bitrate_kbps = info.pointer("/rate/rx").and_then(|r| r.as_u64()).map(|r| r as u32);
+        station.tx_bitrate_kbps = info.pointer("/rate/tx").and_then(|r| r.as_u64()).map(|r| r as u32);
+        station.rx_bytes = info.pointer("/bytes/rx").and_then(|b| b.as_u64());
+        station.tx_bytes = info.pointer("/bytes/tx").and_then(|b| b.as_u64());
+        station.connected_time_secs = info.get("connected_time").and_then(|t| t.as_u64()).map(|t| t as u32);
+        out.push((mac, station));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_station() {
+        let mut rate = Vec::new();
+        netlink::push_attribute(&mut rate, NL80211_RATE_INFO_BITRATE, &1200u16.to_ne_bytes());
+        netlink::push_attribute(&mut rate, NL80211_RATE_INFO_BITRATE32, &8647u32.to_ne_bytes());
+
+        let mut info = Vec::new();
+        netlink::push_attribute(&mut info, NL80211_STA_INFO_INACTIVE_TIME, &40u32.to_ne_bytes());
+        netlink::push_attribute(&mut info, NL80211_STA_INFO_RX_BYTES64, &5_000_000_000u64.to_ne_bytes());
+        netlink::push_attribute(&mut info, NL80211_STA_INFO_RX_BYTES, &1234u32.to_ne_bytes());
+        netlink::push_attribute(&mut info, NL80211_STA_INFO_TX_BYTES, &4321u32.to_ne_bytes());
+        netlink::push_attribute(&mut info, NL80211_STA_INFO_SIGNAL, &[(-52i8) as u8]);
+        netlink::push_attribute(&mut info, NL80211_STA_INFO_TX_BITRATE, &rate);
+        netlink::push_attribute(&mut info, NL80211_STA_INFO_CONNECTED_TIME, &360u32.to_ne_bytes());
+
+        let mac = [0x02, 0x11, 0x22, 0x33, 0x44, 0x55];
+        let mut payload = genl_header(NL80211_CMD_GET_STATION);
+        netlink::push_attribute(&mut payload, NL80211_ATTR_MAC, &mac);
+        netlink::push_attribute(&mut payload, NL80211_ATTR_STA_INFO, &info);
+
+        let (parsed_mac, station) = parse_station(&payload, "wlan0").unwrap();
+        assert_eq!(parsed_mac, mac);
+        assert_eq!(station.interface, "wlan0");
+        assert_eq!(station.signal_dbm, Some(-52));
+        assert_eq!(station.tx_bitrate_kbps, Some(864_700));
+        assert_eq!(station.rx_bitrate_kbps, None);
+        assert_eq!(station.rx_bytes, Some(5_000_000_000));
+        assert_eq!(station.tx_bytes, Some(4321));
+        assert_eq!(station.connected_time_secs, Some(360));
+        assert_eq!(station.inactive_time_ms, Some(40));
+        assert_eq!(station.source, WirelessSource::Nl80211);
+    }
+
+    #[test]
+    fn test_parse_hostapd_clients() {
+        let v: Value = serde_json::json!({
+            "freq": 5180,
+            "clients": {
+                "aa:bb:cc:dd:ee:ff": {
+                    "signal": -61,
+                    "rate": { "rx": 390000, "tx": 433300 },
+                    "bytes": { "rx": 1000, "tx": 2000 }
+                },
+                "invalid": {}
+            }
+        });
+
+        let stations = parse_hostapd_clients("phy0-ap0", Some("home".to_string()), &v);
+        assert_eq!(stations.len(), 1);
+        let (mac, station) = &stations[0];
+        assert_eq!(*mac, [0xaa, 0xbb, 0xcc, 0xdd, 0xee, 0xff]);
+        assert_eq!(station.band(), Some("5GHz"));
+        assert_eq!(station.ssid.as_deref(), Some("home"));
+        assert_eq!(station.signal_dbm, Some(-61));
+        assert_eq!(station.tx_bitrate_kbps, Some(433_300));
+        assert_eq!(station.rx_bytes, Some(1000));
+        assert_eq!(station.source, WirelessSource::Ubus);
+    }
+}